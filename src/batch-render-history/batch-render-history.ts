@@ -5,6 +5,12 @@ export interface WorkerRenderArea {
   workerIdx: number;
   rect: Rect;
   elapsed: number;
+  /** solid guessing / boundary tracingで計算せずに埋めたピクセル数 */
+  guessedCount: number;
+  /** 実際にiterationを回したピクセル数 */
+  iteratedCount: number;
+  /** 埋め間違えたピクセル数。Debug Mode中のみ数える */
+  mistakenGuessCount: number;
 }
 
 export interface BatchRenderEntry {
@@ -39,11 +45,12 @@ export const recordWorkerResult = (
   workerIdx: number,
   rect: Rect,
   elapsed: number,
+  stats: Pick<WorkerRenderArea, "guessedCount" | "iteratedCount" | "mistakenGuessCount">,
 ): void => {
   if (!pendingBatches.has(batchId)) {
     pendingBatches.set(batchId, []);
   }
-  pendingBatches.get(batchId)!.push({ workerId, workerIdx, rect, elapsed });
+  pendingBatches.get(batchId)!.push({ workerId, workerIdx, rect, elapsed, ...stats });
 };
 
 /**
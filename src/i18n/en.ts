@@ -120,10 +120,22 @@ export const en = {
   "settings.generate": "Generate",
   "settings.useWasm": "Use Wasm for reference orbit",
   "settings.useWasmTooltip": "Approximately 10x faster. Recommended to keep ON.",
+  "settings.renderStrategy": "Render Strategy",
+  "settings.renderStrategy.scan": "Scan every pixel",
+  "settings.renderStrategy.solidGuessing": "Solid guessing",
+  "settings.renderStrategy.boundaryTracing": "Boundary tracing",
+  "settings.renderStrategyTooltip":
+    "Guessing and tracing skip areas surrounded by the same iteration. Faster, but may miss thin details.",
   // supersampling
   "supersampling.result": "Supersampling Result",
   "supersampling.actualSize": "Actual Size",
   "supersampling.fitToScreen": "Fit to Screen",
+  "supersampling.samples": "Samples per Pixel",
+  "supersampling.pattern": "Sample Pattern",
+  "supersampling.pattern.grid": "Grid",
+  "supersampling.pattern.rotatedGrid": "Rotated Grid",
+  "supersampling.pattern.jittered": "Jittered",
+  "supersampling.adaptive": "Sample only edges",
 
   // dialog descriptions (a11y用、視覚的には非表示)
   "dialog.description.share": "Share current view by URL",
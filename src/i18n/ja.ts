@@ -120,11 +120,23 @@ export const ja: Dictionary = {
   "settings.generate": "生成",
   "settings.useWasm": "Reference Orbit計算にWasmを使用",
   "settings.useWasmTooltip": "10倍ほど高速になるのでON推奨",
+  "settings.renderStrategy": "計算順",
+  "settings.renderStrategy.scan": "全ピクセルを計算",
+  "settings.renderStrategy.solidGuessing": "矩形分割で推測",
+  "settings.renderStrategy.boundaryTracing": "境界追跡",
+  "settings.renderStrategyTooltip":
+    "推測と境界追跡は同じiterationに囲まれた領域を計算しない。高速になるが、細い構造を見落とすことがある",
 
   // supersampling
   "supersampling.result": "Supersamplingの結果",
   "supersampling.actualSize": "原寸で表示",
   "supersampling.fitToScreen": "全体を表示",
+  "supersampling.samples": "1ピクセルあたりのサンプル数",
+  "supersampling.pattern": "サンプル配置",
+  "supersampling.pattern.grid": "格子",
+  "supersampling.pattern.rotatedGrid": "回転格子",
+  "supersampling.pattern.jittered": "ジッター",
+  "supersampling.adaptive": "境界だけサンプリング",
 
   // dialog descriptions (a11y用、視覚的には非表示)
   "dialog.description.share": "現在の表示をURLで共有",
@@ -26,9 +26,16 @@ import {
   getWholeCanvasRect,
 } from "./rendering/renderer";
 import { getStore } from "./store/store";
+import type { SuperSamplingParams } from "./types";
 import { getWorkerCount } from "./worker-pool/pool-instance";
 import { cancelBatch, registerBatch, startBatch } from "./worker-pool/worker-pool";
 
+/** jitteredのsupersamplingで使うseed。固定しておけば同じ設定で同じ画像になる */
+const SUPERSAMPLING_SEED = 1;
+
+/** 1 jobあたりのサンプル数がこの倍率を超えないように、supersampling時は描画範囲を細かく分ける */
+const MAX_SUPERSAMPLING_RECT_DIVISION = 16;
+
 /**
  * 現在のパラメータと設定で計算を開始する
  */
@@ -49,6 +56,14 @@ export const startCalculation = async (
 
   const supersamplingWidth = getStore("supersamplingWidth");
   const supersamplingHeight = getStore("supersamplingHeight");
+  const superSampling: SuperSamplingParams | undefined = isSuperSampling
+    ? {
+        samplesPerAxis: getStore("supersamplingSamplesPerAxis"),
+        pattern: getStore("supersamplingPattern"),
+        seed: SUPERSAMPLING_SEED,
+        adaptive: getStore("supersamplingAdaptive"),
+      }
+    : undefined;
 
   if (isSuperSampling) {
     // supersampling用のcanvasを用意
@@ -88,7 +103,9 @@ export const startCalculation = async (
   addTraceEvent("renderer", { type: "flushed", elapsed: Math.floor(flushElapsed) });
 
   // workerに分配するために描画範囲を分割
-  const divideRectCount = getWorkerCount("calc-iteration") * (isSuperSampling ? 4 : 1);
+  const samplesPerPixel = superSampling ? superSampling.samplesPerAxis ** 2 : 1;
+  const divideRectCount =
+    getWorkerCount("calc-iteration") * Math.min(samplesPerPixel, MAX_SUPERSAMPLING_RECT_DIVISION);
   const currentParams = { ...getCurrentParams(), isSuperSampling };
   const calculationRects = getCalculationTargetRects(
     canvasWidth,
@@ -114,6 +131,10 @@ export const startCalculation = async (
     pixelWidth: canvasWidth,
     pixelHeight: canvasHeight,
     terminator,
+    superSampling,
+    renderStrategy: getStore("renderStrategy"),
+    // Debug Mode中は埋めたピクセルも計算して、埋め間違いがないか確かめる
+    checksGuesses: getStore("isDebugMode"),
     flushElapsed,
   });
 };
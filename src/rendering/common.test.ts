@@ -83,5 +83,16 @@ describe("bufferLocalLogicalIndex", () => {
       );
       expect(result).toEqual([9898, 9899, 9998, 9999]);
     });
+
+    it("3x3サンプルの場合は9点を返す", () => {
+      const result = bufferLocalLogicalIndex(
+        1,
+        1,
+        { x: 0, y: 0, width: 10, height: 10 },
+        { width: 30, height: 30 },
+        true,
+      );
+      expect(result).toEqual([93, 94, 95, 123, 124, 125, 153, 154, 155]);
+    });
   });
 });
@@ -110,11 +110,14 @@ export const bufferLocalLogicalIndex = (
   if (!isSuperSampled) {
     return [scaledX + scaledY * resolution.width];
   } else {
-    const idx00 = scaledX + scaledY * resolution.width;
-    const idx10 = scaledX + 1 + scaledY * resolution.width;
-    const idx01 = scaledX + (scaledY + 1) * resolution.width;
-    const idx11 = scaledX + 1 + (scaledY + 1) * resolution.width;
-    return [idx00, idx10, idx01, idx11];
+    // 1ピクセル分のサンプル (ratioX x ratioY) のindexを行ごとに並べる
+    const indices: number[] = [];
+    for (let sampleY = 0; sampleY < ratioY; sampleY++) {
+      for (let sampleX = 0; sampleX < ratioX; sampleX++) {
+        indices.push(scaledX + sampleX + (scaledY + sampleY) * resolution.width);
+      }
+    }
+    return indices;
   }
 };
 
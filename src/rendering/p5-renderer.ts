@@ -478,40 +478,26 @@ const renderIterationsToPixelSupersampled = (
             const scaledX = Math.floor(localX * ratioX);
             const scaledY = Math.floor(localY * ratioY);
 
+            // 1ピクセル分のサンプル (ratioX x ratioY) の色を平均する。
+            // wasm側で平均済みのiterationが返ってきた場合はratioが1になる
             let r = 0;
             let g = 0;
             let b = 0;
-            let iteration = 0;
-
-            // supersamplingの場合は4点平均
-            const idx00 = scaledX + scaledY * resolution.width;
-            const idx10 = scaledX + 1 + scaledY * resolution.width;
-            const idx01 = scaledX + (scaledY + 1) * resolution.width;
-            const idx11 = scaledX + 1 + (scaledY + 1) * resolution.width;
-
-            iteration = Math.round(
-              (buffer[idx00] + buffer[idx10] + buffer[idx01] + buffer[idx11]) / 4,
-            );
-
-            const r00 = palette.r(buffer[idx00]);
-            const g00 = palette.g(buffer[idx00]);
-            const b00 = palette.b(buffer[idx00]);
-
-            const r10 = palette.r(buffer[idx10]);
-            const g10 = palette.g(buffer[idx10]);
-            const b10 = palette.b(buffer[idx10]);
-
-            const r01 = palette.r(buffer[idx01]);
-            const g01 = palette.g(buffer[idx01]);
-            const b01 = palette.b(buffer[idx01]);
-
-            const r11 = palette.r(buffer[idx11]);
-            const g11 = palette.g(buffer[idx11]);
-            const b11 = palette.b(buffer[idx11]);
-
-            r = (r00 + r10 + r01 + r11) / 4;
-            g = (g00 + g10 + g01 + g11) / 4;
-            b = (b00 + b10 + b01 + b11) / 4;
+            let iterationSum = 0;
+            for (let sampleY = 0; sampleY < ratioY; sampleY++) {
+              for (let sampleX = 0; sampleX < ratioX; sampleX++) {
+                const n = buffer[scaledX + sampleX + (scaledY + sampleY) * resolution.width];
+                r += palette.r(n);
+                g += palette.g(n);
+                b += palette.b(n);
+                iterationSum += n;
+              }
+            }
+            const sampleCount = ratioX * ratioY;
+            const iteration = Math.round(iterationSum / sampleCount);
+            r /= sampleCount;
+            g /= sampleCount;
+            b /= sampleCount;
 
             if (iteration !== params.N) {
               pixelData[pixelIndex + 0] = r;
@@ -1,6 +1,6 @@
 import type { Locale } from "../i18n/types";
 import type { InterestingPointsDebugData } from "../interesting-points/find-interesting-points";
-import type { POIData, ResultSpans } from "../types";
+import type { POIData, RenderStrategy, ResultSpans, SamplePattern } from "../types";
 import BigNumber from "bignumber.js";
 import { eventmit } from "eventmit";
 import { useEffect, useState } from "react";
@@ -44,6 +44,8 @@ type Store = {
   // mandelbrot state
   /** reference orbit計算にwasmを使うかどうか */
   useWasm: boolean;
+  /** band内のピクセルの計算順。scan以外は一部のピクセルを計算せずに埋める */
+  renderStrategy: RenderStrategy;
   /** 手動指定の limb 数 override（null なら自動計算） */
   manualLimbsOverride: number | null;
 
@@ -56,6 +58,11 @@ type Store = {
   // supersampling settings
   supersamplingWidth: number;
   supersamplingHeight: number;
+  /** 1軸あたりのサンプル数 */
+  supersamplingSamplesPerAxis: number;
+  supersamplingPattern: SamplePattern;
+  /** 境界付近のピクセルだけを細かく計算するか */
+  supersamplingAdaptive: boolean;
 
   // state
   progress: string | ResultSpans;
@@ -100,6 +107,7 @@ const store: Store = {
   poiDrawerSnap: "closed",
   // mandelbrot state
   useWasm: true,
+  renderStrategy: "scan",
   manualLimbsOverride: null,
   // palette settings
   paletteId: "d3-chromatic,RdYlBlu,1",
@@ -109,6 +117,9 @@ const store: Store = {
   // supersampling settings
   supersamplingWidth: 1920,
   supersamplingHeight: 1080,
+  supersamplingSamplesPerAxis: 2,
+  supersamplingPattern: "grid",
+  supersamplingAdaptive: false,
   // state
   progress: "",
   nHitRatio: null,
@@ -2,6 +2,7 @@ import { getStore } from "../store";
 
 import type { Locale } from "../../i18n/types";
 import type { RendererType } from "../../rendering/common";
+import type { RenderStrategy, SamplePattern } from "../../types";
 
 export type Settings = {
   locale: Locale;
@@ -13,6 +14,9 @@ export type Settings = {
   rendererType: RendererType;
   supersamplingWidth: number;
   supersamplingHeight: number;
+  supersamplingSamplesPerAxis: number;
+  supersamplingPattern: SamplePattern;
+  supersamplingAdaptive: boolean;
   showInterestingPoints: boolean;
   /** 常にIP debugデータを計算するか */
   alwaysComputeIPDebugData: boolean;
@@ -20,6 +24,8 @@ export type Settings = {
   debugModeTab: string;
   /** reference orbit計算にwasmを使うかどうか */
   useWasm: boolean;
+  /** band内のピクセルの計算順 */
+  renderStrategy: RenderStrategy;
 };
 
 export const DEFAULT_WORKER_COUNT =
@@ -39,10 +45,14 @@ const defaultSettings = {
   rendererType: "p5js" as RendererType,
   supersamplingWidth: 1920,
   supersamplingHeight: 1080,
+  supersamplingSamplesPerAxis: 2,
+  supersamplingPattern: "grid" as SamplePattern,
+  supersamplingAdaptive: false,
   showInterestingPoints: false,
   alwaysComputeIPDebugData: false,
   debugModeTab: "batch-render",
   useWasm: true,
+  renderStrategy: "scan" as RenderStrategy,
 } satisfies Settings;
 
 export const isSettingField = (key: string): key is keyof Settings => key in defaultSettings;
@@ -58,10 +68,14 @@ export const writeSettingsToStorage = () => {
     rendererType: getStore("rendererType"),
     supersamplingWidth: getStore("supersamplingWidth"),
     supersamplingHeight: getStore("supersamplingHeight"),
+    supersamplingSamplesPerAxis: getStore("supersamplingSamplesPerAxis"),
+    supersamplingPattern: getStore("supersamplingPattern"),
+    supersamplingAdaptive: getStore("supersamplingAdaptive"),
     showInterestingPoints: getStore("showInterestingPoints"),
     alwaysComputeIPDebugData: getStore("alwaysComputeIPDebugData"),
     debugModeTab: getStore("debugModeTab"),
     useWasm: getStore("useWasm"),
+    renderStrategy: getStore("renderStrategy"),
   } satisfies Settings;
 
   const serialized = JSON.stringify(settings);
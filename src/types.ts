@@ -62,6 +62,11 @@ export interface IterationWorkerParams {
   r: string;
   N: number;
   isSuperSampling: boolean;
+  /** isSuperSamplingのときのサンプルの取り方。なければ2x2の格子 */
+  superSampling?: SuperSamplingParams;
+  renderStrategy: RenderStrategy;
+  /** 埋めたピクセルも計算して埋め間違いを数えるか (Debug Mode用) */
+  checksGuesses: boolean;
   startX: number;
   endX: number;
   startY: number;
@@ -95,6 +100,30 @@ export interface RefOrbitWorkerParams {
   limbCountOverride: number | null;
 }
 
+/** supersamplingのサンプル配置。wasm-iterのbegin_sampling_passに渡すpatternの順と一致させる */
+export const samplePatterns = ["grid", "rotated-grid", "jittered"] as const;
+export type SamplePattern = (typeof samplePatterns)[number];
+
+export interface SuperSamplingParams {
+  /** 1軸あたりのサンプル数。1ピクセルあたり samplesPerAxis^2 サンプル */
+  samplesPerAxis: number;
+  pattern: SamplePattern;
+  /** jitteredのときの乱数seed。同じseedなら同じ画像になる */
+  seed: number;
+  /**
+   * trueなら全ピクセルを1サンプルで計算してから、周りとiterationが大きく違うピクセルだけを
+   * samplesPerAxis^2 サンプルで計算し直す (adaptive supersampling)
+   */
+  adaptive: boolean;
+}
+
+/**
+ * band内のピクセルをどの順に計算するか。wasm-iterのset_render_strategyに渡す値の順と一致させる。
+ * scan以外は周りと同じiterationになる領域を計算せずに埋める
+ */
+export const renderStrategies = ["scan", "solid-guessing", "boundary-tracing"] as const;
+export type RenderStrategy = (typeof renderStrategies)[number];
+
 export const mandelbrotWorkerTypes = ["normal", "perturbation"] as const;
 export type MandelbrotWorkerType = (typeof mandelbrotWorkerTypes)[number];
 
@@ -152,6 +181,10 @@ export interface BatchContext {
   xn?: XnBuffer;
   blaTable?: BLATableBuffer;
   terminator: SharedArrayBuffer;
+  /** supersamplingのバッチのみ。バッチ開始時点の設定を全jobで使う */
+  superSampling?: SuperSamplingParams;
+  renderStrategy: RenderStrategy;
+  checksGuesses: boolean;
 
   /** iteration cacheのtranslateとGPUへのflushにかかった時間 */
   flushElapsed: number;
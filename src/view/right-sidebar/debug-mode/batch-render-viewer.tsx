@@ -21,6 +21,13 @@ const WORKER_COLORS = [
   "#f97316", // orange
 ];
 
+/** 値を決めたピクセルのうち、埋めずに実際にiterationを回したものの割合 */
+const formatIteratedRatio = ({ iteratedCount, guessedCount }: WorkerRenderArea) => {
+  const pixels = iteratedCount + guessedCount;
+  if (pixels === 0) return "-";
+  return `${((iteratedCount / pixels) * 100).toFixed(1)}%`;
+};
+
 const BatchMinimap = ({ entry, index }: { entry: BatchRenderEntry; index: number }) => {
   const [hoveredWorker, setHoveredWorker] = useState<WorkerRenderArea | null>(null);
   const canvasSize = getCanvasSize();
@@ -144,6 +151,13 @@ const BatchMinimap = ({ entry, index }: { entry: BatchRenderEntry; index: number
               Size: {hoveredWorker.rect.width.toFixed(1)}×{hoveredWorker.rect.height.toFixed(1)}
             </div>
             <div>Elapsed: {hoveredWorker.elapsed.toFixed(1)}ms</div>
+            <div>
+              Iterated: {formatIteratedRatio(hoveredWorker)} (guessed{" "}
+              {hoveredWorker.guessedCount}px
+              {hoveredWorker.mistakenGuessCount > 0 &&
+                `, ${hoveredWorker.mistakenGuessCount}px wrong`}
+              )
+            </div>
             <div className="absolute bottom-full left-4 h-0 w-0 border-r-4 border-b-4 border-l-4 border-transparent border-b-black" />
           </div>
         )}
@@ -22,6 +22,7 @@ import { Switch } from "../../shadcn/components/ui/switch";
 import { toast } from "sonner";
 import { updateStore, updateStoreWith, useStoreValue } from "../../store/store";
 import { DEFAULT_WORKER_COUNT } from "../../store/sync-storage/settings";
+import { renderStrategies, type RenderStrategy } from "../../types";
 import { useIsMobile } from "../use-is-mobile";
 import { prepareWorkerPool } from "../../worker-pool/pool-instance";
 import { IconHelp, IconSettings } from "@tabler/icons-react";
@@ -61,6 +62,13 @@ const RenderingSection = () => {
   const workerCount = useStoreValue("workerCount");
   const maxCanvasSize = useStoreValue("maxCanvasSize");
   const useWasm = useStoreValue("useWasm");
+  const renderStrategy = useStoreValue("renderStrategy");
+
+  const renderStrategyLabels: Record<RenderStrategy, string> = {
+    scan: t("Scan every pixel", "settings.renderStrategy.scan"),
+    "solid-guessing": t("Solid guessing", "settings.renderStrategy.solidGuessing"),
+    "boundary-tracing": t("Boundary tracing", "settings.renderStrategy.boundaryTracing"),
+  };
 
   const [webGPUSupported, setWebGPUSupported] = useState(false);
   const [rendererType, setRendererType] = useState<RendererType>("p5js");
@@ -125,6 +133,32 @@ const RenderingSection = () => {
         </FieldDescription>
       </div>
 
+      <div>
+        <div className="mb-2 ml-2 text-sm">{t("Render Strategy")}</div>
+        <RadioGroup
+          value={renderStrategy}
+          onValueChange={(value: RenderStrategy) => updateStore("renderStrategy", value)}
+          className="flex flex-col space-y-1"
+        >
+          {renderStrategies.map((strategy) => (
+            <div key={strategy} className="flex items-center space-x-2">
+              <RadioGroupItem value={strategy} id={`settings-render-strategy-${strategy}`} />
+              <Label
+                htmlFor={`settings-render-strategy-${strategy}`}
+                className="cursor-pointer text-sm"
+              >
+                {renderStrategyLabels[strategy]}
+              </Label>
+            </div>
+          ))}
+        </RadioGroup>
+        <FieldDescription>
+          {t(
+            "Guessing and tracing skip areas surrounded by the same iteration. Faster, but may miss thin details.",
+          )}
+        </FieldDescription>
+      </div>
+
       <div>
         <div className="mb-1 ml-2 text-sm">
           {t("Max Canvas Size")}: {maxCanvasSizePreview}
@@ -10,7 +10,9 @@ import {
   SelectTrigger,
   SelectValue,
 } from "../../shadcn/components/ui/select";
+import { Switch } from "../../shadcn/components/ui/switch";
 import { updateStore, useStoreValue } from "../../store/store";
+import { samplePatterns, type SamplePattern } from "../../types";
 import { Expand } from "lucide-react";
 import { useState } from "react";
 
@@ -18,6 +20,9 @@ const MIN_SIZE = 100;
 const MAX_WIDTH = 7680;
 const MAX_HEIGHT = 4320;
 
+/** 1軸あたりのサンプル数の選択肢。2なら従来の2x2 */
+const SAMPLES_PER_AXIS_OPTIONS = [2, 3, 4, 6, 8];
+
 /** サイズをmin/max範囲にclampする */
 const clampSize = (value: number, min: number, max: number): number =>
   Math.max(min, Math.min(max, value));
@@ -76,6 +81,15 @@ export const SupersamplingForm = ({ onAfterGenerate }: { onAfterGenerate?: () =>
   const [width, setWidth] = useState(storedWidth);
   const [height, setHeight] = useState(storedHeight);
   const [presetKey, setPresetKey] = useState(() => findPresetKey(storedWidth, storedHeight));
+  const samplesPerAxis = useStoreValue("supersamplingSamplesPerAxis");
+  const samplePattern = useStoreValue("supersamplingPattern");
+  const isAdaptive = useStoreValue("supersamplingAdaptive");
+
+  const patternLabels: Record<SamplePattern, string> = {
+    grid: t("Grid", "supersampling.pattern.grid"),
+    "rotated-grid": t("Rotated Grid", "supersampling.pattern.rotatedGrid"),
+    jittered: t("Jittered", "supersampling.pattern.jittered"),
+  };
 
   /** プリセット選択時のハンドラ */
   const handlePresetChange = (key: string) => {
@@ -171,6 +185,59 @@ export const SupersamplingForm = ({ onAfterGenerate }: { onAfterGenerate?: () =>
         />
       </div>
 
+      <Label className="text-xs text-muted-foreground">
+        {t("Samples per Pixel", "supersampling.samples")}
+      </Label>
+
+      <Select
+        value={String(samplesPerAxis)}
+        onValueChange={(value) =>
+          updateStore("supersamplingSamplesPerAxis", Number.parseInt(value, 10))
+        }
+      >
+        <SelectTrigger className="h-8 text-xs">
+          <SelectValue />
+        </SelectTrigger>
+        <SelectContent>
+          {SAMPLES_PER_AXIS_OPTIONS.map((n) => (
+            <SelectItem key={n} value={String(n)}>
+              {`${n}x${n} (${n * n})`}
+            </SelectItem>
+          ))}
+        </SelectContent>
+      </Select>
+
+      <Label className="text-xs text-muted-foreground">
+        {t("Sample Pattern", "supersampling.pattern")}
+      </Label>
+
+      <Select
+        value={samplePattern}
+        onValueChange={(value) => updateStore("supersamplingPattern", value as SamplePattern)}
+      >
+        <SelectTrigger className="h-8 text-xs">
+          <SelectValue />
+        </SelectTrigger>
+        <SelectContent>
+          {samplePatterns.map((pattern) => (
+            <SelectItem key={pattern} value={pattern}>
+              {patternLabels[pattern]}
+            </SelectItem>
+          ))}
+        </SelectContent>
+      </Select>
+
+      <div className="flex items-center space-x-2">
+        <Switch
+          id="supersampling-adaptive"
+          checked={isAdaptive}
+          onCheckedChange={(checked) => updateStore("supersamplingAdaptive", checked)}
+        />
+        <Label htmlFor="supersampling-adaptive" className="text-xs">
+          {t("Sample only edges", "supersampling.adaptive")}
+        </Label>
+      </div>
+
       <Button size="sm" onClick={handleGenerate}>
         <Expand className="mr-1 size-4" />
         {t("Generate", "settings.generate")}
@@ -26,7 +26,8 @@ export const onIterationWorkerProgress: IterationProgressCallback = (result, job
 };
 
 export const onIterationWorkerResult: IterationResultCallback = (result, job) => {
-  const { iterations, resolution, elapsed, hitCount } = result;
+  const { iterations, resolution, elapsed, hitCount, guessedCount, iteratedCount } = result;
+  const { mistakenGuessCount } = result;
   const { rect } = job;
   const batchContext = getBatchContext(job.batchId);
 
@@ -53,7 +54,15 @@ export const onIterationWorkerResult: IterationResultCallback = (result, job) =>
     elapsed: Math.floor(elapsed),
   });
 
-  recordWorkerResult(job.batchId, getWorkerId(job), job.workerIdx ?? 0, rect, elapsed);
+  if (mistakenGuessCount > 0) {
+    console.warn(`${job.id}: ${mistakenGuessCount} guessed pixels differ from the iterated ones`);
+  }
+
+  recordWorkerResult(job.batchId, getWorkerId(job), job.workerIdx ?? 0, rect, elapsed, {
+    guessedCount,
+    iteratedCount,
+    mistakenGuessCount,
+  });
 
   batchContext.onChangeProgress();
 
@@ -1,3 +1,4 @@
+import { debounce } from "es-toolkit";
 import { getStore, updateStore } from "../store/store";
 import type { JobType, MandelbrotWorkerType } from "../types";
 import { type MandelbrotJob } from "../types";
@@ -11,7 +12,7 @@ import {
   onRefOrbitWorkerResult,
   onRefOrbitWorkerTerminated,
 } from "./callbacks/ref-orbit-worker";
-import { clearTaskQueue } from "./task-queue";
+import { clearTaskQueue, hasRunningJob, hasWaitingJob } from "./task-queue";
 import type { MandelbrotFacadeLike } from "./worker-facade";
 import { CalcIterationWorker, RefOrbitWorker } from "./worker-facade";
 import { clearBatchContext, tickWorkerPool, tickWorkerPoolThrottled } from "./worker-pool";
@@ -19,6 +20,11 @@ import { clearWorkerReference } from "./worker-reference";
 
 type WorkerPool = MandelbrotFacadeLike[];
 
+/** 全jobが終わってからこの時間何も来なければ、大きくなったworkerを作り直す */
+const IDLE_TRIM_DELAY_MS = 10_000;
+/** これを超えるwasm memoryを抱えたままのworkerを作り直す */
+const IDLE_WORKER_MEMORY_LIMIT_BYTES = 256 * 1024 * 1024;
+
 const pool: Map<JobType, WorkerPool> = new Map([
   ["calc-iteration", []],
   ["calc-ref-orbit", []],
@@ -91,6 +97,33 @@ export const getWorkerId = (job: MandelbrotJob): string => {
   return `iteration-${job.workerIdx}`;
 };
 
+/**
+ * 空いているiteration workerのうち、wasm memoryが大きくなったものを作り直す
+ *
+ * 8K supersamplingのような大きなjobのあとは、wasm-iterのバッファが伸びたままになる。
+ * wasmのlinear memoryは縮められないので、worker自体を作り直して手放す
+ */
+export function trimIdleIterationWorkers(limitBytes: number = IDLE_WORKER_MEMORY_LIMIT_BYTES) {
+  for (const worker of getWorkerPool("calc-iteration")) {
+    if (!(worker instanceof CalcIterationWorker)) continue;
+    if (worker.isRunning() || worker.memoryBytes <= limitBytes) continue;
+
+    console.info("Recycle idle iteration worker", {
+      memoryBytes: worker.memoryBytes,
+      bufferBytes: worker.bufferBytes,
+    });
+    worker.recycle();
+  }
+}
+
+/**
+ * jobがなくなったときに呼ぶ。しばらく新しいjobが来なければtrimIdleIterationWorkersする
+ */
+export const scheduleIdleWorkerTrim = debounce(() => {
+  if (hasRunningJob() || hasWaitingJob()) return;
+  trimIdleIterationWorkers();
+}, IDLE_TRIM_DELAY_MS);
+
 /**
  * WorkerPoolを再構築する
  * countやworkerTypeが変わった場合に呼ばれる
@@ -23,6 +23,16 @@ export interface IterationResult {
   elapsed: number;
   /** iterationがmaxIterationに到達したピクセル数。worker側のループ内で数えている */
   hitCount: number;
+  /** solid guessing / boundary tracingで計算せずに埋めたピクセル数 */
+  guessedCount: number;
+  /** 実際にiterationを回したピクセル数 */
+  iteratedCount: number;
+  /** 埋めたピクセルのうち計算した値と違っていた数。checksGuessesのときだけ数える */
+  mistakenGuessCount: number;
+  /** wasm-iterを使うworkerのみ。wasmのlinear memoryのサイズ */
+  memoryBytes?: number;
+  /** wasm-iterを使うworkerのみ。wasm-iterが確保しているバッファの合計 */
+  bufferBytes?: number;
 }
 
 export type RefOrbitResultCallback = (result: RefOrbitContext, job: CalcRefOrbitJob) => void;
@@ -57,17 +67,36 @@ export interface MandelbrotFacadeLike {
 
 export class CalcIterationWorker implements MandelbrotFacadeLike {
   worker: Worker;
+  workerType: MandelbrotWorkerType;
   running = false;
+  /** 直近のresultで報告されたwasmのlinear memoryのサイズ。wasmを使わないworkerでは0のまま */
+  memoryBytes = 0;
+  bufferBytes = 0;
 
   resultCallback?: IterationResultCallback;
   intermediateResultCallback?: IterationIntermediateResultCallback;
   progressCallback?: IterationProgressCallback;
 
   constructor(workerType: MandelbrotWorkerType) {
+    this.workerType = workerType;
     const workerConstructor = workerPaths[workerType];
     this.worker = new workerConstructor();
   }
 
+  /**
+   * workerを作り直してwasmのlinear memoryを手放す
+   * wasmのmemoryは一度growすると縮まないので、大きなjobのあとで空いているworkerに使う
+   */
+  recycle = () => {
+    if (this.running) return;
+
+    this.worker.terminate();
+    const workerConstructor = workerPaths[this.workerType];
+    this.worker = new workerConstructor();
+    this.memoryBytes = 0;
+    this.bufferBytes = 0;
+  };
+
   isRunning = () => {
     return this.running;
   };
@@ -86,9 +115,31 @@ export class CalcIterationWorker implements MandelbrotFacadeLike {
 
       switch (data.type) {
         case "result": {
-          const { iterations, resolution, elapsed, hitCount } = data;
-
-          this.resultCallback?.({ type: "result", iterations, resolution, elapsed, hitCount }, job);
+          const {
+            iterations,
+            resolution,
+            elapsed,
+            hitCount,
+            guessedCount,
+            iteratedCount,
+            mistakenGuessCount,
+          } = data;
+          this.memoryBytes = data.memoryBytes ?? 0;
+          this.bufferBytes = data.bufferBytes ?? 0;
+
+          this.resultCallback?.(
+            {
+              type: "result",
+              iterations,
+              resolution,
+              elapsed,
+              hitCount,
+              guessedCount,
+              iteratedCount,
+              mistakenGuessCount,
+            },
+            job,
+          );
 
           this.worker.removeEventListener("message", f);
           this.running = false;
@@ -122,8 +173,19 @@ export class CalcIterationWorker implements MandelbrotFacadeLike {
     };
 
     const { rect, id } = job;
-    const { pixelHeight, pixelWidth, xn, blaTable, refX, refY, terminator, mandelbrotParams } =
-      batchContext;
+    const {
+      pixelHeight,
+      pixelWidth,
+      xn,
+      blaTable,
+      refX,
+      refY,
+      terminator,
+      mandelbrotParams,
+      superSampling,
+      renderStrategy,
+      checksGuesses,
+    } = batchContext;
 
     this.worker.addEventListener("message", f);
 
@@ -137,6 +199,9 @@ export class CalcIterationWorker implements MandelbrotFacadeLike {
       r: mandelbrotParams.r.toString(),
       N: mandelbrotParams.N,
       isSuperSampling: mandelbrotParams.isSuperSampling ?? false,
+      superSampling,
+      renderStrategy,
+      checksGuesses,
       pixelHeight,
       pixelWidth,
       startX: rect.x,
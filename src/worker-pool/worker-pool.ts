@@ -14,6 +14,7 @@ import {
   findFreeWorkerIndex,
   getWorkerId,
   getWorkerPool,
+  scheduleIdleWorkerTrim,
 } from "./pool-instance";
 import { getRefOrbitCacheIfAvailable } from "./ref-orbit-cache";
 import {
@@ -274,6 +275,10 @@ export function tickWorkerPool() {
       waiting: countWaitingJobs(),
     });
   }
+
+  if (!hasRunningJob() && countWaitingJobs() === 0) {
+    scheduleIdleWorkerTrim();
+  }
 }
 export const tickWorkerPoolThrottled = throttle(tickWorkerPool, 100);
 
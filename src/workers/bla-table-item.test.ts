@@ -0,0 +1,80 @@
+import { readFileSync } from "node:fs";
+import { beforeAll, describe, expect, it } from "vitest";
+import {
+  BLATableView,
+  calcBLACoefficient,
+  encodeBlaTableItems,
+  ITEM_BYTE_LENGTH,
+  toBlaTableBuffer,
+} from "./bla-table-item";
+import { calc_bla_table, initSync } from "../../wasm-fp/pkg/apfp.js";
+
+/**
+ * BLATable の producer は wasm-fp (`calc_bla_table`) と、wasm が使えないときの JS fallback
+ * (`calcBLACoefficient` + `encodeBlaTableItems`) の 2 つある。同じテーブルを作ることを確かめる。
+ */
+
+/** c を中心とした reference orbit を double で計算して [re, im, ...] で返す */
+const createXn = (cRe: number, cIm: number, length: number): Float64Array => {
+  const xn = new Float64Array(length * 2);
+  let zRe = 0;
+  let zIm = 0;
+  for (let i = 0; i < length; i++) {
+    xn[i * 2] = zRe;
+    xn[i * 2 + 1] = zIm;
+    const nextRe = zRe * zRe - zIm * zIm + cRe;
+    zIm = 2 * zRe * zIm + cIm;
+    zRe = nextRe;
+  }
+  return xn;
+};
+
+/**
+ * 全要素の値を [a.re, a.im, b.re, b.im, r², l, ...] で並べる。
+ * 発散した orbit では NaN になる要素があり、NaN の bit 列は producer ごとに違いうるので値で比べる
+ */
+const readItems = (table: BLATableView): number[] => {
+  const values: number[] = [];
+  for (let row = 0; row < table.length; row++) {
+    const rowOffset = table.rowOffsets[row * 2];
+    for (let column = 0; column < table.rowOffsets[row * 2 + 1]; column++) {
+      const byteOffset = rowOffset + column * ITEM_BYTE_LENGTH;
+      for (let field = 0; field < 5; field++) {
+        values.push(table.view.getFloat64(byteOffset + field * 8, true));
+      }
+      values.push(table.view.getInt32(byteOffset + 40, true));
+    }
+  }
+  return values;
+};
+
+beforeAll(() => {
+  const wasmPath = new URL("../../public/wasm/apfp_bg.wasm", import.meta.url);
+  initSync({ module: readFileSync(wasmPath) });
+});
+
+describe("BLATable producers", () => {
+  it.each([
+    // 集合の内側に留まる orbit
+    { cRe: -0.1, cIm: 0.1, length: 4096, pixelSpacing: 1e-8 },
+    // 途中で発散して inf / NaN を含む orbit
+    { cRe: -0.7451, cIm: 0.11302, length: 5000, pixelSpacing: 1e-6 },
+    { cRe: -1.25066, cIm: 0.02012, length: 1000, pixelSpacing: 1e-10 },
+    // 要素数が 2 の冪でなく、余りの要素をそのまま次の行に送る orbit
+    { cRe: 0.3, cIm: 0.5, length: 37, pixelSpacing: 1e-3 },
+    // 捨てる行しかない短い orbit
+    { cRe: -0.75, cIm: 0.15, length: 2, pixelSpacing: 1e-4 },
+  ])("JS fallback が wasm-fp と同じテーブルを作る (%o)", ({ cRe, cIm, length, pixelSpacing }) => {
+    const xn = createXn(cRe, cIm, length);
+
+    const wasm = new BLATableView(toBlaTableBuffer(calc_bla_table(xn, pixelSpacing)));
+    const js = new BLATableView(
+      encodeBlaTableItems(calcBLACoefficient(xn, xn.length / 2, pixelSpacing)),
+    );
+
+    expect(js.view.byteLength).toBe(wasm.view.byteLength);
+    expect(js.length).toBe(wasm.length);
+    expect(Array.from(js.rowOffsets)).toEqual(Array.from(wasm.rowOffsets));
+    expect(readItems(js)).toEqual(readItems(wasm));
+  });
+});
@@ -1,5 +1,9 @@
-import type { Complex } from "../math/complex";
+import { add, mul, norm, type Complex } from "../math/complex";
 
+/**
+ * 1要素のバイト数。
+ * producer (wasm-fp の `bla::ITEM_BYTE_LENGTH`) と consumer (wasm-iter の `ITEM_BYTE_LENGTH`) と一致させる
+ */
 export const ITEM_BYTE_LENGTH = 44;
 
 /*
@@ -9,6 +13,8 @@ export const ITEM_BYTE_LENGTH = 44;
  * 現状は2。ここまでは試してみてほとんど速度変わらなかったから。
  * 4にすると地点によるがN=200000で300msくらい落ちる
  * たぶん
+ *
+ * テーブルを作るのは wasm-fp なので、変えるときは `bla::SKIP_BLA_ENTRY_UNTIL_THIS_L` も揃えること
  */
 export const SKIP_BLA_ENTRY_UNTIL_THIS_L = 2;
 
@@ -21,6 +27,83 @@ export type BLATableItem = {
 
 // このファイルはほとんどChatGPTくんによって生成されました
 
+/**
+ * wasm-fp の `calc_bla_table` が返したバイト列を、worker間で共有できるbufferに載せ替える
+ */
+export function toBlaTableBuffer(bytes: Uint8Array): SharedArrayBuffer {
+  const buffer = new SharedArrayBuffer(bytes.byteLength);
+  new Uint8Array(buffer).set(bytes);
+  return buffer;
+}
+
+/**
+ * 計算済みのReference OrbitからBLAの係数を計算する
+ *
+ * xn は [re_0, im_0, ...] レイアウトのFloat64Array。refLen = xn.length / 2。
+ * wasm が使えないときの fallback。wasm-fp の `bla::build_bla_table` と同じテーブルを作ること
+ * (`bla-table-item.test.ts` で比べている)。
+ */
+export function calcBLACoefficient(xn: Float64Array, refLen: number, pixelSpacing: number) {
+  // Reference: https://mathr.co.uk/tmp/mandelbla.pdf
+
+  const blaTable: BLATableItem[][] = [];
+
+  const eps = 0.0001;
+
+  blaTable[0] = Array.from({ length: refLen - 1 });
+  for (let i = 1; i < refLen; i++) {
+    const zRe = xn[i * 2];
+    const zIm = xn[i * 2 + 1];
+    const aRe = zRe * 2;
+    const aIm = zIm * 2;
+    const a = { re: aRe, im: aIm };
+    const b = { re: 1.0, im: 0.0 };
+
+    const absA = Math.sqrt(aRe * aRe + aIm * aIm);
+    const r = Math.max(0, (eps * absA - pixelSpacing) / (absA + 1));
+    blaTable[0][i - 1] = { a, b, r, l: 1 };
+  }
+
+  const max = Math.floor(Math.log2(refLen));
+
+  for (let d = 0; d <= max; d++) {
+    const nextTableLength = Math.floor((blaTable[d].length + 1) / 2);
+    blaTable[d + 1] = Array.from({ length: nextTableLength });
+
+    for (let j = 0; j < nextTableLength; j++) {
+      const jx = j * 2;
+      const jy = jx + 1;
+
+      if (jy < blaTable[d].length) {
+        const x = blaTable[d][jx];
+        const y = blaTable[d][jy];
+
+        const a = mul(y.a, x.a);
+        const b = add(mul(y.a, x.b), y.b);
+        const absXA = Math.sqrt(norm(x.a));
+        const absXB = Math.sqrt(norm(x.b));
+        const r = Math.min(x.r, Math.max(0, (y.r - absXB * pixelSpacing) / absXA));
+        blaTable[d + 1][j] = { a, b, r, l: x.l + y.l };
+      } else {
+        blaTable[d + 1][j] = blaTable[d][jx];
+      }
+    }
+    if (blaTable[d + 1].length === 1) break;
+  }
+
+  // スキップ量が少なくデータ量が多いエントリを抜いておく
+  for (let idx = 0; idx <= Math.log2(SKIP_BLA_ENTRY_UNTIL_THIS_L); idx++) {
+    blaTable[idx] = [];
+  }
+
+  // console.debug("blaTable", blaTable);
+
+  return blaTable;
+}
+
+/**
+ * wasm が使えないときに JS で作る BLATable を、wasm-fp と同じレイアウトにエンコードする
+ */
 export function encodeBlaTableItems(items: BLATableItem[][]): SharedArrayBuffer {
   // 行の数と、それぞれの行の要素数を格納するのに必要なバイト数を加算
   let totalSize = 4; // 最初の4バイトは行の数
@@ -1,26 +1,26 @@
 /// <reference lib="webworker" />
 
 import {
+  calcBLACoefficient,
   encodeBlaTableItems,
-  SKIP_BLA_ENTRY_UNTIL_THIS_L,
-  type BLATableItem,
+  toBlaTableBuffer,
   type BLATableView,
 } from "./bla-table-item";
 import type { ComplexArrayView } from "./xn-buffer";
 import { encodeFloat64AsXnBuffer } from "./xn-buffer";
 import BigNumber from "bignumber.js";
-import wasmInit, { calculate as wasmCalculate } from "../../wasm-fp/pkg/apfp.js";
+import wasmInit, {
+  calc_bla_table as wasmCalcBlaTable,
+  calculate as wasmCalculate,
+} from "../../wasm-fp/pkg/apfp.js";
 import { calcRequiredLimbs, clampLimbs } from "../math/calc-required-limbs";
 import type { ComplexArbitrary } from "../math/complex";
 import {
-  add,
   complexArbitary,
   dAdd,
   dNorm,
   dReduce,
   dSquare,
-  mul,
-  norm,
   pixelToComplexCoordinateComplexArbitrary,
   toComplex,
 } from "../math/complex";
@@ -106,66 +106,21 @@ function calcRefOrbit(
 }
 
 /**
- * 計算済みのReference OrbitからBLAの係数を計算する
+ * 計算済みのReference OrbitからBLATableを作る
  *
- * xn は [re_0, im_0, ...] レイアウトのFloat64Array。refLen = xn.length / 2。
+ * マージツリーの構築とエンコードは wasm-fp で行い、BLATableView がそのまま読めるレイアウトで受け取る。
+ * wasm が使えない場合は JS の calcBLACoefficient で同じテーブルを作る。
  */
-function calcBLACoefficient(xn: Float64Array, refLen: number, pixelSpacing: number) {
-  // Reference: https://mathr.co.uk/tmp/mandelbla.pdf
-
-  const blaTable: BLATableItem[][] = [];
-
-  const eps = 0.0001;
-
-  blaTable[0] = Array.from({ length: refLen - 1 });
-  for (let i = 1; i < refLen; i++) {
-    const zRe = xn[i * 2];
-    const zIm = xn[i * 2 + 1];
-    const aRe = zRe * 2;
-    const aIm = zIm * 2;
-    const a = { re: aRe, im: aIm };
-    const b = { re: 1.0, im: 0.0 };
-
-    const absA = Math.sqrt(aRe * aRe + aIm * aIm);
-    const r = Math.max(0, (eps * absA - pixelSpacing) / (absA + 1));
-    blaTable[0][i - 1] = { a, b, r, l: 1 };
-  }
-
-  const max = Math.floor(Math.log2(refLen));
-
-  for (let d = 0; d <= max; d++) {
-    const nextTableLength = Math.floor((blaTable[d].length + 1) / 2);
-    blaTable[d + 1] = Array.from({ length: nextTableLength });
-
-    for (let j = 0; j < nextTableLength; j++) {
-      const jx = j * 2;
-      const jy = jx + 1;
-
-      if (jy < blaTable[d].length) {
-        const x = blaTable[d][jx];
-        const y = blaTable[d][jy];
-
-        const a = mul(y.a, x.a);
-        const b = add(mul(y.a, x.b), y.b);
-        const absXA = Math.sqrt(norm(x.a));
-        const absXB = Math.sqrt(norm(x.b));
-        const r = Math.min(x.r, Math.max(0, (y.r - absXB * pixelSpacing) / absXA));
-        blaTable[d + 1][j] = { a, b, r, l: x.l + y.l };
-      } else {
-        blaTable[d + 1][j] = blaTable[d][jx];
-      }
+function calcBLATable(xn: Float64Array, pixelSpacing: number): SharedArrayBuffer {
+  if (wasmReady) {
+    try {
+      return toBlaTableBuffer(wasmCalcBlaTable(xn, pixelSpacing));
+    } catch (e) {
+      console.warn("Failed to calculate BLA table with wasm. Fallback.", e);
     }
-    if (blaTable[d + 1].length === 1) break;
   }
-
-  // スキップ量が少なくデータ量が多いエントリを抜いておく
-  for (let idx = 0; idx <= Math.log2(SKIP_BLA_ENTRY_UNTIL_THIS_L); idx++) {
-    blaTable[idx] = [];
-  }
-
-  // console.debug("blaTable", blaTable);
-
-  return blaTable;
+  const blaTable = calcBLACoefficient(xn, xn.length / 2, pixelSpacing);
+  return encodeBlaTableItems(blaTable);
 }
 
 /**
@@ -265,12 +220,10 @@ async function setup() {
         return;
       }
 
-      const refLen = xn.length / 2;
       const pixelSpacing = radius.toNumber() / Math.max(pixelWidth, pixelHeight);
-      const blaTable = calcBLACoefficient(xn, refLen, pixelSpacing);
 
       const xnConverted = encodeFloat64AsXnBuffer(xn);
-      const blaTableConverted = encodeBlaTableItems(blaTable);
+      const blaTableConverted = calcBLATable(xn, pixelSpacing);
 
       const elapsed = performance.now() - startedAt;
 
@@ -1,6 +1,7 @@
 import { readFileSync, writeFileSync } from "node:fs";
 import { beforeAll, describe, expect, it } from "vitest";
-import { BLATableView, ITEM_BYTE_LENGTH } from "./bla-table-item";
+import { BLATableView, ITEM_BYTE_LENGTH, toBlaTableBuffer } from "./bla-table-item";
+import { calc_bla_table, initSync as initApfpSync } from "../../wasm-fp/pkg/apfp.js";
 import {
   alloc_job,
   begin_iteration_job,
@@ -8,8 +9,12 @@ import {
   bla_bytes_ptr,
   bla_row_offsets_ptr,
   calc_iteration_band,
+  get_series_skip,
   initSync,
   scaled_iterations_ptr,
+  set_f32_fast_path,
+  set_interleaved_pixels,
+  set_series_approximation,
   xn_ptr,
 } from "../../wasm-iter/pkg/mandelbrot_iter.js";
 
@@ -17,6 +22,12 @@ import {
  * wasm-iter の hot loop の速度を測るローカル用ベンチ。
  *
  * target-feature を変えたときの差をブラウザ往復せずに見るためのもの。
+ * BLA のみと、series approximation (SA) で初期 iteration を飛ばした SA+BLA の両方を測る。
+ * 複数ピクセルを 1 つのループで交互に進める interleave (`set_interleaved_pixels`) の 2, 4 ピクセルも測る。
+ * f32 の kernel は既定で無効なので、上の行は f64 で測り、
+ * 最後に浅いズームで f32 の kernel (`set_f32_fast_path`) を使う行を足す。
+ * BLATable のメモリレイアウトの差はキャッシュに載りきらない大きさで効くので、
+ * wasm-fp で作った長い orbit の BLATable でも測る。
  * セットアップは `mandelbrot-iteration-wasm.test.ts` と同じ作りで、
  * BLATable の中身は数学的に正しくなくてよい (経路が現実的に散ればよい)。
  *
@@ -98,69 +109,145 @@ const createBlaTable = (
 const AREA = 512;
 const XN_LENGTH = 4096;
 const MAX_ITERATION = 4096;
+/** 大きい BLATable 用。c = -0.75 付近は発散が遅く、長い orbit の上を BLA で飛び続ける */
+const LARGE_AREA = 64;
+const LARGE_XN_LENGTH = 1 << 18;
+const LARGE_DELTA_C_SCALE = 1e-9;
 const RUNS = 5;
+/** SA+BLA で使う級数の項数と probe 点の許容誤差 */
+const SERIES_TERMS = 16;
+const SERIES_TOLERANCE = 1e-6;
+/** interleave で比べる同時ピクセル数 (1 は interleave なし) */
+const INTERLEAVED_PIXELS = [1, 2, 4];
+
+type BenchJob = {
+  xn: Float64Array;
+  blaBuffer: SharedArrayBuffer;
+  area: number;
+  maxIteration: number;
+  deltaCScale: number;
+  seriesTerms: number;
+  interleavedPixels?: number;
+  f32FastPath?: boolean;
+};
+
+/** job を RUNS 回 (+ウォームアップ2回) 計算して1ピクセルあたりのnsを文字列で返す */
+const measure = ({
+  xn,
+  blaBuffer,
+  area,
+  maxIteration,
+  deltaCScale,
+  seriesTerms,
+  interleavedPixels = 1,
+  f32FastPath = false,
+}: BenchJob) => {
+  const blaTableView = new BLATableView(blaBuffer);
+  const areaPixels = area * area;
+
+  alloc_job(
+    xn.length,
+    blaBuffer.byteLength,
+    blaTableView.rowOffsets.length,
+    areaPixels,
+    areaPixels,
+  );
+  new Float64Array(wasmMemory.buffer, xn_ptr(), xn.length).set(xn);
+  new Uint8Array(wasmMemory.buffer, bla_bytes_ptr(), blaBuffer.byteLength).set(
+    new Uint8Array(blaBuffer),
+  );
+  new Int32Array(wasmMemory.buffer, bla_row_offsets_ptr(), blaTableView.rowOffsets.length).set(
+    blaTableView.rowOffsets,
+  );
+  set_series_approximation(seriesTerms, SERIES_TOLERANCE);
+  set_interleaved_pixels(interleavedPixels);
+  set_f32_fast_path(f32FastPath);
+
+  const samples: number[] = [];
+  for (let i = 0; i < RUNS + 2; i++) {
+    // 毎回jobを開始し直してiterationsキャッシュをクリアする
+    begin_iteration_job(
+      maxIteration,
+      xn.length / 2 - 1,
+      blaTableView.length,
+      START_BLA_INDEX,
+      deltaCScale,
+      area / 2,
+      area / 2,
+      area,
+      area,
+      0,
+      0,
+    );
+    begin_pass(1, 1, area, false, true);
+
+    const started = performance.now();
+    calc_iteration_band(0, area);
+    const elapsed = performance.now() - started;
+
+    // 結果を読んで最適化除去を防ぐ
+    const out = new Uint32Array(wasmMemory.buffer, scaled_iterations_ptr(), areaPixels);
+    if (out[0] === 0xffffffff) throw new Error("unreachable");
+
+    if (i >= 2) samples.push((elapsed * 1e6) / areaPixels);
+  }
+  set_series_approximation(0, 0);
+  set_interleaved_pixels(1);
+  set_f32_fast_path(false);
+
+  expect(samples).toHaveLength(RUNS);
+
+  const best = Math.min(...samples);
+  const mean = samples.reduce((a, b) => a + b, 0) / samples.length;
+  return `best=${best.toFixed(2)} ns/px  mean=${mean.toFixed(2)} ns/px  skip=${get_series_skip()}`;
+};
 
 describe("wasm-iter hot loop bench", () => {
   beforeAll(() => {
     const wasmPath = new URL("../../public/wasm/mandelbrot_iter_bg.wasm", import.meta.url);
     const output = initSync({ module: readFileSync(wasmPath) });
     wasmMemory = output.memory;
+
+    const apfpPath = new URL("../../public/wasm/apfp_bg.wasm", import.meta.url);
+    initApfpSync({ module: readFileSync(apfpPath) });
   });
 
   it("1ピクセルあたりのnsを出す", { timeout: 600000 }, () => {
     const xn = createXn(-0.7451, 0.11302, XN_LENGTH);
     const blaBuffer = createBlaTable(12, XN_LENGTH - 1, 12345);
-    const blaTableView = new BLATableView(blaBuffer);
-    const areaPixels = AREA * AREA;
-
-    alloc_job(
-      xn.length,
-      blaBuffer.byteLength,
-      blaTableView.rowOffsets.length,
-      areaPixels,
-      areaPixels,
-    );
-    new Float64Array(wasmMemory.buffer, xn_ptr(), xn.length).set(xn);
-    new Uint8Array(wasmMemory.buffer, bla_bytes_ptr(), blaBuffer.byteLength).set(
-      new Uint8Array(blaBuffer),
-    );
-    new Int32Array(wasmMemory.buffer, bla_row_offsets_ptr(), blaTableView.rowOffsets.length).set(
-      blaTableView.rowOffsets,
-    );
-
-    const samples: number[] = [];
-    for (let i = 0; i < RUNS + 2; i++) {
-      // 毎回jobを開始し直してiterationsキャッシュをクリアする
-      begin_iteration_job(
-        MAX_ITERATION,
-        xn.length / 2 - 1,
-        blaTableView.length,
-        START_BLA_INDEX,
-        5e-4,
-        AREA / 2,
-        AREA / 2,
-        AREA,
-        AREA,
-        0,
-        0,
-      );
-      begin_pass(1, 1, AREA, false, true);
-
-      const started = performance.now();
-      calc_iteration_band(0, AREA);
-      const elapsed = performance.now() - started;
-
-      // 結果を読んで最適化除去を防ぐ
-      const out = new Uint32Array(wasmMemory.buffer, scaled_iterations_ptr(), areaPixels);
-      if (out[0] === 0xffffffff) throw new Error("unreachable");
-
-      if (i >= 2) samples.push((elapsed * 1e6) / areaPixels);
-    }
-
-    expect(samples).toHaveLength(RUNS);
-
-    const best = Math.min(...samples);
-    const mean = samples.reduce((a, b) => a + b, 0) / samples.length;
-    writeFileSync(OUT, `best=${best.toFixed(2)} ns/px  mean=${mean.toFixed(2)} ns/px\n`);
+    const job = {
+      xn,
+      blaBuffer,
+      area: AREA,
+      maxIteration: MAX_ITERATION,
+      deltaCScale: 5e-4,
+    };
+
+    const largeXn = createXn(-0.75, 1e-9, LARGE_XN_LENGTH);
+    const largeBlaBuffer = toBlaTableBuffer(calc_bla_table(largeXn, LARGE_DELTA_C_SCALE));
+
+    const lines = INTERLEAVED_PIXELS.flatMap((interleavedPixels) => {
+      const blaOnly = measure({ ...job, seriesTerms: 0, interleavedPixels });
+      const seriesAndBla = measure({ ...job, seriesTerms: SERIES_TERMS, interleavedPixels });
+      const largeTable = measure({
+        xn: largeXn,
+        blaBuffer: largeBlaBuffer,
+        area: LARGE_AREA,
+        maxIteration: LARGE_XN_LENGTH,
+        deltaCScale: LARGE_DELTA_C_SCALE,
+        seriesTerms: 0,
+        interleavedPixels,
+      });
+      return [
+        `interleave ${interleavedPixels}:`,
+        `  bla:    ${blaOnly}`,
+        `  sa+bla: ${seriesAndBla}`,
+        `  large:  ${largeTable} (BLATable ${largeBlaBuffer.byteLength} bytes)`,
+      ];
+    });
+    const f32BlaOnly = measure({ ...job, seriesTerms: 0, f32FastPath: true });
+    const f32SeriesAndBla = measure({ ...job, seriesTerms: SERIES_TERMS, f32FastPath: true });
+    lines.push("f32:", `  bla:    ${f32BlaOnly}`, `  sa+bla: ${f32SeriesAndBla}`);
+    writeFileSync(OUT, `${lines.join("\n")}\n`);
   });
 });
@@ -12,6 +12,7 @@ import {
   get_hit_count,
   initSync,
   scaled_iterations_ptr,
+  set_f32_fast_path,
   xn_ptr,
 } from "../../wasm-iter/pkg/mandelbrot_iter.js";
 
@@ -279,6 +280,8 @@ function setupWasmJob(job: TestJob, isSuperSampling: boolean, maxScaledPixels: n
     job.blaTableView.rowOffsets,
   );
 
+  // bit 単位で比べるので、浅いズームでも f32 の kernel を使わせない
+  set_f32_fast_path(false);
   begin_iteration_job(
     job.maxIteration,
     job.xn.length / 2 - 1,
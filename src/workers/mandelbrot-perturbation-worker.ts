@@ -1,40 +1,28 @@
 /// <reference lib="webworker" />
 
-import { generateLowResDiffSequence } from "../math/low-res-diff-sequence";
 import { BLATableView, SKIP_BLA_ENTRY_UNTIL_THIS_L } from "./bla-table-item";
+import { initWasm, planIterationPasses, runIterationPasses } from "./wasm-iteration-passes";
 import { ComplexArrayView } from "./xn-buffer";
 import BigNumber from "bignumber.js";
-import wasmInit, {
+import {
   alloc_job,
   begin_iteration_job,
-  begin_pass,
   bla_bytes_ptr,
   bla_row_offsets_ptr,
-  calc_iteration_band,
-  get_calculated_count,
-  get_hit_count,
-  scaled_iterations_ptr,
+  get_job_error_message,
+  set_f32_fast_path,
+  set_guess_check,
+  set_render_strategy,
+  validate_job,
   xn_ptr,
 } from "../../wasm-iter/pkg/mandelbrot_iter.js";
-import type { IterationWorkerParams } from "../types";
-
-/** progress postMessageのスロットリング間隔 */
-const PROGRESS_INTERVAL_MS = 50;
+import { renderStrategies, type IterationWorkerParams } from "../types";
 
 let wasmMemory: WebAssembly.Memory | null = null;
 
-/**
- * wasm-iterモジュールを初期化する。
- * workerスクリプトのロード直後に開始しておき、最初のcalc受信時に完了を待つ
- */
-async function initWasm(): Promise<void> {
-  const base = import.meta.env.BASE_URL ?? "/";
-  const wasmUrl = new URL(`${base}wasm/mandelbrot_iter_bg.wasm`, self.location.origin);
-  const output = await wasmInit({ module_or_path: wasmUrl });
-  wasmMemory = output.memory;
-}
-
-const initPromise = initWasm();
+const initPromise = initWasm().then((memory) => {
+  wasmMemory = memory;
+});
 
 const calcHandler = (data: IterationWorkerParams) => {
   if (wasmMemory == null) throw new Error("wasm-iter is not initialized");
@@ -48,6 +36,9 @@ const calcHandler = (data: IterationWorkerParams) => {
     r: rStr,
     N: maxIteration,
     isSuperSampling,
+    superSampling,
+    renderStrategy,
+    checksGuesses,
     startX,
     endX,
     startY,
@@ -71,7 +62,6 @@ const calcHandler = (data: IterationWorkerParams) => {
   const areaWidth = endX - startX;
   const areaHeight = endY - startY;
   const pixelNum = areaHeight * areaWidth;
-  const totalPixelCount = pixelNum * (isSuperSampling ? 4 : 1); // FIXME: supersamplingの倍率が固定値になっている
 
   const minDim = Math.min(pixelWidth, pixelHeight);
 
@@ -89,32 +79,25 @@ const calcHandler = (data: IterationWorkerParams) => {
   // データ節約のために空にしたBLATableの次のindexから開始
   const startBLAIndex = Math.floor(Math.log2(SKIP_BLA_ENTRY_UNTIL_THIS_L)) + 1;
 
-  let { xDiffs, yDiffs } = generateLowResDiffSequence(6, areaWidth, areaHeight);
-
-  if (isSuperSampling) {
-    // FIXME: 2倍決め打ちになってしまっている
-    xDiffs = [0.5];
-    yDiffs = [0.5];
-  }
-
-  // scaled_iterationsのバッファサイズ決定用に、全passで最大となるscaled pixel数を求める
-  let maxScaledPixels = 0;
-  for (let i = 0; i < xDiffs.length; i++) {
-    const scaledPixels = Math.floor(areaWidth / xDiffs[i]) * Math.floor(areaHeight / yDiffs[i]);
-    if (maxScaledPixels < scaledPixels) maxScaledPixels = scaledPixels;
-  }
+  const plan = planIterationPasses(
+    areaWidth,
+    areaHeight,
+    isSuperSampling,
+    superSampling,
+    renderStrategy,
+  );
 
   const xnF64Length = xnView.view.length;
   const blaBytesLength = blaTableBuffer.byteLength;
   const rowOffsetsLength = blaTableView.rowOffsets.length;
 
-  // supersampling時はiterationsキャッシュを参照しないので確保させない
+  // iterationsキャッシュを参照しないsupersamplingでは確保させない
   alloc_job(
     xnF64Length,
     blaBytesLength,
     rowOffsetsLength,
-    isSuperSampling ? 0 : pixelNum,
-    maxScaledPixels,
+    plan.usesIterationsCache ? pixelNum : 0,
+    plan.maxScaledPixels,
   );
 
   // SAB → wasm memory へjobごとに1回だけコピーする。
@@ -128,6 +111,13 @@ const calcHandler = (data: IterationWorkerParams) => {
     blaTableView.rowOffsets,
   );
 
+  if (!set_render_strategy(renderStrategies.indexOf(renderStrategy))) {
+    throw new Error(`wasm-iter: unknown render strategy ${renderStrategy}`);
+  }
+  set_guess_check(checksGuesses);
+  // 浅いズームでは f32 の kernel で計算させる (wasm-iter の既定は無効)
+  set_f32_fast_path(true);
+
   begin_iteration_job(
     maxIteration,
     xnView.length - 1,
@@ -142,80 +132,19 @@ const calcHandler = (data: IterationWorkerParams) => {
     startY,
   );
 
-  let lastProgressSentAt = 0;
-  let terminated = false;
-
-  for (let i = 0; i < xDiffs.length; i++) {
-    const xDiff = xDiffs[i];
-    const yDiff = yDiffs[i];
-
-    // resultとして送るpassかどうか。hitCountはこのpassの書き込みだけを数える
-    const isResultPass = isSuperSampling || i === xDiffs.length - 1;
-
-    const scaledAreaWidth = Math.floor(areaWidth / xDiff);
-    const scaledAreaHeight = Math.floor(areaHeight / yDiff);
-
-    begin_pass(xDiff, yDiff, scaledAreaWidth, isSuperSampling, isResultPass);
-
-    // JS版と同じくscaled-y 1行ごとにterminatorとprogressを見る。
-    // 行単位に切っても呼び出し回数は1 passあたり高々数千回で、wasm境界のコストは誤差
-    for (let scaledY = 0; scaledY < scaledAreaHeight; scaledY++) {
-      calc_iteration_band(scaledY, scaledY + 1);
-
-      if (terminateChecker[workerIdx] !== 0) {
-        terminated = true;
-        break;
-      }
-
-      const nowMs = performance.now();
-      if (nowMs - lastProgressSentAt >= PROGRESS_INTERVAL_MS) {
-        lastProgressSentAt = nowMs;
-        self.postMessage({
-          type: "progress",
-          progress: get_calculated_count() / totalPixelCount,
-        });
-      }
-    }
-
-    if (terminated) break;
-
-    // pass結果をwasm memoryからtransferできるバッファに複製する
-    const scaledPixels = scaledAreaWidth * scaledAreaHeight;
-    const scaledIterations = new Uint32Array(scaledPixels);
-    scaledIterations.set(new Uint32Array(memory.buffer, scaled_iterations_ptr(), scaledPixels));
-
-    if (isResultPass) {
-      // 最終passの結果はintermediateResultではなくresultとして送り、
-      // 別途末尾でiterationsを送り直す重複を避ける
-      const elapsed = performance.now() - startedAt;
-      self.postMessage(
-        {
-          type: "result",
-          iterations: scaledIterations,
-          resolution: { width: scaledAreaWidth, height: scaledAreaHeight },
-          elapsed,
-          hitCount: get_hit_count(),
-        },
-        [scaledIterations.buffer],
-      );
-    } else {
-      self.postMessage(
-        {
-          type: "intermediateResult",
-          iterations: scaledIterations,
-          resolution: { width: scaledAreaWidth, height: scaledAreaHeight },
-        },
-        [scaledIterations.buffer],
-      );
-    }
+  // 壊れたバッファのままbandを呼ぶとwasm側でpanicしてinstanceごと使えなくなるので、先に弾く
+  if (validate_job() !== 0) {
+    throw new Error(`wasm-iter: ${get_job_error_message()}`);
   }
 
-  if (terminateChecker[workerIdx] !== 0) {
-    console.debug(`${jobId}: terminated`);
-    self.postMessage({
-      type: "terminated",
-    });
-  }
+  runIterationPasses({
+    memory,
+    plan,
+    terminateChecker,
+    workerIdx,
+    jobId,
+    startedAt,
+  });
 };
 
 self.addEventListener("message", async (event) => {
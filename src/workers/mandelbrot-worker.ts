@@ -1,99 +1,111 @@
 /// <reference lib="webworker" />
-declare const self: DedicatedWorkerGlobalScope;
 
-import type { IterationWorkerParams } from "../types";
+import { initWasm, planIterationPasses, runIterationPasses } from "./wasm-iteration-passes";
+import {
+  alloc_job,
+  begin_direct_iteration_job,
+  get_job_error_message,
+  set_guess_check,
+  set_render_strategy,
+  validate_job,
+} from "../../wasm-iter/pkg/mandelbrot_iter.js";
+import { renderStrategies, type IterationWorkerParams } from "../types";
+
+let wasmMemory: WebAssembly.Memory | null = null;
+
+const initPromise = initWasm().then((memory) => {
+  wasmMemory = memory;
+});
+
+/**
+ * reference orbitを使わず、z^2 + cをそのままdoubleで計算する。
+ * pass / band / progressの進め方はperturbation workerと同じwasm-iterのjobを使う
+ */
+const calcHandler = (data: IterationWorkerParams) => {
+  if (wasmMemory == null) throw new Error("wasm-iter is not initialized");
+  const memory = wasmMemory;
 
-self.addEventListener("message", (event) => {
   const {
     pixelHeight,
     pixelWidth,
     cx: cxStr,
     cy: cyStr,
     r: rStr,
-    N,
+    N: maxIteration,
     isSuperSampling,
+    superSampling,
+    renderStrategy,
+    checksGuesses,
     startX,
     endX,
     startY,
     endY,
-  } = event.data as IterationWorkerParams;
+    jobId,
+    terminator,
+    workerIdx,
+  } = data;
 
   const startedAt = performance.now();
 
-  const sampleScale = isSuperSampling ? 2 : 1;
-  const areaPixelWidth = (endX - startX) * sampleScale;
-  const areaPixelHeight = (endY - startY) * sampleScale;
-
-  const pixelNum = areaPixelHeight * areaPixelWidth;
-
-  const iterations = new Uint32Array(pixelNum);
-
-  const cx = parseFloat(cxStr);
-  const cy = parseFloat(cyStr);
-  const r = parseFloat(rStr);
-  const R2 = 4;
-
-  const scaleX = pixelWidth / Math.min(pixelWidth, pixelHeight);
-  const scaleY = pixelHeight / Math.min(pixelWidth, pixelHeight);
-
-  const diffX = isSuperSampling ? 0.5 : 1;
-  const diffY = isSuperSampling ? 0.5 : 1;
-
-  let hitCount = 0;
-  // progress postMessageのスロットリング用。前回送信時刻からPROGRESS_INTERVAL_MS経過時のみ送る
-  let lastProgressSentAt = 0;
-  const PROGRESS_INTERVAL_MS = 50;
-
-  let scaledY = 0;
-  for (let y = startY; y < endY; y = y + diffY, scaledY++) {
-    let scaledX = 0;
-    for (let x = startX; x < endX; x = x + diffX, scaledX++) {
-      let zr = 0.0;
-      let zi = 0.0;
-      const cr = cx + ((x * 2) / pixelWidth - 1.0) * r * scaleX;
-      const ci = cy - ((y * 2) / pixelHeight - 1.0) * r * scaleY;
-
-      let n = 0;
-      let zr2 = 0.0;
-      let zi2 = 0.0;
-      while (zr2 + zi2 <= R2 && n < N) {
-        zi = (zr + zr) * zi + ci;
-        zr = zr2 - zi2 + cr;
-        zr2 = zr * zr;
-        zi2 = zi * zi;
-
-        n++;
-      }
-
-      if (isSuperSampling) {
-        const scaledIndex = scaledX + scaledY * areaPixelWidth;
-        iterations[scaledIndex] = n;
-      } else {
-        const index = Math.floor(x - startX + (y - startY) * areaPixelWidth);
-        iterations[index] = n;
-      }
-
-      if (n === N) hitCount++;
-    }
-    const nowMs = performance.now();
-    if (nowMs - lastProgressSentAt >= PROGRESS_INTERVAL_MS) {
-      lastProgressSentAt = nowMs;
-      self.postMessage({
-        type: "progress",
-        progress: (y - startY) / areaPixelHeight,
-      });
-    }
-  }
+  const terminateChecker = new Uint8Array(terminator);
 
-  const elapsed = performance.now() - startedAt;
-  self.postMessage(
-    {
-      type: "result",
-      iterations,
-      resolution: { width: areaPixelWidth, height: areaPixelHeight },
-      elapsed,
-      hitCount,
-    },
-    [iterations.buffer],
+  const areaWidth = endX - startX;
+  const areaHeight = endY - startY;
+  const pixelNum = areaHeight * areaWidth;
+
+  // c = (cx, cy) + (x - W/2, -(y - H/2)) * 2r / min(W, H)
+  const deltaCScale = (2 * parseFloat(rStr)) / Math.min(pixelWidth, pixelHeight);
+
+  const plan = planIterationPasses(
+    areaWidth,
+    areaHeight,
+    isSuperSampling,
+    superSampling,
+    renderStrategy,
   );
+
+  // reference orbitは使わないのでxn / BLATableのバッファは確保しない。
+  // iterationsキャッシュを参照しないsupersamplingでは確保させない
+  alloc_job(0, 0, 0, plan.usesIterationsCache ? pixelNum : 0, plan.maxScaledPixels);
+
+  if (!set_render_strategy(renderStrategies.indexOf(renderStrategy))) {
+    throw new Error(`wasm-iter: unknown render strategy ${renderStrategy}`);
+  }
+  set_guess_check(checksGuesses);
+
+  begin_direct_iteration_job(
+    maxIteration,
+    parseFloat(cxStr),
+    parseFloat(cyStr),
+    deltaCScale,
+    pixelWidth / 2,
+    pixelHeight / 2,
+    areaWidth,
+    areaHeight,
+    startX,
+    startY,
+  );
+
+  if (validate_job() !== 0) {
+    throw new Error(`wasm-iter: ${get_job_error_message()}`);
+  }
+
+  runIterationPasses({
+    memory,
+    plan,
+    terminateChecker,
+    workerIdx,
+    jobId,
+    startedAt,
+  });
+};
+
+self.addEventListener("message", async (event) => {
+  switch (event.data.type) {
+    case "calc": {
+      await initPromise;
+      calcHandler(event.data);
+      break;
+    }
+  }
 });
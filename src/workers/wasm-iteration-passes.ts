@@ -0,0 +1,296 @@
+/// <reference lib="webworker" />
+declare const self: DedicatedWorkerGlobalScope;
+
+import { generateLowResDiffSequence } from "../math/low-res-diff-sequence";
+import { samplePatterns, type RenderStrategy, type SuperSamplingParams } from "../types";
+import wasmInit, {
+  begin_pass,
+  begin_refinement_pass,
+  begin_sampling_pass,
+  calc_iteration_band,
+  get_buffer_bytes,
+  get_calculated_count,
+  get_guessed_count,
+  get_hit_count,
+  get_iterated_count,
+  get_job_error_message,
+  get_mistaken_guess_count,
+  scaled_iterations_ptr,
+} from "../../wasm-iter/pkg/mandelbrot_iter.js";
+
+/** progress postMessageのスロットリング間隔 */
+const PROGRESS_INTERVAL_MS = 50;
+
+/**
+ * supersamplingで全サンプルをそのまま返す1軸あたりのサンプル数の上限。
+ * これより多い場合はwasm側でピクセルごとに平均したiterationを返し、転送量とメモリを抑える
+ */
+const MAX_ALL_SAMPLES_PER_AXIS = 4;
+
+/**
+ * adaptive supersamplingで計算し直すピクセルの閾値。
+ * 隣のピクセルとのiterationの差がこれを超えると、paletteの色が変わる境界とみなす
+ */
+const ADAPTIVE_SUPERSAMPLING_THRESHOLD = 2;
+
+/**
+ * solid guessing / boundary tracing時に1回のcalc_iteration_bandで計算する行数。
+ * どちらもband内で閉じているので、1行ずつでは何も埋められない
+ */
+const GUESSING_BAND_ROWS = 16;
+
+/** supersamplingの設定がない場合のサンプルの取り方 */
+const DEFAULT_SUPER_SAMPLING: SuperSamplingParams = {
+  samplesPerAxis: 2,
+  pattern: "grid",
+  seed: 0,
+  adaptive: false,
+};
+
+/**
+ * wasm-iterモジュールを初期化してlinear memoryを返す。
+ * workerスクリプトのロード直後に開始しておき、最初のcalc受信時に完了を待つ
+ */
+export async function initWasm(): Promise<WebAssembly.Memory> {
+  const base = import.meta.env.BASE_URL ?? "/";
+  const wasmUrl = new URL(`${base}wasm/mandelbrot_iter_bg.wasm`, self.location.origin);
+  const output = await wasmInit({ module_or_path: wasmUrl });
+  return output.memory;
+}
+
+/** 1 pass分の設定 */
+interface IterationPass {
+  /** begin_pass / begin_sampling_pass でwasm側のpassを始める */
+  begin: (isResultPass: boolean) => void;
+  /** calc_iteration_bandに渡す行の数 */
+  bandCount: number;
+  /** 1回のcalc_iteration_bandで計算する行数 */
+  bandRows: number;
+  /** passの出力の大きさ */
+  resolution: { width: number; height: number };
+  /** falseなら最終passでない場合もintermediateResultを送らない */
+  isPreviewable: boolean;
+}
+
+export interface IterationPassPlan {
+  passes: IterationPass[];
+  /** scaled_iterationsのバッファサイズ。全passで最大となるscaled pixel数 */
+  maxScaledPixels: number;
+  /** progressの分母。全passで計算するピクセル数 (supersamplingではサンプル数) */
+  totalPixelCount: number;
+  /** iterationsキャッシュを使うか。falseならalloc_jobのarea_pixelsに0を渡して確保させない */
+  usesIterationsCache: boolean;
+}
+
+/**
+ * areaを計算するpassの列を決める。alloc_jobより前に呼んでバッファサイズを決める
+ */
+export function planIterationPasses(
+  areaWidth: number,
+  areaHeight: number,
+  isSuperSampling: boolean,
+  superSampling: SuperSamplingParams = DEFAULT_SUPER_SAMPLING,
+  renderStrategy: RenderStrategy = "scan",
+): IterationPassPlan {
+  const pixelNum = areaWidth * areaHeight;
+  const normalBandRows = renderStrategy === "scan" ? 1 : GUESSING_BAND_ROWS;
+
+  if (isSuperSampling) {
+    const { samplesPerAxis, pattern, seed, adaptive } = superSampling;
+    const patternIndex = samplePatterns.indexOf(pattern);
+
+    if (adaptive) {
+      const resolution = { width: areaWidth, height: areaHeight };
+      return {
+        passes: [
+          // 1ピクセル1サンプルでiterationsキャッシュを埋める。supersamplingの画像とは
+          // 解像度が違うのでintermediateResultとしては送らない
+          {
+            begin: (isResultPass) => begin_pass(1, 1, areaWidth, false, isResultPass),
+            bandCount: areaHeight,
+            bandRows: normalBandRows,
+            resolution,
+            isPreviewable: false,
+          },
+          {
+            begin: (isResultPass) => {
+              if (
+                !begin_refinement_pass(
+                  samplesPerAxis,
+                  patternIndex,
+                  seed,
+                  ADAPTIVE_SUPERSAMPLING_THRESHOLD,
+                  isResultPass,
+                )
+              ) {
+                throw new Error(`wasm-iter: unknown sample pattern ${pattern}`);
+              }
+            },
+            bandCount: areaHeight,
+            bandRows: 1,
+            resolution,
+            isPreviewable: false,
+          },
+        ],
+        maxScaledPixels: pixelNum,
+        // 計算し直すピクセル数は事前に分からないので、全ピクセルを計算し直す場合を分母にする
+        totalPixelCount: pixelNum * (1 + samplesPerAxis * samplesPerAxis),
+        usesIterationsCache: true,
+      };
+    }
+
+    const isAveraged = samplesPerAxis > MAX_ALL_SAMPLES_PER_AXIS;
+    const outputScale = isAveraged ? 1 : samplesPerAxis;
+    const resolution = { width: areaWidth * outputScale, height: areaHeight * outputScale };
+
+    return {
+      passes: [
+        {
+          begin: (isResultPass) => {
+            if (
+              !begin_sampling_pass(samplesPerAxis, patternIndex, seed, isAveraged, isResultPass)
+            ) {
+              throw new Error(`wasm-iter: unknown sample pattern ${pattern}`);
+            }
+          },
+          // supersamplingのpassはareaのピクセル行単位で計算する
+          bandCount: areaHeight,
+          bandRows: 1,
+          resolution,
+          isPreviewable: false,
+        },
+      ],
+      maxScaledPixels: resolution.width * resolution.height,
+      totalPixelCount: pixelNum * samplesPerAxis * samplesPerAxis,
+      usesIterationsCache: false,
+    };
+  }
+
+  const { xDiffs, yDiffs } = generateLowResDiffSequence(6, areaWidth, areaHeight);
+
+  const passes = xDiffs.map((xDiff, i): IterationPass => {
+    const yDiff = yDiffs[i];
+    const scaledAreaWidth = Math.floor(areaWidth / xDiff);
+    const scaledAreaHeight = Math.floor(areaHeight / yDiff);
+    return {
+      begin: (isResultPass) => begin_pass(xDiff, yDiff, scaledAreaWidth, false, isResultPass),
+      bandCount: scaledAreaHeight,
+      bandRows: normalBandRows,
+      resolution: { width: scaledAreaWidth, height: scaledAreaHeight },
+      isPreviewable: true,
+    };
+  });
+
+  let maxScaledPixels = 0;
+  for (const { resolution } of passes) {
+    const scaledPixels = resolution.width * resolution.height;
+    if (maxScaledPixels < scaledPixels) maxScaledPixels = scaledPixels;
+  }
+
+  return { passes, maxScaledPixels, totalPixelCount: pixelNum, usesIterationsCache: true };
+}
+
+export interface RunIterationPassesParams {
+  memory: WebAssembly.Memory;
+  plan: IterationPassPlan;
+  terminateChecker: Uint8Array;
+  workerIdx: number;
+  jobId: string;
+  startedAt: number;
+}
+
+/**
+ * begin_iteration_job / begin_direct_iteration_job済みのjobをpassごとに計算し、
+ * progress / intermediateResult / result / terminated をpostMessageする
+ */
+export function runIterationPasses({
+  memory,
+  plan,
+  terminateChecker,
+  workerIdx,
+  jobId,
+  startedAt,
+}: RunIterationPassesParams): void {
+  const { passes, totalPixelCount } = plan;
+
+  let lastProgressSentAt = 0;
+  let terminated = false;
+
+  for (let i = 0; i < passes.length; i++) {
+    const { begin, bandCount, bandRows, resolution, isPreviewable } = passes[i];
+    const { width: scaledAreaWidth, height: scaledAreaHeight } = resolution;
+
+    // resultとして送るpassかどうか。hitCountはこのpassの書き込みだけを数える
+    const isResultPass = i === passes.length - 1;
+
+    begin(isResultPass);
+
+    // JS版と同じく1行 (scan以外のrenderStrategyではbandRows行) ごとにterminatorとprogressを見る。
+    // 行単位に切っても呼び出し回数は1 passあたり高々数千回で、wasm境界のコストは誤差
+    for (let bandY = 0; bandY < bandCount; bandY += bandRows) {
+      if (calc_iteration_band(bandY, Math.min(bandY + bandRows, bandCount)) !== 0) {
+        throw new Error(`wasm-iter: ${get_job_error_message()}`);
+      }
+
+      if (terminateChecker[workerIdx] !== 0) {
+        terminated = true;
+        break;
+      }
+
+      const nowMs = performance.now();
+      if (nowMs - lastProgressSentAt >= PROGRESS_INTERVAL_MS) {
+        lastProgressSentAt = nowMs;
+        self.postMessage({
+          type: "progress",
+          progress: get_calculated_count() / totalPixelCount,
+        });
+      }
+    }
+
+    if (terminated) break;
+    if (!isResultPass && !isPreviewable) continue;
+
+    // pass結果をwasm memoryからtransferできるバッファに複製する
+    const scaledPixels = scaledAreaWidth * scaledAreaHeight;
+    const scaledIterations = new Uint32Array(scaledPixels);
+    scaledIterations.set(new Uint32Array(memory.buffer, scaled_iterations_ptr(), scaledPixels));
+
+    if (isResultPass) {
+      // 最終passの結果はintermediateResultではなくresultとして送り、
+      // 別途末尾でiterationsを送り直す重複を避ける
+      const elapsed = performance.now() - startedAt;
+      self.postMessage(
+        {
+          type: "result",
+          iterations: scaledIterations,
+          resolution: { width: scaledAreaWidth, height: scaledAreaHeight },
+          elapsed,
+          hitCount: get_hit_count(),
+          guessedCount: get_guessed_count(),
+          iteratedCount: get_iterated_count(),
+          mistakenGuessCount: get_mistaken_guess_count(),
+          // wasmのlinear memoryは縮まないので、poolがworkerを作り直すかの判断に使う
+          memoryBytes: memory.buffer.byteLength,
+          bufferBytes: get_buffer_bytes(),
+        },
+        [scaledIterations.buffer],
+      );
+    } else {
+      self.postMessage(
+        {
+          type: "intermediateResult",
+          iterations: scaledIterations,
+          resolution: { width: scaledAreaWidth, height: scaledAreaHeight },
+        },
+        [scaledIterations.buffer],
+      );
+    }
+  }
+
+  if (terminateChecker[workerIdx] !== 0) {
+    console.debug(`${jobId}: terminated`);
+    self.postMessage({
+      type: "terminated",
+    });
+  }
+}
@@ -1,6 +1,13 @@
 /* tslint:disable */
 /* eslint-disable */
 
+/**
+ * 計算済みの reference orbit から BLA テーブルを作り、エンコード済みのバイト列を返す。
+ * 入力: `xn` — `[re0, im0, re1, im1, ...]`、`pixel_spacing` — 1 ピクセルあたりの幅
+ * 出力: `Uint8Array` — `BLATableView` でそのまま読めるレイアウト
+ */
+export function calc_bla_table(xn: Float64Array, pixel_spacing: number): Uint8Array;
+
 /**
  * JS から呼ぶエントリポイント。
  * 入力: `{ type, x, y, max_iter, active_limbs? }` オブジェクト
@@ -12,6 +19,7 @@ export type InitInput = RequestInfo | URL | Response | BufferSource | WebAssembl
 
 export interface InitOutput {
     readonly memory: WebAssembly.Memory;
+    readonly calc_bla_table: (a: number, b: number, c: number) => [number, number];
     readonly calculate: (a: any) => [number, number];
     readonly __wbindgen_malloc: (a: number, b: number) => number;
     readonly __wbindgen_realloc: (a: number, b: number, c: number, d: number) => number;
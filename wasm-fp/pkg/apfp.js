@@ -1,5 +1,22 @@
 /* @ts-self-types="./apfp.d.ts" */
 
+/**
+ * 計算済みの reference orbit から BLA テーブルを作り、エンコード済みのバイト列を返す。
+ * 入力: `xn` — `[re0, im0, re1, im1, ...]`、`pixel_spacing` — 1 ピクセルあたりの幅
+ * 出力: `Uint8Array` — `BLATableView` でそのまま読めるレイアウト
+ * @param {Float64Array} xn
+ * @param {number} pixel_spacing
+ * @returns {Uint8Array}
+ */
+export function calc_bla_table(xn, pixel_spacing) {
+    const ptr0 = passArrayF64ToWasm0(xn, wasm.__wbindgen_malloc);
+    const len0 = WASM_VECTOR_LEN;
+    const ret = wasm.calc_bla_table(ptr0, len0, pixel_spacing);
+    var v2 = getArrayU8FromWasm0(ret[0], ret[1]).slice();
+    wasm.__wbindgen_free(ret[0], ret[1] * 1, 1);
+    return v2;
+}
+
 /**
  * JS から呼ぶエントリポイント。
  * 入力: `{ type, x, y, max_iter, active_limbs? }` オブジェクト
@@ -239,6 +256,13 @@ function isLikeNone(x) {
     return x === undefined || x === null;
 }
 
+function passArrayF64ToWasm0(arg, malloc) {
+    const ptr = malloc(arg.length * 8, 8) >>> 0;
+    getFloat64ArrayMemory0().set(arg, ptr / 8);
+    WASM_VECTOR_LEN = arg.length;
+    return ptr;
+}
+
 function passStringToWasm0(arg, malloc, realloc) {
     if (realloc === undefined) {
         const buf = cachedTextEncoder.encode(arg);
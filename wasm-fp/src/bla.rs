@@ -0,0 +1,260 @@
+//! Reference orbit から BLA (bivariate linear approximation) のテーブルを作る。
+//!
+//! Reference: https://mathr.co.uk/tmp/mandelbla.pdf
+//!
+//! 出力は `src/workers/bla-table-item.ts` の `BLATableView` と wasm-iter がそのまま読む
+//! バイト列で、JS 側で中間オブジェクトを作らずに済むよう行ごとに直接書き込む。
+
+/// 1 要素のバイト数。bla-table-item.ts と wasm-iter の `ITEM_BYTE_LENGTH` と一致させる
+pub const ITEM_BYTE_LENGTH: usize = 44;
+
+/// l がこれと同値までの行は書き出さない (bla-table-item.ts の `SKIP_BLA_ENTRY_UNTIL_THIS_L`)。
+/// 2 なら l = 1, 2 の行 (index 0, 1) を捨てる
+pub const SKIP_BLA_ENTRY_UNTIL_THIS_L: u32 = 2;
+
+/// 近似の許容誤差
+const EPSILON: f64 = 0.0001;
+
+#[derive(Clone, Copy)]
+struct BlaItem {
+    a_re: f64,
+    a_im: f64,
+    b_re: f64,
+    b_im: f64,
+    r: f64,
+    l: i32,
+}
+
+impl BlaItem {
+    /// x のあとに y を適用する 1 つの近似にまとめる
+    fn merge(x: &BlaItem, y: &BlaItem, pixel_spacing: f64) -> BlaItem {
+        let a_re = y.a_re * x.a_re - y.a_im * x.a_im;
+        let a_im = y.a_re * x.a_im + y.a_im * x.a_re;
+        let b_re = (y.a_re * x.b_re - y.a_im * x.b_im) + y.b_re;
+        let b_im = (y.a_re * x.b_im + y.a_im * x.b_re) + y.b_im;
+        let abs_xa = (x.a_re * x.a_re + x.a_im * x.a_im).sqrt();
+        let abs_xb = (x.b_re * x.b_re + x.b_im * x.b_im).sqrt();
+        let r = js_min(x.r, js_max(0.0, (y.r - abs_xb * pixel_spacing) / abs_xa));
+        BlaItem {
+            a_re,
+            a_im,
+            b_re,
+            b_im,
+            r,
+            l: x.l + y.l,
+        }
+    }
+
+    fn write(&self, bytes: &mut Vec<u8>) {
+        bytes.extend_from_slice(&self.a_re.to_le_bytes());
+        bytes.extend_from_slice(&self.a_im.to_le_bytes());
+        bytes.extend_from_slice(&self.b_re.to_le_bytes());
+        bytes.extend_from_slice(&self.b_im.to_le_bytes());
+        // hot loop で |dz| < r を dzNorm < r² で判定したいので r² を書く
+        bytes.extend_from_slice(&(self.r * self.r).to_le_bytes());
+        bytes.extend_from_slice(&self.l.to_le_bytes());
+    }
+}
+
+/// JS の `Math.max` と同じく、どちらかが NaN なら NaN を返す
+fn js_max(a: f64, b: f64) -> f64 {
+    if a.is_nan() || b.is_nan() {
+        f64::NAN
+    } else {
+        a.max(b)
+    }
+}
+
+/// JS の `Math.min` と同じく、どちらかが NaN なら NaN を返す
+fn js_min(a: f64, b: f64) -> f64 {
+    if a.is_nan() || b.is_nan() {
+        f64::NAN
+    } else {
+        a.min(b)
+    }
+}
+
+/// エンコード済みの BLA テーブル。
+pub struct BlaTable {
+    /// `[i32 行数] ([i32 要素数] [要素 × 要素数]) × 行数` (little-endian)
+    pub bytes: Vec<u8>,
+    /// 各行の `[要素の先頭 byteOffset, 要素数]` をフラットに並べたもの。
+    /// `BLATableView.rowOffsets` と同じ内容
+    pub row_offsets: Vec<i32>,
+}
+
+/// 計算済みの reference orbit から BLA テーブルを作る。
+/// `xn` は `[re0, im0, re1, im1, ...]`、`pixel_spacing` は 1 ピクセルあたりの複素平面上の幅。
+pub fn build_bla_table(xn: &[f64], pixel_spacing: f64) -> BlaTable {
+    let ref_len = xn.len() / 2;
+    let skip_until_row = SKIP_BLA_ENTRY_UNTIL_THIS_L.ilog2() as usize;
+
+    let mut writer = TableWriter::new(skip_until_row);
+
+    let mut row: Vec<BlaItem> = (1..ref_len)
+        .map(|i| {
+            let a_re = xn[i * 2] * 2.0;
+            let a_im = xn[i * 2 + 1] * 2.0;
+            let abs_a = (a_re * a_re + a_im * a_im).sqrt();
+            BlaItem {
+                a_re,
+                a_im,
+                b_re: 1.0,
+                b_im: 0.0,
+                r: js_max(0.0, (EPSILON * abs_a - pixel_spacing) / (abs_a + 1.0)),
+                l: 1,
+            }
+        })
+        .collect();
+    writer.push_row(&row);
+
+    // 隣り合う 2 要素をまとめて次の行を作る。要素が 1 つになったら終わり
+    let max_depth = if ref_len == 0 { 0 } else { ref_len.ilog2() };
+    for _ in 0..=max_depth {
+        let next: Vec<BlaItem> = row
+            .chunks(2)
+            .map(|pair| match pair {
+                [x, y] => BlaItem::merge(x, y, pixel_spacing),
+                [x] => *x,
+                _ => unreachable!(),
+            })
+            .collect();
+        writer.push_row(&next);
+        row = next;
+        if row.len() == 1 {
+            break;
+        }
+    }
+
+    // 捨てる行が生成された行より多い場合も、要素数 0 の行として揃える
+    while writer.row_count <= skip_until_row {
+        writer.push_row(&[]);
+    }
+
+    writer.finish()
+}
+
+/// 行を 1 つずつ書き足していく
+struct TableWriter {
+    bytes: Vec<u8>,
+    row_offsets: Vec<i32>,
+    row_count: usize,
+    skip_until_row: usize,
+}
+
+impl TableWriter {
+    fn new(skip_until_row: usize) -> Self {
+        Self {
+            // 先頭の行数はあとで書き戻す
+            bytes: 0i32.to_le_bytes().to_vec(),
+            row_offsets: Vec::new(),
+            row_count: 0,
+            skip_until_row,
+        }
+    }
+
+    fn push_row(&mut self, row: &[BlaItem]) {
+        // 捨てる行も行数には数えて、要素数 0 の行として書く
+        let items: &[BlaItem] = if self.row_count <= self.skip_until_row {
+            &[]
+        } else {
+            row
+        };
+        self.bytes
+            .extend_from_slice(&(items.len() as i32).to_le_bytes());
+        self.row_offsets.push(self.bytes.len() as i32);
+        self.row_offsets.push(items.len() as i32);
+        self.bytes.reserve(items.len() * ITEM_BYTE_LENGTH);
+        for item in items {
+            item.write(&mut self.bytes);
+        }
+        self.row_count += 1;
+    }
+
+    fn finish(mut self) -> BlaTable {
+        self.bytes[..4].copy_from_slice(&(self.row_count as i32).to_le_bytes());
+        BlaTable {
+            bytes: self.bytes,
+            row_offsets: self.row_offsets,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn create_xn(c_re: f64, c_im: f64, length: usize) -> Vec<f64> {
+        let mut xn = Vec::with_capacity(length * 2);
+        let (mut z_re, mut z_im) = (0.0f64, 0.0f64);
+        for _ in 0..length {
+            xn.push(z_re);
+            xn.push(z_im);
+            let next_re = z_re * z_re - z_im * z_im + c_re;
+            z_im = 2.0 * z_re * z_im + c_im;
+            z_re = next_re;
+        }
+        xn
+    }
+
+    fn read_i32(bytes: &[u8], offset: usize) -> i32 {
+        i32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+    }
+
+    fn read_f64(bytes: &[u8], offset: usize) -> f64 {
+        f64::from_le_bytes(bytes[offset..offset + 8].try_into().unwrap())
+    }
+
+    /// `BLATableView` のコンストラクタと同じ手順で row offsets を読み出す
+    fn parse_row_offsets(bytes: &[u8]) -> Vec<i32> {
+        let length = read_i32(bytes, 0) as usize;
+        let mut row_offsets = Vec::with_capacity(length * 2);
+        let mut byte_offset = 4;
+        for _ in 0..length {
+            let row_length = read_i32(bytes, byte_offset);
+            row_offsets.push(byte_offset as i32 + 4);
+            row_offsets.push(row_length);
+            byte_offset += 4 + ITEM_BYTE_LENGTH * row_length as usize;
+        }
+        assert_eq!(byte_offset, bytes.len());
+        row_offsets
+    }
+
+    #[test]
+    fn layout_matches_view() {
+        let xn = create_xn(-0.7451, 0.11302, 1000);
+        let table = build_bla_table(&xn, 1e-6);
+        assert_eq!(parse_row_offsets(&table.bytes), table.row_offsets);
+
+        // 1000 要素: 999 → 500 → 250 → ... → 1 の 11 行
+        let lengths: Vec<i32> = table.row_offsets.chunks(2).map(|o| o[1]).collect();
+        assert_eq!(lengths, [0, 0, 250, 125, 63, 32, 16, 8, 4, 2, 1]);
+    }
+
+    #[test]
+    fn items_merge_adjacent_steps() {
+        let xn = create_xn(-0.7451, 0.11302, 64);
+        let table = build_bla_table(&xn, 1e-9);
+        let row = 2;
+        let offset = table.row_offsets[row * 2] as usize;
+
+        // row 2 の先頭は X_1..X_4 の 4 step を 1 つにまとめたもの: A = ∏ 2X_k
+        let (mut a_re, mut a_im) = (1.0f64, 0.0f64);
+        for k in 1..=4 {
+            let (x_re, x_im) = (xn[k * 2] * 2.0, xn[k * 2 + 1] * 2.0);
+            (a_re, a_im) = (x_re * a_re - x_im * a_im, x_re * a_im + x_im * a_re);
+        }
+        assert!((read_f64(&table.bytes, offset) - a_re).abs() < 1e-12);
+        assert!((read_f64(&table.bytes, offset + 8) - a_im).abs() < 1e-12);
+        assert!(read_f64(&table.bytes, offset + 32) >= 0.0);
+        assert_eq!(read_i32(&table.bytes, offset + 40), 4);
+    }
+
+    #[test]
+    fn short_orbit_keeps_skipped_rows() {
+        // X_0 だけ: 要素 0 の行がスキップ分だけ並ぶ
+        let table = build_bla_table(&[0.0, 0.0], 1e-6);
+        assert_eq!(table.row_offsets, [8, 0, 12, 0]);
+        assert_eq!(parse_row_offsets(&table.bytes), table.row_offsets);
+    }
+}
@@ -1,3 +1,4 @@
+pub mod bla;
 pub mod complex;
 pub mod fixed;
 
@@ -77,6 +78,14 @@ pub fn calculate(req: JsValue) -> Vec<f64> {
     perform_calculation(req)
 }
 
+/// 計算済みの reference orbit から BLA テーブルを作り、エンコード済みのバイト列を返す。
+/// 入力: `xn` — `[re0, im0, re1, im1, ...]`、`pixel_spacing` — 1 ピクセルあたりの幅
+/// 出力: `Uint8Array` — `BLATableView` でそのまま読めるレイアウト
+#[wasm_bindgen]
+pub fn calc_bla_table(xn: &[f64], pixel_spacing: f64) -> Vec<u8> {
+    bla::build_bla_table(xn, pixel_spacing).bytes
+}
+
 /// 指定リム数でreference orbitを計算する（精度検証用）。
 #[cfg(test)]
 fn perform_calculation_with_limbs(req: &CalculationRequest, limbs: usize) -> Vec<f64> {
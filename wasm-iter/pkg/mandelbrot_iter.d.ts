@@ -2,63 +2,700 @@
 /* eslint-disable */
 
 /**
- * 入力バッファを確保する。このあと `xn_ptr` などでポインタを取得して JS 側からコピーする。
+ * 1 job 分の入力バッファと計算パラメータ。job をまたいで再利用し、足りないときだけ伸ばす。
  *
- * `area_pixels` に 0 を渡すと iterations キャッシュを確保しない (supersampling 時に使う)。
+ * JS からは `new IterationJob()` で必要な数だけ作れる (preview 用と本番用など)。
+ */
+export class IterationJob {
+    free(): void;
+    [Symbol.dispose](): void;
+    /**
+     * 集計結果の iteration (u32) の pass 出力。取得タイミングは `accum_values_ptr` と同じ
+     */
+    accum_iterations_ptr(): number;
+    /**
+     * 平均系の集計で、最後の項を除いた平均 (f64) の pass 出力。取得タイミングは `accum_values_ptr` と同じ
+     */
+    accum_prev_values_ptr(): number;
+    /**
+     * 集計結果の値 (f64) の pass 出力。集計が有効な job の `begin` 以降に取得すること
+     */
+    accum_values_ptr(): number;
+    /**
+     * 入力バッファを確保する。このあと `xn_ptr` などでポインタを取得して JS 側からコピーする。
+     *
+     * `area_pixels` に 0 を渡すと iterations キャッシュを確保しない (supersampling 時に使う)。
+     */
+    alloc(xn_f64_len: number, bla_bytes_len: number, bla_row_offsets_len: number, area_pixels: number, max_scaled_pixels: number): void;
+    /**
+     * `import_iterations` に渡す前の画像の iteration 数の入力バッファを `pixels` 要素分確保する。
+     * このあと `iteration_import_ptr` でポインタを取得して JS 側からコピーする。
+     */
+    alloc_iteration_import(pixels: number): void;
+    /**
+     * `calc_pixels` に渡すピクセル座標の入力バッファと、結果の出力バッファを `count` 個分確保する。
+     * このあと `pixel_list_ptr` に (x, y) の順で f64 を 2 つずつ書く。
+     */
+    alloc_pixel_list(count: number): void;
+    /**
+     * glitch 補正用の secondary reference の入力バッファを確保する。`index` は 1 始まり (0 は primary)。
+     * このあと `reference_xn_ptr` などでポインタを取得して JS 側からコピーし、`set_reference` で設定する。
+     * index が `MAX_SECONDARY_REFERENCES` (16) を超える場合は何もせず false を返す。
+     */
+    alloc_reference(index: number, xn_f64_len: number, bla_bytes_len: number, bla_row_offsets_len: number): boolean;
+    /**
+     * atom domain (u32) の pass 出力。atom domain が有効な job の `begin` 以降に取得すること
+     */
+    atom_domains_ptr(): number;
+    /**
+     * job 全体のパラメータを確定する。iterations キャッシュはここで 0 クリアされる
+     * (`set_iteration_shift` で前の job から引き継ぐ場合を除く)。
+     *
+     * 入力バッファはここで検証し、壊れていれば `calc_band` は計算せずにエラーコードを返す。
+     * 結果は `validate` で確認できる。
+     */
+    begin(max_iteration: number, max_ref_iteration: number, bla_rows: number, start_bla_index: number, delta_c_scale: number, ref_pixel_x: number, ref_pixel_y: number, area_width: number, area_height: number, area_start_x: number, area_start_y: number): void;
+    /**
+     * reference orbit を使わない direct mode で job を始める。`begin` の代わりに呼ぶ。
+     *
+     * pixel 座標 (x, y) は c = center + ((x - center_pixel_x), -(y - center_pixel_y)) * delta_c_scale
+     * として計算する。`alloc` の xn / BLA の長さは 0 でよい。
+     * pass と band の進め方、iterations キャッシュ、progress と hit count は `begin` の job と同じ。
+     * 追加出力 (`set_*` で有効にしたもの) は direct mode では出力しない
+     */
+    begin_direct(max_iteration: number, center_re: number, center_im: number, delta_c_scale: number, center_pixel_x: number, center_pixel_y: number, area_width: number, area_height: number, area_start_x: number, area_start_y: number): void;
+    /**
+     * 1 pass 分のパラメータを設定する。hit count と glitch count はここでリセットされる。
+     */
+    begin_pass(x_diff: number, y_diff: number, scaled_width: number, is_super_sampling: boolean, is_result_pass: boolean): void;
+    /**
+     * adaptive supersampling の refinement pass を始める。hit count と glitch count はここでリセットされる。
+     *
+     * 通常の pass で埋めた iterations キャッシュを元に、8 近傍のどれかとの iteration 数の差が
+     * `threshold` を超えるピクセルと、maxIteration に達したかどうかが近傍と違うピクセルだけを
+     * `begin_sampling_pass` と同じ配置の `samples_per_axis`×`samples_per_axis` 個のサンプルで計算し直す。
+     * `calc_band` には area のピクセル行 [from, to) を渡す。出力は幅 `area_width` の scaled 出力で、
+     * 計算し直したピクセルはサンプルの平均 (四捨五入)、それ以外はキャッシュの値になる。
+     * 各ピクセルで使ったサンプル数 (1 か samples_per_axis^2) は `sample_counts_ptr` に書く。
+     * iterations キャッシュは書き換えず、追加出力も書かない。
+     * 未知の `pattern` を渡した場合は何もせず false を返す。
+     */
+    begin_refinement_pass(samples_per_axis: number, pattern: number, seed: number, threshold: number, is_result_pass: boolean): boolean;
+    /**
+     * 1 ピクセルを `samples_per_axis`×`samples_per_axis` 個のサンプルで計算する supersampling の pass を始める。
+     * hit count と glitch count はここでリセットされる。
+     *
+     * `pattern` は 0: 格子, 1: 傾けた格子, 2: jitter (`seed` とピクセル座標で決まる)。
+     * `samples_per_axis` は 1..=16 に丸める。
+     * この pass の `calc_band` には scaled 座標ではなく area のピクセル行 [from, to) を渡す。
+     * `is_averaged` が false なら全サンプルを幅 `area_width * samples_per_axis` の scaled 出力に、
+     * true ならピクセルごとの iteration 数の平均 (四捨五入) を幅 `area_width` で書く。
+     * iterations キャッシュは使わず、追加出力も書かない。
+     * 未知の `pattern` を渡した場合は何もせず false を返す。
+     */
+    begin_sampling_pass(samples_per_axis: number, pattern: number, seed: number, is_averaged: boolean, is_result_pass: boolean): boolean;
+    /**
+     * ポインタを渡したあとは JS が書き換えるものとして、次の `begin` で BLA の表を作り直す
+     */
+    bla_bytes_ptr(): number;
+    /**
+     * `bla_bytes_ptr` と同じく、次の `begin` で BLA の表を作り直す
+     */
+    bla_row_offsets_ptr(): number;
+    /**
+     * 確保しているバッファの合計バイト数 (Vec の容量ベース)
+     */
+    buffer_bytes(): number;
+    /**
+     * pass 内の scaled_y が [from, to) の範囲を計算する。
+     * `begin_sampling_pass` / `begin_refinement_pass` で始めた pass では area のピクセル行 [from, to) を計算する。
+     *
+     * 呼び出し粒度が progress 更新と terminator チェックの粒度になる。
+     * job の入力か band の範囲が壊れていれば何も計算せずにエラーコードを返す (0 なら成功)。
+     */
+    calc_band(band_scaled_y_from: number, band_scaled_y_to: number): number;
+    /**
+     * `pixel_list_ptr` に書いた `count` 個のピクセル座標の iteration 数を、同じ順に `pixel_results_ptr` に書く。
+     *
+     * 座標は `calc_band` と同じ area_start を含む pixel 座標で、小数でもよい。
+     * adaptive な refinement、glitch したピクセルの計算し直し、カーソル位置の iteration 数の表示など、
+     * 行単位ではなく散らばったピクセルだけを計算したいときに使う。
+     * `begin` / `begin_direct` のあとなら pass の途中でも呼べて、iterations キャッシュ、pass の出力、
+     * 追加出力、`calculated_count` と hit count は変えない。追加出力のある job でも iteration 数だけを返し、
+     * glitch の補正はしない (secondary reference を設定した job でも primary の結果を返す)。
+     * job の入力が壊れているか、`count` がバッファより多ければ何も計算せずにエラーコードを返す (0 なら成功)。
+     */
+    calc_pixels(count: number): number;
+    /**
+     * job 開始以降に実際に計算したピクセル数を返す。JS 側の progress 表示に使う。
+     * solid guessing で埋めたピクセルと、前の job から引き継いだピクセルも含む。
+     */
+    calculated_count(): number;
+    /**
+     * 集計を無効にする。次の `begin` から反映される
+     */
+    clear_accumulation(): void;
+    /**
+     * 直近に返したエラーの説明。エラーがなければ空文字列
+     */
+    error_message(): string;
+    /**
+     * 直近の pass で glitch と判定したピクセル数を返す。
+     * hit count と同じく `is_result_pass` を立てた pass でのみ数えている。
+     */
+    glitch_count(): number;
+    /**
+     * glitch 判定の結果 (u8、glitch なら 1) の pass 出力。glitch 判定が有効な job の `begin` 以降に取得すること
+     */
+    glitch_flags_ptr(): number;
+    /**
+     * job 開始以降に solid guessing / boundary tracing で計算せずに埋めたピクセル数を返す。
+     * `set_guess_check` が有効な job では、埋める代わりに計算したピクセルも含む。
+     */
+    guessed_count(): number;
+    /**
+     * 直近の pass で iteration が maxIteration に達したピクセル数を返す。
+     * `is_result_pass` を立てた pass でのみ数えている。
+     */
+    hit_count(): number;
+    /**
+     * `alloc_iteration_import` のバッファにコピーした前の画像の iteration 数を、iterations キャッシュの
+     * 重なるピクセルに書く。`begin` / `begin_direct` のあと、最初の pass より前に呼ぶ。
+     *
+     * 前の画像は幅 `src_width` 高さ `src_height` で、そのピクセル (sx, sy) が新しい画像の
+     * pixel 座標 (offset_x + sx * scale, offset_y + sy * scale) に重なるものとして扱う。
+     * 2 倍の拡大なら scale = 2 で、新しいピクセルの 4 つに 1 つが埋まる。
+     * 重ならないピクセル、前の画像で 0 (未計算) のピクセル、計算済みのピクセルは書かない。
+     * 書いたピクセルは pass で計算済みとして飛ばし、`reused_count` に加える。
+     * 前の画像が同じ maxIteration で同じ点を計算したものかは確かめないので、呼び出し側で保証する。
+     * バッファが足りない場合、scale が 0 の場合、キャッシュを持たない job と
+     * 追加出力を使う job では何もしない。書いたピクセル数を返す。
+     */
+    import_iterations(src_width: number, src_height: number, scale: number, offset_x: number, offset_y: number): number;
+    /**
+     * 内部判定で検出した周期軌道の |multiplier| (f64) の pass 出力。取得タイミングは `interior_periods_ptr` と同じ
+     */
+    interior_multipliers_ptr(): number;
+    /**
+     * 内部判定で検出した周期 (u32) の pass 出力。内部判定が有効な job の `begin` 以降に取得すること
+     */
+    interior_periods_ptr(): number;
+    /**
+     * job 開始以降に iteration を実際に回したピクセル数を返す。
+     * `calculated_count` から埋めたピクセル (check 有効時は埋めるはずだったピクセル) と
+     * 引き継いだピクセルを除いたもので、
+     * 全ピクセルに対する割合が
+     * solid guessing / boundary tracing で減らせた計算量の目安になる。
+     */
+    iterated_count(): number;
+    iteration_import_ptr(): number;
+    /**
+     * job 開始以降に solid guessing / boundary tracing で埋め間違えたピクセル数を返す。
+     * `set_guess_check` を有効にした job でのみ数えている。
+     */
+    mistaken_guess_count(): number;
+    /**
+     * 空の job を作る。バッファは `alloc` で確保する
+     */
+    constructor();
+    pixel_list_ptr(): number;
+    pixel_results_ptr(): number;
+    /**
+     * `alloc_reference` が false を返す index では null。次の `set_reference` で BLA の表を作り直す
+     */
+    reference_bla_bytes_ptr(index: number): number;
+    /**
+     * `alloc_reference` が false を返す index では null。次の `set_reference` で BLA の表を作り直す
+     */
+    reference_bla_row_offsets_ptr(index: number): number;
+    /**
+     * `alloc_reference` が false を返す index では null
+     */
+    reference_xn_ptr(index: number): number;
+    /**
+     * 確保したバッファをすべて手放す。設定 (`set_*`) は残る。
+     * 次に使うときは `alloc` からやり直す
+     */
+    release(): void;
+    /**
+     * 直近の `begin` / `begin_direct` で前の job の iterations キャッシュから引き継いだピクセル数と、
+     * そのあと `import_iterations` で書いたピクセル数の合計を返す。
+     * `set_iteration_shift` も `import_iterations` も使わなかった job では 0。
+     */
+    reused_count(): number;
+    /**
+     * refinement pass で各ピクセルに使ったサンプル数
+     */
+    sample_counts_ptr(): number;
+    scaled_iterations_ptr(): number;
+    /**
+     * 直近の `begin` で決まった series approximation のスキップ先 iteration。使っていなければ 0。
+     * 追加出力のある job では求めても使われない
+     */
+    series_skip(): number;
+    /**
+     * atom domain (|z|² が最小になった iteration) の出力を有効/無効にする。`begin` より前に呼ぶ。
+     *
+     * 結果は `atom_domains_ptr` に入る。z_0 は数えないので、bailout までに 1 度も観測しなければ 0。
+     */
+    set_atom_domain(enabled: boolean): void;
+    /**
+     * 平均系の色付け (TIA / curvature average) による集計を有効にする。`begin` より前に呼ぶ。
+     *
+     * `kind` は 0: triangle inequality average, 1: curvature average。
+     * `accum_values_ptr` に全項の平均、`accum_prev_values_ptr` に最後の項を除いた平均、
+     * `accum_iterations_ptr` に項数が入る。前 2 つを smooth iteration の小数部で補間して使う。
+     * 未知の `kind` を渡した場合は何もせず false を返す。
+     */
+    set_average_coloring(kind: number): boolean;
+    /**
+     * maxIteration に達したピクセルの計算途中の状態を残すかを設定する (既定は無効)。`begin` より前に呼ぶ。
+     *
+     * 有効な job のあと、同じ reference (同じ c で、orbit は伸ばしてもよい)・同じ area・同じスケールで
+     * maxIteration だけを上げた job を始めると、bailout 済みのピクセルはキャッシュの値をそのまま使い、
+     * maxIteration に達したピクセルは止まったところから計算を続ける。
+     * 引き継いだピクセル数は `reused_count` で返す。
+     * 状態は 1 ピクセルずつ計算する kernel で読み書きするので、有効な job では f32 / simd /
+     * interleave の kernel と `set_render_strategy` の 1, 2 は使わない。
+     * direct mode の job と追加出力のある job では何もしない。
+     */
+    set_continuation(enabled: boolean): void;
+    /**
+     * 浅いズームで f32 の kernel を使うかを設定する (既定は無効)。`begin` より前に呼ぶ。
+     *
+     * 結果が f64 とビット単位では一致しなくなるので、使う側 (worker) で明示的に有効にする。
+     * 有効にすると job ごとに自動で選ぶ。Δc のスケールが小さい job、reference の orbit が f32 で表せない job
+     * (`fast32` を参照) と追加出力のある job では f64 で計算する。
+     * f32 で精度が足りなくなったピクセルは f64 で計算し直すので、結果はほぼ f64 と同じになる。
+     */
+    set_f32_fast_path(enabled: boolean): void;
+    /**
+     * glitch 判定を設定する。`begin` より前に呼ぶ。
+     *
+     * いずれかの iteration で |z|² < `tolerance` * |Δ|² となったピクセルを glitch とみなす
+     * (Pauldelbrot の判定を rebase 前提に読み替えたもの。|z| / |Δ| < 1e-3 なら 1e-6 を渡す)。
+     * 0 以下を渡すと無効になる。結果は `glitch_flags_ptr` と `glitch_count` で取得する。
+     * `set_reference` で reference を追加してあれば、glitch したピクセルは近い reference から順に
+     * 計算し直され、どれでも glitch しなかった場合だけ glitch として残る。
+     */
+    set_glitch_detection(tolerance: number): void;
+    /**
+     * `set_render_strategy` で埋めたピクセルも計算して答え合わせするかを設定する (既定は無効)。
+     *
+     * デバッグ用。有効なら出力は計算した値になり、違っていた数を `mistaken_guess_count` で返す。
+     * 計算量は全ピクセルを計算する場合と同じになる。
+     */
+    set_guess_check(enabled: boolean): void;
+    /**
+     * 内部判定を設定する。`begin` より前に呼ぶ。
+     *
+     * `flags` は bit 0: periodicity (Brent 法で z の周期を検出)、bit 1: derivative (dz/dz が十分小さい)。
+     * 0 を渡すと無効になる。両方立てると periodicity は |multiplier| < 1 も満たすときだけ採用する。
+     * `periodicity_epsilon_sq` は |z_n - z_m|² の閾値、`derivative_epsilon_sq` は |dz/dz|² の閾値。
+     *
+     * 内部と判定したピクセルは maxIteration として扱い、`interior_periods_ptr` に周期が入る
+     * (内部でなければ 0、derivative 判定だけで周期が分からなければ u32::MAX)。
+     * `interior_multipliers_ptr` には周期軌道の |multiplier| が入る (内部でなければ 0、周期が分からなければ NaN)。
+     */
+    set_interior_detection(flags: number, periodicity_epsilon_sq: number, derivative_epsilon_sq: number): void;
+    /**
+     * 依存のない複数のピクセルを 1 つのループで交互に進める数を設定する (1〜4、既定は 1)。
+     *
+     * 結果は 1 ピクセルずつ計算した場合と変わらない。速くなるかは CPU と経路次第なので、
+     * 2 以上にするのはベンチで確かめてから。追加出力のある job では使われない。
+     * simd128 のビルドで 1 のときは 2 ピクセル同時の simd kernel を使う。
+     */
+    set_interleaved_pixels(count: number): void;
+    /**
+     * 次の `begin` / `begin_direct` で、前の job の iterations キャッシュを (dx, dy) ピクセル
+     * ずらして引き継ぐ。前の job のピクセル (x, y) の値が次の job の (x + dx, y + dy) に入る。
+     *
+     * maxIteration、Δc のスケール、area、reference (direct mode では c の位置) が前の job を
+     * ちょうど (dx, dy) ずらしたものになっている場合だけ引き継ぎ、新しく見えた行と列を
+     * 未計算にする。それ以外の場合と、追加出力を使う job では通常通り 0 クリアする。
+     * 指定は次の job 1 回だけに効く。引き継いだピクセル数は `reused_count` で返す。
+     */
+    set_iteration_shift(dx: number, dy: number): void;
+    /**
+     * orbit trap による集計を有効にする。`begin` より前に呼ぶ。
+     * 集計モードは 1 つだけで、`set_average_coloring` とは後から呼んだ方が有効になる。
+     *
+     * `shape` は 0: point, 1: cross, 2: line, 3: circle。中心は reference の c からの相対座標で、
+     * `param` は line なら偏角 [rad]、circle なら半径 (それ以外では使わない)。
+     * 結果は `accum_values_ptr` に最小距離、`accum_iterations_ptr` にそのときの iteration が入る。
+     * 未知の `shape` を渡した場合は何もせず false を返す。
+     */
+    set_orbit_trap(shape: number, offset_re: number, offset_im: number, param: number): boolean;
+    /**
+     * secondary reference のパラメータを設定する。`begin` のあとに呼ぶ。
+     *
+     * 設定した reference は、glitch 判定が有効なときに glitch したピクセルの計算し直しに使われる。
+     * index は 1 から順に設定し、1..=n を設定した job では n 本が使われる (`begin` でリセットされる)。
+     * BLATable を持たない reference は `bla_rows` に 0 を渡す。
+     * 入力が壊れているか、index が飛んでいるか上限を超えていればエラーコードを返し、
+     * その job の band は計算されなくなる。
+     */
+    set_reference(index: number, max_ref_iteration: number, bla_rows: number, ref_pixel_x: number, ref_pixel_y: number): number;
+    /**
+     * band 内のピクセルの計算順を設定する (既定は 0)。
+     *
+     * 0: 全ピクセルを計算する。
+     * 1: Mariani–Silver の矩形分割。矩形の周囲を計算し、全部同じ iteration 数なら内側をその値で埋める。
+     * 2: boundary tracing。iteration 数の境界をたどって計算し、囲まれた内側を埋める。
+     * 1 と 2 は細い構造を埋め間違えることがあるので、結果は 1 ピクセルずつ計算した場合と
+     * 一致するとは限らない。band の行数が多いほど効き、1 行の band では何も埋めない。
+     * 追加出力のある job と supersampling の pass (`begin_sampling_pass` など) では 0 として扱う。
+     * 未知の値を渡した場合は何もせず false を返す。
+     */
+    set_render_strategy(strategy: number): boolean;
+    /**
+     * series approximation による初期スキップを設定する。`begin` より前に呼ぶ。
+     *
+     * `terms` は級数の項数 (0 で無効、上限 64)。`begin` で係数を 1 回だけ求め、
+     * area の四隅と各辺の中点に置いた probe 点で相対誤差が `tolerance` 以内に収まる iteration まで
+     * 全ピクセルの計算を飛ばす。スキップ先は `series_skip` で取得できる。
+     * スキップは iteration 数にだけ効く。orbit trap / average coloring、内部判定、atom domain、
+     * glitch 判定のいずれかが有効な job ではスキップした iteration の z が必要なので使われない。
+     */
+    set_series_approximation(terms: number, tolerance: number): void;
+    /**
+     * バッファを直近の `alloc` / `begin` で必要とされたサイズまで縮める。
+     *
+     * `alloc` はバッファを伸ばすだけなので、大きな job のあとはその分の容量が残り続ける。
+     * 縮めた分は allocator に返るが、wasm の linear memory 自体は縮まないので、
+     * memory ごと手放したい場合は worker を作り直す
+     */
+    shrink(): void;
+    /**
+     * 直近の `begin` で f32 の kernel を使うと決まったか
+     */
+    uses_f32(): boolean;
+    /**
+     * 現在の job の入力 (primary と追加した reference) を検証し直して、エラーコードを返す。
+     *
+     * 0 なら正常。0 以外なら band は計算されないので、`error_message` で理由を取得する。
+     * - 1: xn が max_ref_iteration に足りない
+     * - 2: bla_row_offsets が bla_rows 段に足りない
+     * - 3: BLA の row が bla_bytes の範囲外
+     * - 4: BLA の row が max_ref_iteration に足りない
+     * - 5: pass が iterations キャッシュの範囲外を読む (band のみ)
+     * - 6: band が pass 出力の範囲外に書く (band のみ)
+     * - 7: ピクセル座標の数がバッファより多い (`calc_pixels` のみ)
+     * - 8: reference の index が飛んでいるか上限を超えている (`set_reference` のみ)
+     */
+    validate(): number;
+    xn_ptr(): number;
+}
+
+export function accum_iterations_ptr(): number;
+
+export function accum_prev_values_ptr(): number;
+
+export function accum_values_ptr(): number;
+
+export function alloc_iteration_import(pixels: number): void;
+
+/**
+ * `IterationJob::alloc`
  */
 export function alloc_job(xn_f64_len: number, bla_bytes_len: number, bla_row_offsets_len: number, area_pixels: number, max_scaled_pixels: number): void;
 
+export function alloc_pixel_list(count: number): void;
+
+export function alloc_reference(index: number, xn_f64_len: number, bla_bytes_len: number, bla_row_offsets_len: number): boolean;
+
+export function atom_domains_ptr(): number;
+
 /**
- * job 全体のパラメータを確定する。iterations キャッシュはここで 0 クリアされる。
+ * `IterationJob::begin_direct`
  */
-export function begin_iteration_job(max_iteration: number, max_ref_iteration: number, bla_rows: number, start_bla_index: number, delta_c_scale: number, ref_pixel_x: number, ref_pixel_y: number, area_width: number, area_height: number, area_start_x: number, area_start_y: number): void;
+export function begin_direct_iteration_job(max_iteration: number, center_re: number, center_im: number, delta_c_scale: number, center_pixel_x: number, center_pixel_y: number, area_width: number, area_height: number, area_start_x: number, area_start_y: number): void;
 
 /**
- * 1 pass 分のパラメータを設定する。hit count はここでリセットされる。
+ * `IterationJob::begin`
  */
+export function begin_iteration_job(max_iteration: number, max_ref_iteration: number, bla_rows: number, start_bla_index: number, delta_c_scale: number, ref_pixel_x: number, ref_pixel_y: number, area_width: number, area_height: number, area_start_x: number, area_start_y: number): void;
+
 export function begin_pass(x_diff: number, y_diff: number, scaled_width: number, is_super_sampling: boolean, is_result_pass: boolean): void;
 
+/**
+ * `IterationJob::begin_refinement_pass`
+ */
+export function begin_refinement_pass(samples_per_axis: number, pattern: number, seed: number, threshold: number, is_result_pass: boolean): boolean;
+
+/**
+ * `IterationJob::begin_sampling_pass`
+ */
+export function begin_sampling_pass(samples_per_axis: number, pattern: number, seed: number, is_averaged: boolean, is_result_pass: boolean): boolean;
+
 export function bla_bytes_ptr(): number;
 
 export function bla_row_offsets_ptr(): number;
 
 /**
- * pass 内の scaled_y が [from, to) の範囲を計算する。
- *
- * 呼び出し粒度が progress 更新と terminator チェックの粒度になる。
+ * `IterationJob::calc_band`
+ */
+export function calc_iteration_band(band_scaled_y_from: number, band_scaled_y_to: number): number;
+
+/**
+ * `IterationJob::calc_pixels`
  */
-export function calc_iteration_band(band_scaled_y_from: number, band_scaled_y_to: number): void;
+export function calc_iteration_pixels(count: number): number;
+
+export function clear_accumulation(): void;
 
 /**
- * job 開始以降に実際に計算したピクセル数を返す。JS 側の progress 表示に使う。
+ * `IterationJob::buffer_bytes`
+ */
+export function get_buffer_bytes(): number;
+
+/**
+ * `IterationJob::calculated_count`
  */
 export function get_calculated_count(): number;
 
 /**
- * 直近の pass で iteration が maxIteration に達したピクセル数を返す。
- * `is_result_pass` を立てた pass でのみ数えている。
+ * `IterationJob::glitch_count`
+ */
+export function get_glitch_count(): number;
+
+/**
+ * `IterationJob::guessed_count`
+ */
+export function get_guessed_count(): number;
+
+/**
+ * `IterationJob::hit_count`
  */
 export function get_hit_count(): number;
 
+/**
+ * `IterationJob::iterated_count`
+ */
+export function get_iterated_count(): number;
+
+/**
+ * `IterationJob::error_message`
+ */
+export function get_job_error_message(): string;
+
+/**
+ * `IterationJob::mistaken_guess_count`
+ */
+export function get_mistaken_guess_count(): number;
+
+/**
+ * `IterationJob::reused_count`
+ */
+export function get_reused_count(): number;
+
+/**
+ * `IterationJob::series_skip`
+ */
+export function get_series_skip(): number;
+
+/**
+ * `IterationJob::uses_f32`
+ */
+export function get_uses_f32(): boolean;
+
+export function glitch_flags_ptr(): number;
+
+/**
+ * `IterationJob::import_iterations`
+ */
+export function import_iterations(src_width: number, src_height: number, scale: number, offset_x: number, offset_y: number): number;
+
+export function interior_multipliers_ptr(): number;
+
+export function interior_periods_ptr(): number;
+
+export function iteration_import_ptr(): number;
+
+export function pixel_list_ptr(): number;
+
+export function pixel_results_ptr(): number;
+
+export function reference_bla_bytes_ptr(index: number): number;
+
+export function reference_bla_row_offsets_ptr(index: number): number;
+
+export function reference_xn_ptr(index: number): number;
+
+/**
+ * `IterationJob::release`
+ */
+export function release_job(): void;
+
+/**
+ * `IterationJob::sample_counts_ptr`
+ */
+export function sample_counts_ptr(): number;
+
 export function scaled_iterations_ptr(): number;
 
+export function set_atom_domain(enabled: boolean): void;
+
+export function set_average_coloring(kind: number): boolean;
+
+export function set_continuation(enabled: boolean): void;
+
+export function set_f32_fast_path(enabled: boolean): void;
+
+export function set_glitch_detection(tolerance: number): void;
+
+export function set_guess_check(enabled: boolean): void;
+
+export function set_interior_detection(flags: number, periodicity_epsilon_sq: number, derivative_epsilon_sq: number): void;
+
+export function set_interleaved_pixels(count: number): void;
+
+export function set_iteration_shift(dx: number, dy: number): void;
+
+export function set_orbit_trap(shape: number, offset_re: number, offset_im: number, param: number): boolean;
+
+export function set_reference(index: number, max_ref_iteration: number, bla_rows: number, ref_pixel_x: number, ref_pixel_y: number): number;
+
+export function set_render_strategy(strategy: number): boolean;
+
+export function set_series_approximation(terms: number, tolerance: number): void;
+
+/**
+ * `IterationJob::shrink`
+ */
+export function shrink_job(): void;
+
+/**
+ * `IterationJob::validate`
+ */
+export function validate_job(): number;
+
 export function xn_ptr(): number;
 
 export type InitInput = RequestInfo | URL | Response | BufferSource | WebAssembly.Module;
 
 export interface InitOutput {
     readonly memory: WebAssembly.Memory;
+    readonly __wbg_iterationjob_free: (a: number, b: number) => void;
+    readonly accum_iterations_ptr: () => number;
+    readonly accum_prev_values_ptr: () => number;
+    readonly accum_values_ptr: () => number;
+    readonly alloc_iteration_import: (a: number) => void;
     readonly alloc_job: (a: number, b: number, c: number, d: number, e: number) => void;
-    readonly xn_ptr: () => number;
+    readonly alloc_pixel_list: (a: number) => void;
+    readonly alloc_reference: (a: number, b: number, c: number, d: number) => number;
+    readonly atom_domains_ptr: () => number;
+    readonly begin_direct_iteration_job: (a: number, b: number, c: number, d: number, e: number, f: number, g: number, h: number, i: number, j: number) => void;
+    readonly begin_iteration_job: (a: number, b: number, c: number, d: number, e: number, f: number, g: number, h: number, i: number, j: number, k: number) => void;
+    readonly begin_pass: (a: number, b: number, c: number, d: number, e: number) => void;
+    readonly begin_refinement_pass: (a: number, b: number, c: number, d: number, e: number) => number;
+    readonly begin_sampling_pass: (a: number, b: number, c: number, d: number, e: number) => number;
     readonly bla_bytes_ptr: () => number;
     readonly bla_row_offsets_ptr: () => number;
-    readonly scaled_iterations_ptr: () => number;
+    readonly calc_iteration_band: (a: number, b: number) => number;
+    readonly calc_iteration_pixels: (a: number) => number;
+    readonly clear_accumulation: () => void;
+    readonly get_buffer_bytes: () => number;
     readonly get_calculated_count: () => number;
+    readonly get_glitch_count: () => number;
+    readonly get_guessed_count: () => number;
     readonly get_hit_count: () => number;
-    readonly begin_pass: (a: number, b: number, c: number, d: number, e: number) => void;
-    readonly calc_iteration_band: (a: number, b: number) => void;
-    readonly begin_iteration_job: (a: number, b: number, c: number, d: number, e: number, f: number, g: number, h: number, i: number, j: number, k: number) => void;
+    readonly get_iterated_count: () => number;
+    readonly get_job_error_message: () => [number, number];
+    readonly get_mistaken_guess_count: () => number;
+    readonly get_reused_count: () => number;
+    readonly get_series_skip: () => number;
+    readonly get_uses_f32: () => number;
+    readonly glitch_flags_ptr: () => number;
+    readonly import_iterations: (a: number, b: number, c: number, d: number, e: number) => number;
+    readonly interior_multipliers_ptr: () => number;
+    readonly interior_periods_ptr: () => number;
+    readonly iteration_import_ptr: () => number;
+    readonly iterationjob_accum_iterations_ptr: (a: number) => number;
+    readonly iterationjob_accum_prev_values_ptr: (a: number) => number;
+    readonly iterationjob_accum_values_ptr: (a: number) => number;
+    readonly iterationjob_alloc: (a: number, b: number, c: number, d: number, e: number, f: number) => void;
+    readonly iterationjob_alloc_iteration_import: (a: number, b: number) => void;
+    readonly iterationjob_alloc_pixel_list: (a: number, b: number) => void;
+    readonly iterationjob_alloc_reference: (a: number, b: number, c: number, d: number, e: number) => number;
+    readonly iterationjob_atom_domains_ptr: (a: number) => number;
+    readonly iterationjob_begin: (a: number, b: number, c: number, d: number, e: number, f: number, g: number, h: number, i: number, j: number, k: number, l: number) => void;
+    readonly iterationjob_begin_direct: (a: number, b: number, c: number, d: number, e: number, f: number, g: number, h: number, i: number, j: number, k: number) => void;
+    readonly iterationjob_begin_pass: (a: number, b: number, c: number, d: number, e: number, f: number) => void;
+    readonly iterationjob_begin_refinement_pass: (a: number, b: number, c: number, d: number, e: number, f: number) => number;
+    readonly iterationjob_begin_sampling_pass: (a: number, b: number, c: number, d: number, e: number, f: number) => number;
+    readonly iterationjob_bla_bytes_ptr: (a: number) => number;
+    readonly iterationjob_bla_row_offsets_ptr: (a: number) => number;
+    readonly iterationjob_buffer_bytes: (a: number) => number;
+    readonly iterationjob_calc_band: (a: number, b: number, c: number) => number;
+    readonly iterationjob_calc_pixels: (a: number, b: number) => number;
+    readonly iterationjob_calculated_count: (a: number) => number;
+    readonly iterationjob_clear_accumulation: (a: number) => void;
+    readonly iterationjob_error_message: (a: number) => [number, number];
+    readonly iterationjob_glitch_count: (a: number) => number;
+    readonly iterationjob_glitch_flags_ptr: (a: number) => number;
+    readonly iterationjob_guessed_count: (a: number) => number;
+    readonly iterationjob_hit_count: (a: number) => number;
+    readonly iterationjob_import_iterations: (a: number, b: number, c: number, d: number, e: number, f: number) => number;
+    readonly iterationjob_interior_multipliers_ptr: (a: number) => number;
+    readonly iterationjob_interior_periods_ptr: (a: number) => number;
+    readonly iterationjob_iterated_count: (a: number) => number;
+    readonly iterationjob_iteration_import_ptr: (a: number) => number;
+    readonly iterationjob_mistaken_guess_count: (a: number) => number;
+    readonly iterationjob_new: () => number;
+    readonly iterationjob_pixel_list_ptr: (a: number) => number;
+    readonly iterationjob_pixel_results_ptr: (a: number) => number;
+    readonly iterationjob_reference_bla_bytes_ptr: (a: number, b: number) => number;
+    readonly iterationjob_reference_bla_row_offsets_ptr: (a: number, b: number) => number;
+    readonly iterationjob_reference_xn_ptr: (a: number, b: number) => number;
+    readonly iterationjob_release: (a: number) => void;
+    readonly iterationjob_reused_count: (a: number) => number;
+    readonly iterationjob_sample_counts_ptr: (a: number) => number;
+    readonly iterationjob_scaled_iterations_ptr: (a: number) => number;
+    readonly iterationjob_series_skip: (a: number) => number;
+    readonly iterationjob_set_atom_domain: (a: number, b: number) => void;
+    readonly iterationjob_set_average_coloring: (a: number, b: number) => number;
+    readonly iterationjob_set_continuation: (a: number, b: number) => void;
+    readonly iterationjob_set_f32_fast_path: (a: number, b: number) => void;
+    readonly iterationjob_set_glitch_detection: (a: number, b: number) => void;
+    readonly iterationjob_set_guess_check: (a: number, b: number) => void;
+    readonly iterationjob_set_interior_detection: (a: number, b: number, c: number, d: number) => void;
+    readonly iterationjob_set_interleaved_pixels: (a: number, b: number) => void;
+    readonly iterationjob_set_iteration_shift: (a: number, b: number, c: number) => void;
+    readonly iterationjob_set_orbit_trap: (a: number, b: number, c: number, d: number, e: number) => number;
+    readonly iterationjob_set_reference: (a: number, b: number, c: number, d: number, e: number, f: number) => number;
+    readonly iterationjob_set_render_strategy: (a: number, b: number) => number;
+    readonly iterationjob_set_series_approximation: (a: number, b: number, c: number) => void;
+    readonly iterationjob_shrink: (a: number) => void;
+    readonly iterationjob_uses_f32: (a: number) => number;
+    readonly iterationjob_validate: (a: number) => number;
+    readonly iterationjob_xn_ptr: (a: number) => number;
+    readonly pixel_list_ptr: () => number;
+    readonly pixel_results_ptr: () => number;
+    readonly reference_bla_bytes_ptr: (a: number) => number;
+    readonly reference_bla_row_offsets_ptr: (a: number) => number;
+    readonly reference_xn_ptr: (a: number) => number;
+    readonly release_job: () => void;
+    readonly sample_counts_ptr: () => number;
+    readonly scaled_iterations_ptr: () => number;
+    readonly set_atom_domain: (a: number) => void;
+    readonly set_average_coloring: (a: number) => number;
+    readonly set_continuation: (a: number) => void;
+    readonly set_f32_fast_path: (a: number) => void;
+    readonly set_glitch_detection: (a: number) => void;
+    readonly set_guess_check: (a: number) => void;
+    readonly set_interior_detection: (a: number, b: number, c: number) => void;
+    readonly set_interleaved_pixels: (a: number) => void;
+    readonly set_iteration_shift: (a: number, b: number) => void;
+    readonly set_orbit_trap: (a: number, b: number, c: number, d: number) => number;
+    readonly set_reference: (a: number, b: number, c: number, d: number, e: number) => number;
+    readonly set_render_strategy: (a: number) => number;
+    readonly set_series_approximation: (a: number, b: number) => void;
+    readonly shrink_job: () => void;
+    readonly validate_job: () => number;
+    readonly xn_ptr: () => number;
     readonly __wbindgen_externrefs: WebAssembly.Table;
+    readonly __wbindgen_free: (a: number, b: number, c: number) => void;
     readonly __wbindgen_start: () => void;
 }
 
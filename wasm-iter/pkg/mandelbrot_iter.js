@@ -1,9 +1,734 @@
 /* @ts-self-types="./mandelbrot_iter.d.ts" */
 
 /**
- * 入力バッファを確保する。このあと `xn_ptr` などでポインタを取得して JS 側からコピーする。
+ * 1 job 分の入力バッファと計算パラメータ。job をまたいで再利用し、足りないときだけ伸ばす。
  *
- * `area_pixels` に 0 を渡すと iterations キャッシュを確保しない (supersampling 時に使う)。
+ * JS からは `new IterationJob()` で必要な数だけ作れる (preview 用と本番用など)。
+ */
+export class IterationJob {
+    __destroy_into_raw() {
+        const ptr = this.__wbg_ptr;
+        this.__wbg_ptr = 0;
+        IterationJobFinalization.unregister(this);
+        return ptr;
+    }
+    free() {
+        const ptr = this.__destroy_into_raw();
+        wasm.__wbg_iterationjob_free(ptr, 0);
+    }
+    /**
+     * 集計結果の iteration (u32) の pass 出力。取得タイミングは `accum_values_ptr` と同じ
+     * @returns {number}
+     */
+    accum_iterations_ptr() {
+        const ret = wasm.iterationjob_accum_iterations_ptr(this.__wbg_ptr);
+        return ret >>> 0;
+    }
+    /**
+     * 平均系の集計で、最後の項を除いた平均 (f64) の pass 出力。取得タイミングは `accum_values_ptr` と同じ
+     * @returns {number}
+     */
+    accum_prev_values_ptr() {
+        const ret = wasm.iterationjob_accum_prev_values_ptr(this.__wbg_ptr);
+        return ret >>> 0;
+    }
+    /**
+     * 集計結果の値 (f64) の pass 出力。集計が有効な job の `begin` 以降に取得すること
+     * @returns {number}
+     */
+    accum_values_ptr() {
+        const ret = wasm.iterationjob_accum_values_ptr(this.__wbg_ptr);
+        return ret >>> 0;
+    }
+    /**
+     * 入力バッファを確保する。このあと `xn_ptr` などでポインタを取得して JS 側からコピーする。
+     *
+     * `area_pixels` に 0 を渡すと iterations キャッシュを確保しない (supersampling 時に使う)。
+     * @param {number} xn_f64_len
+     * @param {number} bla_bytes_len
+     * @param {number} bla_row_offsets_len
+     * @param {number} area_pixels
+     * @param {number} max_scaled_pixels
+     */
+    alloc(xn_f64_len, bla_bytes_len, bla_row_offsets_len, area_pixels, max_scaled_pixels) {
+        wasm.iterationjob_alloc(this.__wbg_ptr, xn_f64_len, bla_bytes_len, bla_row_offsets_len, area_pixels, max_scaled_pixels);
+    }
+    /**
+     * `import_iterations` に渡す前の画像の iteration 数の入力バッファを `pixels` 要素分確保する。
+     * このあと `iteration_import_ptr` でポインタを取得して JS 側からコピーする。
+     * @param {number} pixels
+     */
+    alloc_iteration_import(pixels) {
+        wasm.iterationjob_alloc_iteration_import(this.__wbg_ptr, pixels);
+    }
+    /**
+     * `calc_pixels` に渡すピクセル座標の入力バッファと、結果の出力バッファを `count` 個分確保する。
+     * このあと `pixel_list_ptr` に (x, y) の順で f64 を 2 つずつ書く。
+     * @param {number} count
+     */
+    alloc_pixel_list(count) {
+        wasm.iterationjob_alloc_pixel_list(this.__wbg_ptr, count);
+    }
+    /**
+     * glitch 補正用の secondary reference の入力バッファを確保する。`index` は 1 始まり (0 は primary)。
+     * このあと `reference_xn_ptr` などでポインタを取得して JS 側からコピーし、`set_reference` で設定する。
+     * index が `MAX_SECONDARY_REFERENCES` (16) を超える場合は何もせず false を返す。
+     * @param {number} index
+     * @param {number} xn_f64_len
+     * @param {number} bla_bytes_len
+     * @param {number} bla_row_offsets_len
+     * @returns {boolean}
+     */
+    alloc_reference(index, xn_f64_len, bla_bytes_len, bla_row_offsets_len) {
+        const ret = wasm.iterationjob_alloc_reference(this.__wbg_ptr, index, xn_f64_len, bla_bytes_len, bla_row_offsets_len);
+        return ret !== 0;
+    }
+    /**
+     * atom domain (u32) の pass 出力。atom domain が有効な job の `begin` 以降に取得すること
+     * @returns {number}
+     */
+    atom_domains_ptr() {
+        const ret = wasm.iterationjob_atom_domains_ptr(this.__wbg_ptr);
+        return ret >>> 0;
+    }
+    /**
+     * job 全体のパラメータを確定する。iterations キャッシュはここで 0 クリアされる
+     * (`set_iteration_shift` で前の job から引き継ぐ場合を除く)。
+     *
+     * 入力バッファはここで検証し、壊れていれば `calc_band` は計算せずにエラーコードを返す。
+     * 結果は `validate` で確認できる。
+     * @param {number} max_iteration
+     * @param {number} max_ref_iteration
+     * @param {number} bla_rows
+     * @param {number} start_bla_index
+     * @param {number} delta_c_scale
+     * @param {number} ref_pixel_x
+     * @param {number} ref_pixel_y
+     * @param {number} area_width
+     * @param {number} area_height
+     * @param {number} area_start_x
+     * @param {number} area_start_y
+     */
+    begin(max_iteration, max_ref_iteration, bla_rows, start_bla_index, delta_c_scale, ref_pixel_x, ref_pixel_y, area_width, area_height, area_start_x, area_start_y) {
+        wasm.iterationjob_begin(this.__wbg_ptr, max_iteration, max_ref_iteration, bla_rows, start_bla_index, delta_c_scale, ref_pixel_x, ref_pixel_y, area_width, area_height, area_start_x, area_start_y);
+    }
+    /**
+     * reference orbit を使わない direct mode で job を始める。`begin` の代わりに呼ぶ。
+     *
+     * pixel 座標 (x, y) は c = center + ((x - center_pixel_x), -(y - center_pixel_y)) * delta_c_scale
+     * として計算する。`alloc` の xn / BLA の長さは 0 でよい。
+     * pass と band の進め方、iterations キャッシュ、progress と hit count は `begin` の job と同じ。
+     * 追加出力 (`set_*` で有効にしたもの) は direct mode では出力しない
+     * @param {number} max_iteration
+     * @param {number} center_re
+     * @param {number} center_im
+     * @param {number} delta_c_scale
+     * @param {number} center_pixel_x
+     * @param {number} center_pixel_y
+     * @param {number} area_width
+     * @param {number} area_height
+     * @param {number} area_start_x
+     * @param {number} area_start_y
+     */
+    begin_direct(max_iteration, center_re, center_im, delta_c_scale, center_pixel_x, center_pixel_y, area_width, area_height, area_start_x, area_start_y) {
+        wasm.iterationjob_begin_direct(this.__wbg_ptr, max_iteration, center_re, center_im, delta_c_scale, center_pixel_x, center_pixel_y, area_width, area_height, area_start_x, area_start_y);
+    }
+    /**
+     * 1 pass 分のパラメータを設定する。hit count と glitch count はここでリセットされる。
+     * @param {number} x_diff
+     * @param {number} y_diff
+     * @param {number} scaled_width
+     * @param {boolean} is_super_sampling
+     * @param {boolean} is_result_pass
+     */
+    begin_pass(x_diff, y_diff, scaled_width, is_super_sampling, is_result_pass) {
+        wasm.iterationjob_begin_pass(this.__wbg_ptr, x_diff, y_diff, scaled_width, is_super_sampling, is_result_pass);
+    }
+    /**
+     * adaptive supersampling の refinement pass を始める。hit count と glitch count はここでリセットされる。
+     *
+     * 通常の pass で埋めた iterations キャッシュを元に、8 近傍のどれかとの iteration 数の差が
+     * `threshold` を超えるピクセルと、maxIteration に達したかどうかが近傍と違うピクセルだけを
+     * `begin_sampling_pass` と同じ配置の `samples_per_axis`×`samples_per_axis` 個のサンプルで計算し直す。
+     * `calc_band` には area のピクセル行 [from, to) を渡す。出力は幅 `area_width` の scaled 出力で、
+     * 計算し直したピクセルはサンプルの平均 (四捨五入)、それ以外はキャッシュの値になる。
+     * 各ピクセルで使ったサンプル数 (1 か samples_per_axis^2) は `sample_counts_ptr` に書く。
+     * iterations キャッシュは書き換えず、追加出力も書かない。
+     * 未知の `pattern` を渡した場合は何もせず false を返す。
+     * @param {number} samples_per_axis
+     * @param {number} pattern
+     * @param {number} seed
+     * @param {number} threshold
+     * @param {boolean} is_result_pass
+     * @returns {boolean}
+     */
+    begin_refinement_pass(samples_per_axis, pattern, seed, threshold, is_result_pass) {
+        const ret = wasm.iterationjob_begin_refinement_pass(this.__wbg_ptr, samples_per_axis, pattern, seed, threshold, is_result_pass);
+        return ret !== 0;
+    }
+    /**
+     * 1 ピクセルを `samples_per_axis`×`samples_per_axis` 個のサンプルで計算する supersampling の pass を始める。
+     * hit count と glitch count はここでリセットされる。
+     *
+     * `pattern` は 0: 格子, 1: 傾けた格子, 2: jitter (`seed` とピクセル座標で決まる)。
+     * `samples_per_axis` は 1..=16 に丸める。
+     * この pass の `calc_band` には scaled 座標ではなく area のピクセル行 [from, to) を渡す。
+     * `is_averaged` が false なら全サンプルを幅 `area_width * samples_per_axis` の scaled 出力に、
+     * true ならピクセルごとの iteration 数の平均 (四捨五入) を幅 `area_width` で書く。
+     * iterations キャッシュは使わず、追加出力も書かない。
+     * 未知の `pattern` を渡した場合は何もせず false を返す。
+     * @param {number} samples_per_axis
+     * @param {number} pattern
+     * @param {number} seed
+     * @param {boolean} is_averaged
+     * @param {boolean} is_result_pass
+     * @returns {boolean}
+     */
+    begin_sampling_pass(samples_per_axis, pattern, seed, is_averaged, is_result_pass) {
+        const ret = wasm.iterationjob_begin_sampling_pass(this.__wbg_ptr, samples_per_axis, pattern, seed, is_averaged, is_result_pass);
+        return ret !== 0;
+    }
+    /**
+     * ポインタを渡したあとは JS が書き換えるものとして、次の `begin` で BLA の表を作り直す
+     * @returns {number}
+     */
+    bla_bytes_ptr() {
+        const ret = wasm.iterationjob_bla_bytes_ptr(this.__wbg_ptr);
+        return ret >>> 0;
+    }
+    /**
+     * `bla_bytes_ptr` と同じく、次の `begin` で BLA の表を作り直す
+     * @returns {number}
+     */
+    bla_row_offsets_ptr() {
+        const ret = wasm.iterationjob_bla_row_offsets_ptr(this.__wbg_ptr);
+        return ret >>> 0;
+    }
+    /**
+     * 確保しているバッファの合計バイト数 (Vec の容量ベース)
+     * @returns {number}
+     */
+    buffer_bytes() {
+        const ret = wasm.iterationjob_buffer_bytes(this.__wbg_ptr);
+        return ret >>> 0;
+    }
+    /**
+     * pass 内の scaled_y が [from, to) の範囲を計算する。
+     * `begin_sampling_pass` / `begin_refinement_pass` で始めた pass では area のピクセル行 [from, to) を計算する。
+     *
+     * 呼び出し粒度が progress 更新と terminator チェックの粒度になる。
+     * job の入力か band の範囲が壊れていれば何も計算せずにエラーコードを返す (0 なら成功)。
+     * @param {number} band_scaled_y_from
+     * @param {number} band_scaled_y_to
+     * @returns {number}
+     */
+    calc_band(band_scaled_y_from, band_scaled_y_to) {
+        const ret = wasm.iterationjob_calc_band(this.__wbg_ptr, band_scaled_y_from, band_scaled_y_to);
+        return ret >>> 0;
+    }
+    /**
+     * `pixel_list_ptr` に書いた `count` 個のピクセル座標の iteration 数を、同じ順に `pixel_results_ptr` に書く。
+     *
+     * 座標は `calc_band` と同じ area_start を含む pixel 座標で、小数でもよい。
+     * adaptive な refinement、glitch したピクセルの計算し直し、カーソル位置の iteration 数の表示など、
+     * 行単位ではなく散らばったピクセルだけを計算したいときに使う。
+     * `begin` / `begin_direct` のあとなら pass の途中でも呼べて、iterations キャッシュ、pass の出力、
+     * 追加出力、`calculated_count` と hit count は変えない。追加出力のある job でも iteration 数だけを返し、
+     * glitch の補正はしない (secondary reference を設定した job でも primary の結果を返す)。
+     * job の入力が壊れているか、`count` がバッファより多ければ何も計算せずにエラーコードを返す (0 なら成功)。
+     * @param {number} count
+     * @returns {number}
+     */
+    calc_pixels(count) {
+        const ret = wasm.iterationjob_calc_pixels(this.__wbg_ptr, count);
+        return ret >>> 0;
+    }
+    /**
+     * job 開始以降に実際に計算したピクセル数を返す。JS 側の progress 表示に使う。
+     * solid guessing で埋めたピクセルと、前の job から引き継いだピクセルも含む。
+     * @returns {number}
+     */
+    calculated_count() {
+        const ret = wasm.iterationjob_calculated_count(this.__wbg_ptr);
+        return ret >>> 0;
+    }
+    /**
+     * 集計を無効にする。次の `begin` から反映される
+     */
+    clear_accumulation() {
+        wasm.iterationjob_clear_accumulation(this.__wbg_ptr);
+    }
+    /**
+     * 直近に返したエラーの説明。エラーがなければ空文字列
+     * @returns {string}
+     */
+    error_message() {
+        let deferred1_0;
+        let deferred1_1;
+        try {
+            const ret = wasm.iterationjob_error_message(this.__wbg_ptr);
+            deferred1_0 = ret[0];
+            deferred1_1 = ret[1];
+            return getStringFromWasm0(ret[0], ret[1]);
+        } finally {
+            wasm.__wbindgen_free(deferred1_0, deferred1_1, 1);
+        }
+    }
+    /**
+     * 直近の pass で glitch と判定したピクセル数を返す。
+     * hit count と同じく `is_result_pass` を立てた pass でのみ数えている。
+     * @returns {number}
+     */
+    glitch_count() {
+        const ret = wasm.iterationjob_glitch_count(this.__wbg_ptr);
+        return ret >>> 0;
+    }
+    /**
+     * glitch 判定の結果 (u8、glitch なら 1) の pass 出力。glitch 判定が有効な job の `begin` 以降に取得すること
+     * @returns {number}
+     */
+    glitch_flags_ptr() {
+        const ret = wasm.iterationjob_glitch_flags_ptr(this.__wbg_ptr);
+        return ret >>> 0;
+    }
+    /**
+     * job 開始以降に solid guessing / boundary tracing で計算せずに埋めたピクセル数を返す。
+     * `set_guess_check` が有効な job では、埋める代わりに計算したピクセルも含む。
+     * @returns {number}
+     */
+    guessed_count() {
+        const ret = wasm.iterationjob_guessed_count(this.__wbg_ptr);
+        return ret >>> 0;
+    }
+    /**
+     * 直近の pass で iteration が maxIteration に達したピクセル数を返す。
+     * `is_result_pass` を立てた pass でのみ数えている。
+     * @returns {number}
+     */
+    hit_count() {
+        const ret = wasm.iterationjob_hit_count(this.__wbg_ptr);
+        return ret >>> 0;
+    }
+    /**
+     * `alloc_iteration_import` のバッファにコピーした前の画像の iteration 数を、iterations キャッシュの
+     * 重なるピクセルに書く。`begin` / `begin_direct` のあと、最初の pass より前に呼ぶ。
+     *
+     * 前の画像は幅 `src_width` 高さ `src_height` で、そのピクセル (sx, sy) が新しい画像の
+     * pixel 座標 (offset_x + sx * scale, offset_y + sy * scale) に重なるものとして扱う。
+     * 2 倍の拡大なら scale = 2 で、新しいピクセルの 4 つに 1 つが埋まる。
+     * 重ならないピクセル、前の画像で 0 (未計算) のピクセル、計算済みのピクセルは書かない。
+     * 書いたピクセルは pass で計算済みとして飛ばし、`reused_count` に加える。
+     * 前の画像が同じ maxIteration で同じ点を計算したものかは確かめないので、呼び出し側で保証する。
+     * バッファが足りない場合、scale が 0 の場合、キャッシュを持たない job と
+     * 追加出力を使う job では何もしない。書いたピクセル数を返す。
+     * @param {number} src_width
+     * @param {number} src_height
+     * @param {number} scale
+     * @param {number} offset_x
+     * @param {number} offset_y
+     * @returns {number}
+     */
+    import_iterations(src_width, src_height, scale, offset_x, offset_y) {
+        const ret = wasm.iterationjob_import_iterations(this.__wbg_ptr, src_width, src_height, scale, offset_x, offset_y);
+        return ret >>> 0;
+    }
+    /**
+     * 内部判定で検出した周期軌道の |multiplier| (f64) の pass 出力。取得タイミングは `interior_periods_ptr` と同じ
+     * @returns {number}
+     */
+    interior_multipliers_ptr() {
+        const ret = wasm.iterationjob_interior_multipliers_ptr(this.__wbg_ptr);
+        return ret >>> 0;
+    }
+    /**
+     * 内部判定で検出した周期 (u32) の pass 出力。内部判定が有効な job の `begin` 以降に取得すること
+     * @returns {number}
+     */
+    interior_periods_ptr() {
+        const ret = wasm.iterationjob_interior_periods_ptr(this.__wbg_ptr);
+        return ret >>> 0;
+    }
+    /**
+     * job 開始以降に iteration を実際に回したピクセル数を返す。
+     * `calculated_count` から埋めたピクセル (check 有効時は埋めるはずだったピクセル) と
+     * 引き継いだピクセルを除いたもので、
+     * 全ピクセルに対する割合が
+     * solid guessing / boundary tracing で減らせた計算量の目安になる。
+     * @returns {number}
+     */
+    iterated_count() {
+        const ret = wasm.iterationjob_iterated_count(this.__wbg_ptr);
+        return ret >>> 0;
+    }
+    /**
+     * @returns {number}
+     */
+    iteration_import_ptr() {
+        const ret = wasm.iterationjob_iteration_import_ptr(this.__wbg_ptr);
+        return ret >>> 0;
+    }
+    /**
+     * job 開始以降に solid guessing / boundary tracing で埋め間違えたピクセル数を返す。
+     * `set_guess_check` を有効にした job でのみ数えている。
+     * @returns {number}
+     */
+    mistaken_guess_count() {
+        const ret = wasm.iterationjob_mistaken_guess_count(this.__wbg_ptr);
+        return ret >>> 0;
+    }
+    /**
+     * 空の job を作る。バッファは `alloc` で確保する
+     */
+    constructor() {
+        const ret = wasm.iterationjob_new();
+        this.__wbg_ptr = ret;
+        IterationJobFinalization.register(this, this.__wbg_ptr, this);
+        return this;
+    }
+    /**
+     * @returns {number}
+     */
+    pixel_list_ptr() {
+        const ret = wasm.iterationjob_pixel_list_ptr(this.__wbg_ptr);
+        return ret >>> 0;
+    }
+    /**
+     * @returns {number}
+     */
+    pixel_results_ptr() {
+        const ret = wasm.iterationjob_pixel_results_ptr(this.__wbg_ptr);
+        return ret >>> 0;
+    }
+    /**
+     * `alloc_reference` が false を返す index では null。次の `set_reference` で BLA の表を作り直す
+     * @param {number} index
+     * @returns {number}
+     */
+    reference_bla_bytes_ptr(index) {
+        const ret = wasm.iterationjob_reference_bla_bytes_ptr(this.__wbg_ptr, index);
+        return ret >>> 0;
+    }
+    /**
+     * `alloc_reference` が false を返す index では null。次の `set_reference` で BLA の表を作り直す
+     * @param {number} index
+     * @returns {number}
+     */
+    reference_bla_row_offsets_ptr(index) {
+        const ret = wasm.iterationjob_reference_bla_row_offsets_ptr(this.__wbg_ptr, index);
+        return ret >>> 0;
+    }
+    /**
+     * `alloc_reference` が false を返す index では null
+     * @param {number} index
+     * @returns {number}
+     */
+    reference_xn_ptr(index) {
+        const ret = wasm.iterationjob_reference_xn_ptr(this.__wbg_ptr, index);
+        return ret >>> 0;
+    }
+    /**
+     * 確保したバッファをすべて手放す。設定 (`set_*`) は残る。
+     * 次に使うときは `alloc` からやり直す
+     */
+    release() {
+        wasm.iterationjob_release(this.__wbg_ptr);
+    }
+    /**
+     * 直近の `begin` / `begin_direct` で前の job の iterations キャッシュから引き継いだピクセル数と、
+     * そのあと `import_iterations` で書いたピクセル数の合計を返す。
+     * `set_iteration_shift` も `import_iterations` も使わなかった job では 0。
+     * @returns {number}
+     */
+    reused_count() {
+        const ret = wasm.iterationjob_reused_count(this.__wbg_ptr);
+        return ret >>> 0;
+    }
+    /**
+     * refinement pass で各ピクセルに使ったサンプル数
+     * @returns {number}
+     */
+    sample_counts_ptr() {
+        const ret = wasm.iterationjob_sample_counts_ptr(this.__wbg_ptr);
+        return ret >>> 0;
+    }
+    /**
+     * @returns {number}
+     */
+    scaled_iterations_ptr() {
+        const ret = wasm.iterationjob_scaled_iterations_ptr(this.__wbg_ptr);
+        return ret >>> 0;
+    }
+    /**
+     * 直近の `begin` で決まった series approximation のスキップ先 iteration。使っていなければ 0。
+     * 追加出力のある job では求めても使われない
+     * @returns {number}
+     */
+    series_skip() {
+        const ret = wasm.iterationjob_series_skip(this.__wbg_ptr);
+        return ret >>> 0;
+    }
+    /**
+     * atom domain (|z|² が最小になった iteration) の出力を有効/無効にする。`begin` より前に呼ぶ。
+     *
+     * 結果は `atom_domains_ptr` に入る。z_0 は数えないので、bailout までに 1 度も観測しなければ 0。
+     * @param {boolean} enabled
+     */
+    set_atom_domain(enabled) {
+        wasm.iterationjob_set_atom_domain(this.__wbg_ptr, enabled);
+    }
+    /**
+     * 平均系の色付け (TIA / curvature average) による集計を有効にする。`begin` より前に呼ぶ。
+     *
+     * `kind` は 0: triangle inequality average, 1: curvature average。
+     * `accum_values_ptr` に全項の平均、`accum_prev_values_ptr` に最後の項を除いた平均、
+     * `accum_iterations_ptr` に項数が入る。前 2 つを smooth iteration の小数部で補間して使う。
+     * 未知の `kind` を渡した場合は何もせず false を返す。
+     * @param {number} kind
+     * @returns {boolean}
+     */
+    set_average_coloring(kind) {
+        const ret = wasm.iterationjob_set_average_coloring(this.__wbg_ptr, kind);
+        return ret !== 0;
+    }
+    /**
+     * maxIteration に達したピクセルの計算途中の状態を残すかを設定する (既定は無効)。`begin` より前に呼ぶ。
+     *
+     * 有効な job のあと、同じ reference (同じ c で、orbit は伸ばしてもよい)・同じ area・同じスケールで
+     * maxIteration だけを上げた job を始めると、bailout 済みのピクセルはキャッシュの値をそのまま使い、
+     * maxIteration に達したピクセルは止まったところから計算を続ける。
+     * 引き継いだピクセル数は `reused_count` で返す。
+     * 状態は 1 ピクセルずつ計算する kernel で読み書きするので、有効な job では f32 / simd /
+     * interleave の kernel と `set_render_strategy` の 1, 2 は使わない。
+     * direct mode の job と追加出力のある job では何もしない。
+     * @param {boolean} enabled
+     */
+    set_continuation(enabled) {
+        wasm.iterationjob_set_continuation(this.__wbg_ptr, enabled);
+    }
+    /**
+     * 浅いズームで f32 の kernel を使うかを設定する (既定は無効)。`begin` より前に呼ぶ。
+     *
+     * 結果が f64 とビット単位では一致しなくなるので、使う側 (worker) で明示的に有効にする。
+     * 有効にすると job ごとに自動で選ぶ。Δc のスケールが小さい job、reference の orbit が f32 で表せない job
+     * (`fast32` を参照) と追加出力のある job では f64 で計算する。
+     * f32 で精度が足りなくなったピクセルは f64 で計算し直すので、結果はほぼ f64 と同じになる。
+     * @param {boolean} enabled
+     */
+    set_f32_fast_path(enabled) {
+        wasm.iterationjob_set_f32_fast_path(this.__wbg_ptr, enabled);
+    }
+    /**
+     * glitch 判定を設定する。`begin` より前に呼ぶ。
+     *
+     * いずれかの iteration で |z|² < `tolerance` * |Δ|² となったピクセルを glitch とみなす
+     * (Pauldelbrot の判定を rebase 前提に読み替えたもの。|z| / |Δ| < 1e-3 なら 1e-6 を渡す)。
+     * 0 以下を渡すと無効になる。結果は `glitch_flags_ptr` と `glitch_count` で取得する。
+     * `set_reference` で reference を追加してあれば、glitch したピクセルは近い reference から順に
+     * 計算し直され、どれでも glitch しなかった場合だけ glitch として残る。
+     * @param {number} tolerance
+     */
+    set_glitch_detection(tolerance) {
+        wasm.iterationjob_set_glitch_detection(this.__wbg_ptr, tolerance);
+    }
+    /**
+     * `set_render_strategy` で埋めたピクセルも計算して答え合わせするかを設定する (既定は無効)。
+     *
+     * デバッグ用。有効なら出力は計算した値になり、違っていた数を `mistaken_guess_count` で返す。
+     * 計算量は全ピクセルを計算する場合と同じになる。
+     * @param {boolean} enabled
+     */
+    set_guess_check(enabled) {
+        wasm.iterationjob_set_guess_check(this.__wbg_ptr, enabled);
+    }
+    /**
+     * 内部判定を設定する。`begin` より前に呼ぶ。
+     *
+     * `flags` は bit 0: periodicity (Brent 法で z の周期を検出)、bit 1: derivative (dz/dz が十分小さい)。
+     * 0 を渡すと無効になる。両方立てると periodicity は |multiplier| < 1 も満たすときだけ採用する。
+     * `periodicity_epsilon_sq` は |z_n - z_m|² の閾値、`derivative_epsilon_sq` は |dz/dz|² の閾値。
+     *
+     * 内部と判定したピクセルは maxIteration として扱い、`interior_periods_ptr` に周期が入る
+     * (内部でなければ 0、derivative 判定だけで周期が分からなければ u32::MAX)。
+     * `interior_multipliers_ptr` には周期軌道の |multiplier| が入る (内部でなければ 0、周期が分からなければ NaN)。
+     * @param {number} flags
+     * @param {number} periodicity_epsilon_sq
+     * @param {number} derivative_epsilon_sq
+     */
+    set_interior_detection(flags, periodicity_epsilon_sq, derivative_epsilon_sq) {
+        wasm.iterationjob_set_interior_detection(this.__wbg_ptr, flags, periodicity_epsilon_sq, derivative_epsilon_sq);
+    }
+    /**
+     * 依存のない複数のピクセルを 1 つのループで交互に進める数を設定する (1〜4、既定は 1)。
+     *
+     * 結果は 1 ピクセルずつ計算した場合と変わらない。速くなるかは CPU と経路次第なので、
+     * 2 以上にするのはベンチで確かめてから。追加出力のある job では使われない。
+     * simd128 のビルドで 1 のときは 2 ピクセル同時の simd kernel を使う。
+     * @param {number} count
+     */
+    set_interleaved_pixels(count) {
+        wasm.iterationjob_set_interleaved_pixels(this.__wbg_ptr, count);
+    }
+    /**
+     * 次の `begin` / `begin_direct` で、前の job の iterations キャッシュを (dx, dy) ピクセル
+     * ずらして引き継ぐ。前の job のピクセル (x, y) の値が次の job の (x + dx, y + dy) に入る。
+     *
+     * maxIteration、Δc のスケール、area、reference (direct mode では c の位置) が前の job を
+     * ちょうど (dx, dy) ずらしたものになっている場合だけ引き継ぎ、新しく見えた行と列を
+     * 未計算にする。それ以外の場合と、追加出力を使う job では通常通り 0 クリアする。
+     * 指定は次の job 1 回だけに効く。引き継いだピクセル数は `reused_count` で返す。
+     * @param {number} dx
+     * @param {number} dy
+     */
+    set_iteration_shift(dx, dy) {
+        wasm.iterationjob_set_iteration_shift(this.__wbg_ptr, dx, dy);
+    }
+    /**
+     * orbit trap による集計を有効にする。`begin` より前に呼ぶ。
+     * 集計モードは 1 つだけで、`set_average_coloring` とは後から呼んだ方が有効になる。
+     *
+     * `shape` は 0: point, 1: cross, 2: line, 3: circle。中心は reference の c からの相対座標で、
+     * `param` は line なら偏角 [rad]、circle なら半径 (それ以外では使わない)。
+     * 結果は `accum_values_ptr` に最小距離、`accum_iterations_ptr` にそのときの iteration が入る。
+     * 未知の `shape` を渡した場合は何もせず false を返す。
+     * @param {number} shape
+     * @param {number} offset_re
+     * @param {number} offset_im
+     * @param {number} param
+     * @returns {boolean}
+     */
+    set_orbit_trap(shape, offset_re, offset_im, param) {
+        const ret = wasm.iterationjob_set_orbit_trap(this.__wbg_ptr, shape, offset_re, offset_im, param);
+        return ret !== 0;
+    }
+    /**
+     * secondary reference のパラメータを設定する。`begin` のあとに呼ぶ。
+     *
+     * 設定した reference は、glitch 判定が有効なときに glitch したピクセルの計算し直しに使われる。
+     * index は 1 から順に設定し、1..=n を設定した job では n 本が使われる (`begin` でリセットされる)。
+     * BLATable を持たない reference は `bla_rows` に 0 を渡す。
+     * 入力が壊れているか、index が飛んでいるか上限を超えていればエラーコードを返し、
+     * その job の band は計算されなくなる。
+     * @param {number} index
+     * @param {number} max_ref_iteration
+     * @param {number} bla_rows
+     * @param {number} ref_pixel_x
+     * @param {number} ref_pixel_y
+     * @returns {number}
+     */
+    set_reference(index, max_ref_iteration, bla_rows, ref_pixel_x, ref_pixel_y) {
+        const ret = wasm.iterationjob_set_reference(this.__wbg_ptr, index, max_ref_iteration, bla_rows, ref_pixel_x, ref_pixel_y);
+        return ret >>> 0;
+    }
+    /**
+     * band 内のピクセルの計算順を設定する (既定は 0)。
+     *
+     * 0: 全ピクセルを計算する。
+     * 1: Mariani–Silver の矩形分割。矩形の周囲を計算し、全部同じ iteration 数なら内側をその値で埋める。
+     * 2: boundary tracing。iteration 数の境界をたどって計算し、囲まれた内側を埋める。
+     * 1 と 2 は細い構造を埋め間違えることがあるので、結果は 1 ピクセルずつ計算した場合と
+     * 一致するとは限らない。band の行数が多いほど効き、1 行の band では何も埋めない。
+     * 追加出力のある job と supersampling の pass (`begin_sampling_pass` など) では 0 として扱う。
+     * 未知の値を渡した場合は何もせず false を返す。
+     * @param {number} strategy
+     * @returns {boolean}
+     */
+    set_render_strategy(strategy) {
+        const ret = wasm.iterationjob_set_render_strategy(this.__wbg_ptr, strategy);
+        return ret !== 0;
+    }
+    /**
+     * series approximation による初期スキップを設定する。`begin` より前に呼ぶ。
+     *
+     * `terms` は級数の項数 (0 で無効、上限 64)。`begin` で係数を 1 回だけ求め、
+     * area の四隅と各辺の中点に置いた probe 点で相対誤差が `tolerance` 以内に収まる iteration まで
+     * 全ピクセルの計算を飛ばす。スキップ先は `series_skip` で取得できる。
+     * スキップは iteration 数にだけ効く。orbit trap / average coloring、内部判定、atom domain、
+     * glitch 判定のいずれかが有効な job ではスキップした iteration の z が必要なので使われない。
+     * @param {number} terms
+     * @param {number} tolerance
+     */
+    set_series_approximation(terms, tolerance) {
+        wasm.iterationjob_set_series_approximation(this.__wbg_ptr, terms, tolerance);
+    }
+    /**
+     * バッファを直近の `alloc` / `begin` で必要とされたサイズまで縮める。
+     *
+     * `alloc` はバッファを伸ばすだけなので、大きな job のあとはその分の容量が残り続ける。
+     * 縮めた分は allocator に返るが、wasm の linear memory 自体は縮まないので、
+     * memory ごと手放したい場合は worker を作り直す
+     */
+    shrink() {
+        wasm.iterationjob_shrink(this.__wbg_ptr);
+    }
+    /**
+     * 直近の `begin` で f32 の kernel を使うと決まったか
+     * @returns {boolean}
+     */
+    uses_f32() {
+        const ret = wasm.iterationjob_uses_f32(this.__wbg_ptr);
+        return ret !== 0;
+    }
+    /**
+     * 現在の job の入力 (primary と追加した reference) を検証し直して、エラーコードを返す。
+     *
+     * 0 なら正常。0 以外なら band は計算されないので、`error_message` で理由を取得する。
+     * - 1: xn が max_ref_iteration に足りない
+     * - 2: bla_row_offsets が bla_rows 段に足りない
+     * - 3: BLA の row が bla_bytes の範囲外
+     * - 4: BLA の row が max_ref_iteration に足りない
+     * - 5: pass が iterations キャッシュの範囲外を読む (band のみ)
+     * - 6: band が pass 出力の範囲外に書く (band のみ)
+     * - 7: ピクセル座標の数がバッファより多い (`calc_pixels` のみ)
+     * - 8: reference の index が飛んでいるか上限を超えている (`set_reference` のみ)
+     * @returns {number}
+     */
+    validate() {
+        const ret = wasm.iterationjob_validate(this.__wbg_ptr);
+        return ret >>> 0;
+    }
+    /**
+     * @returns {number}
+     */
+    xn_ptr() {
+        const ret = wasm.iterationjob_xn_ptr(this.__wbg_ptr);
+        return ret >>> 0;
+    }
+}
+if (Symbol.dispose) IterationJob.prototype[Symbol.dispose] = IterationJob.prototype.free;
+
+/**
+ * @returns {number}
+ */
+export function accum_iterations_ptr() {
+    const ret = wasm.accum_iterations_ptr();
+    return ret >>> 0;
+}
+
+/**
+ * @returns {number}
+ */
+export function accum_prev_values_ptr() {
+    const ret = wasm.accum_prev_values_ptr();
+    return ret >>> 0;
+}
+
+/**
+ * @returns {number}
+ */
+export function accum_values_ptr() {
+    const ret = wasm.accum_values_ptr();
+    return ret >>> 0;
+}
+
+/**
+ * @param {number} pixels
+ */
+export function alloc_iteration_import(pixels) {
+    wasm.alloc_iteration_import(pixels);
+}
+
+/**
+ * `IterationJob::alloc`
  * @param {number} xn_f64_len
  * @param {number} bla_bytes_len
  * @param {number} bla_row_offsets_len
@@ -15,7 +740,51 @@ export function alloc_job(xn_f64_len, bla_bytes_len, bla_row_offsets_len, area_p
 }
 
 /**
- * job 全体のパラメータを確定する。iterations キャッシュはここで 0 クリアされる。
+ * @param {number} count
+ */
+export function alloc_pixel_list(count) {
+    wasm.alloc_pixel_list(count);
+}
+
+/**
+ * @param {number} index
+ * @param {number} xn_f64_len
+ * @param {number} bla_bytes_len
+ * @param {number} bla_row_offsets_len
+ * @returns {boolean}
+ */
+export function alloc_reference(index, xn_f64_len, bla_bytes_len, bla_row_offsets_len) {
+    const ret = wasm.alloc_reference(index, xn_f64_len, bla_bytes_len, bla_row_offsets_len);
+    return ret !== 0;
+}
+
+/**
+ * @returns {number}
+ */
+export function atom_domains_ptr() {
+    const ret = wasm.atom_domains_ptr();
+    return ret >>> 0;
+}
+
+/**
+ * `IterationJob::begin_direct`
+ * @param {number} max_iteration
+ * @param {number} center_re
+ * @param {number} center_im
+ * @param {number} delta_c_scale
+ * @param {number} center_pixel_x
+ * @param {number} center_pixel_y
+ * @param {number} area_width
+ * @param {number} area_height
+ * @param {number} area_start_x
+ * @param {number} area_start_y
+ */
+export function begin_direct_iteration_job(max_iteration, center_re, center_im, delta_c_scale, center_pixel_x, center_pixel_y, area_width, area_height, area_start_x, area_start_y) {
+    wasm.begin_direct_iteration_job(max_iteration, center_re, center_im, delta_c_scale, center_pixel_x, center_pixel_y, area_width, area_height, area_start_x, area_start_y);
+}
+
+/**
+ * `IterationJob::begin`
  * @param {number} max_iteration
  * @param {number} max_ref_iteration
  * @param {number} bla_rows
@@ -33,7 +802,6 @@ export function begin_iteration_job(max_iteration, max_ref_iteration, bla_rows,
 }
 
 /**
- * 1 pass 分のパラメータを設定する。hit count はここでリセットされる。
  * @param {number} x_diff
  * @param {number} y_diff
  * @param {number} scaled_width
@@ -44,6 +812,34 @@ export function begin_pass(x_diff, y_diff, scaled_width, is_super_sampling, is_r
     wasm.begin_pass(x_diff, y_diff, scaled_width, is_super_sampling, is_result_pass);
 }
 
+/**
+ * `IterationJob::begin_refinement_pass`
+ * @param {number} samples_per_axis
+ * @param {number} pattern
+ * @param {number} seed
+ * @param {number} threshold
+ * @param {boolean} is_result_pass
+ * @returns {boolean}
+ */
+export function begin_refinement_pass(samples_per_axis, pattern, seed, threshold, is_result_pass) {
+    const ret = wasm.begin_refinement_pass(samples_per_axis, pattern, seed, threshold, is_result_pass);
+    return ret !== 0;
+}
+
+/**
+ * `IterationJob::begin_sampling_pass`
+ * @param {number} samples_per_axis
+ * @param {number} pattern
+ * @param {number} seed
+ * @param {boolean} is_averaged
+ * @param {boolean} is_result_pass
+ * @returns {boolean}
+ */
+export function begin_sampling_pass(samples_per_axis, pattern, seed, is_averaged, is_result_pass) {
+    const ret = wasm.begin_sampling_pass(samples_per_axis, pattern, seed, is_averaged, is_result_pass);
+    return ret !== 0;
+}
+
 /**
  * @returns {number}
  */
@@ -61,18 +857,41 @@ export function bla_row_offsets_ptr() {
 }
 
 /**
- * pass 内の scaled_y が [from, to) の範囲を計算する。
- *
- * 呼び出し粒度が progress 更新と terminator チェックの粒度になる。
+ * `IterationJob::calc_band`
  * @param {number} band_scaled_y_from
  * @param {number} band_scaled_y_to
+ * @returns {number}
  */
 export function calc_iteration_band(band_scaled_y_from, band_scaled_y_to) {
-    wasm.calc_iteration_band(band_scaled_y_from, band_scaled_y_to);
+    const ret = wasm.calc_iteration_band(band_scaled_y_from, band_scaled_y_to);
+    return ret >>> 0;
 }
 
 /**
- * job 開始以降に実際に計算したピクセル数を返す。JS 側の progress 表示に使う。
+ * `IterationJob::calc_pixels`
+ * @param {number} count
+ * @returns {number}
+ */
+export function calc_iteration_pixels(count) {
+    const ret = wasm.calc_iteration_pixels(count);
+    return ret >>> 0;
+}
+
+export function clear_accumulation() {
+    wasm.clear_accumulation();
+}
+
+/**
+ * `IterationJob::buffer_bytes`
+ * @returns {number}
+ */
+export function get_buffer_bytes() {
+    const ret = wasm.get_buffer_bytes();
+    return ret >>> 0;
+}
+
+/**
+ * `IterationJob::calculated_count`
  * @returns {number}
  */
 export function get_calculated_count() {
@@ -81,8 +900,25 @@ export function get_calculated_count() {
 }
 
 /**
- * 直近の pass で iteration が maxIteration に達したピクセル数を返す。
- * `is_result_pass` を立てた pass でのみ数えている。
+ * `IterationJob::glitch_count`
+ * @returns {number}
+ */
+export function get_glitch_count() {
+    const ret = wasm.get_glitch_count();
+    return ret >>> 0;
+}
+
+/**
+ * `IterationJob::guessed_count`
+ * @returns {number}
+ */
+export function get_guessed_count() {
+    const ret = wasm.get_guessed_count();
+    return ret >>> 0;
+}
+
+/**
+ * `IterationJob::hit_count`
  * @returns {number}
  */
 export function get_hit_count() {
@@ -90,6 +926,173 @@ export function get_hit_count() {
     return ret >>> 0;
 }
 
+/**
+ * `IterationJob::iterated_count`
+ * @returns {number}
+ */
+export function get_iterated_count() {
+    const ret = wasm.get_iterated_count();
+    return ret >>> 0;
+}
+
+/**
+ * `IterationJob::error_message`
+ * @returns {string}
+ */
+export function get_job_error_message() {
+    let deferred1_0;
+    let deferred1_1;
+    try {
+        const ret = wasm.get_job_error_message();
+        deferred1_0 = ret[0];
+        deferred1_1 = ret[1];
+        return getStringFromWasm0(ret[0], ret[1]);
+    } finally {
+        wasm.__wbindgen_free(deferred1_0, deferred1_1, 1);
+    }
+}
+
+/**
+ * `IterationJob::mistaken_guess_count`
+ * @returns {number}
+ */
+export function get_mistaken_guess_count() {
+    const ret = wasm.get_mistaken_guess_count();
+    return ret >>> 0;
+}
+
+/**
+ * `IterationJob::reused_count`
+ * @returns {number}
+ */
+export function get_reused_count() {
+    const ret = wasm.get_reused_count();
+    return ret >>> 0;
+}
+
+/**
+ * `IterationJob::series_skip`
+ * @returns {number}
+ */
+export function get_series_skip() {
+    const ret = wasm.get_series_skip();
+    return ret >>> 0;
+}
+
+/**
+ * `IterationJob::uses_f32`
+ * @returns {boolean}
+ */
+export function get_uses_f32() {
+    const ret = wasm.get_uses_f32();
+    return ret !== 0;
+}
+
+/**
+ * @returns {number}
+ */
+export function glitch_flags_ptr() {
+    const ret = wasm.glitch_flags_ptr();
+    return ret >>> 0;
+}
+
+/**
+ * `IterationJob::import_iterations`
+ * @param {number} src_width
+ * @param {number} src_height
+ * @param {number} scale
+ * @param {number} offset_x
+ * @param {number} offset_y
+ * @returns {number}
+ */
+export function import_iterations(src_width, src_height, scale, offset_x, offset_y) {
+    const ret = wasm.import_iterations(src_width, src_height, scale, offset_x, offset_y);
+    return ret >>> 0;
+}
+
+/**
+ * @returns {number}
+ */
+export function interior_multipliers_ptr() {
+    const ret = wasm.interior_multipliers_ptr();
+    return ret >>> 0;
+}
+
+/**
+ * @returns {number}
+ */
+export function interior_periods_ptr() {
+    const ret = wasm.interior_periods_ptr();
+    return ret >>> 0;
+}
+
+/**
+ * @returns {number}
+ */
+export function iteration_import_ptr() {
+    const ret = wasm.iteration_import_ptr();
+    return ret >>> 0;
+}
+
+/**
+ * @returns {number}
+ */
+export function pixel_list_ptr() {
+    const ret = wasm.pixel_list_ptr();
+    return ret >>> 0;
+}
+
+/**
+ * @returns {number}
+ */
+export function pixel_results_ptr() {
+    const ret = wasm.pixel_results_ptr();
+    return ret >>> 0;
+}
+
+/**
+ * @param {number} index
+ * @returns {number}
+ */
+export function reference_bla_bytes_ptr(index) {
+    const ret = wasm.reference_bla_bytes_ptr(index);
+    return ret >>> 0;
+}
+
+/**
+ * @param {number} index
+ * @returns {number}
+ */
+export function reference_bla_row_offsets_ptr(index) {
+    const ret = wasm.reference_bla_row_offsets_ptr(index);
+    return ret >>> 0;
+}
+
+/**
+ * @param {number} index
+ * @returns {number}
+ */
+export function reference_xn_ptr(index) {
+    const ret = wasm.reference_xn_ptr(index);
+    return ret >>> 0;
+}
+
+/**
+ * `IterationJob::release`
+ */
+export function release_job() {
+    wasm.release_job();
+}
+
+/**
+ * `IterationJob::sample_counts_ptr`
+ * @returns {number}
+ */
+export function sample_counts_ptr() {
+    const ret = wasm.sample_counts_ptr();
+    return ret >>> 0;
+}
+
 /**
  * @returns {number}
  */
@@ -98,6 +1101,132 @@ export function scaled_iterations_ptr() {
     return ret >>> 0;
 }
 
+/**
+ * @param {boolean} enabled
+ */
+export function set_atom_domain(enabled) {
+    wasm.set_atom_domain(enabled);
+}
+
+/**
+ * @param {number} kind
+ * @returns {boolean}
+ */
+export function set_average_coloring(kind) {
+    const ret = wasm.set_average_coloring(kind);
+    return ret !== 0;
+}
+
+/**
+ * @param {boolean} enabled
+ */
+export function set_continuation(enabled) {
+    wasm.set_continuation(enabled);
+}
+
+/**
+ * @param {boolean} enabled
+ */
+export function set_f32_fast_path(enabled) {
+    wasm.set_f32_fast_path(enabled);
+}
+
+/**
+ * @param {number} tolerance
+ */
+export function set_glitch_detection(tolerance) {
+    wasm.set_glitch_detection(tolerance);
+}
+
+/**
+ * @param {boolean} enabled
+ */
+export function set_guess_check(enabled) {
+    wasm.set_guess_check(enabled);
+}
+
+/**
+ * @param {number} flags
+ * @param {number} periodicity_epsilon_sq
+ * @param {number} derivative_epsilon_sq
+ */
+export function set_interior_detection(flags, periodicity_epsilon_sq, derivative_epsilon_sq) {
+    wasm.set_interior_detection(flags, periodicity_epsilon_sq, derivative_epsilon_sq);
+}
+
+/**
+ * @param {number} count
+ */
+export function set_interleaved_pixels(count) {
+    wasm.set_interleaved_pixels(count);
+}
+
+/**
+ * @param {number} dx
+ * @param {number} dy
+ */
+export function set_iteration_shift(dx, dy) {
+    wasm.set_iteration_shift(dx, dy);
+}
+
+/**
+ * @param {number} shape
+ * @param {number} offset_re
+ * @param {number} offset_im
+ * @param {number} param
+ * @returns {boolean}
+ */
+export function set_orbit_trap(shape, offset_re, offset_im, param) {
+    const ret = wasm.set_orbit_trap(shape, offset_re, offset_im, param);
+    return ret !== 0;
+}
+
+/**
+ * @param {number} index
+ * @param {number} max_ref_iteration
+ * @param {number} bla_rows
+ * @param {number} ref_pixel_x
+ * @param {number} ref_pixel_y
+ * @returns {number}
+ */
+export function set_reference(index, max_ref_iteration, bla_rows, ref_pixel_x, ref_pixel_y) {
+    const ret = wasm.set_reference(index, max_ref_iteration, bla_rows, ref_pixel_x, ref_pixel_y);
+    return ret >>> 0;
+}
+
+/**
+ * @param {number} strategy
+ * @returns {boolean}
+ */
+export function set_render_strategy(strategy) {
+    const ret = wasm.set_render_strategy(strategy);
+    return ret !== 0;
+}
+
+/**
+ * @param {number} terms
+ * @param {number} tolerance
+ */
+export function set_series_approximation(terms, tolerance) {
+    wasm.set_series_approximation(terms, tolerance);
+}
+
+/**
+ * `IterationJob::shrink`
+ */
+export function shrink_job() {
+    wasm.shrink_job();
+}
+
+/**
+ * `IterationJob::validate`
+ * @returns {number}
+ */
+export function validate_job() {
+    const ret = wasm.validate_job();
+    return ret >>> 0;
+}
+
 /**
  * @returns {number}
  */
@@ -108,6 +1237,9 @@ export function xn_ptr() {
 function __wbg_get_imports() {
     const import0 = {
         __proto__: null,
+        __wbg___wbindgen_throw_bb96b2010945f0bc: function(arg0, arg1) {
+            throw new Error(getStringFromWasm0(arg0, arg1));
+        },
         __wbindgen_init_externref_table: function() {
             const table = wasm.__wbindgen_externrefs;
             const offset = table.grow(4);
@@ -124,11 +1256,42 @@ function __wbg_get_imports() {
     };
 }
 
+const IterationJobFinalization = (typeof FinalizationRegistry === 'undefined')
+    ? { register: () => {}, unregister: () => {} }
+    : new FinalizationRegistry(ptr => wasm.__wbg_iterationjob_free(ptr, 1));
+
+function getStringFromWasm0(ptr, len) {
+    return decodeText(ptr >>> 0, len);
+}
+
+let cachedUint8ArrayMemory0 = null;
+function getUint8ArrayMemory0() {
+    if (cachedUint8ArrayMemory0 === null || cachedUint8ArrayMemory0.byteLength === 0) {
+        cachedUint8ArrayMemory0 = new Uint8Array(wasm.memory.buffer);
+    }
+    return cachedUint8ArrayMemory0;
+}
+
+let cachedTextDecoder = new TextDecoder('utf-8', { ignoreBOM: true, fatal: true });
+cachedTextDecoder.decode();
+const MAX_SAFARI_DECODE_BYTES = 2146435072;
+let numBytesDecoded = 0;
+function decodeText(ptr, len) {
+    numBytesDecoded += len;
+    if (numBytesDecoded >= MAX_SAFARI_DECODE_BYTES) {
+        cachedTextDecoder = new TextDecoder('utf-8', { ignoreBOM: true, fatal: true });
+        cachedTextDecoder.decode();
+        numBytesDecoded = len;
+    }
+    return cachedTextDecoder.decode(getUint8ArrayMemory0().subarray(ptr, ptr + len));
+}
+
 let wasmModule, wasmInstance, wasm;
 function __wbg_finalize_init(instance, module) {
     wasmInstance = instance;
     wasm = instance.exports;
     wasmModule = module;
+    cachedUint8ArrayMemory0 = null;
     wasm.__wbindgen_start();
     return wasm;
 }
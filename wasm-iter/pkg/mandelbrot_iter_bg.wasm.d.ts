@@ -1,15 +1,124 @@
 /* tslint:disable */
 /* eslint-disable */
 export const memory: WebAssembly.Memory;
+export const __wbg_iterationjob_free: (a: number, b: number) => void;
+export const accum_iterations_ptr: () => number;
+export const accum_prev_values_ptr: () => number;
+export const accum_values_ptr: () => number;
+export const alloc_iteration_import: (a: number) => void;
 export const alloc_job: (a: number, b: number, c: number, d: number, e: number) => void;
-export const xn_ptr: () => number;
+export const alloc_pixel_list: (a: number) => void;
+export const alloc_reference: (a: number, b: number, c: number, d: number) => number;
+export const atom_domains_ptr: () => number;
+export const begin_direct_iteration_job: (a: number, b: number, c: number, d: number, e: number, f: number, g: number, h: number, i: number, j: number) => void;
+export const begin_iteration_job: (a: number, b: number, c: number, d: number, e: number, f: number, g: number, h: number, i: number, j: number, k: number) => void;
+export const begin_pass: (a: number, b: number, c: number, d: number, e: number) => void;
+export const begin_refinement_pass: (a: number, b: number, c: number, d: number, e: number) => number;
+export const begin_sampling_pass: (a: number, b: number, c: number, d: number, e: number) => number;
 export const bla_bytes_ptr: () => number;
 export const bla_row_offsets_ptr: () => number;
-export const scaled_iterations_ptr: () => number;
+export const calc_iteration_band: (a: number, b: number) => number;
+export const calc_iteration_pixels: (a: number) => number;
+export const clear_accumulation: () => void;
+export const get_buffer_bytes: () => number;
 export const get_calculated_count: () => number;
+export const get_glitch_count: () => number;
+export const get_guessed_count: () => number;
 export const get_hit_count: () => number;
-export const begin_pass: (a: number, b: number, c: number, d: number, e: number) => void;
-export const calc_iteration_band: (a: number, b: number) => void;
-export const begin_iteration_job: (a: number, b: number, c: number, d: number, e: number, f: number, g: number, h: number, i: number, j: number, k: number) => void;
+export const get_iterated_count: () => number;
+export const get_job_error_message: () => [number, number];
+export const get_mistaken_guess_count: () => number;
+export const get_reused_count: () => number;
+export const get_series_skip: () => number;
+export const get_uses_f32: () => number;
+export const glitch_flags_ptr: () => number;
+export const import_iterations: (a: number, b: number, c: number, d: number, e: number) => number;
+export const interior_multipliers_ptr: () => number;
+export const interior_periods_ptr: () => number;
+export const iteration_import_ptr: () => number;
+export const iterationjob_accum_iterations_ptr: (a: number) => number;
+export const iterationjob_accum_prev_values_ptr: (a: number) => number;
+export const iterationjob_accum_values_ptr: (a: number) => number;
+export const iterationjob_alloc: (a: number, b: number, c: number, d: number, e: number, f: number) => void;
+export const iterationjob_alloc_iteration_import: (a: number, b: number) => void;
+export const iterationjob_alloc_pixel_list: (a: number, b: number) => void;
+export const iterationjob_alloc_reference: (a: number, b: number, c: number, d: number, e: number) => number;
+export const iterationjob_atom_domains_ptr: (a: number) => number;
+export const iterationjob_begin: (a: number, b: number, c: number, d: number, e: number, f: number, g: number, h: number, i: number, j: number, k: number, l: number) => void;
+export const iterationjob_begin_direct: (a: number, b: number, c: number, d: number, e: number, f: number, g: number, h: number, i: number, j: number, k: number) => void;
+export const iterationjob_begin_pass: (a: number, b: number, c: number, d: number, e: number, f: number) => void;
+export const iterationjob_begin_refinement_pass: (a: number, b: number, c: number, d: number, e: number, f: number) => number;
+export const iterationjob_begin_sampling_pass: (a: number, b: number, c: number, d: number, e: number, f: number) => number;
+export const iterationjob_bla_bytes_ptr: (a: number) => number;
+export const iterationjob_bla_row_offsets_ptr: (a: number) => number;
+export const iterationjob_buffer_bytes: (a: number) => number;
+export const iterationjob_calc_band: (a: number, b: number, c: number) => number;
+export const iterationjob_calc_pixels: (a: number, b: number) => number;
+export const iterationjob_calculated_count: (a: number) => number;
+export const iterationjob_clear_accumulation: (a: number) => void;
+export const iterationjob_error_message: (a: number) => [number, number];
+export const iterationjob_glitch_count: (a: number) => number;
+export const iterationjob_glitch_flags_ptr: (a: number) => number;
+export const iterationjob_guessed_count: (a: number) => number;
+export const iterationjob_hit_count: (a: number) => number;
+export const iterationjob_import_iterations: (a: number, b: number, c: number, d: number, e: number, f: number) => number;
+export const iterationjob_interior_multipliers_ptr: (a: number) => number;
+export const iterationjob_interior_periods_ptr: (a: number) => number;
+export const iterationjob_iterated_count: (a: number) => number;
+export const iterationjob_iteration_import_ptr: (a: number) => number;
+export const iterationjob_mistaken_guess_count: (a: number) => number;
+export const iterationjob_new: () => number;
+export const iterationjob_pixel_list_ptr: (a: number) => number;
+export const iterationjob_pixel_results_ptr: (a: number) => number;
+export const iterationjob_reference_bla_bytes_ptr: (a: number, b: number) => number;
+export const iterationjob_reference_bla_row_offsets_ptr: (a: number, b: number) => number;
+export const iterationjob_reference_xn_ptr: (a: number, b: number) => number;
+export const iterationjob_release: (a: number) => void;
+export const iterationjob_reused_count: (a: number) => number;
+export const iterationjob_sample_counts_ptr: (a: number) => number;
+export const iterationjob_scaled_iterations_ptr: (a: number) => number;
+export const iterationjob_series_skip: (a: number) => number;
+export const iterationjob_set_atom_domain: (a: number, b: number) => void;
+export const iterationjob_set_average_coloring: (a: number, b: number) => number;
+export const iterationjob_set_continuation: (a: number, b: number) => void;
+export const iterationjob_set_f32_fast_path: (a: number, b: number) => void;
+export const iterationjob_set_glitch_detection: (a: number, b: number) => void;
+export const iterationjob_set_guess_check: (a: number, b: number) => void;
+export const iterationjob_set_interior_detection: (a: number, b: number, c: number, d: number) => void;
+export const iterationjob_set_interleaved_pixels: (a: number, b: number) => void;
+export const iterationjob_set_iteration_shift: (a: number, b: number, c: number) => void;
+export const iterationjob_set_orbit_trap: (a: number, b: number, c: number, d: number, e: number) => number;
+export const iterationjob_set_reference: (a: number, b: number, c: number, d: number, e: number, f: number) => number;
+export const iterationjob_set_render_strategy: (a: number, b: number) => number;
+export const iterationjob_set_series_approximation: (a: number, b: number, c: number) => void;
+export const iterationjob_shrink: (a: number) => void;
+export const iterationjob_uses_f32: (a: number) => number;
+export const iterationjob_validate: (a: number) => number;
+export const iterationjob_xn_ptr: (a: number) => number;
+export const pixel_list_ptr: () => number;
+export const pixel_results_ptr: () => number;
+export const reference_bla_bytes_ptr: (a: number) => number;
+export const reference_bla_row_offsets_ptr: (a: number) => number;
+export const reference_xn_ptr: (a: number) => number;
+export const release_job: () => void;
+export const sample_counts_ptr: () => number;
+export const scaled_iterations_ptr: () => number;
+export const set_atom_domain: (a: number) => void;
+export const set_average_coloring: (a: number) => number;
+export const set_continuation: (a: number) => void;
+export const set_f32_fast_path: (a: number) => void;
+export const set_glitch_detection: (a: number) => void;
+export const set_guess_check: (a: number) => void;
+export const set_interior_detection: (a: number, b: number, c: number) => void;
+export const set_interleaved_pixels: (a: number) => void;
+export const set_iteration_shift: (a: number, b: number) => void;
+export const set_orbit_trap: (a: number, b: number, c: number, d: number) => number;
+export const set_reference: (a: number, b: number, c: number, d: number, e: number) => number;
+export const set_render_strategy: (a: number) => number;
+export const set_series_approximation: (a: number, b: number) => void;
+export const shrink_job: () => void;
+export const validate_job: () => number;
+export const xn_ptr: () => number;
 export const __wbindgen_externrefs: WebAssembly.Table;
+export const __wbindgen_free: (a: number, b: number, c: number) => void;
 export const __wbindgen_start: () => void;
@@ -0,0 +1,353 @@
+//! iteration 中の z から色付け用の値を集計する。
+//!
+//! 集計は全 iteration の z を見る必要があるので、有効な job は BLA を使わずに 1 step ずつ進める。
+
+use crate::OrbitObserver;
+
+/// orbit trap の形状。JS からは `set_orbit_trap` の `shape` に数値で渡す
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) enum TrapShape {
+    /// 中心からの距離
+    Point = 0,
+    /// 中心を通る実軸・虚軸に平行な 2 直線への距離の小さい方
+    Cross = 1,
+    /// 中心を通り、偏角 `param` [rad] の方向を持つ直線への距離
+    Line = 2,
+    /// 中心、半径 `param` の円周への距離
+    Circle = 3,
+}
+
+impl TrapShape {
+    pub(crate) fn from_u32(value: u32) -> Option<Self> {
+        match value {
+            0 => Some(Self::Point),
+            1 => Some(Self::Cross),
+            2 => Some(Self::Line),
+            3 => Some(Self::Circle),
+            _ => None,
+        }
+    }
+}
+
+/// orbit trap の設定。中心は reference の c からの相対座標で受け取る
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct OrbitTrap {
+    shape: TrapShape,
+    offset_re: f64,
+    offset_im: f64,
+    param: f64,
+}
+
+impl OrbitTrap {
+    pub(crate) fn new(shape: TrapShape, offset_re: f64, offset_im: f64, param: f64) -> Self {
+        Self {
+            shape,
+            offset_re,
+            offset_im,
+            param,
+        }
+    }
+
+    /// reference の c を使って z 平面上の絶対座標に直す
+    fn resolve(&self, ref_c_re: f64, ref_c_im: f64) -> ResolvedTrap {
+        // Line は法線ベクトルとの内積で距離を出すので、ここで方向を計算しておく
+        let (sin, cos) = self.param.sin_cos();
+        ResolvedTrap {
+            shape: self.shape,
+            center_re: ref_c_re + self.offset_re,
+            center_im: ref_c_im + self.offset_im,
+            normal_re: -sin,
+            normal_im: cos,
+            radius: self.param,
+        }
+    }
+}
+
+/// z 平面上の座標に解決済みの orbit trap
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct ResolvedTrap {
+    shape: TrapShape,
+    center_re: f64,
+    center_im: f64,
+    normal_re: f64,
+    normal_im: f64,
+    radius: f64,
+}
+
+impl ResolvedTrap {
+    #[inline(always)]
+    fn distance(&self, z_re: f64, z_im: f64) -> f64 {
+        let dx = z_re - self.center_re;
+        let dy = z_im - self.center_im;
+        match self.shape {
+            TrapShape::Point => (dx * dx + dy * dy).sqrt(),
+            TrapShape::Cross => dx.abs().min(dy.abs()),
+            TrapShape::Line => (dx * self.normal_re + dy * self.normal_im).abs(),
+            TrapShape::Circle => ((dx * dx + dy * dy).sqrt() - self.radius).abs(),
+        }
+    }
+}
+
+/// 平均系の色付けで使う、1 iteration ごとの値の種類。JS からは `set_average_coloring` の `kind` に数値で渡す
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) enum AverageKind {
+    /// Triangle Inequality Average。|z_n| が三角不等式の範囲 [||z_{n-1}²| - |c||, |z_{n-1}²| + |c|]
+    /// のどこにあるかを [0, 1] で表したものの平均
+    TriangleInequality = 0,
+    /// Curvature Average。|arg((z_n - z_{n-1}) / (z_{n-1} - z_{n-2}))| / π の平均
+    Curvature = 1,
+}
+
+impl AverageKind {
+    pub(crate) fn from_u32(value: u32) -> Option<Self> {
+        match value {
+            0 => Some(Self::TriangleInequality),
+            1 => Some(Self::Curvature),
+            _ => None,
+        }
+    }
+}
+
+/// JS から設定する集計モード
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) enum AccumulationMode {
+    #[default]
+    None,
+    OrbitTrap(OrbitTrap),
+    Average(AverageKind),
+}
+
+impl AccumulationMode {
+    /// primary reference の c を使って解決する。`IterationJob::begin` で 1 回だけ呼ぶ
+    pub(crate) fn resolve(&self, (ref_c_re, ref_c_im): (f64, f64)) -> Option<ResolvedMode> {
+        match self {
+            Self::None => None,
+            Self::OrbitTrap(trap) => {
+                Some(ResolvedMode::OrbitTrap(trap.resolve(ref_c_re, ref_c_im)))
+            }
+            Self::Average(kind) => Some(ResolvedMode::Average(*kind)),
+        }
+    }
+}
+
+/// job 中に使う解決済みの集計モード
+#[derive(Clone, Copy, Debug)]
+pub(crate) enum ResolvedMode {
+    OrbitTrap(ResolvedTrap),
+    Average(AverageKind),
+}
+
+/// 1 ピクセル分の集計結果
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct AccumulationResult {
+    /// orbit trap なら最小距離、平均系なら全項の平均
+    pub(crate) value: f64,
+    /// 平均系なら最後の項を除いた平均。smooth iteration の小数部で value と補間して使う。
+    /// orbit trap では value と同じ値を入れる
+    pub(crate) prev_value: f64,
+    /// orbit trap なら最小距離をとった iteration、平均系なら平均をとった項数
+    pub(crate) iteration: u32,
+}
+
+/// 1 ピクセル分の集計状態
+pub(crate) struct Accumulator {
+    mode: ResolvedMode,
+    c_re: f64,
+    c_im: f64,
+    /// orbit trap の最小距離、または平均系の項の総和
+    value: f64,
+    /// 平均系で最後の項を足す前の総和
+    prev_value: f64,
+    /// orbit trap の最小距離をとった iteration、または平均系の項数
+    iteration: u32,
+    /// 1 つ前と 2 つ前の z。平均系で使う
+    z1_re: f64,
+    z1_im: f64,
+    z2_re: f64,
+    z2_im: f64,
+}
+
+impl Accumulator {
+    pub(crate) fn new(mode: ResolvedMode) -> Self {
+        let value = match mode {
+            ResolvedMode::OrbitTrap(_) => f64::INFINITY,
+            ResolvedMode::Average(_) => 0.0,
+        };
+        Self {
+            mode,
+            c_re: 0.0,
+            c_im: 0.0,
+            value,
+            prev_value: value,
+            iteration: 0,
+            z1_re: 0.0,
+            z1_im: 0.0,
+            z2_re: 0.0,
+            z2_im: 0.0,
+        }
+    }
+
+    pub(crate) fn finish(&self) -> AccumulationResult {
+        match self.mode {
+            ResolvedMode::OrbitTrap(_) => AccumulationResult {
+                value: self.value,
+                prev_value: self.value,
+                iteration: self.iteration,
+            },
+            ResolvedMode::Average(_) => {
+                let count = self.iteration;
+                let average = |sum: f64, n: u32| if n == 0 { 0.0 } else { sum / n as f64 };
+                AccumulationResult {
+                    value: average(self.value, count),
+                    prev_value: average(self.prev_value, count.saturating_sub(1)),
+                    iteration: count,
+                }
+            }
+        }
+    }
+
+    #[inline(always)]
+    fn add_term(&mut self, term: f64) {
+        // 0/0 などで NaN になった項は平均に混ぜない
+        if term.is_finite() {
+            self.prev_value = self.value;
+            self.value += term;
+            self.iteration += 1;
+        }
+    }
+}
+
+impl OrbitObserver for Accumulator {
+    #[inline(always)]
+    fn allow_bla(&self) -> bool {
+        false
+    }
+
+    const NEEDS_C: bool = true;
+
+    #[inline(always)]
+    fn start(&mut self, c_re: f64, c_im: f64) {
+        self.c_re = c_re;
+        self.c_im = c_im;
+    }
+
+    #[inline(always)]
+    fn observe(&mut self, iteration: u32, z_re: f64, z_im: f64) -> bool {
+        // z_0 = 0 は全ピクセル共通なので集計に含めない
+        if iteration == 0 {
+            return false;
+        }
+        match self.mode {
+            ResolvedMode::OrbitTrap(trap) => {
+                let distance = trap.distance(z_re, z_im);
+                if distance < self.value {
+                    self.value = distance;
+                    self.iteration = iteration;
+                }
+            }
+            ResolvedMode::Average(AverageKind::TriangleInequality) => {
+                // z_1 = c のときは範囲の幅が 0 になるので n >= 2 から数える
+                if iteration >= 2 {
+                    let prev_sq_abs = self.z1_re * self.z1_re + self.z1_im * self.z1_im;
+                    let c_abs = (self.c_re * self.c_re + self.c_im * self.c_im).sqrt();
+                    let min = (prev_sq_abs - c_abs).abs();
+                    let max = prev_sq_abs + c_abs;
+                    let z_abs = (z_re * z_re + z_im * z_im).sqrt();
+                    self.add_term((z_abs - min) / (max - min));
+                }
+            }
+            ResolvedMode::Average(AverageKind::Curvature) => {
+                if iteration >= 3 {
+                    let num_re = z_re - self.z1_re;
+                    let num_im = z_im - self.z1_im;
+                    let den_re = self.z1_re - self.z2_re;
+                    let den_im = self.z1_im - self.z2_im;
+                    // arg(a / b) = atan2(Im(a * conj(b)), Re(a * conj(b)))
+                    let re = num_re * den_re + num_im * den_im;
+                    let im = num_im * den_re - num_re * den_im;
+                    self.add_term(im.atan2(re).abs() / std::f64::consts::PI);
+                }
+            }
+        }
+        self.z2_re = self.z1_re;
+        self.z2_im = self.z1_im;
+        self.z1_re = z_re;
+        self.z1_im = z_im;
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn trap(shape: TrapShape, param: f64) -> ResolvedTrap {
+        OrbitTrap::new(shape, 0.5, -0.25, param).resolve(-1.0, 0.0)
+    }
+
+    #[test]
+    fn trap_distances() {
+        // 中心は (-1 + 0.5, 0 - 0.25) = (-0.5, -0.25)
+        let z = (-0.5 + 3.0, -0.25 + 4.0);
+        assert_eq!(trap(TrapShape::Point, 0.0).distance(z.0, z.1), 5.0);
+        assert_eq!(trap(TrapShape::Cross, 0.0).distance(z.0, z.1), 3.0);
+        assert_eq!(trap(TrapShape::Circle, 2.0).distance(z.0, z.1), 3.0);
+        // 実軸方向の直線なら虚部の差がそのまま距離になる
+        assert_eq!(trap(TrapShape::Line, 0.0).distance(z.0, z.1), 4.0);
+        // 虚軸方向の直線なら実部の差
+        let vertical = trap(TrapShape::Line, std::f64::consts::FRAC_PI_2).distance(z.0, z.1);
+        assert!((vertical - 3.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn accumulator_keeps_minimum() {
+        let mode = AccumulationMode::OrbitTrap(OrbitTrap::new(TrapShape::Point, 0.0, 0.0, 0.0))
+            .resolve((0.0, 0.0))
+            .unwrap();
+        let mut acc = Accumulator::new(mode);
+        acc.start(0.0, 0.0);
+        acc.observe(0, 0.0, 0.0);
+        acc.observe(1, 2.0, 0.0);
+        acc.observe(2, 0.0, -0.5);
+        acc.observe(3, 1.0, 1.0);
+        assert_eq!(
+            acc.finish(),
+            AccumulationResult {
+                value: 0.5,
+                prev_value: 0.5,
+                iteration: 2
+            }
+        );
+    }
+
+    fn run_average(kind: AverageKind, c: (f64, f64), steps: u32) -> AccumulationResult {
+        let mode = AccumulationMode::Average(kind).resolve(c).unwrap();
+        let mut acc = Accumulator::new(mode);
+        acc.start(c.0, c.1);
+        let (mut z_re, mut z_im) = (0.0f64, 0.0f64);
+        for n in 0..steps {
+            acc.observe(n, z_re, z_im);
+            let next_re = z_re * z_re - z_im * z_im + c.0;
+            z_im = 2.0 * z_re * z_im + c.1;
+            z_re = next_re;
+        }
+        acc.finish()
+    }
+
+    #[test]
+    fn triangle_inequality_average_is_normalized() {
+        let result = run_average(AverageKind::TriangleInequality, (-0.12, 0.74), 200);
+        assert_eq!(result.iteration, 198);
+        assert!((0.0..=1.0).contains(&result.value));
+        assert!((0.0..=1.0).contains(&result.prev_value));
+    }
+
+    #[test]
+    fn curvature_average_of_fixed_point() {
+        // c = -1 は 0 と -1 を往復するので、向きが毎回反転して曲率は常に π
+        let result = run_average(AverageKind::Curvature, (-1.0, 0.0), 10);
+        assert_eq!(result.iteration, 7);
+        assert_eq!(result.value, 1.0);
+        assert_eq!(result.prev_value, 1.0);
+    }
+}
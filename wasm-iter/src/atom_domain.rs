@@ -0,0 +1,69 @@
+//! atom domain (|z_n|² が最小値を更新した最後の iteration) を求める。
+//!
+//! atom domain の値 p は近くにある周期 p の minibrot を指すので、interesting points の探索と
+//! 色付けの両方で使える。
+//!
+//! BLA の skip 中の z は観測できないが、|X| が小さい区間は BLA の有効半径も小さくなり
+//! ほぼ skip されないので、最小値を取りこぼすことはまずない。
+
+use crate::OrbitObserver;
+
+/// 1 ピクセル分の atom domain の状態
+pub(crate) struct AtomDomainTracker {
+    min_norm: f64,
+    iteration: u32,
+}
+
+impl AtomDomainTracker {
+    pub(crate) fn new() -> Self {
+        Self {
+            min_norm: f64::INFINITY,
+            iteration: 0,
+        }
+    }
+
+    /// |z|² が最小になった iteration。1 度も観測していなければ 0
+    pub(crate) fn finish(&self) -> u32 {
+        self.iteration
+    }
+}
+
+impl OrbitObserver for AtomDomainTracker {
+    #[inline(always)]
+    fn allow_bla(&self) -> bool {
+        true
+    }
+
+    #[inline(always)]
+    fn observe(&mut self, iteration: u32, z_re: f64, z_im: f64) -> bool {
+        // z_0 = 0 は全ピクセル共通で、常に最小になってしまうので除く
+        if iteration > 0 {
+            let norm = z_re * z_re + z_im * z_im;
+            if norm < self.min_norm {
+                self.min_norm = norm;
+                self.iteration = iteration;
+            }
+        }
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn atom_domain_points_to_nearby_period() {
+        // 周期 3 の成分 (douady rabbit) の近くでは z_3 が最も 0 に近づく
+        let c = (-0.122, 0.745);
+        let mut tracker = AtomDomainTracker::new();
+        let (mut z_re, mut z_im) = (0.0f64, 0.0f64);
+        for n in 0..1000 {
+            tracker.observe(n, z_re, z_im);
+            let next_re = z_re * z_re - z_im * z_im + c.0;
+            z_im = 2.0 * z_re * z_im + c.1;
+            z_re = next_re;
+        }
+        assert_eq!(tracker.finish() % 3, 0);
+    }
+}
@@ -0,0 +1,231 @@
+//! hot loop で引く BLATable の struct-of-arrays 表現。
+//!
+//! JS から受け取るのは `src/workers/bla-table-item.ts` の 44 bytes/要素のレイアウトだが、
+//! BLA の探索では各段の r² だけを段ごとに 1 要素ずつ読んで比較し、係数は最後に 1 回しか読まない。
+//! 44 bytes の要素から r² だけ拾うと段ごとに別のキャッシュラインを触るので、job 開始時に
+//! r² (f32) だけの配列と、係数と l の配列に分けて詰め直しておく。
+//!
+//! f32 の r² は判定を絞り込むためだけに使い、f32 の丸め幅に dz_norm が入ったときだけ
+//! 係数の側に残した f64 の r² で判定し直す。どの BLA を使うかは f64 の r² で判定した場合
+//! (JS の `calcIterationAt`) と変わらない。
+
+use crate::ITEM_BYTE_LENGTH;
+
+/// little-endian の f64 をバイト列から読む
+pub(crate) fn read_f64(bytes: &[u8], offset: usize) -> f64 {
+    f64::from_le_bytes(bytes[offset..offset + 8].try_into().unwrap())
+}
+
+/// little-endian の i32 をバイト列から読む
+fn read_i32(bytes: &[u8], offset: usize) -> i32 {
+    i32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+}
+
+/// r² を f32 に丸める。元の r² が [丸めた値, その次の f32) に入るよう、必ず元の値以下に丸める
+/// (NaN は NaN のままなので比較は常に false)
+fn round_radius_sq_down(r_sq: f64) -> f32 {
+    let rounded = r_sq as f32;
+    if (rounded as f64) > r_sq {
+        rounded.next_down()
+    } else {
+        rounded
+    }
+}
+
+/// 全段を連結した BLATable。段 d の j 番目は `level_starts[d] + j` 番目に入っている
+pub(crate) struct BlaTable {
+    level_starts: Vec<u32>,
+    /// 探索に使う r² (元の値以下に丸めた f32)。判定しきれないときは `BlaStep::radius_sq` を見る
+    radii_sq: Vec<f32>,
+    /// 探索が終わってから 1 回だけ読むので、係数と l はまとめておく
+    steps: Vec<BlaStep>,
+}
+
+/// BLA 1 要素分の係数
+#[derive(Clone, Copy)]
+pub(crate) struct BlaStep {
+    pub(crate) a_re: f64,
+    pub(crate) a_im: f64,
+    pub(crate) b_re: f64,
+    pub(crate) b_im: f64,
+    /// スキップする iteration 数 (l)
+    pub(crate) skip: u32,
+    /// JS から受け取った r²。f32 の r² で判定しきれないときだけ読む
+    radius_sq: f64,
+}
+
+impl BlaTable {
+    pub(crate) const fn new() -> Self {
+        Self {
+            level_starts: Vec::new(),
+            radii_sq: Vec::new(),
+            steps: Vec::new(),
+        }
+    }
+
+    /// JS から受け取ったバイト列と row offsets から詰め直す。確保済みの容量は使い回す
+    pub(crate) fn rebuild(&mut self, bytes: &[u8], row_offsets: &[i32], rows: usize) {
+        self.level_starts.clear();
+        self.radii_sq.clear();
+        self.steps.clear();
+
+        for row in 0..rows {
+            self.level_starts.push(self.radii_sq.len() as u32);
+            let row_offset = row_offsets[row * 2] as usize;
+            let row_length = row_offsets[row * 2 + 1] as usize;
+            for j in 0..row_length {
+                let offset = row_offset + j * ITEM_BYTE_LENGTH;
+                let radius_sq = read_f64(bytes, offset + 32);
+                self.radii_sq.push(round_radius_sq_down(radius_sq));
+                self.steps.push(BlaStep {
+                    a_re: read_f64(bytes, offset),
+                    a_im: read_f64(bytes, offset + 8),
+                    b_re: read_f64(bytes, offset + 16),
+                    b_im: read_f64(bytes, offset + 24),
+                    skip: read_i32(bytes, offset + 40) as u32,
+                    radius_sq,
+                });
+            }
+        }
+    }
+
+    /// 余った容量を allocator に返す
+    pub(crate) fn shrink(&mut self) {
+        self.level_starts.shrink_to_fit();
+        self.radii_sq.shrink_to_fit();
+        self.steps.shrink_to_fit();
+    }
+
+    pub(crate) fn capacity_bytes(&self) -> usize {
+        crate::capacity_bytes(&self.level_starts)
+            + crate::capacity_bytes(&self.radii_sq)
+            + crate::capacity_bytes(&self.steps)
+    }
+
+    /// 今の ref_iteration から使える BLA のうち、最大の l を持つものの index を返す。
+    ///
+    /// refIteration === (jIdx << d) + 1 と |dz| < r を満たす、最大の l を持つデータを探す。
+    /// 前者は「refM1 の下位 d bit が 0」と等価なので、d の上限は refM1 の trailing zeros 数。
+    /// |dz| < r は sqrt を省くため dzNorm < r² で判定する (encode 時に r² を書き込んでいる)。
+    /// 判定結果は f64 の r² と比べた場合と同じ
+    #[inline(always)]
+    pub(crate) fn find(
+        &self,
+        rows: i32,
+        start_row: i32,
+        ref_iteration: u32,
+        dz_norm: f64,
+    ) -> Option<usize> {
+        if ref_iteration == 0 {
+            return None;
+        }
+        let ref_m1 = (ref_iteration - 1) as i32;
+        // ctz(refM1): refM1 === 0 のときは上限なし (rows 側に任せる)
+        let ctz = if ref_m1 == 0 {
+            32
+        } else {
+            ref_m1.trailing_zeros() as i32
+        };
+        let max_d = if ctz < rows { ctz } else { rows - 1 };
+        let mut found = None;
+        let mut d = start_row;
+        while d <= max_d {
+            let index = self.index(d, ref_m1 >> d);
+            if self.is_within_radius(index, dz_norm) {
+                found = Some(index);
+            } else {
+                break;
+            }
+            d += 1;
+        }
+        found
+    }
+
+    /// 段 `level` の `column` 番目の要素の index
+    #[inline(always)]
+    pub(crate) fn index(&self, level: i32, column: i32) -> usize {
+        self.level_starts[level as usize] as usize + column as usize
+    }
+
+    /// dz_norm < r² か。f32 の r² の丸め幅に入ったときだけ f64 の r² を読む
+    #[inline(always)]
+    fn is_within_radius(&self, index: usize, dz_norm: f64) -> bool {
+        let lower = self.radii_sq[index];
+        if dz_norm < lower as f64 {
+            return true;
+        }
+        if dz_norm >= lower.next_up() as f64 {
+            return false;
+        }
+        dz_norm < self.steps[index].radius_sq
+    }
+
+    #[inline(always)]
+    pub(crate) fn step(&self, index: usize) -> &BlaStep {
+        &self.steps[index]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn radius_is_rounded_down() {
+        for r_sq in [1e-8, 0.1, 1.0 / 3.0, 2.5e-300, 1e300, 0.0] {
+            assert!(round_radius_sq_down(r_sq) as f64 <= r_sq);
+        }
+        assert_eq!(round_radius_sq_down(0.25), 0.25);
+        assert!(round_radius_sq_down(f64::NAN).is_nan());
+    }
+
+    #[test]
+    fn radius_check_matches_f64() {
+        let r_sq = 1.0 / 3.0;
+        let lower = round_radius_sq_down(r_sq);
+        let mut bytes = vec![0; ITEM_BYTE_LENGTH];
+        bytes[32..40].copy_from_slice(&r_sq.to_le_bytes());
+        let mut table = BlaTable::new();
+        table.rebuild(&bytes, &[0, 1], 1);
+
+        // f32 の丸め幅の前後と中を、f64 の r² と比べた結果と揃える
+        let below_f64 = f64::from_bits(r_sq.to_bits() - 1);
+        for dz_norm in [
+            lower as f64,
+            below_f64,
+            r_sq,
+            f64::from_bits(r_sq.to_bits() + 1),
+            lower.next_up() as f64,
+            0.0,
+            1.0,
+            f64::NAN,
+        ] {
+            assert_eq!(
+                table.is_within_radius(0, dz_norm),
+                dz_norm < r_sq,
+                "{dz_norm}"
+            );
+        }
+    }
+
+    #[test]
+    fn rebuild_keeps_rows() {
+        let xn: Vec<f64> = (0..200).map(|i| (i as f64 * 0.37).sin() * 0.5).collect();
+        let encoded = apfp::bla::build_bla_table(&xn, 1e-6);
+        let rows = encoded.row_offsets.len() / 2;
+        let mut table = BlaTable::new();
+        table.rebuild(&encoded.bytes, &encoded.row_offsets, rows);
+
+        for row in 0..rows {
+            let offset = encoded.row_offsets[row * 2] as usize;
+            for j in 0..encoded.row_offsets[row * 2 + 1] as usize {
+                let item = offset + j * ITEM_BYTE_LENGTH;
+                let index = table.index(row as i32, j as i32);
+                let step = table.step(index);
+                assert_eq!(step.b_re, read_f64(&encoded.bytes, item + 16));
+                assert_eq!(step.skip, read_i32(&encoded.bytes, item + 40) as u32);
+                assert_eq!(step.radius_sq, read_f64(&encoded.bytes, item + 32));
+            }
+        }
+    }
+}
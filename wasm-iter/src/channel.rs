@@ -0,0 +1,60 @@
+//! iteration 数以外のピクセルごとの出力バッファ。
+
+/// ピクセルごとの追加出力 1 種類分。
+///
+/// `IterationJob` の `iterations` / `scaled_iterations` と同じく、area 座標で持つキャッシュと
+/// pass の出力 (scaled 座標) の 2 本を持つ。キャッシュにヒットしたピクセルは
+/// iteration を再計算しないので、追加出力もキャッシュ側から書き戻す必要がある。
+pub(crate) struct PixelChannel<T> {
+    pub(crate) area: Vec<T>,
+    pub(crate) scaled: Vec<T>,
+}
+
+impl<T: Copy + Default> PixelChannel<T> {
+    pub(crate) const fn new() -> Self {
+        Self {
+            area: Vec::new(),
+            scaled: Vec::new(),
+        }
+    }
+
+    /// job 開始時に呼ぶ。バッファを `ensure_len` と同じく縮めずに確保し、area キャッシュの
+    /// 先頭 `area_pixels` 要素をクリアする
+    pub(crate) fn prepare(
+        &mut self,
+        alloc_area_pixels: usize,
+        alloc_scaled_pixels: usize,
+        area_pixels: usize,
+    ) {
+        crate::ensure_len(&mut self.area, alloc_area_pixels);
+        crate::ensure_len(&mut self.scaled, alloc_scaled_pixels);
+        if area_pixels <= self.area.len() {
+            self.area[..area_pixels].fill(T::default());
+        }
+    }
+
+    /// area キャッシュと pass 出力をそれぞれの長さまで縮める
+    pub(crate) fn shrink(&mut self, area_len: usize, scaled_len: usize) {
+        crate::shrink_len(&mut self.area, area_len);
+        crate::shrink_len(&mut self.scaled, scaled_len);
+    }
+
+    pub(crate) fn capacity_bytes(&self) -> usize {
+        crate::capacity_bytes(&self.area) + crate::capacity_bytes(&self.scaled)
+    }
+
+    /// 計算結果を書き込む。supersampling 時は area キャッシュを持たないので `area_index` は None
+    #[inline(always)]
+    pub(crate) fn store(&mut self, area_index: Option<usize>, scaled_index: usize, value: T) {
+        if let Some(index) = area_index {
+            self.area[index] = value;
+        }
+        self.scaled[scaled_index] = value;
+    }
+
+    /// キャッシュ済みの値を pass 出力に書き戻す
+    #[inline(always)]
+    pub(crate) fn restore(&mut self, area_index: usize, scaled_index: usize) {
+        self.scaled[scaled_index] = self.area[area_index];
+    }
+}
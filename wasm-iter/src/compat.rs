@@ -0,0 +1,444 @@
+//! worker ごとに 1 つの job だけを使う API。JS の worker は今もこちらを使っている。
+//!
+//! thread_local の job に対して `IterationJob` の対応するメソッド (関数の doc に書いてある) を呼ぶだけ。
+
+use crate::IterationJob;
+use std::cell::RefCell;
+use wasm_bindgen::prelude::*;
+
+thread_local! {
+    static JOB: RefCell<IterationJob> = const { RefCell::new(IterationJob::empty()) };
+}
+
+/// thread_local の job を可変で借りて処理を行う
+pub(crate) fn with_job<R>(f: impl FnOnce(&mut IterationJob) -> R) -> R {
+    JOB.with(|job| f(&mut job.borrow_mut()))
+}
+
+/// `IterationJob::alloc`
+#[wasm_bindgen]
+pub fn alloc_job(
+    xn_f64_len: u32,
+    bla_bytes_len: u32,
+    bla_row_offsets_len: u32,
+    area_pixels: u32,
+    max_scaled_pixels: u32,
+) {
+    with_job(|job| {
+        job.alloc(
+            xn_f64_len,
+            bla_bytes_len,
+            bla_row_offsets_len,
+            area_pixels,
+            max_scaled_pixels,
+        )
+    });
+}
+
+/// `IterationJob::shrink`
+#[wasm_bindgen]
+pub fn shrink_job() {
+    with_job(|job| job.shrink());
+}
+
+/// `IterationJob::release`
+#[wasm_bindgen]
+pub fn release_job() {
+    with_job(|job| job.release());
+}
+
+/// `IterationJob::buffer_bytes`
+#[wasm_bindgen]
+pub fn get_buffer_bytes() -> usize {
+    with_job(|job| job.buffer_bytes())
+}
+
+#[wasm_bindgen]
+pub fn xn_ptr() -> *mut f64 {
+    with_job(|job| job.xn_ptr())
+}
+
+#[wasm_bindgen]
+pub fn bla_bytes_ptr() -> *mut u8 {
+    with_job(|job| job.bla_bytes_ptr())
+}
+
+#[wasm_bindgen]
+pub fn bla_row_offsets_ptr() -> *mut i32 {
+    with_job(|job| job.bla_row_offsets_ptr())
+}
+
+#[wasm_bindgen]
+pub fn scaled_iterations_ptr() -> *mut u32 {
+    with_job(|job| job.scaled_iterations_ptr())
+}
+
+/// `IterationJob::sample_counts_ptr`
+#[wasm_bindgen]
+pub fn sample_counts_ptr() -> *mut u32 {
+    with_job(|job| job.sample_counts_ptr())
+}
+
+#[wasm_bindgen]
+pub fn set_orbit_trap(shape: u32, offset_re: f64, offset_im: f64, param: f64) -> bool {
+    with_job(|job| job.set_orbit_trap(shape, offset_re, offset_im, param))
+}
+
+#[wasm_bindgen]
+pub fn set_average_coloring(kind: u32) -> bool {
+    with_job(|job| job.set_average_coloring(kind))
+}
+
+#[wasm_bindgen]
+pub fn clear_accumulation() {
+    with_job(|job| job.clear_accumulation());
+}
+
+#[wasm_bindgen]
+pub fn accum_values_ptr() -> *mut f64 {
+    with_job(|job| job.accum_values_ptr())
+}
+
+#[wasm_bindgen]
+pub fn accum_prev_values_ptr() -> *mut f64 {
+    with_job(|job| job.accum_prev_values_ptr())
+}
+
+#[wasm_bindgen]
+pub fn accum_iterations_ptr() -> *mut u32 {
+    with_job(|job| job.accum_iterations_ptr())
+}
+
+#[wasm_bindgen]
+pub fn set_series_approximation(terms: u32, tolerance: f64) {
+    with_job(|job| job.set_series_approximation(terms, tolerance));
+}
+
+#[wasm_bindgen]
+pub fn set_interleaved_pixels(count: u32) {
+    with_job(|job| job.set_interleaved_pixels(count));
+}
+
+#[wasm_bindgen]
+pub fn set_f32_fast_path(enabled: bool) {
+    with_job(|job| job.set_f32_fast_path(enabled));
+}
+
+#[wasm_bindgen]
+pub fn set_render_strategy(strategy: u32) -> bool {
+    with_job(|job| job.set_render_strategy(strategy))
+}
+
+#[wasm_bindgen]
+pub fn set_guess_check(enabled: bool) {
+    with_job(|job| job.set_guess_check(enabled));
+}
+
+#[wasm_bindgen]
+pub fn set_iteration_shift(dx: i32, dy: i32) {
+    with_job(|job| job.set_iteration_shift(dx, dy));
+}
+
+#[wasm_bindgen]
+pub fn set_continuation(enabled: bool) {
+    with_job(|job| job.set_continuation(enabled));
+}
+
+/// `IterationJob::uses_f32`
+#[wasm_bindgen]
+pub fn get_uses_f32() -> bool {
+    with_job(|job| job.uses_f32())
+}
+
+/// `IterationJob::series_skip`
+#[wasm_bindgen]
+pub fn get_series_skip() -> u32 {
+    with_job(|job| job.series_skip())
+}
+
+#[wasm_bindgen]
+pub fn set_interior_detection(flags: u32, periodicity_epsilon_sq: f64, derivative_epsilon_sq: f64) {
+    with_job(|job| {
+        job.set_interior_detection(flags, periodicity_epsilon_sq, derivative_epsilon_sq)
+    });
+}
+
+#[wasm_bindgen]
+pub fn interior_periods_ptr() -> *mut u32 {
+    with_job(|job| job.interior_periods_ptr())
+}
+
+#[wasm_bindgen]
+pub fn interior_multipliers_ptr() -> *mut f64 {
+    with_job(|job| job.interior_multipliers_ptr())
+}
+
+#[wasm_bindgen]
+pub fn set_atom_domain(enabled: bool) {
+    with_job(|job| job.set_atom_domain(enabled));
+}
+
+#[wasm_bindgen]
+pub fn atom_domains_ptr() -> *mut u32 {
+    with_job(|job| job.atom_domains_ptr())
+}
+
+#[wasm_bindgen]
+pub fn set_glitch_detection(tolerance: f64) {
+    with_job(|job| job.set_glitch_detection(tolerance));
+}
+
+#[wasm_bindgen]
+pub fn glitch_flags_ptr() -> *mut u8 {
+    with_job(|job| job.glitch_flags_ptr())
+}
+
+#[wasm_bindgen]
+pub fn alloc_iteration_import(pixels: u32) {
+    with_job(|job| job.alloc_iteration_import(pixels));
+}
+
+#[wasm_bindgen]
+pub fn iteration_import_ptr() -> *mut u32 {
+    with_job(|job| job.iteration_import_ptr())
+}
+
+/// `IterationJob::import_iterations`
+#[wasm_bindgen]
+pub fn import_iterations(
+    src_width: u32,
+    src_height: u32,
+    scale: u32,
+    offset_x: i32,
+    offset_y: i32,
+) -> u32 {
+    with_job(|job| job.import_iterations(src_width, src_height, scale, offset_x, offset_y))
+}
+
+#[wasm_bindgen]
+pub fn alloc_reference(
+    index: u32,
+    xn_f64_len: u32,
+    bla_bytes_len: u32,
+    bla_row_offsets_len: u32,
+) -> bool {
+    with_job(|job| job.alloc_reference(index, xn_f64_len, bla_bytes_len, bla_row_offsets_len))
+}
+
+#[wasm_bindgen]
+pub fn reference_xn_ptr(index: u32) -> *mut f64 {
+    with_job(|job| job.reference_xn_ptr(index))
+}
+
+#[wasm_bindgen]
+pub fn reference_bla_bytes_ptr(index: u32) -> *mut u8 {
+    with_job(|job| job.reference_bla_bytes_ptr(index))
+}
+
+#[wasm_bindgen]
+pub fn reference_bla_row_offsets_ptr(index: u32) -> *mut i32 {
+    with_job(|job| job.reference_bla_row_offsets_ptr(index))
+}
+
+#[wasm_bindgen]
+pub fn set_reference(
+    index: u32,
+    max_ref_iteration: u32,
+    bla_rows: u32,
+    ref_pixel_x: f64,
+    ref_pixel_y: f64,
+) -> u32 {
+    with_job(|job| job.set_reference(index, max_ref_iteration, bla_rows, ref_pixel_x, ref_pixel_y))
+}
+
+/// `IterationJob::begin`
+#[wasm_bindgen]
+#[allow(clippy::too_many_arguments)]
+pub fn begin_iteration_job(
+    max_iteration: u32,
+    max_ref_iteration: u32,
+    bla_rows: u32,
+    start_bla_index: u32,
+    delta_c_scale: f64,
+    ref_pixel_x: f64,
+    ref_pixel_y: f64,
+    area_width: u32,
+    area_height: u32,
+    area_start_x: i32,
+    area_start_y: i32,
+) {
+    with_job(|job| {
+        job.begin(
+            max_iteration,
+            max_ref_iteration,
+            bla_rows,
+            start_bla_index,
+            delta_c_scale,
+            ref_pixel_x,
+            ref_pixel_y,
+            area_width,
+            area_height,
+            area_start_x,
+            area_start_y,
+        )
+    });
+}
+
+/// `IterationJob::begin_direct`
+#[wasm_bindgen]
+#[allow(clippy::too_many_arguments)]
+pub fn begin_direct_iteration_job(
+    max_iteration: u32,
+    center_re: f64,
+    center_im: f64,
+    delta_c_scale: f64,
+    center_pixel_x: f64,
+    center_pixel_y: f64,
+    area_width: u32,
+    area_height: u32,
+    area_start_x: i32,
+    area_start_y: i32,
+) {
+    with_job(|job| {
+        job.begin_direct(
+            max_iteration,
+            center_re,
+            center_im,
+            delta_c_scale,
+            center_pixel_x,
+            center_pixel_y,
+            area_width,
+            area_height,
+            area_start_x,
+            area_start_y,
+        )
+    });
+}
+
+/// `IterationJob::calculated_count`
+#[wasm_bindgen]
+pub fn get_calculated_count() -> u32 {
+    with_job(|job| job.calculated_count())
+}
+
+/// `IterationJob::hit_count`
+#[wasm_bindgen]
+pub fn get_hit_count() -> u32 {
+    with_job(|job| job.hit_count())
+}
+
+/// `IterationJob::glitch_count`
+#[wasm_bindgen]
+pub fn get_glitch_count() -> u32 {
+    with_job(|job| job.glitch_count())
+}
+
+/// `IterationJob::guessed_count`
+#[wasm_bindgen]
+pub fn get_guessed_count() -> u32 {
+    with_job(|job| job.guessed_count())
+}
+
+/// `IterationJob::iterated_count`
+#[wasm_bindgen]
+pub fn get_iterated_count() -> u32 {
+    with_job(|job| job.iterated_count())
+}
+
+/// `IterationJob::reused_count`
+#[wasm_bindgen]
+pub fn get_reused_count() -> u32 {
+    with_job(|job| job.reused_count())
+}
+
+/// `IterationJob::mistaken_guess_count`
+#[wasm_bindgen]
+pub fn get_mistaken_guess_count() -> u32 {
+    with_job(|job| job.mistaken_guess_count())
+}
+
+/// `IterationJob::validate`
+#[wasm_bindgen]
+pub fn validate_job() -> u32 {
+    with_job(|job| job.validate())
+}
+
+/// `IterationJob::error_message`
+#[wasm_bindgen]
+pub fn get_job_error_message() -> String {
+    with_job(|job| job.error_message())
+}
+
+#[wasm_bindgen]
+pub fn begin_pass(
+    x_diff: f64,
+    y_diff: f64,
+    scaled_width: u32,
+    is_super_sampling: bool,
+    is_result_pass: bool,
+) {
+    with_job(|job| {
+        job.begin_pass(
+            x_diff,
+            y_diff,
+            scaled_width,
+            is_super_sampling,
+            is_result_pass,
+        )
+    });
+}
+
+/// `IterationJob::begin_sampling_pass`
+#[wasm_bindgen]
+pub fn begin_sampling_pass(
+    samples_per_axis: u32,
+    pattern: u32,
+    seed: u32,
+    is_averaged: bool,
+    is_result_pass: bool,
+) -> bool {
+    with_job(|job| {
+        job.begin_sampling_pass(samples_per_axis, pattern, seed, is_averaged, is_result_pass)
+    })
+}
+
+/// `IterationJob::begin_refinement_pass`
+#[wasm_bindgen]
+pub fn begin_refinement_pass(
+    samples_per_axis: u32,
+    pattern: u32,
+    seed: u32,
+    threshold: u32,
+    is_result_pass: bool,
+) -> bool {
+    with_job(|job| {
+        job.begin_refinement_pass(samples_per_axis, pattern, seed, threshold, is_result_pass)
+    })
+}
+
+#[wasm_bindgen]
+pub fn alloc_pixel_list(count: u32) {
+    with_job(|job| job.alloc_pixel_list(count));
+}
+
+#[wasm_bindgen]
+pub fn pixel_list_ptr() -> *mut f64 {
+    with_job(|job| job.pixel_list_ptr())
+}
+
+#[wasm_bindgen]
+pub fn pixel_results_ptr() -> *mut u32 {
+    with_job(|job| job.pixel_results_ptr())
+}
+
+/// `IterationJob::calc_pixels`
+#[wasm_bindgen]
+pub fn calc_iteration_pixels(count: u32) -> u32 {
+    with_job(|job| job.calc_pixels(count))
+}
+
+/// `IterationJob::calc_band`
+#[wasm_bindgen]
+pub fn calc_iteration_band(band_scaled_y_from: u32, band_scaled_y_to: u32) -> u32 {
+    with_job(|job| job.calc_band(band_scaled_y_from, band_scaled_y_to))
+}
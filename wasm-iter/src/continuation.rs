@@ -0,0 +1,72 @@
+//! maxIteration を上げたときに、前の job で maxIteration に達したピクセルの計算を続ける。
+//!
+//! `IterationJob::set_continuation` を有効にした perturbation の job は、maxIteration に達した
+//! ピクセルの計算途中の状態 (Δ, iteration, ref_iteration) を area 座標で持っておく。
+//! 次の job が同じ格子で maxIteration だけを上げたものなら、bailout 済みのピクセルはそのまま使い、
+//! maxIteration に達したピクセルだけをその状態から計算し直す。
+//! 状態は 1 ピクセルずつ計算する scalar の kernel で読み書きするので、この job では
+//! f32 / simd / interleave の kernel と solid guessing などは使わない。
+
+use crate::{IterationJob, NoObserver, PixelKernel, continue_iteration_with_state, start_pixel};
+
+/// maxIteration に達したピクセルの計算途中の状態。iteration が 0 なら状態なし
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub(crate) struct Continuation {
+    pub(crate) delta_n_re: f64,
+    pub(crate) delta_n_im: f64,
+    pub(crate) iteration: u32,
+    pub(crate) ref_iteration: u32,
+}
+
+/// 続きから計算できる job で使う kernel。追加出力の代わりに状態を出す
+pub(crate) struct ContinuationKernel;
+
+impl PixelKernel for ContinuationKernel {
+    type Extra = Continuation;
+
+    #[inline(always)]
+    fn calc(job: &IterationJob, x: f64, y: f64) -> (u32, Continuation) {
+        let reference = &job.primary;
+        // Δc は状態に持たないので、毎回 `start_pixel` で求める
+        let mut state = start_pixel(job, reference, x, y, &mut NoObserver);
+        // supersampling の pass は area のピクセルと対応しないので、続きからは計算しない
+        if !job.is_super_sampling {
+            let index = (x - job.area_start_x as f64) as usize
+                + (y - job.area_start_y as f64) as usize * job.area_width as usize;
+            let saved = job.continuations[index];
+            if saved.iteration != 0 {
+                state.delta_n_re = saved.delta_n_re;
+                state.delta_n_im = saved.delta_n_im;
+                state.iteration = saved.iteration;
+                state.ref_iteration = saved.ref_iteration;
+            }
+        }
+
+        let (n, state) = continue_iteration_with_state(job, reference, state, &mut NoObserver);
+        if n < job.max_iteration {
+            return (n, Continuation::default());
+        }
+        let continuation = Continuation {
+            delta_n_re: state.delta_n_re,
+            delta_n_im: state.delta_n_im,
+            iteration: state.iteration,
+            ref_iteration: state.ref_iteration,
+        };
+        (n, continuation)
+    }
+
+    #[inline(always)]
+    fn store(
+        job: &mut IterationJob,
+        area_index: Option<usize>,
+        _scaled_index: usize,
+        continuation: Continuation,
+    ) {
+        if let Some(index) = area_index {
+            job.continuations[index] = continuation;
+        }
+    }
+
+    #[inline(always)]
+    fn restore(_job: &mut IterationJob, _area_index: usize, _scaled_index: usize) {}
+}
@@ -0,0 +1,77 @@
+//! reference orbit を使わずに z_{n+1} = z_n^2 + c をそのまま double で回す direct mode。
+//!
+//! 浅いズームでは perturbation の準備 (reference orbit と BLATable) が要らないので、
+//! `src/workers/mandelbrot-worker.ts` は `IterationJob::begin_direct` で job を始める。
+//! pass / band の進め方、iterations キャッシュ、hit count の数え方は perturbation と共通で、
+//! 違うのは 1 ピクセル分の計算だけ。
+
+use crate::{BAILOUT_RADIUS, IterationJob, PixelKernel};
+
+/// direct mode の job で、どの pixel 座標がどの c に当たるか
+#[derive(Clone, Copy)]
+pub(crate) struct DirectView {
+    /// `center_pixel_x`, `center_pixel_y` の位置にある c
+    pub(crate) center_re: f64,
+    pub(crate) center_im: f64,
+    pub(crate) center_pixel_x: f64,
+    pub(crate) center_pixel_y: f64,
+}
+
+impl DirectView {
+    /// pixel 座標の c。1 pixel あたりの幅は job の `delta_c_scale`
+    #[inline(always)]
+    fn c(&self, delta_c_scale: f64, pixel_x: f64, pixel_y: f64) -> (f64, f64) {
+        (
+            self.center_re + (pixel_x - self.center_pixel_x) * delta_c_scale,
+            self.center_im - (pixel_y - self.center_pixel_y) * delta_c_scale,
+        )
+    }
+}
+
+/// c の iteration 数。|z_n|^2 が `BAILOUT_RADIUS` を超えた n か `max_iteration` を返すので、
+/// perturbation の `calc_iteration_at` と同じ数え方になる
+#[inline(always)]
+pub(crate) fn calc_iteration_direct(max_iteration: u32, c_re: f64, c_im: f64) -> u32 {
+    let mut z_re = 0.0f64;
+    let mut z_im = 0.0f64;
+    let mut z_re2 = 0.0f64;
+    let mut z_im2 = 0.0f64;
+    let mut n = 0;
+    while z_re2 + z_im2 <= BAILOUT_RADIUS && n < max_iteration {
+        z_im = (z_re + z_re) * z_im + c_im;
+        z_re = z_re2 - z_im2 + c_re;
+        z_re2 = z_re * z_re;
+        z_im2 = z_im * z_im;
+        n += 1;
+    }
+    n
+}
+
+/// direct mode の job で使う kernel。追加出力は出さない
+pub(crate) struct DirectKernel;
+
+impl PixelKernel for DirectKernel {
+    type Extra = ();
+
+    #[inline(always)]
+    fn calc(job: &IterationJob, x: f64, y: f64) -> (u32, ()) {
+        let view = job
+            .direct
+            .as_ref()
+            .expect("DirectKernel is used only for direct jobs");
+        let (c_re, c_im) = view.c(job.delta_c_scale, x, y);
+        (calc_iteration_direct(job.max_iteration, c_re, c_im), ())
+    }
+
+    #[inline(always)]
+    fn store(
+        _job: &mut IterationJob,
+        _area_index: Option<usize>,
+        _scaled_index: usize,
+        _extra: (),
+    ) {
+    }
+
+    #[inline(always)]
+    fn restore(_job: &mut IterationJob, _area_index: usize, _scaled_index: usize) {}
+}
@@ -0,0 +1,100 @@
+//! job の入力が壊れているときのエラー。
+//!
+//! wasm 内で panic するとその worker の instance ごと使えなくなるので、
+//! バッファを読む前に検証して、JS にはコードとメッセージで返す。
+
+/// エラーなしを表すコード
+pub(crate) const JOB_OK: u32 = 0;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum JobError {
+    /// xn が max_ref_iteration + 1 要素より短い
+    XnTooShort {
+        reference: u32,
+        required: usize,
+        actual: usize,
+    },
+    /// bla_row_offsets が bla_rows 段分より短い
+    RowOffsetsTooShort {
+        reference: u32,
+        rows: u32,
+        actual: usize,
+    },
+    /// row offsets が bla_bytes の範囲外を指している
+    BlaRowOutOfBounds { reference: u32, row: u32 },
+    /// BLA の探索で参照する列が row に含まれていない
+    BlaRowTooShort {
+        reference: u32,
+        row: u32,
+        required: usize,
+        actual: usize,
+    },
+    /// pass の座標が iterations キャッシュの範囲を超えている
+    AreaOutOfBounds,
+    /// band の出力が scaled バッファに収まらない
+    BandOutOfBounds,
+    /// `calc_pixels` に渡した数がピクセル座標のバッファに収まらない
+    PixelListOutOfBounds { count: u32, capacity: usize },
+    /// `set_reference` の index が 1 から連続していないか、上限を超えている
+    ReferenceIndexOutOfRange { index: u32, max: u32 },
+}
+
+impl JobError {
+    /// JS に返すコード。0 はエラーなし
+    pub(crate) fn code(&self) -> u32 {
+        match self {
+            Self::XnTooShort { .. } => 1,
+            Self::RowOffsetsTooShort { .. } => 2,
+            Self::BlaRowOutOfBounds { .. } => 3,
+            Self::BlaRowTooShort { .. } => 4,
+            Self::AreaOutOfBounds => 5,
+            Self::BandOutOfBounds => 6,
+            Self::PixelListOutOfBounds { .. } => 7,
+            Self::ReferenceIndexOutOfRange { .. } => 8,
+        }
+    }
+
+    pub(crate) fn message(&self) -> String {
+        match *self {
+            Self::XnTooShort {
+                reference,
+                required,
+                actual,
+            } => format!(
+                "reference {reference}: xn has {actual} points but max_ref_iteration needs {required}"
+            ),
+            Self::RowOffsetsTooShort {
+                reference,
+                rows,
+                actual,
+            } => format!(
+                "reference {reference}: bla_row_offsets has {actual} entries but {rows} rows need {}",
+                rows * 2
+            ),
+            Self::BlaRowOutOfBounds { reference, row } => {
+                format!("reference {reference}: BLA row {row} is outside of bla_bytes")
+            }
+            Self::BlaRowTooShort {
+                reference,
+                row,
+                required,
+                actual,
+            } => format!(
+                "reference {reference}: BLA row {row} has {actual} items but max_ref_iteration needs {required}"
+            ),
+            Self::AreaOutOfBounds => "pass reads outside of the iterations cache".to_string(),
+            Self::BandOutOfBounds => "band writes outside of the scaled output".to_string(),
+            Self::PixelListOutOfBounds { count, capacity } => {
+                format!("pixel list has {capacity} entries but {count} were requested")
+            }
+            Self::ReferenceIndexOutOfRange { index, max } => {
+                format!("reference {index}: index must be between 0 and {max}")
+            }
+        }
+    }
+}
+
+/// `Result` を JS に返すコードにする
+pub(crate) fn to_code(result: Result<(), JobError>) -> u32 {
+    result.err().map_or(JOB_OK, |error| error.code())
+}
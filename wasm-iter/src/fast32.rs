@@ -0,0 +1,484 @@
+//! 浅いズーム用に、4 ピクセルを f32x4 の lane に載せて同時に進める iteration ループ。
+//!
+//! 画面の半径が 1e-6 程度より大きければ、Δ と Δc は f32 の指数の範囲に収まり、
+//! 仮数 24 bit でも iteration 数はほとんど変わらない。f64x2 の 2 倍の lane 数で回せる。
+//! lane の扱いは `simd` の f64x2 版と同じで、BLA を使う lane と使わない lane が混ざっても
+//! Δ' = A Δ + U として lane ごとに A と U を選ぶ。BLA の係数は f64 の表から使うときに丸める。
+//!
+//! `set_f32_fast_path` で有効にした job のうち、次を満たすものは `begin` で自動的にこの kernel になる。
+//! - Δc のスケールが `F32_MIN_DELTA_C_SCALE` 以上 (Δc と Δ が f32 の仮数で足りる)
+//! - reference が bailout するまでの X_n が f32 の正規化数か 0 で表せる (`Reference::fits_f32`)
+//!
+//! X_n の大きさは bailout までは 2 以下なので、orbit について job 単位で見るのは指数の下限だけでよい。
+//! X_n を丸めた誤差は Δ' = 2XΔ + Δ² + Δc の相対誤差にしかならず、桁落ちや指数のはみ出しが
+//! 起きるかはピクセルの Δ によって変わるので、ピクセルごとに判定して f64 の
+//! `calc_iteration_at` で最初から計算し直す。
+//! - rebase で Z = X + Δ が桁落ちするとき (|Z|² < `REBASE_MIN_RATIO_SQ` |X|²)
+//! - |Z|² が bailout 半径に近すぎて、丸め誤差で脱出する iteration がずれうるとき
+//! - Δ が f32 で表せなくなったとき (BLA の係数が大きすぎる場合など)
+
+use crate::reference::Reference;
+use crate::{
+    BAILOUT_RADIUS, IterationJob, NoObserver, PixelState, calc_iteration_at, continue_iteration,
+    start_pixel,
+};
+use lanes::F32x4;
+
+/// f32 で計算する job の Δc のスケール (1 ピクセルの幅) の下限。
+/// 1000 ピクセル幅の画面なら半径 1e-6 程度まで
+pub(crate) const F32_MIN_DELTA_C_SCALE: f64 = 1e-9;
+
+/// rebase で |Z|² / |X|² がこれより小さくなったら、X + Δ の桁落ちで Z を f32 で表せないとみなす
+const REBASE_MIN_RATIO_SQ: f32 = 1e-6;
+
+/// |Z|² がこの相対幅で bailout 半径に近いピクセルは f64 で計算し直す
+const BAILOUT_MARGIN: f32 = 1e-4;
+
+const LANE_COUNT: usize = 4;
+
+#[cfg(all(target_arch = "wasm32", target_feature = "simd128"))]
+mod lanes {
+    use core::arch::wasm32::*;
+    use std::ops::{Add, Mul, Sub};
+
+    #[derive(Clone, Copy)]
+    pub(crate) struct F32x4(v128);
+
+    impl F32x4 {
+        #[inline(always)]
+        pub(crate) fn new(lanes: [f32; 4]) -> Self {
+            Self(f32x4(lanes[0], lanes[1], lanes[2], lanes[3]))
+        }
+
+        #[inline(always)]
+        pub(crate) fn splat(value: f32) -> Self {
+            Self(f32x4_splat(value))
+        }
+
+        #[inline(always)]
+        pub(crate) fn to_array(self) -> [f32; 4] {
+            [
+                f32x4_extract_lane::<0>(self.0),
+                f32x4_extract_lane::<1>(self.0),
+                f32x4_extract_lane::<2>(self.0),
+                f32x4_extract_lane::<3>(self.0),
+            ]
+        }
+
+        /// `mask` の bit が立っている lane は `if_true`、それ以外は `if_false` を取る
+        #[inline(always)]
+        pub(crate) fn select(mask: u8, if_true: Self, if_false: Self) -> Self {
+            let bit = |lane: u8| -(((mask >> lane) & 1) as i32);
+            let mask = i32x4(bit(0), bit(1), bit(2), bit(3));
+            Self(v128_bitselect(if_true.0, if_false.0, mask))
+        }
+
+        /// self < other となる lane の bit を立てる
+        #[inline(always)]
+        pub(crate) fn lt_bits(self, other: Self) -> u8 {
+            i32x4_bitmask(f32x4_lt(self.0, other.0))
+        }
+
+        /// self <= other となる lane の bit を立てる (NaN の lane は立たない)
+        #[inline(always)]
+        pub(crate) fn le_bits(self, other: Self) -> u8 {
+            i32x4_bitmask(f32x4_le(self.0, other.0))
+        }
+    }
+
+    impl Add for F32x4 {
+        type Output = Self;
+        #[inline(always)]
+        fn add(self, rhs: Self) -> Self {
+            Self(f32x4_add(self.0, rhs.0))
+        }
+    }
+
+    impl Sub for F32x4 {
+        type Output = Self;
+        #[inline(always)]
+        fn sub(self, rhs: Self) -> Self {
+            Self(f32x4_sub(self.0, rhs.0))
+        }
+    }
+
+    impl Mul for F32x4 {
+        type Output = Self;
+        #[inline(always)]
+        fn mul(self, rhs: Self) -> Self {
+            Self(f32x4_mul(self.0, rhs.0))
+        }
+    }
+}
+
+/// simd128 がないビルド用。同じ API を配列で実装する
+#[cfg(not(all(target_arch = "wasm32", target_feature = "simd128")))]
+mod lanes {
+    use std::ops::{Add, Mul, Sub};
+
+    #[derive(Clone, Copy)]
+    pub(crate) struct F32x4([f32; 4]);
+
+    impl F32x4 {
+        #[inline(always)]
+        pub(crate) fn new(lanes: [f32; 4]) -> Self {
+            Self(lanes)
+        }
+
+        #[inline(always)]
+        pub(crate) fn splat(value: f32) -> Self {
+            Self([value; 4])
+        }
+
+        #[inline(always)]
+        pub(crate) fn to_array(self) -> [f32; 4] {
+            self.0
+        }
+
+        /// `mask` の bit が立っている lane は `if_true`、それ以外は `if_false` を取る
+        #[inline(always)]
+        pub(crate) fn select(mask: u8, if_true: Self, if_false: Self) -> Self {
+            Self(std::array::from_fn(|lane| {
+                if mask & (1 << lane) != 0 {
+                    if_true.0[lane]
+                } else {
+                    if_false.0[lane]
+                }
+            }))
+        }
+
+        /// self < other となる lane の bit を立てる
+        #[inline(always)]
+        pub(crate) fn lt_bits(self, other: Self) -> u8 {
+            (0..4).fold(0, |bits, lane| {
+                bits | ((self.0[lane] < other.0[lane]) as u8) << lane
+            })
+        }
+
+        /// self <= other となる lane の bit を立てる (NaN の lane は立たない)
+        #[inline(always)]
+        pub(crate) fn le_bits(self, other: Self) -> u8 {
+            (0..4).fold(0, |bits, lane| {
+                bits | ((self.0[lane] <= other.0[lane]) as u8) << lane
+            })
+        }
+    }
+
+    impl Add for F32x4 {
+        type Output = Self;
+        #[inline(always)]
+        fn add(self, rhs: Self) -> Self {
+            Self(std::array::from_fn(|lane| self.0[lane] + rhs.0[lane]))
+        }
+    }
+
+    impl Sub for F32x4 {
+        type Output = Self;
+        #[inline(always)]
+        fn sub(self, rhs: Self) -> Self {
+            Self(std::array::from_fn(|lane| self.0[lane] - rhs.0[lane]))
+        }
+    }
+
+    impl Mul for F32x4 {
+        type Output = Self;
+        #[inline(always)]
+        fn mul(self, rhs: Self) -> Self {
+            Self(std::array::from_fn(|lane| self.0[lane] * rhs.0[lane]))
+        }
+    }
+}
+
+/// lane の計算が終わった理由
+#[derive(Clone, Copy)]
+enum Outcome {
+    /// bailout したか maxIteration に達した
+    Done(u32),
+    /// f32 では精度が足りないので f64 で計算し直す
+    Fallback,
+}
+
+/// 4 lane 分の計算途中の状態
+struct Lanes {
+    /// lane が計算中のピクセルの、`points` 内の index
+    pixels: [usize; LANE_COUNT],
+    iteration: [u32; LANE_COUNT],
+    ref_iteration: [u32; LANE_COUNT],
+    delta_re: [f32; LANE_COUNT],
+    delta_im: [f32; LANE_COUNT],
+    delta_c_re: [f32; LANE_COUNT],
+    delta_c_im: [f32; LANE_COUNT],
+}
+
+impl Lanes {
+    /// lane に新しいピクセルを載せる。初期値は f64 の `start_pixel` で求めて丸める
+    #[inline(always)]
+    fn start(&mut self, lane: usize, pixel: usize, state: PixelState) {
+        self.pixels[lane] = pixel;
+        self.iteration[lane] = state.iteration;
+        self.ref_iteration[lane] = state.ref_iteration;
+        self.delta_re[lane] = state.delta_n_re as f32;
+        self.delta_im[lane] = state.delta_n_im as f32;
+        self.delta_c_re[lane] = state.delta_c_re as f32;
+        self.delta_c_im[lane] = state.delta_c_im as f32;
+    }
+
+    /// lane の状態を f64 の `continue_iteration` に渡せる形で取り出す。
+    /// Δc は丸める前の値を使う
+    #[inline(always)]
+    fn state(&self, lane: usize, delta_c: (f64, f64)) -> PixelState {
+        PixelState {
+            delta_n_re: self.delta_re[lane] as f64,
+            delta_n_im: self.delta_im[lane] as f64,
+            delta_c_re: delta_c.0,
+            delta_c_im: delta_c.1,
+            iteration: self.iteration[lane],
+            ref_iteration: self.ref_iteration[lane],
+        }
+    }
+}
+
+/// `points` の各ピクセル座標の iteration 数を `results` に書く。
+/// f32 で計算し、精度が足りなくなったピクセルだけ f64 で計算し直す
+pub(crate) fn calc_iterations_f32x4(
+    job: &IterationJob,
+    reference: &Reference,
+    points: &[(f64, f64)],
+    results: &mut [u32],
+) {
+    let start = |pixel: usize| {
+        let (x, y) = points[pixel];
+        start_pixel(job, reference, x, y, &mut NoObserver)
+    };
+    let calc_f64 = |pixel: usize| {
+        let (x, y) = points[pixel];
+        calc_iteration_at(job, reference, x, y, &mut NoObserver)
+    };
+
+    // 4 lane を埋められなければ f64 で計算する
+    if points.len() < LANE_COUNT {
+        for (pixel, result) in results.iter_mut().enumerate() {
+            *result = calc_f64(pixel);
+        }
+        return;
+    }
+
+    let mut lanes = Lanes {
+        pixels: [0; LANE_COUNT],
+        iteration: [0; LANE_COUNT],
+        ref_iteration: [0; LANE_COUNT],
+        delta_re: [0.0; LANE_COUNT],
+        delta_im: [0.0; LANE_COUNT],
+        delta_c_re: [0.0; LANE_COUNT],
+        delta_c_im: [0.0; LANE_COUNT],
+    };
+    for lane in 0..LANE_COUNT {
+        lanes.start(lane, lane, start(lane));
+    }
+    let mut next_pixel = LANE_COUNT;
+
+    loop {
+        let finished = run_lanes(job, reference, &mut lanes);
+
+        // 終わった lane にはすぐ次のピクセルを載せる
+        let mut is_running = [true; LANE_COUNT];
+        for (lane, outcome) in finished.into_iter().enumerate() {
+            let Some(outcome) = outcome else {
+                continue;
+            };
+            let pixel = lanes.pixels[lane];
+            results[pixel] = match outcome {
+                Outcome::Done(n) => n,
+                Outcome::Fallback => calc_f64(pixel),
+            };
+            if next_pixel < points.len() {
+                lanes.start(lane, next_pixel, start(next_pixel));
+                next_pixel += 1;
+            } else {
+                is_running[lane] = false;
+            }
+        }
+        if is_running.iter().all(|&is_running| is_running) {
+            continue;
+        }
+
+        // 載せるピクセルがなくなったら、計算中の lane は f64 で最後まで進める
+        for (lane, is_running) in is_running.into_iter().enumerate() {
+            if is_running {
+                let pixel = lanes.pixels[lane];
+                let delta_c = {
+                    let state = start(pixel);
+                    (state.delta_c_re, state.delta_c_im)
+                };
+                let state = lanes.state(lane, delta_c);
+                results[pixel] = continue_iteration(job, reference, state, &mut NoObserver);
+            }
+        }
+        break;
+    }
+}
+
+/// すべての lane が計算中のあいだ進めて、どれかが終わったらその lane の結果を返す。
+/// 終わった lane の状態はその iteration の前のまま残る
+#[inline(always)]
+fn run_lanes(
+    job: &IterationJob,
+    reference: &Reference,
+    lanes: &mut Lanes,
+) -> [Option<Outcome>; LANE_COUNT] {
+    let max_iteration = job.max_iteration;
+    let max_ref_iteration = reference.max_ref_iteration;
+    let bla_rows = reference.bla_rows;
+    let start_bla_index = job.start_bla_index;
+    let xn = &reference.xn_f32;
+    let bla = &reference.bla;
+    let near_bailout = F32x4::splat(BAILOUT_RADIUS as f32 * (1.0 - BAILOUT_MARGIN));
+    let escaped_radius = BAILOUT_RADIUS as f32 * (1.0 + BAILOUT_MARGIN);
+    let rebase_ratio = F32x4::splat(REBASE_MIN_RATIO_SQ);
+    let two = F32x4::splat(2.0);
+
+    let delta_c_re = F32x4::new(lanes.delta_c_re);
+    let delta_c_im = F32x4::new(lanes.delta_c_im);
+    let mut delta_re = F32x4::new(lanes.delta_re);
+    let mut delta_im = F32x4::new(lanes.delta_im);
+    let mut iteration = lanes.iteration;
+    let mut ref_iteration = lanes.ref_iteration;
+
+    let has_bla = start_bla_index < bla_rows;
+    // どれかの lane が maxIteration に達するまでの iteration 数。lane ごとの比較を毎回しないで済む
+    let remaining = |iteration: &[u32; LANE_COUNT]| {
+        iteration
+            .iter()
+            .map(|&n| max_iteration.saturating_sub(n))
+            .min()
+            .unwrap_or(0)
+    };
+    let mut budget = remaining(&iteration);
+
+    let finished = loop {
+        if budget == 0 {
+            break iteration.map(|n| (n >= max_iteration).then_some(Outcome::Done(max_iteration)));
+        }
+
+        let index = ref_iteration.map(|n| n as usize * 2);
+        let x_re = F32x4::new(index.map(|i| xn[i]));
+        let x_im = F32x4::new(index.map(|i| xn[i + 1]));
+        let z_re = x_re + delta_re;
+        let z_im = x_im + delta_im;
+        let z_norm = z_re * z_re + z_im * z_im;
+
+        // bailout 半径の近く、外側、NaN の lane はここで終わる。外側でも近すぎれば f64 で計算し直す
+        let stopped = !z_norm.le_bits(near_bailout) & 0xf;
+        if stopped != 0 {
+            let z_norm = z_norm.to_array();
+            break std::array::from_fn(|lane| {
+                (stopped & (1 << lane) != 0).then(|| {
+                    let z_norm = z_norm[lane];
+                    if z_norm > escaped_radius && z_norm.is_finite() {
+                        Outcome::Done(iteration[lane])
+                    } else {
+                        Outcome::Fallback
+                    }
+                })
+            });
+        }
+
+        // rebase
+        let dz_norm = delta_re * delta_re + delta_im * delta_im;
+        let mut rebase = z_norm.lt_bits(dz_norm);
+        for (lane, &ref_iteration) in ref_iteration.iter().enumerate() {
+            if ref_iteration == max_ref_iteration {
+                rebase |= 1 << lane;
+            }
+        }
+        let mut cur_x_re = x_re;
+        let mut cur_x_im = x_im;
+        if rebase != 0 {
+            // Z が X + Δ の桁落ちで決まっている lane は f32 では続けられない
+            let x_norm = x_re * x_re + x_im * x_im;
+            let lost = rebase & z_norm.lt_bits(x_norm * rebase_ratio);
+            if lost != 0 {
+                break std::array::from_fn(|lane| {
+                    (lost & (1 << lane) != 0).then_some(Outcome::Fallback)
+                });
+            }
+
+            delta_re = F32x4::select(rebase, z_re, delta_re);
+            delta_im = F32x4::select(rebase, z_im, delta_im);
+            cur_x_re = F32x4::select(rebase, F32x4::splat(xn[0]), x_re);
+            cur_x_im = F32x4::select(rebase, F32x4::splat(xn[1]), x_im);
+            for (lane, ref_iteration) in ref_iteration.iter_mut().enumerate() {
+                if rebase & (1 << lane) != 0 {
+                    *ref_iteration = 0;
+                }
+            }
+        }
+
+        // BLA は lane ごとに探す
+        let dz_norm = dz_norm.to_array();
+        let steps: [_; LANE_COUNT] = std::array::from_fn(|lane| {
+            if !has_bla {
+                return None;
+            }
+            bla.find(
+                bla_rows,
+                start_bla_index,
+                ref_iteration[lane],
+                dz_norm[lane] as f64,
+            )
+            .map(|index| bla.step(index))
+            .filter(|step| ref_iteration[lane].wrapping_add(step.skip) < max_ref_iteration)
+        });
+
+        // perturbation: Δ' = (2X + Δ) Δ + Δc
+        let t_re = cur_x_re * two + delta_re;
+        let t_im = cur_x_im * two + delta_im;
+        if steps.iter().all(Option::is_none) {
+            let new_re = (t_re * delta_re - t_im * delta_im) + delta_c_re;
+            let new_im = (t_re * delta_im + t_im * delta_re) + delta_c_im;
+            delta_re = new_re;
+            delta_im = new_im;
+            ref_iteration = ref_iteration.map(|n| n + 1);
+            iteration = iteration.map(|n| n + 1);
+            budget -= 1;
+            continue;
+        }
+
+        // BLA を使う lane が混ざるときは `simd` と同じく Δ' = A Δ + U で lane ごとに選ぶ
+        let mut use_bla = 0;
+        let mut a = [[0.0; 2]; LANE_COUNT];
+        let mut b = [[1.0, 0.0]; LANE_COUNT];
+        for (lane, step) in steps.iter().enumerate() {
+            if let Some(step) = step {
+                use_bla |= 1 << lane;
+                a[lane] = [step.a_re as f32, step.a_im as f32];
+                b[lane] = [step.b_re as f32, step.b_im as f32];
+                ref_iteration[lane] = ref_iteration[lane].wrapping_add(step.skip);
+                iteration[lane] = iteration[lane].wrapping_add(step.skip);
+            } else {
+                ref_iteration[lane] += 1;
+                iteration[lane] += 1;
+            }
+        }
+        let a_re = F32x4::select(use_bla, F32x4::new(a.map(|a| a[0])), t_re);
+        let a_im = F32x4::select(use_bla, F32x4::new(a.map(|a| a[1])), t_im);
+        let b_re = F32x4::new(b.map(|b| b[0]));
+        let b_im = F32x4::new(b.map(|b| b[1]));
+        let bdc_re = b_re * delta_c_re - b_im * delta_c_im;
+        let bdc_im = b_re * delta_c_im + b_im * delta_c_re;
+        let u_re = F32x4::select(use_bla, bdc_re, delta_c_re);
+        let u_im = F32x4::select(use_bla, bdc_im, delta_c_im);
+
+        let new_re = (a_re * delta_re - a_im * delta_im) + u_re;
+        let new_im = (a_re * delta_im + a_im * delta_re) + u_im;
+        delta_re = new_re;
+        delta_im = new_im;
+        budget = remaining(&iteration);
+    };
+
+    lanes.delta_re = delta_re.to_array();
+    lanes.delta_im = delta_im.to_array();
+    lanes.iteration = iteration;
+    lanes.ref_iteration = ref_iteration;
+    finished
+}
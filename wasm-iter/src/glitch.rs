@@ -0,0 +1,61 @@
+//! Pauldelbrot 式の glitch 判定。
+//!
+//! z = X + Δ で |z| が |Δ| に比べて極端に小さいとき、z は X と Δ の桁落ちで精度を失っている。
+//! rebase はこの z を新しい Δ として使い続けるので、以降の結果も同じだけ精度を失う。
+//! その度合いが閾値を超えたピクセルを glitch として印をつける。
+
+use crate::OrbitObserver;
+
+/// 1 ピクセル分の glitch 判定の状態
+pub(crate) struct GlitchDetector {
+    /// |z|² < tolerance * |Δ|² なら glitch とみなす
+    tolerance: f64,
+    glitched: bool,
+}
+
+impl GlitchDetector {
+    pub(crate) fn new(tolerance: f64) -> Self {
+        Self {
+            tolerance,
+            glitched: false,
+        }
+    }
+
+    pub(crate) fn finish(&self) -> bool {
+        self.glitched
+    }
+}
+
+impl OrbitObserver for GlitchDetector {
+    #[inline(always)]
+    fn allow_bla(&self) -> bool {
+        true
+    }
+
+    #[inline(always)]
+    fn observe(&mut self, _iteration: u32, _z_re: f64, _z_im: f64) -> bool {
+        false
+    }
+
+    #[inline(always)]
+    fn on_delta(&mut self, z_norm: f64, dz_norm: f64) {
+        if z_norm < self.tolerance * dz_norm {
+            self.glitched = true;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn flags_only_when_z_is_tiny_relative_to_delta() {
+        let mut detector = GlitchDetector::new(1e-6);
+        detector.on_delta(1e-3, 1.0);
+        detector.on_delta(0.5, 1.0);
+        assert!(!detector.finish());
+        detector.on_delta(1e-7, 1.0);
+        assert!(detector.finish());
+    }
+}
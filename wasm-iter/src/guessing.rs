@@ -0,0 +1,303 @@
+//! Mariani–Silver の矩形分割による solid guessing。
+//!
+//! band 内の矩形の周囲だけを計算し、周囲が全部同じ iteration 数なら内側も同じ値で埋める。
+//! 違えば長い方の辺で 2 つに分けて繰り返す。集合の内部や広い単色の領域では
+//! 計算するピクセルが周囲の分だけになる。
+//! 周囲が同じでも内側に細い構造が入り込んでいれば埋め間違えるので、
+//! `IterationJob::set_guess_check` を有効にすると埋めたピクセルも計算して間違いを数える。
+//!
+//! band のどのピクセルを計算するかの選択 (`RenderStrategy`) と、band 単位でキャッシュを
+//! 読み書きする処理は boundary tracing (`tracing.rs`) と共通。
+
+use crate::{IterationJob, PointsKernel};
+
+/// band 内のピクセルをどの順に計算するか。`IterationJob::set_render_strategy` で選ぶ
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum RenderStrategy {
+    /// 全ピクセルを計算する
+    Scan,
+    /// 矩形分割で、周囲が同じ値の矩形の内側を埋める
+    SolidGuessing,
+    /// iteration 数の境界をたどり、囲まれた内側を埋める
+    BoundaryTracing,
+}
+
+impl RenderStrategy {
+    pub(crate) fn from_u32(value: u32) -> Option<Self> {
+        match value {
+            0 => Some(Self::Scan),
+            1 => Some(Self::SolidGuessing),
+            2 => Some(Self::BoundaryTracing),
+            _ => None,
+        }
+    }
+}
+
+/// scaled 座標の矩形。両端を含む
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct GuessRect {
+    x0: u32,
+    y0: u32,
+    x1: u32,
+    y1: u32,
+}
+
+impl GuessRect {
+    /// 周囲のピクセル。角は 1 回ずつ
+    fn border(&self) -> impl Iterator<Item = (u32, u32)> + '_ {
+        let top = (self.x0..=self.x1).map(|x| (x, self.y0));
+        let bottom = (self.x0..=self.x1)
+            .filter(|_| self.y1 > self.y0)
+            .map(|x| (x, self.y1));
+        let right = (self.x1 > self.x0).then_some(self.x1);
+        let sides = (self.y0 + 1..self.y1)
+            .flat_map(move |y| std::iter::once(self.x0).chain(right).map(move |x| (x, y)));
+        top.chain(bottom).chain(sides)
+    }
+
+    /// 周囲を除いた内側のピクセル
+    fn interior(&self) -> impl Iterator<Item = (u32, u32)> + '_ {
+        (self.y0 + 1..self.y1).flat_map(|y| (self.x0 + 1..self.x1).map(move |x| (x, y)))
+    }
+
+    fn has_interior(&self) -> bool {
+        self.x1 - self.x0 >= 2 && self.y1 - self.y0 >= 2
+    }
+
+    /// 長い方の辺の中央で、中央の列 (行) を共有する 2 つに分ける
+    fn split(&self) -> [GuessRect; 2] {
+        if self.x1 - self.x0 >= self.y1 - self.y0 {
+            let mid = (self.x0 + self.x1) / 2;
+            [
+                GuessRect { x1: mid, ..*self },
+                GuessRect { x0: mid, ..*self },
+            ]
+        } else {
+            let mid = (self.y0 + self.y1) / 2;
+            [
+                GuessRect { y1: mid, ..*self },
+                GuessRect { y0: mid, ..*self },
+            ]
+        }
+    }
+}
+
+/// band の計算中の状態。`values` は band 内の scaled 座標ごとの iteration 数で、0 なら未計算
+struct GuessBand {
+    band_y_from: u32,
+    scaled_w: u32,
+    values: Vec<u32>,
+    points: Vec<(f64, f64)>,
+    targets: Vec<usize>,
+    results: Vec<u32>,
+}
+
+impl GuessBand {
+    fn index(&self, x: u32, y: u32) -> usize {
+        (x + (y - self.band_y_from) * self.scaled_w) as usize
+    }
+
+    /// `pixels` のうち未計算のものをまとめて `calc_points` で計算する
+    fn calc_pixels(
+        &mut self,
+        job: &mut IterationJob,
+        pixels: impl Iterator<Item = (u32, u32)>,
+        calc_points: PointsKernel,
+    ) {
+        self.points.clear();
+        self.targets.clear();
+        for (x, y) in pixels {
+            let index = self.index(x, y);
+            if self.values[index] != 0 {
+                continue;
+            }
+            self.points.push(point(job, x, y));
+            self.targets.push(index);
+        }
+        if self.points.is_empty() {
+            return;
+        }
+        self.results.clear();
+        self.results.resize(self.points.len(), 0);
+        calc_points(job, &job.primary, &self.points, &mut self.results);
+        job.calculated_count += self.points.len() as u32;
+        for (&index, &n) in self.targets.iter().zip(&self.results) {
+            self.values[index] = n;
+        }
+    }
+
+    /// 周囲が全部同じ iteration 数ならその値
+    fn uniform_border(&self, rect: &GuessRect) -> Option<u32> {
+        let mut border = rect.border().map(|(x, y)| self.values[self.index(x, y)]);
+        let first = border.next()?;
+        border.all(|n| n == first).then_some(first)
+    }
+
+    /// 内側の未計算のピクセルを `n` で埋める。
+    /// check が有効なら埋める代わりに計算して答え合わせし、計算した値を使う
+    fn fill(
+        &mut self,
+        job: &mut IterationJob,
+        rect: &GuessRect,
+        n: u32,
+        calc_points: PointsKernel,
+    ) {
+        let guessed: Vec<usize> = rect
+            .interior()
+            .map(|(x, y)| self.index(x, y))
+            .filter(|&index| self.values[index] == 0)
+            .collect();
+        job.guessed_count += guessed.len() as u32;
+
+        if job.is_guess_check_enabled {
+            self.calc_pixels(job, rect.interior(), calc_points);
+            let mistaken = guessed.iter().filter(|&&index| self.values[index] != n);
+            job.mistaken_guess_count += mistaken.count() as u32;
+            return;
+        }
+        // 埋めたピクセルも progress の分母に入っているので計算済みとして数える
+        job.calculated_count += guessed.len() as u32;
+        for index in guessed {
+            self.values[index] = n;
+        }
+    }
+}
+
+/// scaled 座標に対応する area の pixel 座標
+pub(crate) fn point(job: &IterationJob, scaled_x: u32, scaled_y: u32) -> (f64, f64) {
+    (
+        job.area_start_x as f64 + scaled_x as f64 * job.x_diff,
+        job.area_start_y as f64 + scaled_y as f64 * job.y_diff,
+    )
+}
+
+/// scaled 座標に対応する iterations キャッシュの index。supersampling の pass では None
+fn area_index(job: &IterationJob, scaled_x: u32, scaled_y: u32) -> Option<usize> {
+    if job.is_super_sampling {
+        return None;
+    }
+    let x = (scaled_x as f64 * job.x_diff) as usize;
+    let y = (scaled_y as f64 * job.y_diff) as usize;
+    Some(x + y * job.area_width as usize)
+}
+
+/// band 内の scaled 座標ごとの iteration 数。前の pass で計算済みのピクセルはキャッシュの値、
+/// それ以外は 0 (未計算)
+pub(crate) fn load_band(
+    job: &IterationJob,
+    band_scaled_y_from: u32,
+    band_scaled_y_to: u32,
+) -> Vec<u32> {
+    let scaled_w = job.scaled_width;
+    let mut values =
+        Vec::with_capacity((scaled_w * (band_scaled_y_to - band_scaled_y_from)) as usize);
+    for y in band_scaled_y_from..band_scaled_y_to {
+        for x in 0..scaled_w {
+            values.push(area_index(job, x, y).map_or(0, |index| job.iterations[index]));
+        }
+    }
+    values
+}
+
+/// `load_band` の並びの iteration 数を scaled 出力とキャッシュに書き、hit count を数える
+pub(crate) fn store_band(job: &mut IterationJob, band_scaled_y_from: u32, values: &[u32]) {
+    let scaled_w = job.scaled_width;
+    let is_result_pass = job.is_result_pass;
+    let max_iteration = job.max_iteration;
+    let offset = (band_scaled_y_from * scaled_w) as usize;
+    for (i, &n) in values.iter().enumerate() {
+        let x = i as u32 % scaled_w;
+        let y = band_scaled_y_from + i as u32 / scaled_w;
+        if let Some(index) = area_index(job, x, y) {
+            job.iterations[index] = n;
+        }
+        job.scaled_iterations[offset + i] = n;
+        if is_result_pass && n == max_iteration {
+            job.hit_count += 1;
+        }
+    }
+}
+
+/// pass 内の scaled_y が [from, to) の band を矩形分割で計算する。
+///
+/// 前の pass で計算済みのピクセルはキャッシュの値を使い、埋めたピクセルもキャッシュに書く。
+/// band 全体を 1 つの矩形として始めるので、1 行ずつの band では全ピクセルを計算することになる
+pub(crate) fn calc_band_guessed(
+    job: &mut IterationJob,
+    band_scaled_y_from: u32,
+    band_scaled_y_to: u32,
+    calc_points: PointsKernel,
+) {
+    if band_scaled_y_from >= band_scaled_y_to || job.scaled_width == 0 {
+        return;
+    }
+    let scaled_w = job.scaled_width;
+    let mut band = GuessBand {
+        band_y_from: band_scaled_y_from,
+        scaled_w,
+        values: load_band(job, band_scaled_y_from, band_scaled_y_to),
+        points: Vec::new(),
+        targets: Vec::new(),
+        results: Vec::new(),
+    };
+
+    let mut stack = vec![GuessRect {
+        x0: 0,
+        y0: band_scaled_y_from,
+        x1: scaled_w - 1,
+        y1: band_scaled_y_to - 1,
+    }];
+    while let Some(rect) = stack.pop() {
+        band.calc_pixels(job, rect.border(), calc_points);
+        if !rect.has_interior() {
+            continue;
+        }
+        match band.uniform_border(&rect) {
+            Some(n) => band.fill(job, &rect, n, calc_points),
+            None => stack.extend(rect.split()),
+        }
+    }
+
+    store_band(job, band_scaled_y_from, &band.values);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rect(x0: u32, y0: u32, x1: u32, y1: u32) -> GuessRect {
+        GuessRect { x0, y0, x1, y1 }
+    }
+
+    #[test]
+    fn border_and_interior_cover_the_rect_once() {
+        for r in [
+            rect(0, 0, 0, 0),
+            rect(2, 3, 2, 7),
+            rect(1, 1, 6, 1),
+            rect(3, 4, 9, 8),
+        ] {
+            let mut pixels: Vec<_> = r.border().chain(r.interior()).collect();
+            pixels.sort();
+            let mut expected: Vec<_> = (r.y0..=r.y1)
+                .flat_map(|y| (r.x0..=r.x1).map(move |x| (x, y)))
+                .collect();
+            expected.sort();
+            assert_eq!(pixels, expected);
+        }
+        assert!(!rect(0, 0, 1, 9).has_interior());
+        assert!(rect(0, 0, 2, 2).has_interior());
+    }
+
+    #[test]
+    fn split_shares_the_middle_line_of_the_longer_side() {
+        assert_eq!(
+            rect(0, 0, 8, 4).split(),
+            [rect(0, 0, 4, 4), rect(4, 0, 8, 4)]
+        );
+        assert_eq!(
+            rect(2, 1, 5, 9).split(),
+            [rect(2, 1, 5, 5), rect(2, 5, 5, 9)]
+        );
+    }
+}
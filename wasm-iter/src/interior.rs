@@ -0,0 +1,250 @@
+//! maxIteration まで回さずに内部 (吸引周期軌道に落ちる点) を判定して打ち切る。
+//!
+//! 2 つの判定を組み合わせられる。
+//! - periodicity: Brent 法で 2 冪ごとに z を保存し、その後の z が保存値に十分近づいたら周期とみなす
+//! - derivative: 保存点からの dz_n/dz_saved を追い、|dz/dz|² が十分小さくなったら吸引的とみなす
+//!
+//! BLA の step では dz/dz に係数 A を掛ける (A は ∏ 2X_k で ∏ 2z_k の近似になっている)ので、
+//! BLA を使ったまま判定できる。
+
+use crate::OrbitObserver;
+
+/// periodicity 判定を使う
+pub(crate) const INTERIOR_PERIODICITY: u32 = 1;
+/// derivative 判定を使う
+pub(crate) const INTERIOR_DERIVATIVE: u32 = 2;
+
+/// derivative 判定だけで内部と分かり、周期が分からなかったときの period
+pub(crate) const UNKNOWN_PERIOD: u32 = u32::MAX;
+
+/// JS から設定する内部判定のパラメータ
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct InteriorDetection {
+    flags: u32,
+    /// periodicity 判定で「同じ点に戻った」とみなす |z - z_saved|² の閾値
+    periodicity_epsilon_sq: f64,
+    /// derivative 判定で吸引的とみなす |dz/dz|² の閾値
+    derivative_epsilon_sq: f64,
+}
+
+impl InteriorDetection {
+    /// 判定が 1 つも有効でなければ None
+    pub(crate) fn new(
+        flags: u32,
+        periodicity_epsilon_sq: f64,
+        derivative_epsilon_sq: f64,
+    ) -> Option<Self> {
+        let flags = flags & (INTERIOR_PERIODICITY | INTERIOR_DERIVATIVE);
+        if flags == 0 {
+            return None;
+        }
+        Some(Self {
+            flags,
+            periodicity_epsilon_sq,
+            derivative_epsilon_sq,
+        })
+    }
+
+    #[inline(always)]
+    fn uses(&self, flag: u32) -> bool {
+        self.flags & flag != 0
+    }
+}
+
+/// 1 ピクセル分の内部判定の結果
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub(crate) struct InteriorResult {
+    /// 検出した周期。内部と判定しなかったら 0
+    pub(crate) period: u32,
+    /// 検出した周期軌道の multiplier の大きさ (internal radius)。
+    /// 内部でなければ 0、周期が分からなかった場合は NaN
+    pub(crate) multiplier_abs: f64,
+}
+
+/// 1 ピクセル分の内部判定の状態
+pub(crate) struct InteriorDetector {
+    config: InteriorDetection,
+    /// 直近に observe した z。次の perturbation step で dz/dz に掛ける
+    z_re: f64,
+    z_im: f64,
+    /// 保存点からの dz_n/dz_saved
+    der_re: f64,
+    der_im: f64,
+    saved_re: f64,
+    saved_im: f64,
+    saved_iteration: u32,
+    /// 次に z を保存する iteration。BLA で飛び越えることがあるので「以上になったら」保存する
+    next_save_iteration: u32,
+    period: u32,
+    multiplier_abs: f64,
+}
+
+impl InteriorDetector {
+    pub(crate) fn new(config: InteriorDetection) -> Self {
+        Self {
+            config,
+            z_re: 0.0,
+            z_im: 0.0,
+            der_re: 1.0,
+            der_im: 0.0,
+            saved_re: 0.0,
+            saved_im: 0.0,
+            saved_iteration: 0,
+            next_save_iteration: 1,
+            period: 0,
+            multiplier_abs: 0.0,
+        }
+    }
+
+    pub(crate) fn finish(&self) -> InteriorResult {
+        InteriorResult {
+            period: self.period,
+            multiplier_abs: self.multiplier_abs,
+        }
+    }
+
+    /// 保存点からの dz/dz の大きさの 2 乗
+    #[inline(always)]
+    fn der_norm(&self) -> f64 {
+        self.der_re * self.der_re + self.der_im * self.der_im
+    }
+
+    #[inline(always)]
+    fn multiply_der(&mut self, re: f64, im: f64) {
+        let der_re = self.der_re * re - self.der_im * im;
+        self.der_im = self.der_re * im + self.der_im * re;
+        self.der_re = der_re;
+    }
+}
+
+impl OrbitObserver for InteriorDetector {
+    #[inline(always)]
+    fn allow_bla(&self) -> bool {
+        true
+    }
+
+    #[inline(always)]
+    fn observe(&mut self, iteration: u32, z_re: f64, z_im: f64) -> bool {
+        self.z_re = z_re;
+        self.z_im = z_im;
+
+        // 最初の保存点は z_1。z_0 = 0 は臨界点で dz_1/dz_0 = 0 になってしまうので比較対象にしない
+        if self.saved_iteration > 0 && iteration > self.saved_iteration {
+            if self.config.uses(INTERIOR_PERIODICITY) {
+                let dx = z_re - self.saved_re;
+                let dy = z_im - self.saved_im;
+                // derivative 判定も有効なら、戻ってきた周期軌道が吸引的 (|multiplier| < 1)
+                // であることも要求して誤検出を減らす
+                if dx * dx + dy * dy < self.config.periodicity_epsilon_sq
+                    && (!self.config.uses(INTERIOR_DERIVATIVE) || self.der_norm() < 1.0)
+                {
+                    // 保存点からちょうど 1 周期分進んだところなので、
+                    // 保存点からの dz/dz がそのまま multiplier (∏ 2z_k) になる
+                    self.period = iteration - self.saved_iteration;
+                    self.multiplier_abs = self.der_norm().sqrt();
+                    return true;
+                }
+            }
+            if self.config.uses(INTERIOR_DERIVATIVE)
+                && self.der_norm() < self.config.derivative_epsilon_sq
+            {
+                self.period = UNKNOWN_PERIOD;
+                self.multiplier_abs = f64::NAN;
+                return true;
+            }
+        }
+
+        if iteration >= self.next_save_iteration {
+            self.saved_re = z_re;
+            self.saved_im = z_im;
+            self.saved_iteration = iteration;
+            self.next_save_iteration = iteration.saturating_mul(2);
+            self.der_re = 1.0;
+            self.der_im = 0.0;
+        }
+        false
+    }
+
+    #[inline(always)]
+    fn on_perturbation_step(&mut self) {
+        // z_{n+1} = z_n² + c なので dz_{n+1}/dz_n = 2z_n
+        self.multiply_der(2.0 * self.z_re, 2.0 * self.z_im);
+    }
+
+    #[inline(always)]
+    fn on_bla_step(&mut self, a_re: f64, a_im: f64) {
+        self.multiply_der(a_re, a_im);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// BLA を使わずに c を直接反復して判定する
+    fn detect(flags: u32, c: (f64, f64), max_iteration: u32) -> Option<u32> {
+        detect_result(flags, c, max_iteration).map(|result| result.period)
+    }
+
+    fn detect_result(flags: u32, c: (f64, f64), max_iteration: u32) -> Option<InteriorResult> {
+        let config = InteriorDetection::new(flags, 1e-20, 1e-12).unwrap();
+        let mut detector = InteriorDetector::new(config);
+        let (mut z_re, mut z_im) = (0.0f64, 0.0f64);
+        for n in 0..max_iteration {
+            if z_re * z_re + z_im * z_im > 4.0 {
+                return None;
+            }
+            if detector.observe(n, z_re, z_im) {
+                return Some(detector.finish());
+            }
+            detector.on_perturbation_step();
+            let next_re = z_re * z_re - z_im * z_im + c.0;
+            z_im = 2.0 * z_re * z_im + c.1;
+            z_re = next_re;
+        }
+        None
+    }
+
+    #[test]
+    fn periodicity_finds_period() {
+        // 主カーディオイド (周期 1)、周期 2 の円、douady rabbit (周期 3)
+        assert_eq!(detect(INTERIOR_PERIODICITY, (-0.1, 0.1), 10000), Some(1));
+        assert_eq!(detect(INTERIOR_PERIODICITY, (-1.0, 0.05), 10000), Some(2));
+        assert_eq!(
+            detect(INTERIOR_PERIODICITY, (-0.122, 0.745), 10000),
+            Some(3)
+        );
+        let both = INTERIOR_PERIODICITY | INTERIOR_DERIVATIVE;
+        assert_eq!(detect(both, (-0.122, 0.745), 10000), Some(3));
+    }
+
+    #[test]
+    fn multiplier_of_fixed_point() {
+        // 周期 1 の成分では c = λ/2 - λ²/4 と書けるので、λ = 0.5 の点で |multiplier| = 0.5 になる
+        let lambda = 0.5f64;
+        let c = (lambda / 2.0 - lambda * lambda / 4.0, 0.0);
+        let result = detect_result(INTERIOR_PERIODICITY, c, 10000).unwrap();
+        assert_eq!(result.period, 1);
+        assert!((result.multiplier_abs - lambda).abs() < 1e-6);
+
+        // 周期 2 の成分の中心 c = -1 は超吸引的
+        let result = detect_result(INTERIOR_PERIODICITY, (-1.0, 0.0), 10000).unwrap();
+        assert_eq!(result.period, 2);
+        assert_eq!(result.multiplier_abs, 0.0);
+    }
+
+    #[test]
+    fn derivative_detects_interior() {
+        assert_eq!(
+            detect(INTERIOR_DERIVATIVE, (-0.1, 0.1), 10000),
+            Some(UNKNOWN_PERIOD)
+        );
+    }
+
+    #[test]
+    fn exterior_is_not_detected() {
+        let both = INTERIOR_PERIODICITY | INTERIOR_DERIVATIVE;
+        assert_eq!(detect(both, (0.3, 0.0), 10000), None);
+        assert_eq!(detect(both, (-0.75, 0.01), 10000), None);
+    }
+}
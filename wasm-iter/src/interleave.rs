@@ -0,0 +1,182 @@
+//! 複数のピクセルを 1 つのループで交互に 1 step ずつ進める scalar の iteration ループ。
+//!
+//! 1 ピクセルの反復は Δ の更新が 1 つ前の Δ に依存する長い依存鎖なので、1 ピクセルずつ回すと
+//! 乗算や加算の latency を待つ間 CPU が空く。依存のない `N` 個のピクセルを同じループで進めると、
+//! その待ち時間に他のピクセルの命令を実行できる。
+//!
+//! 各ピクセルの 1 step は scalar の `continue_iteration` のループ 1 回分とまったく同じ計算なので、
+//! iteration 数は 1 ピクセルずつ計算した場合と一致する。
+//! 終わったピクセルの枠にはすぐ次のピクセルを詰め、詰めるものがなくなったら残りは
+//! `continue_iteration` で 1 つずつ終わらせる。
+
+use crate::bla::BlaStep;
+use crate::reference::Reference;
+use crate::{
+    BAILOUT_RADIUS, IterationJob, NoObserver, PixelState, continue_iteration, mul_im, mul_re,
+    n_norm, start_pixel,
+};
+
+/// 同時に進めるピクセル数の上限。これより増やしても f64 のレジスタが足りなくなるだけ
+pub(crate) const MAX_INTERLEAVED_PIXELS: u32 = 4;
+
+/// `points` の各ピクセル座標の iteration 数を `results` に書く。
+/// 追加出力を持たない (`NoObserver` の) `calc_iteration_at` と同じ結果になる
+pub(crate) fn calc_iterations_interleaved<const N: usize>(
+    job: &IterationJob,
+    reference: &Reference,
+    points: &[(f64, f64)],
+    results: &mut [u32],
+) {
+    let start = |pixel: usize| {
+        let (x, y) = points[pixel];
+        start_pixel(job, reference, x, y, &mut NoObserver)
+    };
+
+    // 全部の枠を埋められなければ 1 つずつ計算する
+    if points.len() < N {
+        for (pixel, result) in results.iter_mut().enumerate() {
+            *result = continue_iteration(job, reference, start(pixel), &mut NoObserver);
+        }
+        return;
+    }
+
+    let mut pixels: [usize; N] = std::array::from_fn(|lane| lane);
+    let mut states: [PixelState; N] = std::array::from_fn(start);
+    let mut next_pixel = N;
+
+    loop {
+        let finished = run_lanes(job, reference, &mut states);
+
+        // 終わった枠にはすぐ次のピクセルを載せる
+        let mut is_running = [true; N];
+        for (lane, n) in finished.into_iter().enumerate() {
+            let Some(n) = n else {
+                continue;
+            };
+            results[pixels[lane]] = n;
+            if next_pixel < points.len() {
+                pixels[lane] = next_pixel;
+                states[lane] = start(next_pixel);
+                next_pixel += 1;
+            } else {
+                is_running[lane] = false;
+            }
+        }
+        if is_running.iter().all(|&is_running| is_running) {
+            continue;
+        }
+
+        // 載せるピクセルがなくなったら、計算中のものは 1 つずつ最後まで進める
+        for ((&pixel, &state), is_running) in pixels.iter().zip(&states).zip(is_running) {
+            if is_running {
+                results[pixel] = continue_iteration(job, reference, state, &mut NoObserver);
+            }
+        }
+        break;
+    }
+}
+
+/// すべての枠を 1 step ずつ進め、どれかが終わったらその枠の iteration 数を返す。
+/// 終わった枠の状態はその step の前のまま残る
+#[inline(always)]
+fn run_lanes<const N: usize>(
+    job: &IterationJob,
+    reference: &Reference,
+    states: &mut [PixelState; N],
+) -> [Option<u32>; N] {
+    // 配列のままだとメモリ経由になりやすいので、ループの間はローカルに持つ
+    let mut local = *states;
+    let finished = loop {
+        let mut finished = [None; N];
+        for (state, finished) in local.iter_mut().zip(&mut finished) {
+            *finished = step(job, reference, state);
+        }
+        if finished.iter().any(Option::is_some) {
+            break finished;
+        }
+    };
+    *states = local;
+    finished
+}
+
+/// `continue_iteration` のループ 1 回分。終わったなら状態を変えずに iteration 数を返す
+#[inline(always)]
+fn step(job: &IterationJob, reference: &Reference, state: &mut PixelState) -> Option<u32> {
+    let max_ref_iteration = reference.max_ref_iteration;
+    let xn_raw = &reference.xn;
+    let bla = &reference.bla;
+
+    if state.iteration >= job.max_iteration {
+        return Some(job.max_iteration);
+    }
+
+    let ref_idx2 = (state.ref_iteration as usize) * 2;
+    let x_re = xn_raw[ref_idx2];
+    let x_im = xn_raw[ref_idx2 + 1];
+    let z_re = x_re + state.delta_n_re;
+    let z_im = x_im + state.delta_n_im;
+    let z_norm = n_norm(z_re, z_im);
+    if z_norm > BAILOUT_RADIUS {
+        return Some(state.iteration);
+    }
+
+    // rebase
+    let dz_norm = n_norm(state.delta_n_re, state.delta_n_im);
+    let mut cur_x_re = x_re;
+    let mut cur_x_im = x_im;
+    if z_norm < dz_norm || state.ref_iteration == max_ref_iteration {
+        state.delta_n_re = z_re;
+        state.delta_n_im = z_im;
+        state.ref_iteration = 0;
+        cur_x_re = xn_raw[0];
+        cur_x_im = xn_raw[1];
+    }
+
+    // BLA
+    let step = bla
+        .find(
+            reference.bla_rows,
+            job.start_bla_index,
+            state.ref_iteration,
+            dz_norm,
+        )
+        .map(|index| bla.step(index));
+    let skipped = step.map_or(0, |step| step.skip);
+    let n = state.ref_iteration.wrapping_add(skipped);
+
+    let PixelState {
+        delta_n_re,
+        delta_n_im,
+        delta_c_re,
+        delta_c_im,
+        ..
+    } = *state;
+    if let Some(&BlaStep {
+        a_re,
+        a_im,
+        b_re,
+        b_im,
+        ..
+    }) = step
+        && n < max_ref_iteration
+    {
+        state.delta_n_re =
+            mul_re(a_re, a_im, delta_n_re, delta_n_im) + mul_re(b_re, b_im, delta_c_re, delta_c_im);
+        state.delta_n_im =
+            mul_im(a_re, a_im, delta_n_re, delta_n_im) + mul_im(b_re, b_im, delta_c_re, delta_c_im);
+
+        state.ref_iteration = n;
+        state.iteration = state.iteration.wrapping_add(skipped);
+    } else {
+        // Δn+1 = (2 * Xn + Δn) * Δn + Δ0
+        let dzr_t = cur_x_re * 2.0 + delta_n_re;
+        let dzi_t = cur_x_im * 2.0 + delta_n_im;
+
+        state.delta_n_re = mul_re(dzr_t, dzi_t, delta_n_re, delta_n_im) + delta_c_re;
+        state.delta_n_im = mul_im(dzr_t, dzi_t, delta_n_re, delta_n_im) + delta_c_im;
+
+        state.ref_iteration += 1;
+        state.iteration += 1;
+    }
+    None
+}
@@ -1,33 +1,111 @@
 //! perturbation + BLA による iteration 計算の hot loop。
 //!
-//! JS 側 (`src/workers/mandelbrot-perturbation-worker.ts`) からは worker ごとに
-//! 1 インスタンスだけ使われる。呼び出し順は
-//! `alloc_job` → (ptr 経由で xn / BLA をコピー) → `begin_iteration_job`
-//! → `begin_pass` → `calc_iteration_band` ... の繰り返し。
+//! 1 つの job は `IterationJob` で表す。呼び出し順は `alloc` → (ptr 経由で xn / BLA をコピー)
+//! → `begin` → `begin_pass` → `calc_band` ... の繰り返し。
+//! orbit trap などの追加出力を使う場合は `begin` の前に `set_*` で設定しておく。
+//! glitch 補正用の reference を追加する場合は `begin` のあとに
+//! `alloc_reference` → (ptr 経由でコピー) → `set_reference` を呼ぶ。
+//! 入力が壊れている job では `calc_band` は何も計算せずにエラーコードを返す
+//! (`validate` / `error_message`)。
+//! 浅いズームで reference orbit を使わない場合は `begin` の代わりに `begin_direct` を呼ぶ。
+//! 行単位ではなく散らばったピクセルだけを計算する場合は、`begin` のあとに
+//! `alloc_pixel_list` → (ptr 経由で座標をコピー) → `calc_pixels` を呼ぶ。
+//!
+//! JS の worker (`src/workers/mandelbrot-perturbation-worker.ts` と `mandelbrot-worker.ts`) は
+//! 今も worker ごとに job を 1 つだけ持つ `compat` の関数 (`alloc_job` / `begin_iteration_job` /
+//! `begin_direct_iteration_job` / `calc_iteration_band` など) を使っている。これらは thread_local の
+//! `IterationJob` の対応するメソッドを呼ぶだけなので、呼び出し順は上と同じ。
+//! preview と本番のように複数の job を並べて持ちたい呼び出し側は、`IterationJob` を直接作って使う。
+
+mod accumulation;
+mod atom_domain;
+mod bla;
+mod channel;
+mod compat;
+mod continuation;
+mod direct;
+mod error;
+mod fast32;
+mod glitch;
+mod guessing;
+mod interior;
+mod interleave;
+mod reference;
+mod sampling;
+mod series;
+mod shift;
+mod simd;
+mod tracing;
 
-use std::cell::RefCell;
+use accumulation::{
+    AccumulationMode, AccumulationResult, Accumulator, AverageKind, OrbitTrap, ResolvedMode,
+    TrapShape,
+};
+use atom_domain::AtomDomainTracker;
+use bla::BlaStep;
+use channel::PixelChannel;
+use continuation::{Continuation, ContinuationKernel};
+use direct::{DirectKernel, DirectView};
+use error::{JOB_OK, JobError, to_code};
+use glitch::GlitchDetector;
+use guessing::RenderStrategy;
+use interior::{InteriorDetection, InteriorDetector, InteriorResult};
+use reference::Reference;
+use sampling::{MAX_SAMPLES_PER_AXIS, RefinementPass, SamplePattern, SamplingPass};
+use series::{SeriesApproximation, SeriesConfig};
+use shift::{CachedGrid, GridAnchor, GridImport, import_grid, shift_grid};
 use wasm_bindgen::prelude::*;
 
 /// BLATable 1 要素のバイト数。`src/workers/bla-table-item.ts` の ITEM_BYTE_LENGTH と一致させる
 const ITEM_BYTE_LENGTH: usize = 44;
 /// bailout 判定に使う半径の 2 乗
 const BAILOUT_RADIUS: f64 = 4.0;
+/// 1 つの job で使える secondary reference の上限
+const MAX_SECONDARY_REFERENCES: u32 = 16;
+
+/// 1 job 分の入力バッファと計算パラメータ。job をまたいで再利用し、足りないときだけ伸ばす。
+///
+/// JS からは `new IterationJob()` で必要な数だけ作れる (preview 用と本番用など)。
+#[wasm_bindgen]
+pub struct IterationJob {
+    /// `IterationJob::alloc` / `begin` で設定する reference
+    primary: Reference,
+    /// glitch したピクセルを計算し直すための reference。`alloc_reference` の index 1 以降
+    secondary_references: Vec<Reference>,
+    /// `secondary_references` のうち、この job で使うものの数
+    secondary_count: usize,
+    /// glitch したピクセルで secondary reference を試す順番。band ごとに band の中央に近い順に並べる
+    secondary_order: Vec<usize>,
+    /// `set_reference` で index を拒否した job なら Some。`validate` でも返す
+    reference_error: Option<JobError>,
+
+    /// `begin_direct` で始めた job なら Some。reference を使わずに z^2 + c をそのまま計算する
+    direct: Option<DirectView>,
 
-/// 1 job 分の入力バッファと計算パラメータ。job をまたいで再利用し、足りないときだけ伸ばす
-struct JobContext {
-    xn: Vec<f64>,
-    bla_bytes: Vec<u8>,
-    bla_row_offsets: Vec<i32>,
     iterations: Vec<u32>,
     scaled_iterations: Vec<u32>,
+    /// 直近の job で iterations キャッシュを書いた格子。次の job に引き継げなければ None
+    cached_grid: Option<CachedGrid>,
+    /// `set_iteration_shift` で指定した、次の job でキャッシュをずらす量
+    pending_shift: Option<(i32, i32)>,
+    /// job 開始時に前の job のキャッシュから引き継いだピクセル数。`import_iterations` の分も含む
+    reused_count: u32,
+    /// `import_iterations` に渡す、前の画像の iteration 数
+    iteration_import: Vec<u32>,
+    /// `calc_pixels` に渡すピクセル座標。(x, y) の順に 2 つずつ並べる
+    pixel_list: Vec<f64>,
+    /// `calc_pixels` の結果。`pixel_list` と同じ順
+    pixel_results: Vec<u32>,
+    /// true なら maxIteration に達したピクセルの状態を残し、maxIteration を上げた次の job で続きから計算する
+    is_continuation_enabled: bool,
+    /// maxIteration に達したピクセルの計算途中の状態。iterations キャッシュと同じ並び
+    continuations: Vec<Continuation>,
+    /// 直近の job が `continuations` を書いたか
+    has_continuations: bool,
 
     max_iteration: u32,
-    max_ref_iteration: u32,
-    bla_rows: i32,
     start_bla_index: i32,
     delta_c_scale: f64,
-    ref_pixel_x: f64,
-    ref_pixel_y: f64,
 
     area_width: u32,
     area_height: u32,
@@ -39,26 +117,371 @@ struct JobContext {
     scaled_width: u32,
     is_super_sampling: bool,
     is_result_pass: bool,
+    /// `begin_sampling_pass` で始めた pass なら Some。band はピクセルの行で指定する
+    sampling: Option<SamplingPass>,
+    /// `begin_refinement_pass` で始めた pass なら Some。band は area のピクセル行で指定する
+    refinement: Option<RefinementPass>,
+    /// refinement pass で各ピクセルに使ったサンプル数。scaled_iterations と同じ並び
+    sample_counts: Vec<u32>,
 
     calculated_count: u32,
     hit_count: u32,
+
+    /// `alloc` で要求されたサイズ。追加出力のバッファを後から確保するときに使う
+    alloc_area_pixels: u32,
+    alloc_scaled_pixels: u32,
+
+    accumulation: AccumulationMode,
+    /// `begin` で解決した集計モード。None なら集計しない
+    resolved_accumulation: Option<ResolvedMode>,
+    accum_values: PixelChannel<f64>,
+    accum_prev_values: PixelChannel<f64>,
+    accum_iterations: PixelChannel<u32>,
+
+    /// None なら内部判定しない
+    interior_detection: Option<InteriorDetection>,
+    /// None なら series approximation を使わない
+    series: Option<SeriesConfig>,
+    /// 1 つのループで同時に進めるピクセル数。1 なら 1 ピクセルずつ計算する
+    interleaved_pixels: u32,
+    /// true なら浅いズームの job で f32 の kernel を使う
+    is_f32_enabled: bool,
+    /// `begin` で決めた、この job を f32 の kernel で計算するか
+    uses_f32: bool,
+    /// band 内のピクセルの計算順。Scan 以外は一部のピクセルを計算せずに埋める
+    render_strategy: RenderStrategy,
+    /// true なら埋めたピクセルも計算して、埋め間違いを数える
+    is_guess_check_enabled: bool,
+    /// job 開始以降に計算せずに埋めたピクセル数
+    guessed_count: u32,
+    /// `guessed_count` のうち、計算した値と違っていたピクセル数。check 有効時のみ数える
+    mistaken_guess_count: u32,
+    interior_periods: PixelChannel<u32>,
+    interior_multipliers: PixelChannel<f64>,
+
+    is_atom_domain_enabled: bool,
+    atom_domains: PixelChannel<u32>,
+
+    /// 0 なら glitch 判定しない
+    glitch_tolerance: f64,
+    /// glitch と判定したピクセルは 1、それ以外は 0
+    glitch_flags: PixelChannel<u8>,
+    glitch_count: u32,
+
+    /// 入力が壊れていて band を計算できない job なら Some
+    job_error: Option<JobError>,
+    /// `get_job_error_message` で返す、直近に JS へ返したエラー
+    last_error: Option<JobError>,
 }
 
-impl JobContext {
-    const fn new() -> Self {
+impl IterationJob {
+    /// iteration 以外の出力が 1 つでも有効か。有効なら band ループで `ExtraKernel` を使う
+    fn has_extra_outputs(&self) -> bool {
+        self.resolved_accumulation.is_some()
+            || self.interior_detection.is_some()
+            || self.is_atom_domain_enabled
+            || self.is_glitch_detection_enabled()
+    }
+
+    fn is_glitch_detection_enabled(&self) -> bool {
+        self.glitch_tolerance > 0.0
+    }
+
+    /// primary reference を f32 の kernel で計算できる job なら `xn_f32` を用意して true を返す。
+    /// `begin` で primary を検証したあとに呼ぶ
+    fn resolve_f32(&mut self) -> bool {
+        let uses_f32 = self.is_f32_enabled
+            && self.delta_c_scale >= fast32::F32_MIN_DELTA_C_SCALE
+            && !self.has_extra_outputs()
+            && !self.is_continuation_enabled
+            && self.primary.fits_f32();
+        if uses_f32 {
+            self.primary.rebuild_xn_f32();
+        }
+        uses_f32
+    }
+
+    /// area の四隅と各辺の中点を probe 点にして primary reference の級数を求める
+    fn build_series(&self, config: SeriesConfig) -> Option<SeriesApproximation> {
+        let reference = &self.primary;
+        let x0 = self.area_start_x as f64;
+        let y0 = self.area_start_y as f64;
+        let w = self.area_width as f64;
+        let h = self.area_height as f64;
+        let mut probes = Vec::with_capacity(8);
+        for (fx, fy) in [
+            (0.0, 0.0),
+            (0.5, 0.0),
+            (1.0, 0.0),
+            (0.0, 0.5),
+            (1.0, 0.5),
+            (0.0, 1.0),
+            (0.5, 1.0),
+            (1.0, 1.0),
+        ] {
+            let delta_c_re = (x0 + w * fx - reference.ref_pixel_x) * self.delta_c_scale;
+            let delta_c_im = -(y0 + h * fy - reference.ref_pixel_y) * self.delta_c_scale;
+            probes.push((delta_c_re, delta_c_im));
+        }
+        SeriesApproximation::build(
+            config,
+            &reference.xn,
+            reference.max_ref_iteration,
+            self.max_iteration,
+            &probes,
+        )
+    }
+
+    /// 座標の列をまとめて計算するときの kernel。`calc_band` の振り分けと同じ順に選ぶ
+    fn points_kernel(&self) -> PointsKernel {
+        match self.interleaved_pixels {
+            _ if self.direct.is_some() => calc_points_with::<DirectKernel>,
+            _ if self.has_extra_outputs() => calc_points_with::<ExtraKernel>,
+            _ if self.uses_f32 => fast32::calc_iterations_f32x4,
+            2 => interleave::calc_iterations_interleaved::<2>,
+            3 => interleave::calc_iterations_interleaved::<3>,
+            4 => interleave::calc_iterations_interleaved::<4>,
+            _ if simd::IS_ENABLED => simd::calc_iterations_x2,
+            _ => calc_points_with::<PlainKernel>,
+        }
+    }
+
+    /// `begin` / `begin_direct` に共通する job の初期化。前の job の mode とエラーを捨てる。
+    /// iterations キャッシュは最後に `prepare_iterations` で用意する
+    fn reset_job(
+        &mut self,
+        max_iteration: u32,
+        delta_c_scale: f64,
+        area_width: u32,
+        area_height: u32,
+        area_start_x: i32,
+        area_start_y: i32,
+    ) {
+        self.max_iteration = max_iteration;
+        self.delta_c_scale = delta_c_scale;
+        self.area_width = area_width;
+        self.area_height = area_height;
+        self.area_start_x = area_start_x;
+        self.area_start_y = area_start_y;
+        self.direct = None;
+        self.secondary_count = 0;
+        self.secondary_order.clear();
+        self.reference_error = None;
+        self.job_error = None;
+        self.last_error = None;
+        self.uses_f32 = false;
+
+        self.calculated_count = 0;
+        self.guessed_count = 0;
+        self.mistaken_guess_count = 0;
+        self.reused_count = 0;
+    }
+
+    /// area 座標の追加出力キャッシュ (`PixelChannel::area`) を使う job か。
+    /// direct mode の job は追加出力を書かない
+    fn uses_area_channels(&self) -> bool {
+        self.direct.is_none()
+            && (self.has_extra_outputs()
+                || self.is_atom_domain_enabled
+                || self.is_glitch_detection_enabled())
+    }
+
+    /// maxIteration に達したピクセルの状態を `continuations` に読み書きする job か。
+    /// direct mode と追加出力のある job では使わない
+    fn uses_continuations(&self) -> bool {
+        self.is_continuation_enabled && self.direct.is_none() && !self.has_extra_outputs()
+    }
+
+    /// `begin` / `begin_direct` の最後に呼ぶ。
+    ///
+    /// 前の job と同じ格子で maxIteration だけを上げた job なら、maxIteration に達したピクセルだけを
+    /// 未計算に戻して続きから計算させる。`set_iteration_shift` の移動が前の job の格子と
+    /// 合っていればキャッシュをずらして引き継ぐ。どちらでもなければ 0 クリアする
+    fn prepare_iterations(&mut self, anchor: GridAnchor) {
+        let grid = CachedGrid {
+            max_iteration: self.max_iteration,
+            delta_c_scale: self.delta_c_scale,
+            area_width: self.area_width,
+            area_height: self.area_height,
+            area_start_x: self.area_start_x,
+            area_start_y: self.area_start_y,
+            uses_f32: self.uses_f32,
+            anchor,
+        };
+        let shift = self.pending_shift.take();
+        let area_pixels = (self.area_width as usize) * (self.area_height as usize);
+        if area_pixels > self.iterations.len() {
+            // キャッシュを確保していない job。前の job の値も残らない
+            self.cached_grid = None;
+            self.has_continuations = false;
+            return;
+        }
+
+        let uses_continuations = self.uses_continuations();
+        let continued_from = self.cached_grid.filter(|prev| {
+            uses_continuations && self.has_continuations && grid.is_continued_from(prev)
+        });
+        if uses_continuations {
+            ensure_len(&mut self.continuations, area_pixels);
+        }
+        self.has_continuations = uses_continuations;
+        if let Some(prev) = continued_from {
+            // bailout 済みのピクセルは maxIteration を上げても変わらないので、そのまま使う。
+            // maxIteration に達したピクセルは状態があればその続きから、なければ最初から計算する
+            for n in &mut self.iterations[..area_pixels] {
+                if *n >= prev.max_iteration {
+                    *n = 0;
+                }
+            }
+            self.reused_count = self.iterations[..area_pixels]
+                .iter()
+                .filter(|&&n| n != 0)
+                .count() as u32;
+            self.calculated_count = self.reused_count;
+            self.cached_grid = Some(grid);
+            return;
+        }
+        if uses_continuations {
+            self.continuations[..area_pixels].fill(Continuation::default());
+        }
+
+        // 追加出力のキャッシュはずらさないので、それを使う job どうしでは引き継がない
+        let uses_area_channels = self.uses_area_channels();
+        let shift = shift.filter(|&(dx, dy)| {
+            !uses_area_channels
+                && self
+                    .cached_grid
+                    .is_some_and(|prev| grid.is_shifted_from(&prev, dx, dy))
+        });
+        let iterations = &mut self.iterations[..area_pixels];
+        match shift {
+            Some((dx, dy)) => {
+                shift_grid(iterations, self.area_width, self.area_height, dx, dy);
+                self.reused_count = iterations.iter().filter(|&&n| n != 0).count() as u32;
+                // 引き継いだピクセルも progress の分母に入っているので計算済みとして数える
+                self.calculated_count = self.reused_count;
+            }
+            None => iterations.fill(0),
+        }
+        self.cached_grid = (!uses_area_channels).then_some(grid);
+    }
+
+    /// job を計算できない状態にする。最初のエラーを残す
+    fn fail(&mut self, error: JobError) {
+        self.job_error.get_or_insert(error);
+        self.last_error = Some(error);
+    }
+
+    /// primary と使用中の secondary reference を検証する
+    fn validate_references(&self) -> Result<(), JobError> {
+        self.reference_error.map_or(Ok(()), Err)?;
+        self.primary.validate(0, self.start_bla_index)?;
+        for (i, reference) in self.secondary_references[..self.secondary_count]
+            .iter()
+            .enumerate()
+        {
+            reference.validate(i as u32 + 1, self.start_bla_index)?;
+        }
+        Ok(())
+    }
+
+    /// band が pass 出力と iterations キャッシュの範囲に収まるか確かめる
+    fn validate_band(
+        &self,
+        band_scaled_y_from: u32,
+        band_scaled_y_to: u32,
+    ) -> Result<(), JobError> {
+        if band_scaled_y_from >= band_scaled_y_to || self.scaled_width == 0 {
+            return Ok(());
+        }
+        if let Some(sampling) = &self.sampling {
+            if band_scaled_y_to > self.area_height {
+                return Err(JobError::AreaOutOfBounds);
+            }
+            let scaled_pixels = sampling.scaled_pixels(self.area_width, band_scaled_y_to);
+            if scaled_pixels > self.alloc_scaled_pixels as u64 {
+                return Err(JobError::BandOutOfBounds);
+            }
+            return Ok(());
+        }
+        let scaled_pixels = band_scaled_y_to as u64 * self.scaled_width as u64;
+        if scaled_pixels > self.alloc_scaled_pixels as u64 {
+            return Err(JobError::BandOutOfBounds);
+        }
+        if self.is_super_sampling {
+            return Ok(());
+        }
+
+        // calc_band と同じ式で、band 内の最大の area 座標がキャッシュに収まるか見る
+        let area_pixels = self.area_width as u64 * self.area_height as u64;
+        let max_x = (self.scaled_width - 1) as f64 * self.x_diff;
+        let max_y = (band_scaled_y_to - 1) as f64 * self.y_diff;
+        let is_inside = max_x < self.area_width as f64 && max_y < self.area_height as f64;
+        if !is_inside || area_pixels > self.alloc_area_pixels as u64 {
+            return Err(JobError::AreaOutOfBounds);
+        }
+        Ok(())
+    }
+
+    /// index 0 は primary、1 以降は secondary。足りなければ secondary を伸ばす。
+    /// `MAX_SECONDARY_REFERENCES` を超える index では None
+    fn reference_mut(&mut self, index: u32) -> Option<&mut Reference> {
+        if index == 0 {
+            return Some(&mut self.primary);
+        }
+        if index > MAX_SECONDARY_REFERENCES {
+            return None;
+        }
+        let secondary_index = (index - 1) as usize;
+        while self.secondary_references.len() <= secondary_index {
+            self.secondary_references.push(Reference::new());
+        }
+        Some(&mut self.secondary_references[secondary_index])
+    }
+
+    /// glitch したピクセルで secondary reference を試す順番を、band の中央のピクセルに近い順に並べる
+    fn order_secondary_references(&mut self, band_y_from: u32, band_y_to: u32) {
+        // supersampling / refinement の pass の band は area のピクセル行で指定する
+        let row_scale = if self.sampling.is_some() || self.refinement.is_some() {
+            1.0
+        } else {
+            self.y_diff
+        };
+        let x = self.area_start_x as f64 + self.area_width as f64 / 2.0;
+        let y = self.area_start_y as f64 + (band_y_from + band_y_to) as f64 / 2.0 * row_scale;
+        let references = &self.secondary_references[..self.secondary_count];
+        self.secondary_order.clear();
+        self.secondary_order.extend(0..references.len());
+        self.secondary_order.sort_by(|&a, &b| {
+            let da = references[a].pixel_distance_sq(x, y);
+            let db = references[b].pixel_distance_sq(x, y);
+            da.total_cmp(&db)
+        });
+    }
+
+    /// thread_local の初期化にも使うので const
+    const fn empty() -> Self {
         Self {
-            xn: Vec::new(),
-            bla_bytes: Vec::new(),
-            bla_row_offsets: Vec::new(),
+            primary: Reference::new(),
+            secondary_references: Vec::new(),
+            secondary_count: 0,
+            secondary_order: Vec::new(),
+            reference_error: None,
+            direct: None,
             iterations: Vec::new(),
             scaled_iterations: Vec::new(),
+            cached_grid: None,
+            pending_shift: None,
+            reused_count: 0,
+            iteration_import: Vec::new(),
+            pixel_list: Vec::new(),
+            pixel_results: Vec::new(),
+            is_continuation_enabled: false,
+            continuations: Vec::new(),
+            has_continuations: false,
             max_iteration: 0,
-            max_ref_iteration: 0,
-            bla_rows: 0,
             start_bla_index: 0,
             delta_c_scale: 0.0,
-            ref_pixel_x: 0.0,
-            ref_pixel_y: 0.0,
             area_width: 0,
             area_height: 0,
             area_start_x: 0,
@@ -68,38 +491,56 @@ impl JobContext {
             scaled_width: 0,
             is_super_sampling: false,
             is_result_pass: false,
+            sampling: None,
+            refinement: None,
+            sample_counts: Vec::new(),
             calculated_count: 0,
             hit_count: 0,
+            alloc_area_pixels: 0,
+            alloc_scaled_pixels: 0,
+            accumulation: AccumulationMode::None,
+            resolved_accumulation: None,
+            accum_values: PixelChannel::new(),
+            accum_prev_values: PixelChannel::new(),
+            accum_iterations: PixelChannel::new(),
+            interior_detection: None,
+            series: None,
+            interleaved_pixels: 1,
+            is_f32_enabled: false,
+            uses_f32: false,
+            render_strategy: RenderStrategy::Scan,
+            is_guess_check_enabled: false,
+            guessed_count: 0,
+            mistaken_guess_count: 0,
+            interior_periods: PixelChannel::new(),
+            interior_multipliers: PixelChannel::new(),
+            is_atom_domain_enabled: false,
+            atom_domains: PixelChannel::new(),
+            glitch_tolerance: 0.0,
+            glitch_flags: PixelChannel::new(),
+            glitch_count: 0,
+            job_error: None,
+            last_error: None,
         }
     }
 }
 
-thread_local! {
-    static JOB: RefCell<JobContext> = const { RefCell::new(JobContext::new()) };
-}
-
-/// JobContext を可変で借りて処理を行う
-fn with_job<R>(f: impl FnOnce(&mut JobContext) -> R) -> R {
-    JOB.with(|job| f(&mut job.borrow_mut()))
-}
-
 /// Vec を最低 len 要素まで伸ばす。既に足りている場合は縮めない (job をまたいだ再利用のため)
-fn ensure_len<T: Clone + Default>(v: &mut Vec<T>, len: usize) {
+pub(crate) fn ensure_len<T: Clone + Default>(v: &mut Vec<T>, len: usize) {
     if v.len() < len {
         v.resize(len, T::default());
     }
 }
 
-/// little-endian の f64 をバイト列から読む
-#[inline(always)]
-fn read_f64(bytes: &[u8], offset: usize) -> f64 {
-    f64::from_le_bytes(bytes[offset..offset + 8].try_into().unwrap())
+/// `ensure_len` で伸ばしたままの Vec を len 要素まで縮めて、余った容量を allocator に返す
+pub(crate) fn shrink_len<T>(v: &mut Vec<T>, len: usize) {
+    v.truncate(len);
+    v.shrink_to_fit();
 }
 
-/// little-endian の i32 をバイト列から読む
-#[inline(always)]
-fn read_i32(bytes: &[u8], offset: usize) -> i32 {
-    i32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+/// Vec が確保している容量のバイト数
+pub(crate) fn capacity_bytes<T>(v: &Vec<T>) -> usize {
+    v.capacity() * size_of::<T>()
 }
 
 #[inline(always)]
@@ -117,289 +558,2923 @@ fn n_norm(re: f64, im: f64) -> f64 {
     re * re + im * im
 }
 
-/// 入力バッファを確保する。このあと `xn_ptr` などでポインタを取得して JS 側からコピーする。
-///
-/// `area_pixels` に 0 を渡すと iterations キャッシュを確保しない (supersampling 時に使う)。
 #[wasm_bindgen]
-pub fn alloc_job(
-    xn_f64_len: u32,
-    bla_bytes_len: u32,
-    bla_row_offsets_len: u32,
-    area_pixels: u32,
-    max_scaled_pixels: u32,
-) {
-    with_job(|job| {
-        ensure_len(&mut job.xn, xn_f64_len as usize);
-        ensure_len(&mut job.bla_bytes, bla_bytes_len as usize);
-        ensure_len(&mut job.bla_row_offsets, bla_row_offsets_len as usize);
-        ensure_len(&mut job.iterations, area_pixels as usize);
-        ensure_len(&mut job.scaled_iterations, max_scaled_pixels as usize);
-    });
-}
+impl IterationJob {
+    /// 空の job を作る。バッファは `alloc` で確保する
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Self {
+        Self::empty()
+    }
 
-#[wasm_bindgen]
-pub fn xn_ptr() -> *mut f64 {
-    with_job(|job| job.xn.as_mut_ptr())
-}
+    /// 入力バッファを確保する。このあと `xn_ptr` などでポインタを取得して JS 側からコピーする。
+    ///
+    /// `area_pixels` に 0 を渡すと iterations キャッシュを確保しない (supersampling 時に使う)。
+    pub fn alloc(
+        &mut self,
+        xn_f64_len: u32,
+        bla_bytes_len: u32,
+        bla_row_offsets_len: u32,
+        area_pixels: u32,
+        max_scaled_pixels: u32,
+    ) {
+        self.primary
+            .alloc(xn_f64_len, bla_bytes_len, bla_row_offsets_len);
+        ensure_len(&mut self.iterations, area_pixels as usize);
+        ensure_len(&mut self.scaled_iterations, max_scaled_pixels as usize);
+        self.alloc_area_pixels = area_pixels;
+        self.alloc_scaled_pixels = max_scaled_pixels;
+    }
 
-#[wasm_bindgen]
-pub fn bla_bytes_ptr() -> *mut u8 {
-    with_job(|job| job.bla_bytes.as_mut_ptr())
-}
+    /// 確保したバッファをすべて手放す。設定 (`set_*`) は残る。
+    /// 次に使うときは `alloc` からやり直す
+    pub fn release(&mut self) {
+        self.primary = Reference::new();
+        self.secondary_references = Vec::new();
+        self.secondary_count = 0;
+        self.secondary_order = Vec::new();
+        self.reference_error = None;
+        self.iterations = Vec::new();
+        self.scaled_iterations = Vec::new();
+        self.cached_grid = None;
+        self.iteration_import = Vec::new();
+        self.pixel_list = Vec::new();
+        self.pixel_results = Vec::new();
+        self.continuations = Vec::new();
+        self.has_continuations = false;
+        self.sample_counts = Vec::new();
+        self.alloc_area_pixels = 0;
+        self.alloc_scaled_pixels = 0;
+        self.accum_values = PixelChannel::new();
+        self.accum_prev_values = PixelChannel::new();
+        self.accum_iterations = PixelChannel::new();
+        self.interior_periods = PixelChannel::new();
+        self.interior_multipliers = PixelChannel::new();
+        self.atom_domains = PixelChannel::new();
+        self.glitch_flags = PixelChannel::new();
+        self.calculated_count = 0;
+        self.job_error = None;
+        self.last_error = None;
+    }
 
-#[wasm_bindgen]
-pub fn bla_row_offsets_ptr() -> *mut i32 {
-    with_job(|job| job.bla_row_offsets.as_mut_ptr())
-}
+    /// バッファを直近の `alloc` / `begin` で必要とされたサイズまで縮める。
+    ///
+    /// `alloc` はバッファを伸ばすだけなので、大きな job のあとはその分の容量が残り続ける。
+    /// 縮めた分は allocator に返るが、wasm の linear memory 自体は縮まないので、
+    /// memory ごと手放したい場合は worker を作り直す
+    pub fn shrink(&mut self) {
+        self.primary.shrink();
+        self.secondary_references.truncate(self.secondary_count);
+        self.secondary_references.shrink_to_fit();
+        for reference in &mut self.secondary_references {
+            reference.shrink();
+        }
 
-#[wasm_bindgen]
-pub fn scaled_iterations_ptr() -> *mut u32 {
-    with_job(|job| job.scaled_iterations.as_mut_ptr())
-}
+        let area = self.alloc_area_pixels as usize;
+        let scaled = self.alloc_scaled_pixels as usize;
+        shrink_len(&mut self.iterations, area);
+        shrink_len(&mut self.scaled_iterations, scaled);
+        let sample_counts_len = if self.refinement.is_some() { scaled } else { 0 };
+        shrink_len(&mut self.sample_counts, sample_counts_len);
+        // 取り込みは job の開始時だけなので、取り込んだあとは手放してよい
+        shrink_len(&mut self.iteration_import, 0);
+        shrink_len(&mut self.pixel_list, 0);
+        shrink_len(&mut self.pixel_results, 0);
+        let continuations_len = if self.has_continuations { area } else { 0 };
+        shrink_len(&mut self.continuations, continuations_len);
 
-/// job 全体のパラメータを確定する。iterations キャッシュはここで 0 クリアされる。
-#[wasm_bindgen]
-#[allow(clippy::too_many_arguments)]
-pub fn begin_iteration_job(
-    max_iteration: u32,
-    max_ref_iteration: u32,
-    bla_rows: u32,
-    start_bla_index: u32,
-    delta_c_scale: f64,
-    ref_pixel_x: f64,
-    ref_pixel_y: f64,
-    area_width: u32,
-    area_height: u32,
-    area_start_x: i32,
-    area_start_y: i32,
-) {
-    with_job(|job| {
-        job.max_iteration = max_iteration;
-        job.max_ref_iteration = max_ref_iteration;
-        job.bla_rows = bla_rows as i32;
-        job.start_bla_index = start_bla_index as i32;
-        job.delta_c_scale = delta_c_scale;
-        job.ref_pixel_x = ref_pixel_x;
-        job.ref_pixel_y = ref_pixel_y;
-        job.area_width = area_width;
-        job.area_height = area_height;
-        job.area_start_x = area_start_x;
-        job.area_start_y = area_start_y;
+        // この job で使っていない追加出力は長さ 0 まで縮めて手放す
+        let lengths = |is_used: bool| if is_used { (area, scaled) } else { (0, 0) };
+        let (a, s) = lengths(self.resolved_accumulation.is_some());
+        self.accum_values.shrink(a, s);
+        self.accum_prev_values.shrink(a, s);
+        self.accum_iterations.shrink(a, s);
+        let (a, s) = lengths(self.interior_detection.is_some());
+        self.interior_periods.shrink(a, s);
+        self.interior_multipliers.shrink(a, s);
+        let (a, s) = lengths(self.is_atom_domain_enabled);
+        self.atom_domains.shrink(a, s);
+        let (a, s) = lengths(self.is_glitch_detection_enabled());
+        self.glitch_flags.shrink(a, s);
+    }
 
-        let area_pixels = (area_width as usize) * (area_height as usize);
-        if area_pixels <= job.iterations.len() {
-            job.iterations[..area_pixels].fill(0);
-        }
-        job.calculated_count = 0;
-    });
-}
+    /// 確保しているバッファの合計バイト数 (Vec の容量ベース)
+    pub fn buffer_bytes(&self) -> usize {
+        let references: usize = self
+            .secondary_references
+            .iter()
+            .map(Reference::capacity_bytes)
+            .sum();
+        self.primary.capacity_bytes()
+            + references
+            + capacity_bytes(&self.iterations)
+            + capacity_bytes(&self.scaled_iterations)
+            + capacity_bytes(&self.sample_counts)
+            + capacity_bytes(&self.iteration_import)
+            + capacity_bytes(&self.pixel_list)
+            + capacity_bytes(&self.pixel_results)
+            + capacity_bytes(&self.continuations)
+            + self.accum_values.capacity_bytes()
+            + self.accum_prev_values.capacity_bytes()
+            + self.accum_iterations.capacity_bytes()
+            + self.interior_periods.capacity_bytes()
+            + self.interior_multipliers.capacity_bytes()
+            + self.atom_domains.capacity_bytes()
+            + self.glitch_flags.capacity_bytes()
+    }
 
-/// job 開始以降に実際に計算したピクセル数を返す。JS 側の progress 表示に使う。
-#[wasm_bindgen]
-pub fn get_calculated_count() -> u32 {
-    with_job(|job| job.calculated_count)
-}
+    pub fn xn_ptr(&mut self) -> *mut f64 {
+        self.primary.xn.as_mut_ptr()
+    }
 
-/// 直近の pass で iteration が maxIteration に達したピクセル数を返す。
-/// `is_result_pass` を立てた pass でのみ数えている。
-#[wasm_bindgen]
-pub fn get_hit_count() -> u32 {
-    with_job(|job| job.hit_count)
-}
+    /// ポインタを渡したあとは JS が書き換えるものとして、次の `begin` で BLA の表を作り直す
+    pub fn bla_bytes_ptr(&mut self) -> *mut u8 {
+        self.primary.invalidate_bla();
+        self.primary.bla_bytes.as_mut_ptr()
+    }
 
-/// 1 pass 分のパラメータを設定する。hit count はここでリセットされる。
-#[wasm_bindgen]
-pub fn begin_pass(
-    x_diff: f64,
-    y_diff: f64,
-    scaled_width: u32,
-    is_super_sampling: bool,
-    is_result_pass: bool,
-) {
-    with_job(|job| {
-        job.x_diff = x_diff;
-        job.y_diff = y_diff;
-        job.scaled_width = scaled_width;
-        job.is_super_sampling = is_super_sampling;
-        job.is_result_pass = is_result_pass;
-        job.hit_count = 0;
-    });
-}
+    /// `bla_bytes_ptr` と同じく、次の `begin` で BLA の表を作り直す
+    pub fn bla_row_offsets_ptr(&mut self) -> *mut i32 {
+        self.primary.invalidate_bla();
+        self.primary.bla_row_offsets.as_mut_ptr()
+    }
 
-/// 1 ピクセル分の iteration を計算する (perturbation + BLA + rebase)。
-///
-/// JS 版 `calcIterationAt` の移植。計算順序を変えると結果が変わるのでそのまま維持している。
-fn calc_iteration_at(job: &JobContext, pixel_x: f64, pixel_y: f64) -> u32 {
-    let max_iteration = job.max_iteration;
-    let max_ref_iteration = job.max_ref_iteration;
-    let bla_rows = job.bla_rows;
-    let start_bla_index = job.start_bla_index;
-    let xn_raw = &job.xn;
-    let bla_bytes = &job.bla_bytes;
-    let row_offsets = &job.bla_row_offsets;
+    pub fn scaled_iterations_ptr(&mut self) -> *mut u32 {
+        self.scaled_iterations.as_mut_ptr()
+    }
 
-    // Δn
-    let mut delta_n_re = 0.0f64;
-    let mut delta_n_im = 0.0f64;
+    /// refinement pass で各ピクセルに使ったサンプル数
+    pub fn sample_counts_ptr(&mut self) -> *mut u32 {
+        self.sample_counts.as_mut_ptr()
+    }
 
-    // Δc = (pixel - refPixel) * deltaCScale。cx と W/2 が相殺されるので double だけで出せる
-    let delta_c_re = (pixel_x - job.ref_pixel_x) * job.delta_c_scale;
-    let delta_c_im = -(pixel_y - job.ref_pixel_y) * job.delta_c_scale;
+    /// orbit trap による集計を有効にする。`begin` より前に呼ぶ。
+    /// 集計モードは 1 つだけで、`set_average_coloring` とは後から呼んだ方が有効になる。
+    ///
+    /// `shape` は 0: point, 1: cross, 2: line, 3: circle。中心は reference の c からの相対座標で、
+    /// `param` は line なら偏角 [rad]、circle なら半径 (それ以外では使わない)。
+    /// 結果は `accum_values_ptr` に最小距離、`accum_iterations_ptr` にそのときの iteration が入る。
+    /// 未知の `shape` を渡した場合は何もせず false を返す。
+    pub fn set_orbit_trap(
+        &mut self,
+        shape: u32,
+        offset_re: f64,
+        offset_im: f64,
+        param: f64,
+    ) -> bool {
+        let Some(shape) = TrapShape::from_u32(shape) else {
+            return false;
+        };
+        self.accumulation =
+            AccumulationMode::OrbitTrap(OrbitTrap::new(shape, offset_re, offset_im, param));
+        true
+    }
 
-    let mut iteration: u32 = 0;
-    let mut ref_iteration: u32 = 0;
+    /// 平均系の色付け (TIA / curvature average) による集計を有効にする。`begin` より前に呼ぶ。
+    ///
+    /// `kind` は 0: triangle inequality average, 1: curvature average。
+    /// `accum_values_ptr` に全項の平均、`accum_prev_values_ptr` に最後の項を除いた平均、
+    /// `accum_iterations_ptr` に項数が入る。前 2 つを smooth iteration の小数部で補間して使う。
+    /// 未知の `kind` を渡した場合は何もせず false を返す。
+    pub fn set_average_coloring(&mut self, kind: u32) -> bool {
+        let Some(kind) = AverageKind::from_u32(kind) else {
+            return false;
+        };
+        self.accumulation = AccumulationMode::Average(kind);
+        true
+    }
 
-    while iteration < max_iteration {
-        let ref_idx2 = (ref_iteration as usize) * 2;
-        let x_re = xn_raw[ref_idx2];
-        let x_im = xn_raw[ref_idx2 + 1];
-        let z_re = x_re + delta_n_re;
-        let z_im = x_im + delta_n_im;
-        let z_norm = n_norm(z_re, z_im);
-        if z_norm > BAILOUT_RADIUS {
-            break;
+    /// 集計を無効にする。次の `begin` から反映される
+    pub fn clear_accumulation(&mut self) {
+        self.accumulation = AccumulationMode::None;
+    }
+
+    /// 集計結果の値 (f64) の pass 出力。集計が有効な job の `begin` 以降に取得すること
+    pub fn accum_values_ptr(&mut self) -> *mut f64 {
+        self.accum_values.scaled.as_mut_ptr()
+    }
+
+    /// 平均系の集計で、最後の項を除いた平均 (f64) の pass 出力。取得タイミングは `accum_values_ptr` と同じ
+    pub fn accum_prev_values_ptr(&mut self) -> *mut f64 {
+        self.accum_prev_values.scaled.as_mut_ptr()
+    }
+
+    /// 集計結果の iteration (u32) の pass 出力。取得タイミングは `accum_values_ptr` と同じ
+    pub fn accum_iterations_ptr(&mut self) -> *mut u32 {
+        self.accum_iterations.scaled.as_mut_ptr()
+    }
+
+    /// series approximation による初期スキップを設定する。`begin` より前に呼ぶ。
+    ///
+    /// `terms` は級数の項数 (0 で無効、上限 64)。`begin` で係数を 1 回だけ求め、
+    /// area の四隅と各辺の中点に置いた probe 点で相対誤差が `tolerance` 以内に収まる iteration まで
+    /// 全ピクセルの計算を飛ばす。スキップ先は `series_skip` で取得できる。
+    /// スキップは iteration 数にだけ効く。orbit trap / average coloring、内部判定、atom domain、
+    /// glitch 判定のいずれかが有効な job ではスキップした iteration の z が必要なので使われない。
+    pub fn set_series_approximation(&mut self, terms: u32, tolerance: f64) {
+        self.series = SeriesConfig::new(terms, tolerance);
+    }
+
+    /// 依存のない複数のピクセルを 1 つのループで交互に進める数を設定する (1〜4、既定は 1)。
+    ///
+    /// 結果は 1 ピクセルずつ計算した場合と変わらない。速くなるかは CPU と経路次第なので、
+    /// 2 以上にするのはベンチで確かめてから。追加出力のある job では使われない。
+    /// simd128 のビルドで 1 のときは 2 ピクセル同時の simd kernel を使う。
+    pub fn set_interleaved_pixels(&mut self, count: u32) {
+        self.interleaved_pixels = count.clamp(1, interleave::MAX_INTERLEAVED_PIXELS);
+    }
+
+    /// 浅いズームで f32 の kernel を使うかを設定する (既定は無効)。`begin` より前に呼ぶ。
+    ///
+    /// 結果が f64 とビット単位では一致しなくなるので、使う側 (worker) で明示的に有効にする。
+    /// 有効にすると job ごとに自動で選ぶ。Δc のスケールが小さい job、reference の orbit が f32 で表せない job
+    /// (`fast32` を参照) と追加出力のある job では f64 で計算する。
+    /// f32 で精度が足りなくなったピクセルは f64 で計算し直すので、結果はほぼ f64 と同じになる。
+    pub fn set_f32_fast_path(&mut self, enabled: bool) {
+        self.is_f32_enabled = enabled;
+    }
+
+    /// band 内のピクセルの計算順を設定する (既定は 0)。
+    ///
+    /// 0: 全ピクセルを計算する。
+    /// 1: Mariani–Silver の矩形分割。矩形の周囲を計算し、全部同じ iteration 数なら内側をその値で埋める。
+    /// 2: boundary tracing。iteration 数の境界をたどって計算し、囲まれた内側を埋める。
+    /// 1 と 2 は細い構造を埋め間違えることがあるので、結果は 1 ピクセルずつ計算した場合と
+    /// 一致するとは限らない。band の行数が多いほど効き、1 行の band では何も埋めない。
+    /// 追加出力のある job と supersampling の pass (`begin_sampling_pass` など) では 0 として扱う。
+    /// 未知の値を渡した場合は何もせず false を返す。
+    pub fn set_render_strategy(&mut self, strategy: u32) -> bool {
+        let Some(strategy) = RenderStrategy::from_u32(strategy) else {
+            return false;
+        };
+        self.render_strategy = strategy;
+        true
+    }
+
+    /// `set_render_strategy` で埋めたピクセルも計算して答え合わせするかを設定する (既定は無効)。
+    ///
+    /// デバッグ用。有効なら出力は計算した値になり、違っていた数を `mistaken_guess_count` で返す。
+    /// 計算量は全ピクセルを計算する場合と同じになる。
+    pub fn set_guess_check(&mut self, enabled: bool) {
+        self.is_guess_check_enabled = enabled;
+    }
+
+    /// 次の `begin` / `begin_direct` で、前の job の iterations キャッシュを (dx, dy) ピクセル
+    /// ずらして引き継ぐ。前の job のピクセル (x, y) の値が次の job の (x + dx, y + dy) に入る。
+    ///
+    /// maxIteration、Δc のスケール、area、reference (direct mode では c の位置) が前の job を
+    /// ちょうど (dx, dy) ずらしたものになっている場合だけ引き継ぎ、新しく見えた行と列を
+    /// 未計算にする。それ以外の場合と、追加出力を使う job では通常通り 0 クリアする。
+    /// 指定は次の job 1 回だけに効く。引き継いだピクセル数は `reused_count` で返す。
+    pub fn set_iteration_shift(&mut self, dx: i32, dy: i32) {
+        self.pending_shift = Some((dx, dy));
+    }
+
+    /// maxIteration に達したピクセルの計算途中の状態を残すかを設定する (既定は無効)。`begin` より前に呼ぶ。
+    ///
+    /// 有効な job のあと、同じ reference (同じ c で、orbit は伸ばしてもよい)・同じ area・同じスケールで
+    /// maxIteration だけを上げた job を始めると、bailout 済みのピクセルはキャッシュの値をそのまま使い、
+    /// maxIteration に達したピクセルは止まったところから計算を続ける。
+    /// 引き継いだピクセル数は `reused_count` で返す。
+    /// 状態は 1 ピクセルずつ計算する kernel で読み書きするので、有効な job では f32 / simd /
+    /// interleave の kernel と `set_render_strategy` の 1, 2 は使わない。
+    /// direct mode の job と追加出力のある job では何もしない。
+    pub fn set_continuation(&mut self, enabled: bool) {
+        self.is_continuation_enabled = enabled;
+    }
+
+    /// 直近の `begin` で f32 の kernel を使うと決まったか
+    pub fn uses_f32(&self) -> bool {
+        self.uses_f32
+    }
+
+    /// 直近の `begin` で決まった series approximation のスキップ先 iteration。使っていなければ 0。
+    /// 追加出力のある job では求めても使われない
+    pub fn series_skip(&self) -> u32 {
+        self.primary
+            .series
+            .as_ref()
+            .map_or(0, |series| series.skip())
+    }
+
+    /// 内部判定を設定する。`begin` より前に呼ぶ。
+    ///
+    /// `flags` は bit 0: periodicity (Brent 法で z の周期を検出)、bit 1: derivative (dz/dz が十分小さい)。
+    /// 0 を渡すと無効になる。両方立てると periodicity は |multiplier| < 1 も満たすときだけ採用する。
+    /// `periodicity_epsilon_sq` は |z_n - z_m|² の閾値、`derivative_epsilon_sq` は |dz/dz|² の閾値。
+    ///
+    /// 内部と判定したピクセルは maxIteration として扱い、`interior_periods_ptr` に周期が入る
+    /// (内部でなければ 0、derivative 判定だけで周期が分からなければ u32::MAX)。
+    /// `interior_multipliers_ptr` には周期軌道の |multiplier| が入る (内部でなければ 0、周期が分からなければ NaN)。
+    pub fn set_interior_detection(
+        &mut self,
+        flags: u32,
+        periodicity_epsilon_sq: f64,
+        derivative_epsilon_sq: f64,
+    ) {
+        self.interior_detection =
+            InteriorDetection::new(flags, periodicity_epsilon_sq, derivative_epsilon_sq);
+    }
+
+    /// 内部判定で検出した周期 (u32) の pass 出力。内部判定が有効な job の `begin` 以降に取得すること
+    pub fn interior_periods_ptr(&mut self) -> *mut u32 {
+        self.interior_periods.scaled.as_mut_ptr()
+    }
+
+    /// 内部判定で検出した周期軌道の |multiplier| (f64) の pass 出力。取得タイミングは `interior_periods_ptr` と同じ
+    pub fn interior_multipliers_ptr(&mut self) -> *mut f64 {
+        self.interior_multipliers.scaled.as_mut_ptr()
+    }
+
+    /// atom domain (|z|² が最小になった iteration) の出力を有効/無効にする。`begin` より前に呼ぶ。
+    ///
+    /// 結果は `atom_domains_ptr` に入る。z_0 は数えないので、bailout までに 1 度も観測しなければ 0。
+    pub fn set_atom_domain(&mut self, enabled: bool) {
+        self.is_atom_domain_enabled = enabled;
+    }
+
+    /// atom domain (u32) の pass 出力。atom domain が有効な job の `begin` 以降に取得すること
+    pub fn atom_domains_ptr(&mut self) -> *mut u32 {
+        self.atom_domains.scaled.as_mut_ptr()
+    }
+
+    /// glitch 判定を設定する。`begin` より前に呼ぶ。
+    ///
+    /// いずれかの iteration で |z|² < `tolerance` * |Δ|² となったピクセルを glitch とみなす
+    /// (Pauldelbrot の判定を rebase 前提に読み替えたもの。|z| / |Δ| < 1e-3 なら 1e-6 を渡す)。
+    /// 0 以下を渡すと無効になる。結果は `glitch_flags_ptr` と `glitch_count` で取得する。
+    /// `set_reference` で reference を追加してあれば、glitch したピクセルは近い reference から順に
+    /// 計算し直され、どれでも glitch しなかった場合だけ glitch として残る。
+    pub fn set_glitch_detection(&mut self, tolerance: f64) {
+        self.glitch_tolerance = tolerance.max(0.0);
+    }
+
+    /// glitch 判定の結果 (u8、glitch なら 1) の pass 出力。glitch 判定が有効な job の `begin` 以降に取得すること
+    pub fn glitch_flags_ptr(&mut self) -> *mut u8 {
+        self.glitch_flags.scaled.as_mut_ptr()
+    }
+
+    /// `import_iterations` に渡す前の画像の iteration 数の入力バッファを `pixels` 要素分確保する。
+    /// このあと `iteration_import_ptr` でポインタを取得して JS 側からコピーする。
+    pub fn alloc_iteration_import(&mut self, pixels: u32) {
+        ensure_len(&mut self.iteration_import, pixels as usize);
+    }
+
+    pub fn iteration_import_ptr(&mut self) -> *mut u32 {
+        self.iteration_import.as_mut_ptr()
+    }
+
+    /// `alloc_iteration_import` のバッファにコピーした前の画像の iteration 数を、iterations キャッシュの
+    /// 重なるピクセルに書く。`begin` / `begin_direct` のあと、最初の pass より前に呼ぶ。
+    ///
+    /// 前の画像は幅 `src_width` 高さ `src_height` で、そのピクセル (sx, sy) が新しい画像の
+    /// pixel 座標 (offset_x + sx * scale, offset_y + sy * scale) に重なるものとして扱う。
+    /// 2 倍の拡大なら scale = 2 で、新しいピクセルの 4 つに 1 つが埋まる。
+    /// 重ならないピクセル、前の画像で 0 (未計算) のピクセル、計算済みのピクセルは書かない。
+    /// 書いたピクセルは pass で計算済みとして飛ばし、`reused_count` に加える。
+    /// 前の画像が同じ maxIteration で同じ点を計算したものかは確かめないので、呼び出し側で保証する。
+    /// バッファが足りない場合、scale が 0 の場合、キャッシュを持たない job と
+    /// 追加出力を使う job では何もしない。書いたピクセル数を返す。
+    pub fn import_iterations(
+        &mut self,
+        src_width: u32,
+        src_height: u32,
+        scale: u32,
+        offset_x: i32,
+        offset_y: i32,
+    ) -> u32 {
+        let area_pixels = (self.area_width as usize) * (self.area_height as usize);
+        let src_pixels = (src_width as usize) * (src_height as usize);
+        if scale == 0
+            || src_pixels > self.iteration_import.len()
+            || area_pixels > self.iterations.len()
+            || self.uses_area_channels()
+        {
+            return 0;
         }
+        let import = GridImport {
+            src_width,
+            src_height,
+            scale,
+            offset_x,
+            offset_y,
+        };
+        let imported = import_grid(
+            &mut self.iterations[..area_pixels],
+            self.area_width,
+            self.area_height,
+            self.area_start_x,
+            self.area_start_y,
+            &self.iteration_import,
+            &import,
+        );
+        self.reused_count += imported;
+        // 引き継いだピクセルと同じく progress の分母に入っているので計算済みとして数える
+        self.calculated_count += imported;
+        imported
+    }
 
-        // rebase
-        // https://fractalforums.org/fractal-mathematics-and-new-theories/28/another-solution-to-perturbation-glitches/4360
-        let dz_norm = n_norm(delta_n_re, delta_n_im);
-        let mut cur_x_re = x_re;
-        let mut cur_x_im = x_im;
-        if z_norm < dz_norm || ref_iteration == max_ref_iteration {
-            delta_n_re = z_re;
-            delta_n_im = z_im;
-            ref_iteration = 0;
-            cur_x_re = xn_raw[0];
-            cur_x_im = xn_raw[1];
+    /// glitch 補正用の secondary reference の入力バッファを確保する。`index` は 1 始まり (0 は primary)。
+    /// このあと `reference_xn_ptr` などでポインタを取得して JS 側からコピーし、`set_reference` で設定する。
+    /// index が `MAX_SECONDARY_REFERENCES` (16) を超える場合は何もせず false を返す。
+    pub fn alloc_reference(
+        &mut self,
+        index: u32,
+        xn_f64_len: u32,
+        bla_bytes_len: u32,
+        bla_row_offsets_len: u32,
+    ) -> bool {
+        let Some(reference) = self.reference_mut(index) else {
+            return false;
+        };
+        reference.alloc(xn_f64_len, bla_bytes_len, bla_row_offsets_len);
+        true
+    }
+
+    /// `alloc_reference` が false を返す index では null
+    pub fn reference_xn_ptr(&mut self, index: u32) -> *mut f64 {
+        self.reference_mut(index)
+            .map_or(std::ptr::null_mut(), |reference| reference.xn.as_mut_ptr())
+    }
+
+    /// `alloc_reference` が false を返す index では null。次の `set_reference` で BLA の表を作り直す
+    pub fn reference_bla_bytes_ptr(&mut self, index: u32) -> *mut u8 {
+        self.reference_mut(index)
+            .map_or(std::ptr::null_mut(), |reference| {
+                reference.invalidate_bla();
+                reference.bla_bytes.as_mut_ptr()
+            })
+    }
+
+    /// `alloc_reference` が false を返す index では null。次の `set_reference` で BLA の表を作り直す
+    pub fn reference_bla_row_offsets_ptr(&mut self, index: u32) -> *mut i32 {
+        self.reference_mut(index)
+            .map_or(std::ptr::null_mut(), |reference| {
+                reference.invalidate_bla();
+                reference.bla_row_offsets.as_mut_ptr()
+            })
+    }
+
+    /// secondary reference のパラメータを設定する。`begin` のあとに呼ぶ。
+    ///
+    /// 設定した reference は、glitch 判定が有効なときに glitch したピクセルの計算し直しに使われる。
+    /// index は 1 から順に設定し、1..=n を設定した job では n 本が使われる (`begin` でリセットされる)。
+    /// BLATable を持たない reference は `bla_rows` に 0 を渡す。
+    /// 入力が壊れているか、index が飛んでいるか上限を超えていればエラーコードを返し、
+    /// その job の band は計算されなくなる。
+    pub fn set_reference(
+        &mut self,
+        index: u32,
+        max_ref_iteration: u32,
+        bla_rows: u32,
+        ref_pixel_x: f64,
+        ref_pixel_y: f64,
+    ) -> u32 {
+        // 間に未設定の reference が残ると、glitch の補正で空の xn を読んでしまう
+        let max_index = (self.secondary_count as u32 + 1).min(MAX_SECONDARY_REFERENCES);
+        if index > max_index {
+            let error = JobError::ReferenceIndexOutOfRange {
+                index,
+                max: max_index,
+            };
+            self.reference_error.get_or_insert(error);
+            self.fail(error);
+            return error.code();
+        }
+        let start_bla_index = self.start_bla_index;
+        let Some(reference) = self.reference_mut(index) else {
+            unreachable!("index is checked against MAX_SECONDARY_REFERENCES");
+        };
+        reference.max_ref_iteration = max_ref_iteration;
+        reference.bla_rows = bla_rows as i32;
+        reference.ref_pixel_x = ref_pixel_x;
+        reference.ref_pixel_y = ref_pixel_y;
+        let result = reference.validate(index, start_bla_index);
+        match result {
+            Ok(()) => reference.rebuild_bla(),
+            Err(error) => self.fail(error),
         }
+        if index > 0 {
+            self.secondary_count = self.secondary_count.max(index as usize);
+        }
+        to_code(result)
+    }
 
-        // BLA
-        // refIteration === (jIdx << d) + 1 と |dz| < r を満たす、最大の l を持つデータを探す。
-        // 前者は「refM1 の下位 d bit が 0」と等価なので、d の上限は refM1 の trailing zeros 数。
-        // |dz| < r は sqrt を省くため dzNorm < r² で判定する (encode 時に r² を書き込んでいる)
-        let mut bla_row_idx: i32 = -1;
-        let mut bla_column_idx: i32 = -1;
-
-        if ref_iteration > 0 {
-            let ref_m1 = (ref_iteration - 1) as i32;
-            // ctz(refM1): refM1 === 0 のときは上限なし (bla_rows 側に任せる)
-            let ctz = if ref_m1 == 0 { 32 } else { ref_m1.trailing_zeros() as i32 };
-            let max_d = if ctz < bla_rows { ctz } else { bla_rows - 1 };
-            let mut d = start_bla_index;
-            while d <= max_d {
-                let j_idx = ref_m1 >> d;
-                let byte_offset =
-                    (row_offsets[(d * 2) as usize] as usize) + (j_idx as usize) * ITEM_BYTE_LENGTH;
-                let r_sq = read_f64(bla_bytes, byte_offset + 32);
-
-                if dz_norm < r_sq {
-                    bla_row_idx = d;
-                    bla_column_idx = j_idx;
-                } else {
-                    break;
-                }
-                d += 1;
+    /// job 全体のパラメータを確定する。iterations キャッシュはここで 0 クリアされる
+    /// (`set_iteration_shift` で前の job から引き継ぐ場合を除く)。
+    ///
+    /// 入力バッファはここで検証し、壊れていれば `calc_band` は計算せずにエラーコードを返す。
+    /// 結果は `validate` で確認できる。
+    #[allow(clippy::too_many_arguments)]
+    pub fn begin(
+        &mut self,
+        max_iteration: u32,
+        max_ref_iteration: u32,
+        bla_rows: u32,
+        start_bla_index: u32,
+        delta_c_scale: f64,
+        ref_pixel_x: f64,
+        ref_pixel_y: f64,
+        area_width: u32,
+        area_height: u32,
+        area_start_x: i32,
+        area_start_y: i32,
+    ) {
+        self.reset_job(
+            max_iteration,
+            delta_c_scale,
+            area_width,
+            area_height,
+            area_start_x,
+            area_start_y,
+        );
+        self.primary.max_ref_iteration = max_ref_iteration;
+        self.primary.bla_rows = bla_rows as i32;
+        self.start_bla_index = start_bla_index as i32;
+        self.primary.ref_pixel_x = ref_pixel_x;
+        self.primary.ref_pixel_y = ref_pixel_y;
+        match self.primary.validate(0, self.start_bla_index) {
+            Ok(()) => {
+                self.primary.rebuild_bla();
+                self.primary.series = self.series.and_then(|config| self.build_series(config));
+            }
+            Err(error) => {
+                self.primary.series = None;
+                self.fail(error);
             }
         }
 
-        let has_bla = bla_row_idx >= 0;
+        let area_pixels = (area_width as usize) * (area_height as usize);
 
-        let mut skipped: i32 = 0;
-        let mut bla_byte_offset: usize = 0;
-        if has_bla {
-            bla_byte_offset = (row_offsets[(bla_row_idx * 2) as usize] as usize)
-                + (bla_column_idx as usize) * ITEM_BYTE_LENGTH;
-            skipped = read_i32(bla_bytes, bla_byte_offset + 40);
+        // 追加出力のバッファは使うときだけ確保する。wasm memory が grow しうるので、
+        // JS 側は出力の ptr をこのあとに取得する
+        self.resolved_accumulation = self.accumulation.resolve(self.primary.c());
+        let area = self.alloc_area_pixels as usize;
+        let scaled = self.alloc_scaled_pixels as usize;
+        if self.resolved_accumulation.is_some() {
+            self.accum_values.prepare(area, scaled, area_pixels);
+            self.accum_prev_values.prepare(area, scaled, area_pixels);
+            self.accum_iterations.prepare(area, scaled, area_pixels);
+        }
+        if self.interior_detection.is_some() {
+            self.interior_periods.prepare(area, scaled, area_pixels);
+            self.interior_multipliers.prepare(area, scaled, area_pixels);
         }
-        let n = ref_iteration.wrapping_add(skipped as u32);
+        if self.is_atom_domain_enabled {
+            self.atom_domains.prepare(area, scaled, area_pixels);
+        }
+        if self.is_glitch_detection_enabled() {
+            self.glitch_flags.prepare(area, scaled, area_pixels);
+        }
+        self.uses_f32 = self.job_error.is_none() && self.resolve_f32();
 
-        if has_bla && n < max_ref_iteration {
-            let a_re = read_f64(bla_bytes, bla_byte_offset);
-            let a_im = read_f64(bla_bytes, bla_byte_offset + 8);
-            let b_re = read_f64(bla_bytes, bla_byte_offset + 16);
-            let b_im = read_f64(bla_bytes, bla_byte_offset + 24);
+        let (c_re, c_im) = self.primary.c();
+        self.prepare_iterations(GridAnchor::Reference {
+            c_re,
+            c_im,
+            max_ref_iteration,
+            pixel_x: ref_pixel_x,
+            pixel_y: ref_pixel_y,
+        });
+    }
 
-            let dz_re = mul_re(a_re, a_im, delta_n_re, delta_n_im)
-                + mul_re(b_re, b_im, delta_c_re, delta_c_im);
-            let dz_im = mul_im(a_re, a_im, delta_n_re, delta_n_im)
-                + mul_im(b_re, b_im, delta_c_re, delta_c_im);
+    /// reference orbit を使わない direct mode で job を始める。`begin` の代わりに呼ぶ。
+    ///
+    /// pixel 座標 (x, y) は c = center + ((x - center_pixel_x), -(y - center_pixel_y)) * delta_c_scale
+    /// として計算する。`alloc` の xn / BLA の長さは 0 でよい。
+    /// pass と band の進め方、iterations キャッシュ、progress と hit count は `begin` の job と同じ。
+    /// 追加出力 (`set_*` で有効にしたもの) は direct mode では出力しない
+    #[allow(clippy::too_many_arguments)]
+    pub fn begin_direct(
+        &mut self,
+        max_iteration: u32,
+        center_re: f64,
+        center_im: f64,
+        delta_c_scale: f64,
+        center_pixel_x: f64,
+        center_pixel_y: f64,
+        area_width: u32,
+        area_height: u32,
+        area_start_x: i32,
+        area_start_y: i32,
+    ) {
+        self.reset_job(
+            max_iteration,
+            delta_c_scale,
+            area_width,
+            area_height,
+            area_start_x,
+            area_start_y,
+        );
+        self.direct = Some(DirectView {
+            center_re,
+            center_im,
+            center_pixel_x,
+            center_pixel_y,
+        });
+        self.resolved_accumulation = None;
+        self.primary.series = None;
+        self.prepare_iterations(GridAnchor::Direct {
+            center_re,
+            center_im,
+            center_pixel_x,
+            center_pixel_y,
+        });
+    }
 
-            delta_n_re = dz_re;
-            delta_n_im = dz_im;
+    /// job 開始以降に実際に計算したピクセル数を返す。JS 側の progress 表示に使う。
+    /// solid guessing で埋めたピクセルと、前の job から引き継いだピクセルも含む。
+    pub fn calculated_count(&self) -> u32 {
+        self.calculated_count
+    }
 
-            ref_iteration = ref_iteration.wrapping_add(skipped as u32);
-            iteration = iteration.wrapping_add(skipped as u32);
-        } else {
-            // Δn+1 = 2 * Xn * Δn + Δn^2 + Δ0 を (2 * Xn + Δn) * Δn に展開して計算
-            let prev_re = delta_n_re;
-            let prev_im = delta_n_im;
+    /// 直近の pass で iteration が maxIteration に達したピクセル数を返す。
+    /// `is_result_pass` を立てた pass でのみ数えている。
+    pub fn hit_count(&self) -> u32 {
+        self.hit_count
+    }
 
-            let dzr_t = cur_x_re * 2.0 + prev_re;
-            let dzi_t = cur_x_im * 2.0 + prev_im;
+    /// 直近の pass で glitch と判定したピクセル数を返す。
+    /// hit count と同じく `is_result_pass` を立てた pass でのみ数えている。
+    pub fn glitch_count(&self) -> u32 {
+        self.glitch_count
+    }
 
-            delta_n_re = mul_re(dzr_t, dzi_t, prev_re, prev_im) + delta_c_re;
-            delta_n_im = mul_im(dzr_t, dzi_t, prev_re, prev_im) + delta_c_im;
+    /// job 開始以降に solid guessing / boundary tracing で計算せずに埋めたピクセル数を返す。
+    /// `set_guess_check` が有効な job では、埋める代わりに計算したピクセルも含む。
+    pub fn guessed_count(&self) -> u32 {
+        self.guessed_count
+    }
 
-            ref_iteration += 1;
-            iteration += 1;
-        }
+    /// job 開始以降に iteration を実際に回したピクセル数を返す。
+    /// `calculated_count` から埋めたピクセル (check 有効時は埋めるはずだったピクセル) と
+    /// 引き継いだピクセルを除いたもので、
+    /// 全ピクセルに対する割合が
+    /// solid guessing / boundary tracing で減らせた計算量の目安になる。
+    pub fn iterated_count(&self) -> u32 {
+        self.calculated_count - self.guessed_count - self.reused_count
     }
 
-    iteration.min(max_iteration)
-}
+    /// 直近の `begin` / `begin_direct` で前の job の iterations キャッシュから引き継いだピクセル数と、
+    /// そのあと `import_iterations` で書いたピクセル数の合計を返す。
+    /// `set_iteration_shift` も `import_iterations` も使わなかった job では 0。
+    pub fn reused_count(&self) -> u32 {
+        self.reused_count
+    }
 
-/// pass 内の scaled_y が [from, to) の範囲を計算する。
-///
-/// 呼び出し粒度が progress 更新と terminator チェックの粒度になる。
-#[wasm_bindgen]
-pub fn calc_iteration_band(band_scaled_y_from: u32, band_scaled_y_to: u32) {
-    with_job(|job| {
-        let x_diff = job.x_diff;
-        let y_diff = job.y_diff;
-        let scaled_w = job.scaled_width;
-        let area_w = job.area_width as f64;
-        let start_x = job.area_start_x as f64;
-        let start_y = job.area_start_y as f64;
-        let is_super_sampling = job.is_super_sampling;
-        let is_result_pass = job.is_result_pass;
-        let max_iteration = job.max_iteration;
-
-        for scaled_y in band_scaled_y_from..band_scaled_y_to {
-            let y = start_y + (scaled_y as f64) * y_diff;
-
-            for scaled_x in 0..scaled_w {
-                let x = start_x + (scaled_x as f64) * x_diff;
-                let scaled_index = (scaled_x + scaled_y * scaled_w) as usize;
-
-                // supersampling 時は 1 pass しかないので iterations キャッシュを使わない。
-                // xDiff = 0.5 のため index が area の範囲に収まらず、そもそも参照できない
-                if !is_super_sampling {
-                    let index = (x - start_x + (y - start_y) * area_w) as usize;
-                    let cached = job.iterations[index];
-                    if cached != 0 {
-                        job.scaled_iterations[scaled_index] = cached;
-                        if is_result_pass && cached == max_iteration {
-                            job.hit_count += 1;
-                        }
-                        continue;
-                    }
+    /// job 開始以降に solid guessing / boundary tracing で埋め間違えたピクセル数を返す。
+    /// `set_guess_check` を有効にした job でのみ数えている。
+    pub fn mistaken_guess_count(&self) -> u32 {
+        self.mistaken_guess_count
+    }
 
-                    let n = calc_iteration_at(job, x, y);
-                    job.calculated_count += 1;
-                    job.iterations[index] = n;
-                    job.scaled_iterations[scaled_index] = n;
-                    if is_result_pass && n == max_iteration {
-                        job.hit_count += 1;
-                    }
-                } else {
-                    let n = calc_iteration_at(job, x, y);
-                    job.calculated_count += 1;
-                    job.scaled_iterations[scaled_index] = n;
-                    if is_result_pass && n == max_iteration {
-                        job.hit_count += 1;
-                    }
-                }
-            }
+    /// 現在の job の入力 (primary と追加した reference) を検証し直して、エラーコードを返す。
+    ///
+    /// 0 なら正常。0 以外なら band は計算されないので、`error_message` で理由を取得する。
+    /// - 1: xn が max_ref_iteration に足りない
+    /// - 2: bla_row_offsets が bla_rows 段に足りない
+    /// - 3: BLA の row が bla_bytes の範囲外
+    /// - 4: BLA の row が max_ref_iteration に足りない
+    /// - 5: pass が iterations キャッシュの範囲外を読む (band のみ)
+    /// - 6: band が pass 出力の範囲外に書く (band のみ)
+    /// - 7: ピクセル座標の数がバッファより多い (`calc_pixels` のみ)
+    /// - 8: reference の index が飛んでいるか上限を超えている (`set_reference` のみ)
+    pub fn validate(&mut self) -> u32 {
+        // direct mode の job は reference を読まない
+        let result = match self.direct {
+            Some(_) => Ok(()),
+            None => self.validate_references(),
+        };
+        self.job_error = result.err();
+        self.last_error = result.err();
+        to_code(result)
+    }
+
+    /// 直近に返したエラーの説明。エラーがなければ空文字列
+    pub fn error_message(&self) -> String {
+        self.last_error
+            .map(|error| error.message())
+            .unwrap_or_default()
+    }
+
+    /// 1 pass 分のパラメータを設定する。hit count と glitch count はここでリセットされる。
+    pub fn begin_pass(
+        &mut self,
+        x_diff: f64,
+        y_diff: f64,
+        scaled_width: u32,
+        is_super_sampling: bool,
+        is_result_pass: bool,
+    ) {
+        self.x_diff = x_diff;
+        self.y_diff = y_diff;
+        self.scaled_width = scaled_width;
+        self.is_super_sampling = is_super_sampling;
+        self.is_result_pass = is_result_pass;
+        self.sampling = None;
+        self.refinement = None;
+        self.hit_count = 0;
+        self.glitch_count = 0;
+    }
+
+    /// 1 ピクセルを `samples_per_axis`×`samples_per_axis` 個のサンプルで計算する supersampling の pass を始める。
+    /// hit count と glitch count はここでリセットされる。
+    ///
+    /// `pattern` は 0: 格子, 1: 傾けた格子, 2: jitter (`seed` とピクセル座標で決まる)。
+    /// `samples_per_axis` は 1..=16 に丸める。
+    /// この pass の `calc_band` には scaled 座標ではなく area のピクセル行 [from, to) を渡す。
+    /// `is_averaged` が false なら全サンプルを幅 `area_width * samples_per_axis` の scaled 出力に、
+    /// true ならピクセルごとの iteration 数の平均 (四捨五入) を幅 `area_width` で書く。
+    /// iterations キャッシュは使わず、追加出力も書かない。
+    /// 未知の `pattern` を渡した場合は何もせず false を返す。
+    pub fn begin_sampling_pass(
+        &mut self,
+        samples_per_axis: u32,
+        pattern: u32,
+        seed: u32,
+        is_averaged: bool,
+        is_result_pass: bool,
+    ) -> bool {
+        let Some(pattern) = SamplePattern::from_u32(pattern) else {
+            return false;
+        };
+        let sampling = SamplingPass {
+            samples_per_axis: samples_per_axis.clamp(1, MAX_SAMPLES_PER_AXIS),
+            pattern,
+            seed,
+            is_averaged,
+        };
+        let step = 1.0 / sampling.samples_per_axis as f64;
+        self.begin_pass(
+            step,
+            step,
+            sampling.scaled_width(self.area_width),
+            true,
+            is_result_pass,
+        );
+        self.sampling = Some(sampling);
+        true
+    }
+
+    /// adaptive supersampling の refinement pass を始める。hit count と glitch count はここでリセットされる。
+    ///
+    /// 通常の pass で埋めた iterations キャッシュを元に、8 近傍のどれかとの iteration 数の差が
+    /// `threshold` を超えるピクセルと、maxIteration に達したかどうかが近傍と違うピクセルだけを
+    /// `begin_sampling_pass` と同じ配置の `samples_per_axis`×`samples_per_axis` 個のサンプルで計算し直す。
+    /// `calc_band` には area のピクセル行 [from, to) を渡す。出力は幅 `area_width` の scaled 出力で、
+    /// 計算し直したピクセルはサンプルの平均 (四捨五入)、それ以外はキャッシュの値になる。
+    /// 各ピクセルで使ったサンプル数 (1 か samples_per_axis^2) は `sample_counts_ptr` に書く。
+    /// iterations キャッシュは書き換えず、追加出力も書かない。
+    /// 未知の `pattern` を渡した場合は何もせず false を返す。
+    pub fn begin_refinement_pass(
+        &mut self,
+        samples_per_axis: u32,
+        pattern: u32,
+        seed: u32,
+        threshold: u32,
+        is_result_pass: bool,
+    ) -> bool {
+        let Some(pattern) = SamplePattern::from_u32(pattern) else {
+            return false;
+        };
+        let sampling = SamplingPass {
+            samples_per_axis: samples_per_axis.clamp(1, MAX_SAMPLES_PER_AXIS),
+            pattern,
+            seed,
+            is_averaged: true,
+        };
+        // band の検証は等倍の pass と同じで、キャッシュと scaled 出力に area が収まるかを見る
+        self.begin_pass(1.0, 1.0, self.area_width, false, is_result_pass);
+        self.refinement = Some(RefinementPass {
+            sampling,
+            threshold,
+        });
+        ensure_len(&mut self.sample_counts, self.alloc_scaled_pixels as usize);
+        true
+    }
+
+    /// pass 内の scaled_y が [from, to) の範囲を計算する。
+    /// `begin_sampling_pass` / `begin_refinement_pass` で始めた pass では area のピクセル行 [from, to) を計算する。
+    ///
+    /// 呼び出し粒度が progress 更新と terminator チェックの粒度になる。
+    /// job の入力か band の範囲が壊れていれば何も計算せずにエラーコードを返す (0 なら成功)。
+    pub fn calc_band(&mut self, band_scaled_y_from: u32, band_scaled_y_to: u32) -> u32 {
+        let result = self
+            .job_error
+            .map_or(Ok(()), Err)
+            .and_then(|()| self.validate_band(band_scaled_y_from, band_scaled_y_to));
+        if let Err(error) = result {
+            self.last_error = Some(error);
+            return error.code();
         }
-    });
+
+        let (from, to) = (band_scaled_y_from, band_scaled_y_to);
+        if self.is_glitch_detection_enabled() && self.secondary_count > 0 {
+            self.order_secondary_references(from, to);
+        }
+        if self.sampling.is_some() {
+            sampling::calc_band_sampled(self, from, to, self.points_kernel());
+            return JOB_OK;
+        }
+        if self.refinement.is_some() {
+            sampling::calc_band_refined(self, from, to, self.points_kernel());
+            return JOB_OK;
+        }
+        match self.render_strategy {
+            _ if self.has_extra_outputs() || self.uses_continuations() => {}
+            RenderStrategy::Scan => {}
+            RenderStrategy::SolidGuessing => {
+                guessing::calc_band_guessed(self, from, to, self.points_kernel());
+                return JOB_OK;
+            }
+            RenderStrategy::BoundaryTracing => {
+                tracing::calc_band_traced(self, from, to, self.points_kernel());
+                return JOB_OK;
+            }
+        }
+        match self.interleaved_pixels {
+            _ if self.direct.is_some() => calc_band::<DirectKernel>(self, from, to),
+            _ if self.has_extra_outputs() => calc_band::<ExtraKernel>(self, from, to),
+            _ if self.uses_continuations() => calc_band::<ContinuationKernel>(self, from, to),
+            _ if self.uses_f32 => calc_band_batched(self, from, to, fast32::calc_iterations_f32x4),
+            2 => calc_band_batched(self, from, to, interleave::calc_iterations_interleaved::<2>),
+            3 => calc_band_batched(self, from, to, interleave::calc_iterations_interleaved::<3>),
+            4 => calc_band_batched(self, from, to, interleave::calc_iterations_interleaved::<4>),
+            _ if simd::IS_ENABLED => calc_band_batched(self, from, to, simd::calc_iterations_x2),
+            _ => calc_band::<PlainKernel>(self, from, to),
+        }
+        JOB_OK
+    }
+
+    /// `calc_pixels` に渡すピクセル座標の入力バッファと、結果の出力バッファを `count` 個分確保する。
+    /// このあと `pixel_list_ptr` に (x, y) の順で f64 を 2 つずつ書く。
+    pub fn alloc_pixel_list(&mut self, count: u32) {
+        ensure_len(&mut self.pixel_list, count as usize * 2);
+        ensure_len(&mut self.pixel_results, count as usize);
+    }
+
+    pub fn pixel_list_ptr(&mut self) -> *mut f64 {
+        self.pixel_list.as_mut_ptr()
+    }
+
+    pub fn pixel_results_ptr(&mut self) -> *mut u32 {
+        self.pixel_results.as_mut_ptr()
+    }
+
+    /// `pixel_list_ptr` に書いた `count` 個のピクセル座標の iteration 数を、同じ順に `pixel_results_ptr` に書く。
+    ///
+    /// 座標は `calc_band` と同じ area_start を含む pixel 座標で、小数でもよい。
+    /// adaptive な refinement、glitch したピクセルの計算し直し、カーソル位置の iteration 数の表示など、
+    /// 行単位ではなく散らばったピクセルだけを計算したいときに使う。
+    /// `begin` / `begin_direct` のあとなら pass の途中でも呼べて、iterations キャッシュ、pass の出力、
+    /// 追加出力、`calculated_count` と hit count は変えない。追加出力のある job でも iteration 数だけを返し、
+    /// glitch の補正はしない (secondary reference を設定した job でも primary の結果を返す)。
+    /// job の入力が壊れているか、`count` がバッファより多ければ何も計算せずにエラーコードを返す (0 なら成功)。
+    pub fn calc_pixels(&mut self, count: u32) -> u32 {
+        let capacity = self.pixel_results.len().min(self.pixel_list.len() / 2);
+        let result = self.job_error.map_or(Ok(()), Err).and_then(|()| {
+            if count as usize > capacity {
+                return Err(JobError::PixelListOutOfBounds { count, capacity });
+            }
+            Ok(())
+        });
+        if let Err(error) = result {
+            self.last_error = Some(error);
+            return error.code();
+        }
+
+        let points: Vec<(f64, f64)> = self.pixel_list[..count as usize * 2]
+            .chunks_exact(2)
+            .map(|point| (point[0], point[1]))
+            .collect();
+        // glitch の補正は band ごとに並べた `secondary_order` を使うので、ここでは補正しない kernel にする
+        let kernel: PointsKernel = if self.direct.is_none() && self.has_extra_outputs() {
+            calc_points_uncorrected
+        } else {
+            self.points_kernel()
+        };
+        // kernel は job を借りるので、出力先をいったん job から外す
+        let mut results = std::mem::take(&mut self.pixel_results);
+        kernel(self, &self.primary, &points, &mut results[..count as usize]);
+        self.pixel_results = results;
+        JOB_OK
+    }
+}
+
+impl Default for IterationJob {
+    fn default() -> Self {
+        Self::empty()
+    }
+}
+
+/// iteration 中の z を覗き見るためのフック。
+///
+/// `calc_iteration_at` を observer ごとに単相化させることで、何も観測しない通常の経路には
+/// 一切コストを乗せない。
+pub(crate) trait OrbitObserver {
+    /// BLA による skip を許すか。skip された iteration の z は観測できないので、
+    /// 全 iteration を見たい observer は false を返す
+    fn allow_bla(&self) -> bool;
+
+    /// series approximation による初期スキップを許すか。
+    /// スキップした iteration は observe されないうえ、級数の誤差で途中の z も少しずれるので、
+    /// iteration 数だけを出す observer 以外は false のまま
+    #[inline(always)]
+    fn allow_series_skip(&self) -> bool {
+        false
+    }
+
+    /// `start` で c を受け取る必要があるか
+    const NEEDS_C: bool = false;
+
+    /// ピクセルの計算開始時に、そのピクセルの c (reference の c + Δc を f64 で足したもの) を渡す
+    #[inline(always)]
+    fn start(&mut self, _c_re: f64, _c_im: f64) {}
+
+    /// bailout しなかった z ごとに呼ばれる。true を返すと内部と判定してそこで打ち切る
+    fn observe(&mut self, iteration: u32, z_re: f64, z_im: f64) -> bool;
+
+    /// observe のあと、rebase の判定に使う |z|² と |Δ|² を渡す
+    #[inline(always)]
+    fn on_delta(&mut self, _z_norm: f64, _dz_norm: f64) {}
+
+    /// 直前に observe した z から 1 iteration 進めたときに呼ばれる
+    #[inline(always)]
+    fn on_perturbation_step(&mut self) {}
+
+    /// 直前に observe した z から BLA で skip したときに、その係数 A を渡す
+    #[inline(always)]
+    fn on_bla_step(&mut self, _a_re: f64, _a_im: f64) {}
+}
+
+/// 何も観測しない observer。通常の描画はこれを使う
+struct NoObserver;
+
+impl OrbitObserver for NoObserver {
+    #[inline(always)]
+    fn allow_bla(&self) -> bool {
+        true
+    }
+
+    #[inline(always)]
+    fn allow_series_skip(&self) -> bool {
+        true
+    }
+
+    #[inline(always)]
+    fn observe(&mut self, _iteration: u32, _z_re: f64, _z_im: f64) -> bool {
+        false
+    }
+}
+
+/// 集計・内部判定・atom domain・glitch 判定をまとめた observer。有効なものだけ動かす
+struct ExtraObserver {
+    accumulator: Option<Accumulator>,
+    interior: Option<InteriorDetector>,
+    atom_domain: Option<AtomDomainTracker>,
+    glitch: Option<GlitchDetector>,
+}
+
+impl ExtraObserver {
+    fn new(job: &IterationJob) -> Self {
+        Self {
+            accumulator: job.resolved_accumulation.map(Accumulator::new),
+            interior: job.interior_detection.map(InteriorDetector::new),
+            atom_domain: job.is_atom_domain_enabled.then(AtomDomainTracker::new),
+            glitch: job
+                .is_glitch_detection_enabled()
+                .then(|| GlitchDetector::new(job.glitch_tolerance)),
+        }
+    }
+
+    fn finish(&self) -> PixelExtras {
+        PixelExtras {
+            accumulation: self.accumulator.as_ref().map(Accumulator::finish),
+            interior: self
+                .interior
+                .as_ref()
+                .map(InteriorDetector::finish)
+                .unwrap_or_default(),
+            atom_domain: self
+                .atom_domain
+                .as_ref()
+                .map_or(0, AtomDomainTracker::finish),
+            glitched: self.glitch.as_ref().is_some_and(GlitchDetector::finish),
+        }
+    }
+}
+
+impl OrbitObserver for ExtraObserver {
+    #[inline(always)]
+    fn allow_bla(&self) -> bool {
+        self.accumulator.is_none()
+    }
+
+    const NEEDS_C: bool = true;
+
+    #[inline(always)]
+    fn start(&mut self, c_re: f64, c_im: f64) {
+        if let Some(accumulator) = &mut self.accumulator {
+            accumulator.start(c_re, c_im);
+        }
+    }
+
+    #[inline(always)]
+    fn observe(&mut self, iteration: u32, z_re: f64, z_im: f64) -> bool {
+        if let Some(accumulator) = &mut self.accumulator {
+            accumulator.observe(iteration, z_re, z_im);
+        }
+        if let Some(atom_domain) = &mut self.atom_domain {
+            atom_domain.observe(iteration, z_re, z_im);
+        }
+        match &mut self.interior {
+            Some(interior) => interior.observe(iteration, z_re, z_im),
+            None => false,
+        }
+    }
+
+    #[inline(always)]
+    fn on_delta(&mut self, z_norm: f64, dz_norm: f64) {
+        if let Some(glitch) = &mut self.glitch {
+            glitch.on_delta(z_norm, dz_norm);
+        }
+    }
+
+    #[inline(always)]
+    fn on_perturbation_step(&mut self) {
+        if let Some(interior) = &mut self.interior {
+            interior.on_perturbation_step();
+        }
+    }
+
+    #[inline(always)]
+    fn on_bla_step(&mut self, a_re: f64, a_im: f64) {
+        if let Some(interior) = &mut self.interior {
+            interior.on_bla_step(a_re, a_im);
+        }
+    }
+}
+
+/// 1 ピクセル分の計算途中の状態。ここから `continue_iteration` で計算を続けられる
+#[derive(Clone, Copy)]
+pub(crate) struct PixelState {
+    pub(crate) delta_n_re: f64,
+    pub(crate) delta_n_im: f64,
+    pub(crate) delta_c_re: f64,
+    pub(crate) delta_c_im: f64,
+    pub(crate) iteration: u32,
+    pub(crate) ref_iteration: u32,
+}
+
+/// 1 ピクセル分の iteration を計算する (perturbation + BLA + rebase)。
+///
+/// JS 版 `calcIterationAt` の移植。計算順序を変えると結果が変わるのでそのまま維持している。
+fn calc_iteration_at<O: OrbitObserver>(
+    job: &IterationJob,
+    reference: &Reference,
+    pixel_x: f64,
+    pixel_y: f64,
+    observer: &mut O,
+) -> u32 {
+    let state = start_pixel(job, reference, pixel_x, pixel_y, observer);
+    continue_iteration(job, reference, state, observer)
+}
+
+/// ピクセルの計算を始める。Δc を求め、series approximation があればスキップ先の Δ から始める
+#[inline(always)]
+fn start_pixel<O: OrbitObserver>(
+    job: &IterationJob,
+    reference: &Reference,
+    pixel_x: f64,
+    pixel_y: f64,
+    observer: &mut O,
+) -> PixelState {
+    // Δc = (pixel - refPixel) * deltaCScale。cx と W/2 が相殺されるので double だけで出せる
+    let delta_c_re = (pixel_x - reference.ref_pixel_x) * job.delta_c_scale;
+    let delta_c_im = -(pixel_y - reference.ref_pixel_y) * job.delta_c_scale;
+    if O::NEEDS_C {
+        let (ref_c_re, ref_c_im) = reference.c();
+        observer.start(ref_c_re + delta_c_re, ref_c_im + delta_c_im);
+    }
+
+    let mut state = PixelState {
+        delta_n_re: 0.0,
+        delta_n_im: 0.0,
+        delta_c_re,
+        delta_c_im,
+        iteration: 0,
+        ref_iteration: 0,
+    };
+
+    // series approximation があれば途中から始める。iteration 数しか出さない経路でだけ使う
+    if let Some(series) = &reference.series
+        && observer.allow_series_skip()
+    {
+        (state.delta_n_re, state.delta_n_im) = series.evaluate(delta_c_re, delta_c_im);
+        state.iteration = series.skip();
+        state.ref_iteration = state.iteration;
+    }
+    state
+}
+
+/// `state` から bailout するか maxIteration に達するまで計算を進め、iteration 数を返す
+#[inline(always)]
+fn continue_iteration<O: OrbitObserver>(
+    job: &IterationJob,
+    reference: &Reference,
+    state: PixelState,
+    observer: &mut O,
+) -> u32 {
+    continue_iteration_with_state(job, reference, state, observer).0
+}
+
+/// `continue_iteration` と同じ計算をして、止まったときの状態も返す。
+/// maxIteration に達したピクセルは、この状態から maxIteration を上げて計算を続けられる
+#[inline(always)]
+fn continue_iteration_with_state<O: OrbitObserver>(
+    job: &IterationJob,
+    reference: &Reference,
+    state: PixelState,
+    observer: &mut O,
+) -> (u32, PixelState) {
+    let max_iteration = job.max_iteration;
+    let max_ref_iteration = reference.max_ref_iteration;
+    let bla_rows = reference.bla_rows;
+    let start_bla_index = job.start_bla_index;
+    let xn_raw = &reference.xn;
+    let bla = &reference.bla;
+
+    let PixelState {
+        mut delta_n_re,
+        mut delta_n_im,
+        delta_c_re,
+        delta_c_im,
+        mut iteration,
+        mut ref_iteration,
+    } = state;
+
+    while iteration < max_iteration {
+        let ref_idx2 = (ref_iteration as usize) * 2;
+        let x_re = xn_raw[ref_idx2];
+        let x_im = xn_raw[ref_idx2 + 1];
+        let z_re = x_re + delta_n_re;
+        let z_im = x_im + delta_n_im;
+        let z_norm = n_norm(z_re, z_im);
+        if z_norm > BAILOUT_RADIUS {
+            break;
+        }
+        if observer.observe(iteration, z_re, z_im) {
+            // 内部と判定された。maxIteration に達したのと同じ扱いにする
+            let state = PixelState {
+                delta_n_re,
+                delta_n_im,
+                delta_c_re,
+                delta_c_im,
+                iteration,
+                ref_iteration,
+            };
+            return (max_iteration, state);
+        }
+
+        // rebase
+        // https://fractalforums.org/fractal-mathematics-and-new-theories/28/another-solution-to-perturbation-glitches/4360
+        let dz_norm = n_norm(delta_n_re, delta_n_im);
+        observer.on_delta(z_norm, dz_norm);
+        let mut cur_x_re = x_re;
+        let mut cur_x_im = x_im;
+        if z_norm < dz_norm || ref_iteration == max_ref_iteration {
+            delta_n_re = z_re;
+            delta_n_im = z_im;
+            ref_iteration = 0;
+            cur_x_re = xn_raw[0];
+            cur_x_im = xn_raw[1];
+        }
+
+        // BLA
+        let bla_index = if observer.allow_bla() {
+            bla.find(bla_rows, start_bla_index, ref_iteration, dz_norm)
+        } else {
+            None
+        };
+
+        let step = bla_index.map(|index| bla.step(index));
+        let skipped = step.map_or(0, |step| step.skip);
+        let n = ref_iteration.wrapping_add(skipped);
+
+        if let Some(&BlaStep {
+            a_re,
+            a_im,
+            b_re,
+            b_im,
+            ..
+        }) = step
+            && n < max_ref_iteration
+        {
+            let dz_re = mul_re(a_re, a_im, delta_n_re, delta_n_im)
+                + mul_re(b_re, b_im, delta_c_re, delta_c_im);
+            let dz_im = mul_im(a_re, a_im, delta_n_re, delta_n_im)
+                + mul_im(b_re, b_im, delta_c_re, delta_c_im);
+
+            delta_n_re = dz_re;
+            delta_n_im = dz_im;
+            observer.on_bla_step(a_re, a_im);
+
+            ref_iteration = ref_iteration.wrapping_add(skipped);
+            iteration = iteration.wrapping_add(skipped);
+        } else {
+            // Δn+1 = 2 * Xn * Δn + Δn^2 + Δ0 を (2 * Xn + Δn) * Δn に展開して計算
+            let prev_re = delta_n_re;
+            let prev_im = delta_n_im;
+
+            let dzr_t = cur_x_re * 2.0 + prev_re;
+            let dzi_t = cur_x_im * 2.0 + prev_im;
+
+            delta_n_re = mul_re(dzr_t, dzi_t, prev_re, prev_im) + delta_c_re;
+            delta_n_im = mul_im(dzr_t, dzi_t, prev_re, prev_im) + delta_c_im;
+
+            observer.on_perturbation_step();
+
+            ref_iteration += 1;
+            iteration += 1;
+        }
+    }
+
+    let state = PixelState {
+        delta_n_re,
+        delta_n_im,
+        delta_c_re,
+        delta_c_im,
+        iteration,
+        ref_iteration,
+    };
+    (iteration.min(max_iteration), state)
+}
+
+/// band ループから呼ぶ 1 ピクセル分の計算。iteration 以外の出力の扱いをここに閉じ込める
+trait PixelKernel {
+    /// iteration 以外の計算結果
+    type Extra: Copy;
+
+    fn calc(job: &IterationJob, x: f64, y: f64) -> (u32, Self::Extra);
+
+    /// 計算結果を書き込む。`area_index` は iterations キャッシュを使わないとき None
+    fn store(
+        job: &mut IterationJob,
+        area_index: Option<usize>,
+        scaled_index: usize,
+        extra: Self::Extra,
+    );
+
+    /// キャッシュにヒットしたピクセルの結果を pass 出力に書き戻す
+    fn restore(job: &mut IterationJob, area_index: usize, scaled_index: usize);
+}
+
+/// iteration だけを出す通常の kernel
+struct PlainKernel;
+
+impl PixelKernel for PlainKernel {
+    type Extra = ();
+
+    #[inline(always)]
+    fn calc(job: &IterationJob, x: f64, y: f64) -> (u32, ()) {
+        (
+            calc_iteration_at(job, &job.primary, x, y, &mut NoObserver),
+            (),
+        )
+    }
+
+    #[inline(always)]
+    fn store(
+        _job: &mut IterationJob,
+        _area_index: Option<usize>,
+        _scaled_index: usize,
+        _extra: (),
+    ) {
+    }
+
+    #[inline(always)]
+    fn restore(_job: &mut IterationJob, _area_index: usize, _scaled_index: usize) {}
+}
+
+/// iteration 以外の計算結果
+#[derive(Clone, Copy)]
+struct PixelExtras {
+    accumulation: Option<AccumulationResult>,
+    interior: InteriorResult,
+    atom_domain: u32,
+    glitched: bool,
+}
+
+fn calc_extras_at(job: &IterationJob, reference: &Reference, x: f64, y: f64) -> (u32, PixelExtras) {
+    let mut observer = ExtraObserver::new(job);
+    let n = calc_iteration_at(job, reference, x, y, &mut observer);
+    (n, observer.finish())
+}
+
+/// glitch したピクセルを secondary reference で計算し直す。
+///
+/// band の中央に近い reference から順に試し (`IterationJob::order_secondary_references`)、
+/// glitch しなかった最初の結果を返す。
+/// 全部 glitch した場合は None (呼び出し側で primary の結果を glitch のまま残す)
+#[cold]
+fn correct_glitch(job: &IterationJob, x: f64, y: f64) -> Option<(u32, PixelExtras)> {
+    job.secondary_order
+        .iter()
+        .map(|&index| calc_extras_at(job, &job.secondary_references[index], x, y))
+        .find(|(_, extras)| !extras.glitched)
+}
+
+/// 集計や内部判定が有効なときの kernel
+struct ExtraKernel;
+
+impl PixelKernel for ExtraKernel {
+    type Extra = PixelExtras;
+
+    #[inline(always)]
+    fn calc(job: &IterationJob, x: f64, y: f64) -> (u32, Self::Extra) {
+        let result = calc_extras_at(job, &job.primary, x, y);
+        if !result.1.glitched || job.secondary_count == 0 {
+            return result;
+        }
+        correct_glitch(job, x, y).unwrap_or(result)
+    }
+
+    #[inline(always)]
+    fn store(
+        job: &mut IterationJob,
+        area_index: Option<usize>,
+        scaled_index: usize,
+        extra: Self::Extra,
+    ) {
+        if let Some(accumulation) = extra.accumulation {
+            job.accum_values
+                .store(area_index, scaled_index, accumulation.value);
+            job.accum_prev_values
+                .store(area_index, scaled_index, accumulation.prev_value);
+            job.accum_iterations
+                .store(area_index, scaled_index, accumulation.iteration);
+        }
+        if job.interior_detection.is_some() {
+            job.interior_periods
+                .store(area_index, scaled_index, extra.interior.period);
+            job.interior_multipliers
+                .store(area_index, scaled_index, extra.interior.multiplier_abs);
+        }
+        if job.is_atom_domain_enabled {
+            job.atom_domains
+                .store(area_index, scaled_index, extra.atom_domain);
+        }
+        if job.is_glitch_detection_enabled() {
+            job.glitch_flags
+                .store(area_index, scaled_index, extra.glitched as u8);
+            if job.is_result_pass && extra.glitched {
+                job.glitch_count += 1;
+            }
+        }
+    }
+
+    #[inline(always)]
+    fn restore(job: &mut IterationJob, area_index: usize, scaled_index: usize) {
+        if job.resolved_accumulation.is_some() {
+            job.accum_values.restore(area_index, scaled_index);
+            job.accum_prev_values.restore(area_index, scaled_index);
+            job.accum_iterations.restore(area_index, scaled_index);
+        }
+        if job.interior_detection.is_some() {
+            job.interior_periods.restore(area_index, scaled_index);
+            job.interior_multipliers.restore(area_index, scaled_index);
+        }
+        if job.is_atom_domain_enabled {
+            job.atom_domains.restore(area_index, scaled_index);
+        }
+        if job.is_glitch_detection_enabled() {
+            job.glitch_flags.restore(area_index, scaled_index);
+            if job.is_result_pass && job.glitch_flags.area[area_index] != 0 {
+                job.glitch_count += 1;
+            }
+        }
+    }
+}
+
+fn calc_band<K: PixelKernel>(
+    job: &mut IterationJob,
+    band_scaled_y_from: u32,
+    band_scaled_y_to: u32,
+) {
+    let x_diff = job.x_diff;
+    let y_diff = job.y_diff;
+    let scaled_w = job.scaled_width;
+    let area_w = job.area_width as f64;
+    let start_x = job.area_start_x as f64;
+    let start_y = job.area_start_y as f64;
+    let is_super_sampling = job.is_super_sampling;
+    let is_result_pass = job.is_result_pass;
+    let max_iteration = job.max_iteration;
+
+    for scaled_y in band_scaled_y_from..band_scaled_y_to {
+        let y = start_y + (scaled_y as f64) * y_diff;
+
+        for scaled_x in 0..scaled_w {
+            let x = start_x + (scaled_x as f64) * x_diff;
+            let scaled_index = (scaled_x + scaled_y * scaled_w) as usize;
+
+            // supersampling 時は 1 pass しかないので iterations キャッシュを使わない。
+            // xDiff = 0.5 のため index が area の範囲に収まらず、そもそも参照できない
+            if !is_super_sampling {
+                let index = (x - start_x + (y - start_y) * area_w) as usize;
+                let cached = job.iterations[index];
+                if cached != 0 {
+                    job.scaled_iterations[scaled_index] = cached;
+                    K::restore(job, index, scaled_index);
+                    if is_result_pass && cached == max_iteration {
+                        job.hit_count += 1;
+                    }
+                    continue;
+                }
+
+                let (n, extra) = K::calc(job, x, y);
+                job.calculated_count += 1;
+                job.iterations[index] = n;
+                job.scaled_iterations[scaled_index] = n;
+                K::store(job, Some(index), scaled_index, extra);
+                if is_result_pass && n == max_iteration {
+                    job.hit_count += 1;
+                }
+            } else {
+                let (n, extra) = K::calc(job, x, y);
+                job.calculated_count += 1;
+                job.scaled_iterations[scaled_index] = n;
+                K::store(job, None, scaled_index, extra);
+                if is_result_pass && n == max_iteration {
+                    job.hit_count += 1;
+                }
+            }
+        }
+    }
+}
+
+/// 座標の列をまとめて計算し、iteration 数を同じ順に書く kernel。
+/// 追加出力のない `calc_iteration_at` と同じ結果を返す
+type PointsKernel = fn(&IterationJob, &Reference, &[(f64, f64)], &mut [u32]);
+
+/// `PixelKernel` で 1 点ずつ計算する `PointsKernel`。iteration 以外の計算結果は捨てる
+fn calc_points_with<K: PixelKernel>(
+    job: &IterationJob,
+    _reference: &Reference,
+    points: &[(f64, f64)],
+    results: &mut [u32],
+) {
+    for (&(x, y), result) in points.iter().zip(results) {
+        *result = K::calc(job, x, y).0;
+    }
+}
+
+/// 追加出力のある job の `PointsKernel`。`ExtraKernel` と違い、glitch したピクセルも計算し直さない
+fn calc_points_uncorrected(
+    job: &IterationJob,
+    reference: &Reference,
+    points: &[(f64, f64)],
+    results: &mut [u32],
+) {
+    for (&(x, y), result) in points.iter().zip(results) {
+        *result = calc_extras_at(job, reference, x, y).0;
+    }
+}
+
+/// 複数ピクセルをまとめて計算する kernel に渡す `calc_band`。追加出力のない job だけで使う。
+///
+/// 1 行ずつキャッシュにないピクセルを集めてから `calc_points` に渡す
+/// (`simd::calc_iterations_x2` / `interleave::calc_iterations_interleaved`)。
+/// キャッシュの読み書きと hit count の数え方は `calc_band` と同じ
+fn calc_band_batched(
+    job: &mut IterationJob,
+    band_scaled_y_from: u32,
+    band_scaled_y_to: u32,
+    calc_points: PointsKernel,
+) {
+    let x_diff = job.x_diff;
+    let y_diff = job.y_diff;
+    let scaled_w = job.scaled_width;
+    let area_w = job.area_width as f64;
+    let start_x = job.area_start_x as f64;
+    let start_y = job.area_start_y as f64;
+    let is_super_sampling = job.is_super_sampling;
+    let is_result_pass = job.is_result_pass;
+    let max_iteration = job.max_iteration;
+
+    let mut points = Vec::with_capacity(scaled_w as usize);
+    // (scaled index, area index)
+    let mut targets = Vec::with_capacity(scaled_w as usize);
+    let mut results = Vec::with_capacity(scaled_w as usize);
+
+    for scaled_y in band_scaled_y_from..band_scaled_y_to {
+        let y = start_y + (scaled_y as f64) * y_diff;
+        points.clear();
+        targets.clear();
+
+        for scaled_x in 0..scaled_w {
+            let x = start_x + (scaled_x as f64) * x_diff;
+            let scaled_index = (scaled_x + scaled_y * scaled_w) as usize;
+
+            // supersampling 時は iterations キャッシュを使わない (`calc_band` を参照)
+            let area_index = if is_super_sampling {
+                None
+            } else {
+                let index = (x - start_x + (y - start_y) * area_w) as usize;
+                let cached = job.iterations[index];
+                if cached != 0 {
+                    job.scaled_iterations[scaled_index] = cached;
+                    if is_result_pass && cached == max_iteration {
+                        job.hit_count += 1;
+                    }
+                    continue;
+                }
+                Some(index)
+            };
+            points.push((x, y));
+            targets.push((scaled_index, area_index));
+        }
+
+        results.clear();
+        results.resize(points.len(), 0);
+        calc_points(job, &job.primary, &points, &mut results);
+
+        for (&(scaled_index, area_index), &n) in targets.iter().zip(&results) {
+            job.calculated_count += 1;
+            if let Some(index) = area_index {
+                job.iterations[index] = n;
+            }
+            job.scaled_iterations[scaled_index] = n;
+            if is_result_pass && n == max_iteration {
+                job.hit_count += 1;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compat::*;
+
+    /// 決定的な疑似乱数生成器 (mulberry32)。TS 側のテストと同じもの
+    fn create_random(seed: u32) -> impl FnMut() -> f64 {
+        let mut state = seed;
+        move || {
+            state = state.wrapping_add(0x6d2b_79f5);
+            let mut t = state;
+            t = (t ^ (t >> 15)).wrapping_mul(t | 1);
+            t ^= t.wrapping_add((t ^ (t >> 7)).wrapping_mul(t | 61));
+            ((t ^ (t >> 14)) as f64) / 4294967296.0
+        }
+    }
+
+    /// c を中心とした reference orbit を double で計算して [re, im, ...] で返す
+    fn create_xn(c_re: f64, c_im: f64, length: usize) -> Vec<f64> {
+        let mut xn = Vec::with_capacity(length * 2);
+        let (mut z_re, mut z_im) = (0.0f64, 0.0f64);
+        for _ in 0..length {
+            xn.push(z_re);
+            xn.push(z_im);
+            let next_re = z_re * z_re - z_im * z_im + c_re;
+            z_im = 2.0 * z_re * z_im + c_im;
+            z_re = next_re;
+        }
+        xn
+    }
+
+    /// `mandelbrot-iteration-wasm.test.ts` と同じ作りのテスト用 BLATable。
+    /// 中身は数学的に正しくなくてよく、BLA が採用される/されない経路が両方出ればよい
+    fn create_bla_table(
+        row_count: usize,
+        max_ref_iteration: usize,
+        seed: u32,
+    ) -> (Vec<u8>, Vec<i32>) {
+        let mut random = create_random(seed);
+        let mut bytes = Vec::new();
+        let mut row_offsets = Vec::new();
+        bytes.extend_from_slice(&(row_count as i32).to_le_bytes());
+        for d in 0..row_count {
+            let row_length = if d < 2 {
+                0
+            } else {
+                (max_ref_iteration >> d) + 2
+            };
+            bytes.extend_from_slice(&(row_length as i32).to_le_bytes());
+            row_offsets.push(bytes.len() as i32);
+            row_offsets.push(row_length as i32);
+            for _ in 0..row_length {
+                let a_re = 1.0 + (random() - 0.5) * 0.1;
+                let a_im = (random() - 0.5) * 0.1;
+                let b_re = (random() - 0.5) * 0.1;
+                let b_im = (random() - 0.5) * 0.1;
+                let r_sq = 10f64.powf(-8.0 * random());
+                for v in [a_re, a_im, b_re, b_im, r_sq] {
+                    bytes.extend_from_slice(&v.to_le_bytes());
+                }
+                bytes.extend_from_slice(&(1i32 << d).to_le_bytes());
+            }
+        }
+        (bytes, row_offsets)
+    }
+
+    const AREA_W: u32 = 48;
+    const AREA_H: u32 = 32;
+
+    /// JS の worker と同じ手順で job を組み立てる
+    fn setup_job(max_iteration: u32) {
+        setup_job_with_bla_rows(max_iteration, 12);
+    }
+
+    /// `bla_rows` に 0 を渡すと BLA を使わない
+    fn setup_job_with_bla_rows(max_iteration: u32, bla_rows: u32) {
+        let xn = create_xn(-0.7451, 0.11302, 512);
+        let (bla_bytes, row_offsets) = create_bla_table(12, xn.len() / 2 - 1, 12345);
+        setup_job_with_table(max_iteration, &xn, &bla_bytes, &row_offsets, bla_rows, 5e-4);
+    }
+
+    fn setup_job_with_table(
+        max_iteration: u32,
+        xn: &[f64],
+        bla_bytes: &[u8],
+        row_offsets: &[i32],
+        bla_rows: u32,
+        delta_c_scale: f64,
+    ) {
+        with_job(|job| {
+            setup_iteration_job(
+                job,
+                max_iteration,
+                xn,
+                bla_bytes,
+                row_offsets,
+                bla_rows,
+                delta_c_scale,
+            )
+        });
+    }
+
+    fn setup_iteration_job(
+        job: &mut IterationJob,
+        max_iteration: u32,
+        xn: &[f64],
+        bla_bytes: &[u8],
+        row_offsets: &[i32],
+        bla_rows: u32,
+        delta_c_scale: f64,
+    ) {
+        let area_pixels = AREA_W * AREA_H;
+
+        job.alloc(
+            xn.len() as u32,
+            bla_bytes.len() as u32,
+            row_offsets.len() as u32,
+            area_pixels,
+            area_pixels,
+        );
+        job.primary.xn[..xn.len()].copy_from_slice(xn);
+        job.primary.bla_bytes[..bla_bytes.len()].copy_from_slice(bla_bytes);
+        job.primary.bla_row_offsets[..row_offsets.len()].copy_from_slice(row_offsets);
+        job.begin(
+            max_iteration,
+            (xn.len() / 2 - 1) as u32,
+            bla_rows,
+            2,
+            delta_c_scale,
+            (AREA_W / 2) as f64,
+            (AREA_H / 2) as f64,
+            AREA_W,
+            AREA_H,
+            0,
+            0,
+        );
+    }
+
+    /// 1 pass 走らせて scaled_iterations を返す
+    fn run_pass(diff: f64, is_result_pass: bool) -> Vec<u32> {
+        let scaled_w = (AREA_W as f64 / diff) as u32;
+        let scaled_h = (AREA_H as f64 / diff) as u32;
+        begin_pass(diff, diff, scaled_w, false, is_result_pass);
+        calc_iteration_band(0, scaled_h);
+        with_job(|job| job.scaled_iterations[..(scaled_w * scaled_h) as usize].to_vec())
+    }
+
+    fn accum_output() -> (Vec<f64>, Vec<u32>) {
+        let pixels = (AREA_W * AREA_H) as usize;
+        with_job(|job| {
+            (
+                job.accum_values.scaled[..pixels].to_vec(),
+                job.accum_iterations.scaled[..pixels].to_vec(),
+            )
+        })
+    }
+
+    #[test]
+    fn reads_bla_table_built_by_wasm_fp() {
+        assert_eq!(ITEM_BYTE_LENGTH, apfp::bla::ITEM_BYTE_LENGTH);
+
+        // BLA が効くくらいまで拡大した area で、BLA なしと比べる
+        let delta_c_scale = 1e-10;
+        let mut xn = create_xn(-1.25066, 0.02012, 5000);
+        if let Some(escaped) = xn
+            .chunks(2)
+            .position(|z| n_norm(z[0], z[1]) > BAILOUT_RADIUS)
+        {
+            xn.truncate(escaped * 2);
+        }
+        setup_job_with_table(5000, &xn, &[], &[], 0, delta_c_scale);
+        let plain = run_pass(1.0, true);
+
+        let table = apfp::bla::build_bla_table(&xn, delta_c_scale);
+        let bla_rows = (table.row_offsets.len() / 2) as u32;
+        let usable = (2..bla_rows as usize)
+            .flat_map(|row| {
+                let offset = table.row_offsets[row * 2] as usize;
+                (0..table.row_offsets[row * 2 + 1] as usize)
+                    .map(move |j| offset + j * ITEM_BYTE_LENGTH)
+            })
+            .filter(|&offset| bla::read_f64(&table.bytes, offset + 32) > 0.0)
+            .count();
+        assert!(usable > 0);
+
+        setup_job_with_table(
+            5000,
+            &xn,
+            &table.bytes,
+            &table.row_offsets,
+            bla_rows,
+            delta_c_scale,
+        );
+        let with_bla = run_pass(1.0, true);
+        assert_eq!(with_bla, plain);
+    }
+
+    #[test]
+    fn orbit_trap_survives_low_res_passes() {
+        assert!(set_orbit_trap(3, 0.0, 0.0, 0.25));
+
+        setup_job(500);
+        for diff in [8.0, 4.0, 2.0] {
+            run_pass(diff, false);
+        }
+        let with_low_res = (run_pass(1.0, true), accum_output());
+
+        setup_job(500);
+        let standalone = (run_pass(1.0, true), accum_output());
+
+        assert_eq!(with_low_res, standalone);
+        let (iterations, (values, trap_iterations)) = standalone;
+        // 最小距離をとった iteration は必ずそのピクセルの iteration 以下になる
+        for ((n, value), trap_n) in iterations.iter().zip(&values).zip(&trap_iterations) {
+            if *n > 1 {
+                assert!(value.is_finite());
+                assert!(trap_n <= n);
+            }
+        }
+        clear_accumulation();
+    }
+
+    #[test]
+    fn average_coloring_outputs_are_normalized() {
+        for kind in [0, 1] {
+            assert!(set_average_coloring(kind));
+            setup_job(500);
+            let iterations = run_pass(1.0, true);
+            let (values, counts) = accum_output();
+            let prev_values = with_job(|job| job.accum_prev_values.scaled[..values.len()].to_vec());
+            for i in 0..iterations.len() {
+                assert!((0.0..=1.0).contains(&values[i]), "kind={kind}");
+                assert!((0.0..=1.0).contains(&prev_values[i]), "kind={kind}");
+                assert!(counts[i] <= iterations[i]);
+            }
+            // prev_value が書かれていないと 0 のままで範囲の確認には引っかからないので、
+            // bailout したピクセルで value と別の値が入っていることも確かめる
+            let escaped: Vec<usize> = (0..iterations.len())
+                .filter(|&i| iterations[i] < 500 && counts[i] >= 2)
+                .collect();
+            assert!(!escaped.is_empty(), "kind={kind}");
+            for i in escaped {
+                assert_ne!(prev_values[i], 0.0, "kind={kind}");
+                assert_ne!(prev_values[i], values[i], "kind={kind}");
+            }
+        }
+        assert!(!set_average_coloring(2));
+        clear_accumulation();
+    }
+
+    #[test]
+    fn interior_detection_only_shortcuts_max_iteration_pixels() {
+        // 追加出力のある job は f64 で計算するので、比べる側も f64 にする
+        set_f32_fast_path(false);
+        setup_job(3000);
+        let plain = run_pass(1.0, true);
+
+        set_interior_detection(1, 1e-24, 0.0);
+        setup_job(3000);
+        let detected = run_pass(1.0, true);
+        let periods = with_job(|job| job.interior_periods.scaled[..plain.len()].to_vec());
+        let multipliers = with_job(|job| job.interior_multipliers.scaled[..plain.len()].to_vec());
+        set_interior_detection(0, 0.0, 0.0);
+
+        for i in 0..plain.len() {
+            if periods[i] == 0 {
+                assert_eq!(detected[i], plain[i]);
+                assert_eq!(multipliers[i], 0.0);
+            } else {
+                assert_eq!(detected[i], 3000);
+                assert!(multipliers[i].is_finite());
+            }
+        }
+        assert!(periods.iter().any(|&p| p > 0));
+    }
+
+    #[test]
+    fn atom_domain_does_not_change_iterations() {
+        // 追加出力のある job は f64 で計算するので、比べる側も f64 にする
+        set_f32_fast_path(false);
+        setup_job(2000);
+        let plain = run_pass(1.0, true);
+
+        set_atom_domain(true);
+        setup_job(2000);
+        for diff in [4.0, 2.0] {
+            run_pass(diff, false);
+        }
+        let with_atom = run_pass(1.0, true);
+        let atoms = with_job(|job| job.atom_domains.scaled[..plain.len()].to_vec());
+        set_atom_domain(false);
+
+        assert_eq!(with_atom, plain);
+        for (atom, n) in atoms.iter().zip(&plain) {
+            assert!(atom <= n);
+        }
+        assert!(atoms.iter().any(|&p| p > 1));
+    }
+
+    #[test]
+    fn glitch_count_matches_flags() {
+        // 追加出力のある job は f64 で計算するので、比べる側も f64 にする
+        set_f32_fast_path(false);
+        setup_job(2000);
+        let plain = run_pass(1.0, true);
+
+        set_glitch_detection(1e-2);
+        setup_job(2000);
+        run_pass(2.0, false);
+        let detected = run_pass(1.0, true);
+        let flags = with_job(|job| job.glitch_flags.scaled[..plain.len()].to_vec());
+        let count = get_glitch_count();
+        set_glitch_detection(0.0);
+
+        assert_eq!(detected, plain);
+        let flagged = flags.iter().filter(|&&f| f != 0).count() as u32;
+        assert_eq!(count, flagged);
+        assert!(count > 0);
+        assert!((count as usize) < plain.len());
+    }
+
+    #[test]
+    fn secondary_reference_corrects_glitches() {
+        set_glitch_detection(1e-2);
+        setup_job(2000);
+        run_pass(1.0, true);
+        let primary_count = get_glitch_count();
+
+        // 左上寄りに BLA なしの reference をもう 1 本置く
+        let (ref_x, ref_y) = (6.0, 4.0);
+        let c_re = -0.7451 + (ref_x - (AREA_W / 2) as f64) * 5e-4;
+        let c_im = 0.11302 - (ref_y - (AREA_H / 2) as f64) * 5e-4;
+        let xn = create_xn(c_re, c_im, 2048);
+        setup_job(2000);
+        alloc_reference(1, xn.len() as u32, 0, 0);
+        with_job(|job| job.secondary_references[0].xn[..xn.len()].copy_from_slice(&xn));
+        set_reference(1, (xn.len() / 2 - 1) as u32, 0, ref_x, ref_y);
+        let corrected = run_pass(1.0, true);
+        let flags = with_job(|job| job.glitch_flags.scaled[..corrected.len()].to_vec());
+        let corrected_count = get_glitch_count();
+        set_glitch_detection(0.0);
+
+        let flagged = flags.iter().filter(|&&f| f != 0).count() as u32;
+        assert_eq!(corrected_count, flagged);
+        assert!(corrected_count < primary_count);
+        // begin で secondary reference は使われなくなる
+        setup_job(2000);
+        with_job(|job| assert_eq!(job.secondary_count, 0));
+    }
+
+    #[test]
+    fn rejects_non_contiguous_references() {
+        set_glitch_detection(1e-2);
+        let xn = create_xn(-0.7451, 0.11302, 512);
+        setup_job(500);
+        alloc_reference(2, xn.len() as u32, 0, 0);
+        with_job(|job| job.secondary_references[1].xn[..xn.len()].copy_from_slice(&xn));
+
+        // index 1 を飛ばして 2 を設定すると、空の reference 1 を使わないように job を止める
+        assert_eq!(set_reference(2, (xn.len() / 2 - 1) as u32, 0, 3.0, 3.0), 8);
+        with_job(|job| assert_eq!(job.secondary_count, 0));
+        assert_eq!(validate_job(), 8);
+        begin_pass(1.0, 1.0, AREA_W, false, true);
+        assert_eq!(calc_iteration_band(0, AREA_H), 8);
+        with_job(|job| assert_eq!(job.calculated_count, 0));
+        set_glitch_detection(0.0);
+
+        // 上限を超える index ではバッファを確保しない
+        assert!(!alloc_reference(1000, 16, 0, 0));
+        assert!(reference_xn_ptr(1000).is_null());
+        with_job(|job| assert!(job.secondary_references.len() <= 16));
+        assert_eq!(set_reference(1000, 0, 0, 0.0, 0.0), 8);
+
+        setup_job(500);
+        assert_eq!(validate_job(), 0);
+    }
+
+    #[test]
+    fn series_approximation_skips_initial_iterations() {
+        // テスト用の BLATable は数学的に正しくないので、BLA なしの perturbation と比べる
+        set_f32_fast_path(false);
+        setup_job_with_bla_rows(2000, 0);
+        let plain = run_pass(1.0, true);
+        assert_eq!(get_series_skip(), 0);
+
+        set_series_approximation(16, 1e-12);
+        setup_job_with_bla_rows(2000, 0);
+        let skip = get_series_skip();
+        let with_series = run_pass(1.0, true);
+        set_series_approximation(0, 0.0);
+        assert!(skip > 0);
+
+        // probe 点のうち area 内にあるもの (四隅と各辺の中点のうち左上側) は一致する
+        for (x, y) in [(0, 0), (AREA_W / 2, 0), (0, AREA_H / 2)] {
+            let i = (x + y * AREA_W) as usize;
+            assert_eq!(with_series[i], plain[i], "probe ({x}, {y})");
+        }
+        // それ以外も、境界近くで誤差が増幅されるピクセルが数 iteration ずれるだけ
+        for (i, (&a, &b)) in with_series.iter().zip(&plain).enumerate() {
+            assert!(a.abs_diff(b) <= 4, "pixel {i}: {a} vs {b}");
+        }
+
+        // 追加出力のある job ではスキップしないので、series approximation の有無で結果が変わらない
+        set_glitch_detection(1e-2);
+        setup_job_with_bla_rows(2000, 0);
+        let plain = run_pass(1.0, true);
+        set_series_approximation(16, 1e-12);
+        setup_job_with_bla_rows(2000, 0);
+        let with_series = run_pass(1.0, true);
+        set_series_approximation(0, 0.0);
+        set_glitch_detection(0.0);
+        assert_eq!(with_series, plain);
+    }
+
+    #[test]
+    fn bla_table_is_rebuilt_only_after_upload() {
+        setup_job(500);
+        let begin_again = || {
+            with_job(|job| {
+                job.begin(
+                    500,
+                    job.primary.max_ref_iteration,
+                    12,
+                    2,
+                    5e-4,
+                    (AREA_W / 2) as f64,
+                    (AREA_H / 2) as f64,
+                    AREA_W,
+                    AREA_H,
+                    0,
+                    0,
+                )
+            })
+        };
+        // 要素のある最初の段の先頭の A.re を書き換える
+        let (row, a_re) = with_job(|job| {
+            let row = (0..12)
+                .find(|&row| job.primary.bla_row_offsets[row * 2 + 1] > 0)
+                .unwrap();
+            let offset = job.primary.bla_row_offsets[row * 2] as usize;
+            let a_re = job
+                .primary
+                .bla
+                .step(job.primary.bla.index(row as i32, 0))
+                .a_re;
+            job.primary.bla_bytes[offset..offset + 8].copy_from_slice(&(a_re + 1.0).to_le_bytes());
+            (row as i32, a_re)
+        });
+        let read_a_re = || with_job(|job| job.primary.bla.step(job.primary.bla.index(row, 0)).a_re);
+
+        // ポインタを渡していなければ、前の job で作った表をそのまま使う
+        begin_again();
+        assert_eq!(read_a_re(), a_re);
+
+        // ポインタを渡したあとの job では作り直す
+        bla_bytes_ptr();
+        begin_again();
+        assert_eq!(read_a_re(), a_re + 1.0);
+    }
+
+    #[test]
+    fn malformed_inputs_refuse_bands() {
+        let xn = create_xn(-0.7451, 0.11302, 512);
+        let (bla_bytes, row_offsets) = create_bla_table(12, xn.len() / 2 - 1, 12345);
+
+        // xn が max_ref_iteration に足りない
+        setup_job_with_table(500, &xn[..200], &bla_bytes, &row_offsets, 0, 5e-4);
+        with_job(|job| job.primary.max_ref_iteration = 511);
+        assert_eq!(validate_job(), 1);
+        assert!(get_job_error_message().contains("xn"));
+
+        // row offsets が bla_bytes の外を指している
+        let mut broken = row_offsets.clone();
+        broken[2 * 5] = bla_bytes.len() as i32;
+        setup_job_with_table(500, &xn, &bla_bytes, &broken, 12, 5e-4);
+        assert_eq!(validate_job(), 3);
+        begin_pass(1.0, 1.0, AREA_W, false, true);
+        assert_eq!(calc_iteration_band(0, AREA_H), 3);
+        with_job(|job| assert_eq!(job.calculated_count, 0));
+
+        // bla_rows に対して row offsets が足りない
+        setup_job_with_table(500, &xn, &bla_bytes, &row_offsets[..8], 12, 5e-4);
+        assert_eq!(validate_job(), 2);
+
+        // 正しい job では band の範囲を検証する
+        setup_job(500);
+        assert_eq!(validate_job(), 0);
+        assert!(get_job_error_message().is_empty());
+        begin_pass(1.0, 1.0, AREA_W, false, true);
+        assert_eq!(calc_iteration_band(0, AREA_H + 1), 6);
+        begin_pass(2.0, 2.0, AREA_W, false, true);
+        assert_eq!(calc_iteration_band(0, 2), 5);
+        begin_pass(1.0, 1.0, AREA_W, false, true);
+        assert_eq!(calc_iteration_band(0, AREA_H), 0);
+    }
+
+    #[test]
+    fn jobs_keep_their_own_buffers() {
+        setup_job(2000);
+        let expected_main = run_pass(1.0, true);
+        setup_job(100);
+        let expected_preview = run_pass(1.0, true);
+
+        // preview と本番の job を band ごとに交互に進めても、それぞれ単独で計算した結果と一致する
+        let xn = create_xn(-0.7451, 0.11302, 512);
+        let (bla_bytes, row_offsets) = create_bla_table(12, xn.len() / 2 - 1, 12345);
+        let mut main = IterationJob::new();
+        let mut preview = IterationJob::new();
+        for (job, max_iteration) in [(&mut main, 2000), (&mut preview, 100)] {
+            setup_iteration_job(job, max_iteration, &xn, &bla_bytes, &row_offsets, 12, 5e-4);
+            job.begin_pass(1.0, 1.0, AREA_W, false, true);
+        }
+        for y in 0..AREA_H {
+            assert_eq!(main.calc_band(y, y + 1), 0);
+            assert_eq!(preview.calc_band(y, y + 1), 0);
+        }
+        let pixels = (AREA_W * AREA_H) as usize;
+        assert_eq!(main.scaled_iterations[..pixels], expected_main);
+        assert_eq!(preview.scaled_iterations[..pixels], expected_preview);
+        assert_eq!(main.calculated_count(), AREA_W * AREA_H);
+
+        // release したあとは alloc し直すまで band を計算しない
+        main.release();
+        assert!(main.scaled_iterations.is_empty());
+        assert_ne!(main.calc_band(0, 1), 0);
+    }
+
+    #[test]
+    fn buffers_shrink_to_last_alloc() {
+        let mut job = IterationJob::new();
+        job.set_atom_domain(true);
+        job.alloc(1 << 16, 1 << 16, 64, 1 << 16, 1 << 16);
+        job.begin(100, 0, 0, 2, 1e-3, 0.0, 0.0, 256, 256, 0, 0);
+        let large = job.buffer_bytes();
+        assert!(large >= (1 << 16) * (8 + 1 + 4 * 2 + 4 * 2));
+
+        // 小さい job に切り替えても alloc だけでは縮まない
+        let xn = create_xn(-0.7451, 0.11302, 512);
+        let (bla_bytes, row_offsets) = create_bla_table(12, xn.len() / 2 - 1, 12345);
+        job.set_atom_domain(false);
+        setup_iteration_job(&mut job, 500, &xn, &bla_bytes, &row_offsets, 12, 5e-4);
+        assert!(job.buffer_bytes() >= large);
+
+        job.shrink();
+        let small = job.buffer_bytes();
+        assert!(small < large / 4);
+        assert!(job.atom_domains.scaled.is_empty());
+        // 縮めたあとも同じ job の計算は続けられる
+        job.begin_pass(1.0, 1.0, AREA_W, false, true);
+        assert_eq!(job.calc_band(0, AREA_H), 0);
+
+        job.release();
+        assert_eq!(job.buffer_bytes(), 0);
+    }
+
+    /// simd128 の x2 kernel と、2〜4 ピクセルの interleave kernel
+    const BATCHED_KERNELS: [PointsKernel; 4] = [
+        simd::calc_iterations_x2,
+        interleave::calc_iterations_interleaved::<2>,
+        interleave::calc_iterations_interleaved::<3>,
+        interleave::calc_iterations_interleaved::<4>,
+    ];
+
+    /// 現在の job の全ピクセルを scalar とまとめて計算する kernel で計算して比べる
+    fn assert_batched_matches_scalar() {
+        with_job(|job| {
+            let points: Vec<(f64, f64)> = (0..AREA_H)
+                .flat_map(|y| (0..AREA_W).map(move |x| (x as f64, y as f64)))
+                .collect();
+            let scalar: Vec<u32> = points
+                .iter()
+                .map(|&(x, y)| calc_iteration_at(job, &job.primary, x, y, &mut NoObserver))
+                .collect();
+            for calc_points in BATCHED_KERNELS {
+                // 枠の数で割り切れなくても、最後は 1 つずつ計算できる
+                for len in [points.len(), points.len() - 1, points.len() - 2, 1] {
+                    let mut results = vec![0; len];
+                    calc_points(job, &job.primary, &points[..len], &mut results);
+                    assert_eq!(results, scalar[..len]);
+                }
+            }
+        });
+    }
+
+    #[test]
+    fn batched_kernels_match_scalar() {
+        setup_job(2000);
+        assert_batched_matches_scalar();
+        setup_job(7);
+        assert_batched_matches_scalar();
+        setup_job_with_bla_rows(500, 0);
+        assert_batched_matches_scalar();
+
+        set_series_approximation(16, 1e-12);
+        setup_job_with_bla_rows(500, 0);
+        assert!(get_series_skip() > 0);
+        assert_batched_matches_scalar();
+        set_series_approximation(0, 0.0);
+
+        let delta_c_scale = 1e-10;
+        let xn = create_xn(-1.25066, 0.02012, 4096);
+        let table = apfp::bla::build_bla_table(&xn, delta_c_scale);
+        let bla_rows = (table.row_offsets.len() / 2) as u32;
+        setup_job_with_table(
+            4000,
+            &xn,
+            &table.bytes,
+            &table.row_offsets,
+            bla_rows,
+            delta_c_scale,
+        );
+        assert_batched_matches_scalar();
+    }
+
+    #[test]
+    fn batched_band_matches_scalar_band() {
+        let run_banded_pass = |calc: &dyn Fn(&mut IterationJob, u32)| {
+            setup_job(2000);
+            let passes: Vec<_> = [4.0, 2.0, 1.0]
+                .into_iter()
+                .map(|diff| {
+                    let scaled_w = (AREA_W as f64 / diff) as u32;
+                    let scaled_h = (AREA_H as f64 / diff) as u32;
+                    with_job(|job| {
+                        job.begin_pass(diff, diff, scaled_w, false, diff == 1.0);
+                        calc(job, scaled_h);
+                        let pixels = (scaled_w * scaled_h) as usize;
+                        (job.scaled_iterations[..pixels].to_vec(), job.hit_count)
+                    })
+                })
+                .collect();
+            assert_eq!(get_calculated_count(), AREA_W * AREA_H);
+            passes
+        };
+
+        let scalar = run_banded_pass(&|job, scaled_h| calc_band::<PlainKernel>(job, 0, scaled_h));
+        for calc_points in BATCHED_KERNELS {
+            let batched =
+                run_banded_pass(&|job, scaled_h| calc_band_batched(job, 0, scaled_h, calc_points));
+            assert_eq!(batched, scalar);
+        }
+    }
+
+    #[test]
+    fn interleaved_pixels_keep_results() {
+        set_f32_fast_path(false);
+        setup_job(2000);
+        let expected = run_pass(1.0, true);
+        for count in [2, 4, 9] {
+            set_interleaved_pixels(count);
+            setup_job(2000);
+            assert_eq!(run_pass(1.0, true), expected);
+        }
+        with_job(|job| assert_eq!(job.interleaved_pixels, 4));
+        set_interleaved_pixels(1);
+    }
+
+    /// 現在の job の全ピクセルを f32 の kernel で計算し、f64 と iteration 数が違うピクセル数を返す。
+    /// 比較用に、f64 でピクセル座標を `jitter` だけずらした場合に違うピクセル数も返す
+    fn count_f32_mismatches(jitter: f64) -> (usize, usize) {
+        with_job(|job| {
+            assert!(job.uses_f32());
+            let points: Vec<(f64, f64)> = (0..AREA_H)
+                .flat_map(|y| (0..AREA_W).map(move |x| (x as f64, y as f64)))
+                .collect();
+            let mut results = vec![0; points.len()];
+            fast32::calc_iterations_f32x4(job, &job.primary, &points, &mut results);
+            let mut f32_mismatches = 0;
+            let mut jitter_mismatches = 0;
+            for (&(x, y), &n) in points.iter().zip(&results) {
+                let expected = calc_iteration_at(job, &job.primary, x, y, &mut NoObserver);
+                let jittered = calc_iteration_at(job, &job.primary, x + jitter, y, &mut NoObserver);
+                f32_mismatches += (n != expected) as usize;
+                jitter_mismatches += (jittered != expected) as usize;
+            }
+            (f32_mismatches, jitter_mismatches)
+        })
+    }
+
+    #[test]
+    fn f32_kernel_follows_f64_on_shallow_zoom() {
+        // seahorse valley の境界付近は、f64 でも座標がわずかにずれるだけで iteration 数が変わる。
+        // f32 の丸め (Δc の相対誤差 6e-8 = 1e-6 ピクセル程度) とその程度のずれで、違いが同程度なら良しとする
+        set_f32_fast_path(true);
+        let delta_c_scale = 1e-4;
+        let xn = create_xn(-0.7451, 0.11302, 4096);
+        let table = apfp::bla::build_bla_table(&xn, delta_c_scale);
+        let bla_rows = (table.row_offsets.len() / 2) as u32;
+        for rows in [bla_rows, 0] {
+            setup_job_with_table(
+                4000,
+                &xn,
+                &table.bytes,
+                &table.row_offsets,
+                rows,
+                delta_c_scale,
+            );
+            let (f32_mismatches, jitter_mismatches) = count_f32_mismatches(1e-5);
+            assert!(f32_mismatches <= jitter_mismatches * 2);
+        }
+
+        // 集合から少し離れて iteration 数がなめらかに変わる領域では、ほとんど一致する
+        let xn = create_xn(-0.75, 0.15, 4096);
+        let table = apfp::bla::build_bla_table(&xn, delta_c_scale);
+        setup_job_with_table(
+            4000,
+            &xn,
+            &table.bytes,
+            &table.row_offsets,
+            (table.row_offsets.len() / 2) as u32,
+            delta_c_scale,
+        );
+        let (f32_mismatches, jitter_mismatches) = count_f32_mismatches(1e-5);
+        assert!(f32_mismatches <= jitter_mismatches * 2);
+        assert!(f32_mismatches * 100 <= (AREA_W * AREA_H) as usize);
+        set_f32_fast_path(false);
+    }
+
+    #[test]
+    fn f32_is_used_only_on_shallow_plain_jobs() {
+        // 既定では使わない
+        setup_job(2000);
+        assert!(!get_uses_f32());
+
+        set_f32_fast_path(true);
+        setup_job(2000);
+        assert!(get_uses_f32());
+
+        // 深いズーム
+        let xn = create_xn(-0.7451, 0.11302, 512);
+        let (bla_bytes, row_offsets) = create_bla_table(12, xn.len() / 2 - 1, 12345);
+        setup_job_with_table(2000, &xn, &bla_bytes, &row_offsets, 12, 1e-12);
+        assert!(!get_uses_f32());
+
+        // 追加出力のある job
+        set_atom_domain(true);
+        setup_job(2000);
+        assert!(!get_uses_f32());
+        set_atom_domain(false);
+
+        // bailout までの orbit に f32 の正規化数で表せない成分がある
+        let mut xn = create_xn(-0.7451, 0.11302, 512);
+        xn[5 * 2 + 1] = 1e-40;
+        setup_job_with_table(2000, &xn, &bla_bytes, &row_offsets, 12, 5e-4);
+        assert!(!get_uses_f32());
+        // bailout したあとの点は見ない
+        let mut xn = create_xn(-0.7451, 0.11302, 512);
+        xn[500 * 2 + 1] = 1e-40;
+        setup_job_with_table(2000, &xn, &bla_bytes, &row_offsets, 12, 5e-4);
+        assert!(get_uses_f32());
+        set_f32_fast_path(false);
+    }
+
+    /// JS の mandelbrot-worker.ts にあったループをそのまま移したもの
+    fn direct_loop(max_iteration: u32, c_re: f64, c_im: f64) -> u32 {
+        let (mut z_re, mut z_im) = (0.0f64, 0.0f64);
+        let mut n = 0;
+        while z_re * z_re + z_im * z_im <= 4.0 && n < max_iteration {
+            let next_re = z_re * z_re - z_im * z_im + c_re;
+            z_im = 2.0 * z_re * z_im + c_im;
+            z_re = next_re;
+            n += 1;
+        }
+        n
+    }
+
+    const DIRECT_SCALE: f64 = 4e-3;
+
+    /// direct mode の job を組み立てる。area の中心が c = -0.75 + 0.1i
+    fn setup_direct_job(job: &mut IterationJob, max_iteration: u32, is_super_sampling: bool) {
+        let area_pixels = AREA_W * AREA_H;
+        let (area, scaled) = if is_super_sampling {
+            (0, area_pixels * 4)
+        } else {
+            (area_pixels, area_pixels)
+        };
+        job.alloc(0, 0, 0, area, scaled);
+        job.begin_direct(
+            max_iteration,
+            -0.75,
+            0.1,
+            DIRECT_SCALE,
+            (AREA_W / 2) as f64,
+            (AREA_H / 2) as f64,
+            AREA_W,
+            AREA_H,
+            0,
+            0,
+        );
+    }
+
+    #[test]
+    fn direct_job_matches_plain_loop() {
+        let expected_at = |x: f64, y: f64| {
+            let c_re = -0.75 + (x - (AREA_W / 2) as f64) * DIRECT_SCALE;
+            let c_im = 0.1 - (y - (AREA_H / 2) as f64) * DIRECT_SCALE;
+            direct_loop(500, c_re, c_im)
+        };
+
+        // 低解像度の pass を重ねても、最終 pass はキャッシュ込みで全ピクセルを 1 回ずつ計算する
+        let mut job = IterationJob::new();
+        setup_direct_job(&mut job, 500, false);
+        assert_eq!(job.validate(), 0);
+        for diff in [4.0, 2.0, 1.0] {
+            let scaled_w = (AREA_W as f64 / diff) as u32;
+            let scaled_h = (AREA_H as f64 / diff) as u32;
+            job.begin_pass(diff, diff, scaled_w, false, diff == 1.0);
+            for y in 0..scaled_h {
+                assert_eq!(job.calc_band(y, y + 1), 0);
+            }
+        }
+        let expected: Vec<u32> = (0..AREA_H)
+            .flat_map(|y| (0..AREA_W).map(move |x| expected_at(x as f64, y as f64)))
+            .collect();
+        let pixels = (AREA_W * AREA_H) as usize;
+        assert_eq!(job.scaled_iterations[..pixels], expected);
+        assert_eq!(job.calculated_count(), AREA_W * AREA_H);
+        let hits = expected.iter().filter(|&&n| n == 500).count();
+        assert!(hits > 0 && hits < pixels);
+        assert_eq!(job.hit_count() as usize, hits);
+
+        // supersampling はキャッシュを使わずに 0.5 pixel 刻みで計算する
+        setup_direct_job(&mut job, 500, true);
+        job.begin_pass(0.5, 0.5, AREA_W * 2, true, true);
+        assert_eq!(job.calc_band(0, AREA_H * 2), 0);
+        let expected: Vec<u32> = (0..AREA_H * 2)
+            .flat_map(|y| (0..AREA_W * 2).map(move |x| expected_at(x as f64 * 0.5, y as f64 * 0.5)))
+            .collect();
+        assert_eq!(job.scaled_iterations[..pixels * 4], expected);
+        assert_eq!(job.calculated_count(), AREA_W * AREA_H * 4);
+    }
+
+    /// direct mode の view で job を始め、1:1 の pass を全部計算して出力を返す
+    fn run_direct_view(
+        job: &mut IterationJob,
+        max_iteration: u32,
+        center_pixel_x: f64,
+        center_pixel_y: f64,
+    ) -> Vec<u32> {
+        job.begin_direct(
+            max_iteration,
+            -0.75,
+            0.1,
+            DIRECT_SCALE,
+            center_pixel_x,
+            center_pixel_y,
+            AREA_W,
+            AREA_H,
+            0,
+            0,
+        );
+        job.begin_pass(1.0, 1.0, AREA_W, false, true);
+        assert_eq!(job.calc_band(0, AREA_H), 0);
+        job.scaled_iterations[..(AREA_W * AREA_H) as usize].to_vec()
+    }
+
+    #[test]
+    fn iteration_shift_reuses_the_cache_of_a_panned_view() {
+        let (center_x, center_y) = ((AREA_W / 2) as f64, (AREA_H / 2) as f64);
+        let mut job = IterationJob::new();
+        setup_direct_job(&mut job, 200, false);
+        run_direct_view(&mut job, 200, center_x, center_y);
+
+        // 同じ c を右に 5、上に 2 ピクセル動かした view
+        job.set_iteration_shift(5, -2);
+        let shifted = run_direct_view(&mut job, 200, center_x + 5.0, center_y - 2.0);
+        let reused = (AREA_W - 5) * (AREA_H - 2);
+        assert_eq!(job.reused_count(), reused);
+        assert_eq!(job.calculated_count(), AREA_W * AREA_H);
+        assert_eq!(job.iterated_count(), AREA_W * AREA_H - reused);
+
+        let mut fresh = IterationJob::new();
+        setup_direct_job(&mut fresh, 200, false);
+        let expected = run_direct_view(&mut fresh, 200, center_x + 5.0, center_y - 2.0);
+        assert_eq!(shifted, expected);
+    }
+
+    #[test]
+    fn iteration_shift_needs_the_same_grid() {
+        let (center_x, center_y) = ((AREA_W / 2) as f64, (AREA_H / 2) as f64);
+        let mut job = IterationJob::new();
+        setup_direct_job(&mut job, 200, false);
+        run_direct_view(&mut job, 200, center_x, center_y);
+
+        // 実際の移動量と違う
+        job.set_iteration_shift(1, 0);
+        run_direct_view(&mut job, 200, center_x + 2.0, center_y);
+        assert_eq!(job.reused_count(), 0);
+
+        // maxIteration が違う
+        job.set_iteration_shift(1, 0);
+        run_direct_view(&mut job, 300, center_x + 3.0, center_y);
+        assert_eq!(job.reused_count(), 0);
+
+        // 指定は次の job 1 回だけに効く
+        job.set_iteration_shift(1, 0);
+        run_direct_view(&mut job, 300, center_x + 4.0, center_y);
+        assert_eq!(job.reused_count(), (AREA_W - 1) * AREA_H);
+        run_direct_view(&mut job, 300, center_x + 4.0, center_y);
+        assert_eq!(job.reused_count(), 0);
+        assert_eq!(job.calculated_count(), AREA_W * AREA_H);
+    }
+
+    #[test]
+    fn imported_iterations_prefill_a_2x_zoom() {
+        let (center_x, center_y) = ((AREA_W / 2) as f64, (AREA_H / 2) as f64);
+        let run_view = |job: &mut IterationJob, scale: f64, prev: Option<&[u32]>| {
+            job.begin_direct(
+                200, -0.75, 0.1, scale, center_x, center_y, AREA_W, AREA_H, 0, 0,
+            );
+            if let Some(prev) = prev {
+                job.alloc_iteration_import(prev.len() as u32);
+                job.iteration_import[..prev.len()].copy_from_slice(prev);
+                // 前の画像のピクセル (sx, sy) は中心を挟んで 2 倍離れた (2sx - 24, 2sy - 16) に来る
+                let imported = job.import_iterations(
+                    AREA_W,
+                    AREA_H,
+                    2,
+                    -(center_x as i32),
+                    -(center_y as i32),
+                );
+                assert_eq!(imported, AREA_W * AREA_H / 4);
+            }
+            job.begin_pass(1.0, 1.0, AREA_W, false, true);
+            assert_eq!(job.calc_band(0, AREA_H), 0);
+            job.scaled_iterations[..(AREA_W * AREA_H) as usize].to_vec()
+        };
+
+        let mut job = IterationJob::new();
+        setup_direct_job(&mut job, 200, false);
+        let prev = run_view(&mut job, DIRECT_SCALE * 2.0, None);
+        let zoomed = run_view(&mut job, DIRECT_SCALE, Some(&prev));
+        assert_eq!(job.reused_count(), AREA_W * AREA_H / 4);
+        assert_eq!(job.calculated_count(), AREA_W * AREA_H);
+        assert_eq!(job.iterated_count(), AREA_W * AREA_H * 3 / 4);
+
+        let mut fresh = IterationJob::new();
+        setup_direct_job(&mut fresh, 200, false);
+        assert_eq!(zoomed, run_view(&mut fresh, DIRECT_SCALE, None));
+    }
+
+    #[test]
+    fn continuation_resumes_pixels_that_hit_max_iteration() {
+        let pixels = AREA_W * AREA_H;
+        let xn = create_xn(-0.7451, 0.11302, 512);
+        let (bla_bytes, row_offsets) = create_bla_table(12, xn.len() / 2 - 1, 12345);
+        let run = |job: &mut IterationJob, max_iteration: u32| {
+            setup_iteration_job(job, max_iteration, &xn, &bla_bytes, &row_offsets, 12, 5e-4);
+            job.begin_pass(1.0, 1.0, AREA_W, false, true);
+            assert_eq!(job.calc_band(0, AREA_H), 0);
+            job.scaled_iterations[..pixels as usize].to_vec()
+        };
+
+        let mut job = IterationJob::new();
+        job.set_continuation(true);
+        let low = run(&mut job, 100);
+        let hits = low.iter().filter(|&&n| n == 100).count() as u32;
+        assert!(hits > 0 && hits < pixels);
+
+        let high = run(&mut job, 400);
+        assert_eq!(job.reused_count(), pixels - hits);
+        assert_eq!(job.iterated_count(), hits);
+        assert_eq!(job.calculated_count(), pixels);
+        assert!(high.iter().any(|&n| n > 100 && n < 400));
+
+        let mut fresh = IterationJob::new();
+        fresh.set_f32_fast_path(false);
+        assert_eq!(high, run(&mut fresh, 400));
+
+        // maxIteration が上がっていなければ続きからは計算しない
+        run(&mut job, 400);
+        assert_eq!(job.reused_count(), 0);
+    }
+
+    #[test]
+    fn pixel_list_matches_direct_loop_at_fractional_pixels() {
+        let mut job = IterationJob::new();
+        setup_direct_job(&mut job, 500, false);
+        let points = [
+            (0.0, 0.0),
+            (3.5, 7.25),
+            (47.0, 31.0),
+            (-2.0, 40.5),
+            (24.0, 16.0),
+        ];
+        job.alloc_pixel_list(points.len() as u32);
+        for (i, &(x, y)) in points.iter().enumerate() {
+            job.pixel_list[i * 2] = x;
+            job.pixel_list[i * 2 + 1] = y;
+        }
+        assert_eq!(job.calc_pixels(points.len() as u32), 0);
+
+        let expected: Vec<u32> = points
+            .iter()
+            .map(|&(x, y)| {
+                let c_re = -0.75 + (x - (AREA_W / 2) as f64) * DIRECT_SCALE;
+                let c_im = 0.1 - (y - (AREA_H / 2) as f64) * DIRECT_SCALE;
+                direct_loop(500, c_re, c_im)
+            })
+            .collect();
+        assert_eq!(job.pixel_results[..points.len()], expected);
+
+        assert_eq!(job.calc_pixels(points.len() as u32 + 1), 7);
+        assert!(job.error_message().contains("pixel list"));
+    }
+
+    #[test]
+    fn pixel_list_matches_band_without_touching_the_pass() {
+        set_f32_fast_path(false);
+        setup_job(300);
+        let band = run_pass(1.0, true);
+        let (calculated, hits) = with_job(|job| (job.calculated_count(), job.hit_count()));
+
+        // 散らばったピクセルを逆順に並べても、band と同じ値が同じ順に返る
+        let pixels: Vec<(u32, u32)> = (0..AREA_H)
+            .step_by(3)
+            .flat_map(|y| (0..AREA_W).step_by(5).map(move |x| (x, y)))
+            .rev()
+            .collect();
+        alloc_pixel_list(pixels.len() as u32);
+        with_job(|job| {
+            for (i, &(x, y)) in pixels.iter().enumerate() {
+                job.pixel_list[i * 2] = x as f64;
+                job.pixel_list[i * 2 + 1] = y as f64;
+            }
+        });
+        assert_eq!(calc_iteration_pixels(pixels.len() as u32), 0);
+
+        with_job(|job| {
+            let expected: Vec<u32> = pixels
+                .iter()
+                .map(|&(x, y)| band[(x + y * AREA_W) as usize])
+                .collect();
+            assert_eq!(job.pixel_results[..pixels.len()], expected);
+            assert_eq!(job.scaled_iterations[..band.len()], band);
+            assert_eq!(job.calculated_count(), calculated);
+            assert_eq!(job.hit_count(), hits);
+        });
+    }
+
+    #[test]
+    fn pixel_list_does_not_correct_glitches() {
+        set_glitch_detection(1e-2);
+        setup_job(2000);
+        let uncorrected = run_pass(1.0, true);
+        let all_pixels = || {
+            alloc_pixel_list(AREA_W * AREA_H);
+            with_job(|job| {
+                for i in 0..(AREA_W * AREA_H) as usize {
+                    job.pixel_list[i * 2] = (i % AREA_W as usize) as f64;
+                    job.pixel_list[i * 2 + 1] = (i / AREA_W as usize) as f64;
+                }
+            });
+            assert_eq!(calc_iteration_pixels(AREA_W * AREA_H), 0);
+            with_job(|job| job.pixel_results[..uncorrected.len()].to_vec())
+        };
+
+        // secondary reference を 3 本使った job
+        let xn = create_xn(-0.7451, 0.11302, 2048);
+        setup_job(2000);
+        for index in 1..=3 {
+            alloc_reference(index, xn.len() as u32, 0, 0);
+            with_job(|job| {
+                job.secondary_references[index as usize - 1].xn[..xn.len()].copy_from_slice(&xn)
+            });
+            let ref_x = (index * 10) as f64;
+            assert_eq!(
+                set_reference(index, (xn.len() / 2 - 1) as u32, 0, ref_x, 4.0),
+                0
+            );
+        }
+        run_pass(1.0, true);
+        assert_eq!(all_pixels(), uncorrected);
+
+        // 次の job で secondary reference を減らして縮めても、前の job の順番を読まない
+        setup_job(2000);
+        alloc_reference(1, xn.len() as u32, 0, 0);
+        with_job(|job| job.secondary_references[0].xn[..xn.len()].copy_from_slice(&xn));
+        assert_eq!(set_reference(1, (xn.len() / 2 - 1) as u32, 0, 6.0, 4.0), 0);
+        with_job(|job| job.shrink());
+        assert_eq!(all_pixels(), uncorrected);
+        set_glitch_detection(0.0);
+    }
+
+    #[test]
+    fn direct_job_does_not_use_references() {
+        // 壊れた reference が残っていても direct mode の job は計算できる
+        let mut job = IterationJob::new();
+        job.set_atom_domain(true);
+        job.alloc(4, 0, 0, AREA_W * AREA_H, AREA_W * AREA_H);
+        job.begin(100, 100, 0, 2, 1e-3, 0.0, 0.0, AREA_W, AREA_H, 0, 0);
+        assert_ne!(job.validate(), 0);
+
+        setup_direct_job(&mut job, 100, false);
+        assert_eq!(job.validate(), 0);
+        job.begin_pass(1.0, 1.0, AREA_W, false, true);
+        assert_eq!(job.calc_band(0, AREA_H), 0);
+        assert!(!job.uses_f32());
+
+        // 次に begin した job は perturbation に戻る
+        let xn = create_xn(-0.7451, 0.11302, 512);
+        let (bla_bytes, row_offsets) = create_bla_table(12, xn.len() / 2 - 1, 12345);
+        job.set_atom_domain(false);
+        setup_iteration_job(&mut job, 100, &xn, &bla_bytes, &row_offsets, 12, 5e-4);
+        assert!(job.direct.is_none());
+        assert_eq!(job.validate(), 0);
+    }
+
+    /// supersampling 用の大きさで job を組み立て直す
+    fn setup_sampling_job(max_iteration: u32, samples_per_axis: u32) {
+        let xn = create_xn(-0.7451, 0.11302, 512);
+        let (bla_bytes, row_offsets) = create_bla_table(12, xn.len() / 2 - 1, 12345);
+        let scaled_pixels = AREA_W * AREA_H * samples_per_axis * samples_per_axis;
+        with_job(|job| {
+            setup_iteration_job(job, max_iteration, &xn, &bla_bytes, &row_offsets, 12, 5e-4);
+            job.alloc(
+                xn.len() as u32,
+                bla_bytes.len() as u32,
+                row_offsets.len() as u32,
+                0,
+                scaled_pixels,
+            );
+        });
+    }
+
+    #[test]
+    fn sampling_pass_of_two_matches_half_pixel_pass() {
+        setup_sampling_job(2000, 2);
+        begin_pass(0.5, 0.5, AREA_W * 2, true, true);
+        calc_iteration_band(0, AREA_H * 2);
+        let pixels = (AREA_W * AREA_H * 4) as usize;
+        let expected = with_job(|job| job.scaled_iterations[..pixels].to_vec());
+        let expected_hits = get_hit_count();
+
+        setup_sampling_job(2000, 2);
+        assert!(begin_sampling_pass(2, 0, 0, false, true));
+        for y in 0..AREA_H {
+            assert_eq!(calc_iteration_band(y, y + 1), 0);
+        }
+        with_job(|job| assert_eq!(job.scaled_iterations[..pixels], expected));
+        assert_eq!(get_hit_count(), expected_hits);
+        assert_eq!(get_calculated_count(), AREA_W * AREA_H * 4);
+    }
+
+    #[test]
+    fn averaged_sampling_pass_rounds_the_sample_mean() {
+        for pattern in [0, 1, 2] {
+            setup_sampling_job(500, 3);
+            assert!(begin_sampling_pass(3, pattern, 42, false, true));
+            assert_eq!(calc_iteration_band(0, AREA_H), 0);
+            let samples =
+                with_job(|job| job.scaled_iterations[..(AREA_W * AREA_H * 9) as usize].to_vec());
+
+            assert!(begin_sampling_pass(3, pattern, 42, true, true));
+            assert_eq!(calc_iteration_band(0, AREA_H), 0);
+            let averaged =
+                with_job(|job| job.scaled_iterations[..(AREA_W * AREA_H) as usize].to_vec());
+
+            let scaled_w = (AREA_W * 3) as usize;
+            for (index, &average) in averaged.iter().enumerate() {
+                let (x, y) = (index % AREA_W as usize, index / AREA_W as usize);
+                let sum: u32 = (0..9)
+                    .map(|sample| samples[x * 3 + sample % 3 + (y * 3 + sample / 3) * scaled_w])
+                    .sum();
+                assert_eq!(average, (sum as f64 / 9.0).round() as u32);
+            }
+            let hits = averaged.iter().filter(|&&n| n == 500).count();
+            assert_eq!(get_hit_count() as usize, hits);
+        }
+    }
+
+    #[test]
+    fn sampling_pass_rejects_bad_settings_and_bands() {
+        setup_sampling_job(500, 2);
+        assert!(!begin_sampling_pass(2, 3, 0, false, true));
+
+        // 4×4 サンプルを全部出力するには 2×2 分の出力バッファでは足りない
+        assert!(begin_sampling_pass(4, 0, 0, false, true));
+        assert_ne!(calc_iteration_band(AREA_H / 2, AREA_H), 0);
+        // 平均なら足りる。area の外の行は計算しない
+        assert!(begin_sampling_pass(4, 0, 0, true, true));
+        assert_eq!(calc_iteration_band(0, AREA_H), 0);
+        assert_ne!(calc_iteration_band(AREA_H, AREA_H + 1), 0);
+
+        // 上限を超えるサンプル数は丸める
+        assert!(begin_sampling_pass(100, 0, 0, true, true));
+        with_job(|job| assert_eq!(job.sampling.unwrap().samples_per_axis, 16));
+    }
+
+    #[test]
+    fn guessing_strategies_fill_the_inside_of_the_set() {
+        // 主 cardioid の内側だけが見える area。周囲が全部 maxIteration なので内側は計算しない。
+        // boundary tracing は縁のピクセルと比べるために 1 つ内側の環も計算する
+        for (strategy, margin) in [(1, 1), (2, 2)] {
+            let mut job = IterationJob::new();
+            assert!(job.set_render_strategy(strategy));
+            job.alloc(0, 0, 0, AREA_W * AREA_H, AREA_W * AREA_H);
+            job.begin_direct(200, -0.2, 0.0, 1e-3, 0.0, 0.0, AREA_W, AREA_H, 0, 0);
+            job.begin_pass(1.0, 1.0, AREA_W, false, true);
+            assert_eq!(job.calc_band(0, AREA_H), 0);
+
+            let pixels = (AREA_W * AREA_H) as usize;
+            assert!(job.scaled_iterations[..pixels].iter().all(|&n| n == 200));
+            assert!(job.iterations[..pixels].iter().all(|&n| n == 200));
+            let inside = (AREA_W - margin * 2) * (AREA_H - margin * 2);
+            assert_eq!(job.guessed_count(), inside);
+            assert_eq!(job.iterated_count(), AREA_W * AREA_H - inside);
+            assert_eq!(job.calculated_count(), AREA_W * AREA_H);
+            assert_eq!(job.hit_count(), AREA_W * AREA_H);
+        }
+        assert!(!IterationJob::new().set_render_strategy(3));
+    }
+
+    #[test]
+    fn boundary_tracing_matches_scan_on_smooth_bands() {
+        // 集合全体が見える area。iteration 数の帯は何ピクセルも幅があるので埋め間違えない
+        let render = |strategy: u32| {
+            let mut job = IterationJob::new();
+            assert!(job.set_render_strategy(strategy));
+            job.alloc(0, 0, 0, AREA_W * AREA_H, AREA_W * AREA_H);
+            job.begin_direct(64, -0.6, 0.0, 0.06, 24.0, 16.0, AREA_W, AREA_H, 0, 0);
+            job.begin_pass(1.0, 1.0, AREA_W, false, true);
+            assert_eq!(job.calc_band(0, AREA_H), 0);
+            let pixels = (AREA_W * AREA_H) as usize;
+            (
+                job.scaled_iterations[..pixels].to_vec(),
+                job.iterated_count(),
+            )
+        };
+        let (expected, scanned) = render(0);
+        let (traced, iterated) = render(2);
+        assert_eq!(traced, expected);
+        assert_eq!(scanned, AREA_W * AREA_H);
+        assert!(iterated < scanned);
+    }
+
+    #[test]
+    fn guess_check_counts_mistaken_fills() {
+        const BAND_ROWS: u32 = 8;
+        // check 有効時は埋めずに計算した値が後の pass の矩形分割に影響するので、1 pass だけで比べる
+        let run_banded_pass = || {
+            begin_pass(1.0, 1.0, AREA_W, false, true);
+            for y in (0..AREA_H).step_by(BAND_ROWS as usize) {
+                assert_eq!(calc_iteration_band(y, y + BAND_ROWS), 0);
+            }
+            let pixels = (AREA_W * AREA_H) as usize;
+            with_job(|job| job.scaled_iterations[..pixels].to_vec())
+        };
+
+        set_f32_fast_path(false);
+        setup_job(500);
+        let expected = run_banded_pass();
+
+        for strategy in [1, 2] {
+            assert!(set_render_strategy(strategy));
+            set_guess_check(true);
+            setup_job(500);
+            assert_eq!(run_banded_pass(), expected);
+            let guessed = get_guessed_count();
+            let mistaken = get_mistaken_guess_count();
+            assert!(guessed > 0);
+            assert_eq!(get_calculated_count(), AREA_W * AREA_H);
+
+            set_guess_check(false);
+            setup_job(500);
+            let guessed_output = run_banded_pass();
+            let differences = guessed_output
+                .iter()
+                .zip(&expected)
+                .filter(|(a, b)| a != b)
+                .count();
+            assert_eq!(get_guessed_count(), guessed);
+            assert_eq!(differences as u32, mistaken);
+            assert_eq!(get_mistaken_guess_count(), 0);
+            assert_eq!(get_calculated_count(), AREA_W * AREA_H);
+        }
+        set_render_strategy(0);
+    }
+
+    /// refinement pass が計算し直すべきピクセルか。8 近傍を素直に見る
+    fn is_refined(base: &[u32], x: i32, y: i32, threshold: u32, max_iteration: u32) -> bool {
+        let n = base[(x + y * AREA_W as i32) as usize];
+        (-1..=1).any(|dy| {
+            (-1..=1).any(|dx| {
+                let (nx, ny) = (x + dx, y + dy);
+                if nx < 0 || ny < 0 || nx >= AREA_W as i32 || ny >= AREA_H as i32 {
+                    return false;
+                }
+                let neighbor = base[(nx + ny * AREA_W as i32) as usize];
+                n.abs_diff(neighbor) > threshold
+                    || (n == max_iteration) != (neighbor == max_iteration)
+            })
+        })
+    }
+
+    #[test]
+    fn refinement_pass_resamples_only_pixels_at_edges() {
+        setup_job(500);
+        let base = run_pass(1.0, false);
+        let pixels = (AREA_W * AREA_H) as usize;
+
+        // 平均を出力する sampling pass を先に計算しておく (キャッシュは書き換えない)
+        assert!(begin_sampling_pass(3, 1, 7, true, false));
+        assert_eq!(calc_iteration_band(0, AREA_H), 0);
+        let averaged = with_job(|job| job.scaled_iterations[..pixels].to_vec());
+
+        for threshold in [0, 4, u32::MAX] {
+            let calculated_before = get_calculated_count();
+            assert!(begin_refinement_pass(3, 1, 7, threshold, true));
+            for y in 0..AREA_H {
+                assert_eq!(calc_iteration_band(y, y + 1), 0);
+            }
+            let (refined, counts) = with_job(|job| {
+                (
+                    job.scaled_iterations[..pixels].to_vec(),
+                    job.sample_counts[..pixels].to_vec(),
+                )
+            });
+
+            let mut refined_count = 0;
+            for index in 0..pixels {
+                let (x, y) = (
+                    (index % AREA_W as usize) as i32,
+                    (index / AREA_W as usize) as i32,
+                );
+                if is_refined(&base, x, y, threshold, 500) {
+                    refined_count += 1;
+                    assert_eq!(counts[index], 9);
+                    assert_eq!(refined[index], averaged[index]);
+                } else {
+                    assert_eq!(counts[index], 1);
+                    assert_eq!(refined[index], base[index]);
+                }
+            }
+            assert!(refined_count > 0 && refined_count < pixels);
+            assert_eq!(
+                get_calculated_count() - calculated_before,
+                refined_count as u32 * 9
+            );
+            let hits = refined.iter().filter(|&&n| n == 500).count();
+            assert_eq!(get_hit_count() as usize, hits);
+        }
+    }
+
+    #[test]
+    fn refinement_pass_needs_the_iterations_cache() {
+        setup_sampling_job(500, 1);
+        assert!(!begin_refinement_pass(2, 3, 0, 0, true));
+        assert!(begin_refinement_pass(2, 0, 0, 0, true));
+        assert_ne!(calc_iteration_band(0, 1), 0);
+
+        // 次の pass を始めれば通常の pass に戻る
+        begin_pass(1.0, 1.0, AREA_W, true, true);
+        assert_eq!(calc_iteration_band(0, 1), 0);
+        with_job(|job| assert!(job.refinement.is_none()));
+    }
+
+    #[test]
+    fn accumulation_is_off_by_default() {
+        setup_job(500);
+        run_pass(1.0, true);
+        with_job(|job| {
+            assert!(job.resolved_accumulation.is_none());
+            assert!(job.accum_values.scaled.is_empty());
+        });
+    }
 }
@@ -0,0 +1,191 @@
+//! reference orbit 1 本分の入力。
+
+use crate::ITEM_BYTE_LENGTH;
+use crate::bla::BlaTable;
+use crate::error::JobError;
+use crate::series::SeriesApproximation;
+
+/// reference orbit とその BLATable。job をまたいで再利用し、足りないときだけ伸ばす
+pub(crate) struct Reference {
+    pub(crate) xn: Vec<f64>,
+    /// f32 で計算する job だけが使う、X_0 ..= X_{max_ref_iteration} を f32 に丸めたもの
+    pub(crate) xn_f32: Vec<f32>,
+    pub(crate) bla_bytes: Vec<u8>,
+    pub(crate) bla_row_offsets: Vec<i32>,
+    /// 直近の `alloc` で JS が申告した長さ。Vec は縮めないので、検証にはこちらを使う
+    xn_len: usize,
+    bla_bytes_len: usize,
+    bla_row_offsets_len: usize,
+    /// `bla_bytes` を hot loop 向けに詰め直したもの
+    pub(crate) bla: BlaTable,
+    /// `bla` を作ったときの bla_rows。JS が `bla_bytes` を書き換えうるようになったら None
+    bla_built_rows: Option<i32>,
+
+    pub(crate) max_ref_iteration: u32,
+    pub(crate) bla_rows: i32,
+    /// reference の c がある位置 (pixel 座標、小数あり)
+    pub(crate) ref_pixel_x: f64,
+    pub(crate) ref_pixel_y: f64,
+    /// primary reference だけが job ごとに持つ
+    pub(crate) series: Option<SeriesApproximation>,
+}
+
+impl Reference {
+    pub(crate) const fn new() -> Self {
+        Self {
+            xn: Vec::new(),
+            xn_f32: Vec::new(),
+            bla_bytes: Vec::new(),
+            bla_row_offsets: Vec::new(),
+            xn_len: 0,
+            bla_bytes_len: 0,
+            bla_row_offsets_len: 0,
+            bla: BlaTable::new(),
+            bla_built_rows: None,
+            max_ref_iteration: 0,
+            bla_rows: 0,
+            ref_pixel_x: 0.0,
+            ref_pixel_y: 0.0,
+            series: None,
+        }
+    }
+
+    pub(crate) fn alloc(&mut self, xn_f64_len: u32, bla_bytes_len: u32, bla_row_offsets_len: u32) {
+        crate::ensure_len(&mut self.xn, xn_f64_len as usize);
+        crate::ensure_len(&mut self.bla_bytes, bla_bytes_len as usize);
+        crate::ensure_len(&mut self.bla_row_offsets, bla_row_offsets_len as usize);
+        self.xn_len = xn_f64_len as usize;
+        self.bla_bytes_len = bla_bytes_len as usize;
+        self.bla_row_offsets_len = bla_row_offsets_len as usize;
+        self.invalidate_bla();
+    }
+
+    /// `bla_bytes` / `bla_row_offsets` のポインタを JS に渡したときに呼ぶ。次の `rebuild_bla` で作り直す
+    pub(crate) fn invalidate_bla(&mut self) {
+        self.bla_built_rows = None;
+    }
+
+    /// 直近の `alloc` で申告された長さまでバッファを縮める
+    pub(crate) fn shrink(&mut self) {
+        crate::shrink_len(&mut self.xn, self.xn_len);
+        self.xn_f32.shrink_to_fit();
+        crate::shrink_len(&mut self.bla_bytes, self.bla_bytes_len);
+        crate::shrink_len(&mut self.bla_row_offsets, self.bla_row_offsets_len);
+        self.bla.shrink();
+    }
+
+    pub(crate) fn capacity_bytes(&self) -> usize {
+        crate::capacity_bytes(&self.xn)
+            + crate::capacity_bytes(&self.xn_f32)
+            + crate::capacity_bytes(&self.bla_bytes)
+            + crate::capacity_bytes(&self.bla_row_offsets)
+            + self.bla.capacity_bytes()
+    }
+
+    /// コピー済みの `bla_bytes` から hot loop 用の表を作り直す。`validate` が通ってから呼ぶこと。
+    /// 前回から `bla_bytes` と bla_rows が変わっていなければ作った表をそのまま使う
+    pub(crate) fn rebuild_bla(&mut self) {
+        if self.bla_built_rows == Some(self.bla_rows) {
+            return;
+        }
+        self.bla.rebuild(
+            &self.bla_bytes,
+            &self.bla_row_offsets,
+            self.bla_rows as usize,
+        );
+        self.bla_built_rows = Some(self.bla_rows);
+    }
+
+    /// reference が bailout するまでの X_n が、f32 の正規化数か 0 で表せるか。`validate` が通ってから呼ぶこと。
+    ///
+    /// 非正規化数に落ちる成分があると、f32 の kernel では X_n の仮数が足りなくなる。
+    /// bailout したあとの点はそこに達したピクセルを f64 で計算し直すので見ない
+    pub(crate) fn fits_f32(&self) -> bool {
+        let range = f32::MIN_POSITIVE as f64..=f32::MAX as f64;
+        self.xn[..(self.max_ref_iteration as usize + 1) * 2]
+            .chunks_exact(2)
+            .take_while(|x| x[0] * x[0] + x[1] * x[1] <= crate::BAILOUT_RADIUS)
+            .flatten()
+            .all(|&value| value == 0.0 || range.contains(&value.abs()))
+    }
+
+    /// `xn_f32` を作り直す。`validate` が通ってから呼ぶこと。
+    ///
+    /// reference が発散したあとの点は f32 では溢れて inf になりうるが、そこに達したピクセルは
+    /// f32 の kernel が f64 で計算し直すのでそのままでよい
+    pub(crate) fn rebuild_xn_f32(&mut self) {
+        let xn = &self.xn[..(self.max_ref_iteration as usize + 1) * 2];
+        self.xn_f32.clear();
+        self.xn_f32.extend(xn.iter().map(|&value| value as f32));
+    }
+
+    /// hot loop が範囲外を読まないことを確かめる。`index` はエラーメッセージ用の reference 番号
+    pub(crate) fn validate(&self, index: u32, start_bla_index: i32) -> Result<(), JobError> {
+        // rebase するまでに X_0 ..= X_{max_ref_iteration} を読む
+        let required = self.max_ref_iteration as usize + 1;
+        let points = self.xn_len / 2;
+        if points < required {
+            return Err(JobError::XnTooShort {
+                reference: index,
+                required,
+                actual: points,
+            });
+        }
+
+        let rows = self.bla_rows as u32;
+        if self.bla_row_offsets_len < rows as usize * 2 {
+            return Err(JobError::RowOffsetsTooShort {
+                reference: index,
+                rows,
+                actual: self.bla_row_offsets_len,
+            });
+        }
+
+        for row in 0..rows {
+            let offset = self.bla_row_offsets[row as usize * 2];
+            let length = self.bla_row_offsets[row as usize * 2 + 1];
+            let end = (length as usize)
+                .checked_mul(ITEM_BYTE_LENGTH)
+                .and_then(|bytes| bytes.checked_add(offset as usize));
+            if offset < 0 || length < 0 || end.is_none_or(|end| end > self.bla_bytes_len) {
+                return Err(JobError::BlaRowOutOfBounds {
+                    reference: index,
+                    row,
+                });
+            }
+
+            // 探索は ref_iteration - 1 (最大 max_ref_iteration - 2) を 2^row で割った列を読む
+            if (row as i32) < start_bla_index || self.max_ref_iteration < 2 {
+                continue;
+            }
+            let required = ((self.max_ref_iteration - 2) as usize)
+                .checked_shr(row)
+                .unwrap_or(0)
+                + 1;
+            if (length as usize) < required {
+                return Err(JobError::BlaRowTooShort {
+                    reference: index,
+                    row,
+                    required,
+                    actual: length as usize,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// reference の c を f64 で返す。X_1 = c なので xn から読める。
+    /// reference が即座に発散していて X_1 がない場合は原点扱いにする
+    pub(crate) fn c(&self) -> (f64, f64) {
+        let c_re = self.xn.get(2).copied().unwrap_or(0.0);
+        let c_im = self.xn.get(3).copied().unwrap_or(0.0);
+        (c_re, c_im)
+    }
+
+    /// ピクセル座標からの距離の 2 乗。glitch 時にどの reference を試すかの順番に使う
+    pub(crate) fn pixel_distance_sq(&self, pixel_x: f64, pixel_y: f64) -> f64 {
+        let dx = pixel_x - self.ref_pixel_x;
+        let dy = pixel_y - self.ref_pixel_y;
+        dx * dx + dy * dy
+    }
+}
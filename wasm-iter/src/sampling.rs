@@ -0,0 +1,352 @@
+//! 1 ピクセルを複数のサンプルで計算する supersampling の pass。
+//!
+//! `begin_pass` に `x_diff = 0.5` を渡す従来の supersampling は 2×2 の格子しか作れないので、
+//! `begin_sampling_pass` ではピクセルごとの N×N 個のサンプル位置をここで決める。
+//! サンプル位置はどのパターンでも、ピクセル座標 (x, y) から見て
+//! [-1/(2N), 1 - 1/(2N)) の正方形に収まる。格子の場合は x + i/N になり、N = 2 なら従来と同じ位置になる。
+//!
+//! band はピクセルの行で指定し、全サンプルを出力する場合は N×N のサンプルを
+//! 幅 N × area_width の scaled 出力の (N x + i, N y + j) に書く (従来の 2×2 と同じ並び)。
+//! 平均を出力する場合は 1 ピクセル 1 要素で、iteration 数の平均を四捨五入して書く。
+//!
+//! adaptive supersampling の refinement pass (`begin_refinement_pass`) は、計算済みの
+//! iterations キャッシュを見て、周りと iteration 数が大きく違うピクセルだけを同じ配置の
+//! サンプルで計算し直す。出力は平均と同じく 1 ピクセル 1 要素で、使ったサンプル数も別に書く。
+
+use crate::{IterationJob, PointsKernel};
+
+/// 1 軸あたりのサンプル数の上限。256 サンプル/ピクセル
+pub(crate) const MAX_SAMPLES_PER_AXIS: u32 = 16;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum SamplePattern {
+    /// N×N の正方格子
+    Grid,
+    /// 格子を傾けた配置 (N-rooks)。どのサンプルも x 座標と y 座標が互いに重ならない
+    RotatedGrid,
+    /// N×N に分けた各マスの中でランダムにずらす。位置は seed とピクセル座標で決まる
+    Jittered,
+}
+
+impl SamplePattern {
+    pub(crate) fn from_u32(value: u32) -> Option<Self> {
+        match value {
+            0 => Some(Self::Grid),
+            1 => Some(Self::RotatedGrid),
+            2 => Some(Self::Jittered),
+            _ => None,
+        }
+    }
+}
+
+/// supersampling の pass の設定
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct SamplingPass {
+    pub(crate) samples_per_axis: u32,
+    pub(crate) pattern: SamplePattern,
+    pub(crate) seed: u32,
+    /// true ならピクセルごとの平均だけを出力する
+    pub(crate) is_averaged: bool,
+}
+
+impl SamplingPass {
+    pub(crate) fn samples_per_pixel(&self) -> u32 {
+        self.samples_per_axis * self.samples_per_axis
+    }
+
+    /// scaled 出力の 1 行の要素数
+    pub(crate) fn scaled_width(&self, area_width: u32) -> u32 {
+        if self.is_averaged {
+            area_width
+        } else {
+            area_width * self.samples_per_axis
+        }
+    }
+
+    /// band の出力が scaled 出力の何要素目までを使うか
+    pub(crate) fn scaled_pixels(&self, area_width: u32, band_y_to: u32) -> u64 {
+        let pixels = area_width as u64 * band_y_to as u64;
+        if self.is_averaged {
+            pixels
+        } else {
+            pixels * self.samples_per_pixel() as u64
+        }
+    }
+
+    /// ピクセル (pixel_x, pixel_y) の i 列 j 行目のサンプルの、ピクセル座標からのずれ
+    fn offset(&self, pixel_x: f64, pixel_y: f64, i: u32, j: u32) -> (f64, f64) {
+        let n = self.samples_per_axis as f64;
+        let (i, j) = (i as f64, j as f64);
+        // 各パターンの [0, 1) の位置を、格子がピクセル座標から始まるように半マス戻す
+        let (u, v) = match self.pattern {
+            SamplePattern::Grid => ((i + 0.5) / n, (j + 0.5) / n),
+            SamplePattern::RotatedGrid => (
+                (i * n + (n - 1.0 - j) + 0.5) / (n * n),
+                (j * n + i + 0.5) / (n * n),
+            ),
+            SamplePattern::Jittered => {
+                let hash = mix(self.seed, pixel_x as i32 as u32, pixel_y as i32 as u32);
+                let hash = mix(hash, i as u32, j as u32);
+                let jitter_x = (hash >> 16) as f64 / 65536.0;
+                let jitter_y = (hash & 0xffff) as f64 / 65536.0;
+                ((i + jitter_x) / n, (j + jitter_y) / n)
+            }
+        };
+        let half_cell = 0.5 / n;
+        (u - half_cell, v - half_cell)
+    }
+}
+
+/// adaptive supersampling の refinement pass の設定
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct RefinementPass {
+    /// 計算し直すピクセルのサンプルの取り方。出力は常に平均
+    pub(crate) sampling: SamplingPass,
+    /// 隣のピクセルとの iteration 数の差がこれを超えたら計算し直す
+    pub(crate) threshold: u32,
+}
+
+impl RefinementPass {
+    /// (x, y) を計算し直すか。8 近傍のどれかと iteration 数の差が threshold を超えるか、
+    /// maxIteration に達したかどうかが違う (内部との境界にある) ピクセルを選ぶ
+    fn needs_refinement(&self, job: &IterationJob, x: u32, y: u32) -> bool {
+        let area_w = job.area_width;
+        let area_h = job.area_height;
+        let base = job.iterations[(x + y * area_w) as usize];
+        let is_inside = base == job.max_iteration;
+        for neighbor_y in y.saturating_sub(1)..=(y + 1).min(area_h - 1) {
+            for neighbor_x in x.saturating_sub(1)..=(x + 1).min(area_w - 1) {
+                let neighbor = job.iterations[(neighbor_x + neighbor_y * area_w) as usize];
+                if base.abs_diff(neighbor) > self.threshold
+                    || (neighbor == job.max_iteration) != is_inside
+                {
+                    return true;
+                }
+            }
+        }
+        false
+    }
+}
+
+/// 32bit の整数ハッシュ。jitter を band や worker の分け方によらずピクセル座標だけで決めるために使う
+fn mix(seed: u32, a: u32, b: u32) -> u32 {
+    let mut h = seed ^ a.wrapping_mul(0x9e37_79b9) ^ b.wrapping_mul(0x85eb_ca6b);
+    h ^= h >> 16;
+    h = h.wrapping_mul(0x7feb_352d);
+    h ^= h >> 15;
+    h = h.wrapping_mul(0x846c_a68b);
+    h ^ (h >> 16)
+}
+
+/// supersampling の pass で、area のピクセル行 [from, to) の全サンプルを計算する。
+///
+/// 1 行分のサンプルをまとめて `calc_points` に渡す。iterations キャッシュは使わず、
+/// calculated count はサンプル数で数える。hit count は出力した要素のうち maxIteration のもの
+pub(crate) fn calc_band_sampled(
+    job: &mut IterationJob,
+    band_y_from: u32,
+    band_y_to: u32,
+    calc_points: PointsKernel,
+) {
+    let Some(sampling) = job.sampling else {
+        return;
+    };
+    let n = sampling.samples_per_axis;
+    let samples = sampling.samples_per_pixel() as usize;
+    let area_w = job.area_width;
+    let scaled_w = sampling.scaled_width(area_w) as usize;
+    let start_x = job.area_start_x as f64;
+    let start_y = job.area_start_y as f64;
+    let is_result_pass = job.is_result_pass;
+    let max_iteration = job.max_iteration;
+
+    let row_samples = area_w as usize * samples;
+    let mut points = Vec::with_capacity(row_samples);
+    let mut results = Vec::with_capacity(row_samples);
+
+    for y in band_y_from..band_y_to {
+        let pixel_y = start_y + y as f64;
+        points.clear();
+        for x in 0..area_w {
+            let pixel_x = start_x + x as f64;
+            for j in 0..n {
+                for i in 0..n {
+                    let (dx, dy) = sampling.offset(pixel_x, pixel_y, i, j);
+                    points.push((pixel_x + dx, pixel_y + dy));
+                }
+            }
+        }
+
+        results.clear();
+        results.resize(points.len(), 0);
+        calc_points(job, &job.primary, &points, &mut results);
+        job.calculated_count += points.len() as u32;
+
+        for (x, pixel_results) in results.chunks_exact(samples).enumerate() {
+            if sampling.is_averaged {
+                let sum: u64 = pixel_results.iter().map(|&n| n as u64).sum();
+                let count = samples as u64;
+                // JS の Math.round(sum / count) と同じく 0.5 は切り上げる
+                let average = ((sum * 2 + count) / (count * 2)) as u32;
+                job.scaled_iterations[x + y as usize * scaled_w] = average;
+                if is_result_pass && average == max_iteration {
+                    job.hit_count += 1;
+                }
+                continue;
+            }
+
+            for (sample, &result) in pixel_results.iter().enumerate() {
+                let i = sample % n as usize;
+                let j = sample / n as usize;
+                let scaled_x = x * n as usize + i;
+                let scaled_y = (y * n) as usize + j;
+                job.scaled_iterations[scaled_x + scaled_y * scaled_w] = result;
+                if is_result_pass && result == max_iteration {
+                    job.hit_count += 1;
+                }
+            }
+        }
+    }
+}
+
+/// refinement pass で、area のピクセル行 [from, to) を出力する。
+///
+/// 計算し直さないピクセルは iterations キャッシュの値をそのまま書き、サンプル数を 1 にする。
+/// 計算し直すピクセルは 1 行分まとめて `calc_points` に渡し、サンプルの平均を書く。
+/// calculated count は計算したサンプル数で数え、hit count は出力した値で数える
+pub(crate) fn calc_band_refined(
+    job: &mut IterationJob,
+    band_y_from: u32,
+    band_y_to: u32,
+    calc_points: PointsKernel,
+) {
+    let Some(refinement) = job.refinement else {
+        return;
+    };
+    let sampling = refinement.sampling;
+    let n = sampling.samples_per_axis;
+    let samples = sampling.samples_per_pixel() as usize;
+    let area_w = job.area_width;
+    let start_x = job.area_start_x as f64;
+    let start_y = job.area_start_y as f64;
+    let is_result_pass = job.is_result_pass;
+    let max_iteration = job.max_iteration;
+
+    let mut points = Vec::new();
+    let mut refined = Vec::new();
+    let mut results = Vec::new();
+
+    for y in band_y_from..band_y_to {
+        let pixel_y = start_y + y as f64;
+        points.clear();
+        refined.clear();
+
+        for x in 0..area_w {
+            let index = (x + y * area_w) as usize;
+            if !refinement.needs_refinement(job, x, y) {
+                job.scaled_iterations[index] = job.iterations[index];
+                job.sample_counts[index] = 1;
+                continue;
+            }
+            let pixel_x = start_x + x as f64;
+            for j in 0..n {
+                for i in 0..n {
+                    let (dx, dy) = sampling.offset(pixel_x, pixel_y, i, j);
+                    points.push((pixel_x + dx, pixel_y + dy));
+                }
+            }
+            refined.push(index);
+        }
+
+        results.clear();
+        results.resize(points.len(), 0);
+        calc_points(job, &job.primary, &points, &mut results);
+        job.calculated_count += points.len() as u32;
+
+        for (&index, pixel_results) in refined.iter().zip(results.chunks_exact(samples)) {
+            let sum: u64 = pixel_results.iter().map(|&n| n as u64).sum();
+            let count = samples as u64;
+            job.scaled_iterations[index] = ((sum * 2 + count) / (count * 2)) as u32;
+            job.sample_counts[index] = samples as u32;
+        }
+
+        if is_result_pass {
+            let row = (y * area_w) as usize..((y + 1) * area_w) as usize;
+            let hits = job.scaled_iterations[row]
+                .iter()
+                .filter(|&&n| n == max_iteration)
+                .count();
+            job.hit_count += hits as u32;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pass(samples_per_axis: u32, pattern: SamplePattern) -> SamplingPass {
+        SamplingPass {
+            samples_per_axis,
+            pattern,
+            seed: 7,
+            is_averaged: false,
+        }
+    }
+
+    fn offsets(sampling: &SamplingPass, pixel_x: f64, pixel_y: f64) -> Vec<(f64, f64)> {
+        let n = sampling.samples_per_axis;
+        (0..n)
+            .flat_map(|j| (0..n).map(move |i| (i, j)))
+            .map(|(i, j)| sampling.offset(pixel_x, pixel_y, i, j))
+            .collect()
+    }
+
+    #[test]
+    fn grid_of_two_matches_half_pixel_steps() {
+        let offsets = offsets(&pass(2, SamplePattern::Grid), 10.0, 20.0);
+        assert_eq!(offsets, [(0.0, 0.0), (0.5, 0.0), (0.0, 0.5), (0.5, 0.5)]);
+    }
+
+    #[test]
+    fn patterns_stay_in_the_pixel_cell() {
+        for n in [1, 2, 3, 5, MAX_SAMPLES_PER_AXIS] {
+            let cell = -0.5 / n as f64..1.0 - 0.5 / n as f64;
+            for pattern in [
+                SamplePattern::Grid,
+                SamplePattern::RotatedGrid,
+                SamplePattern::Jittered,
+            ] {
+                for (dx, dy) in offsets(&pass(n, pattern), -3.0, 41.0) {
+                    assert!(cell.contains(&dx) && cell.contains(&dy), "{pattern:?} {n}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn rotated_grid_has_distinct_columns_and_rows() {
+        let n = 4;
+        let offsets = offsets(&pass(n, SamplePattern::RotatedGrid), 0.0, 0.0);
+        let mut xs: Vec<_> = offsets.iter().map(|&(x, _)| x).collect();
+        let mut ys: Vec<_> = offsets.iter().map(|&(_, y)| y).collect();
+        xs.sort_by(f64::total_cmp);
+        ys.sort_by(f64::total_cmp);
+        xs.dedup();
+        ys.dedup();
+        assert_eq!(xs.len(), (n * n) as usize);
+        assert_eq!(ys.len(), (n * n) as usize);
+    }
+
+    #[test]
+    fn jitter_depends_only_on_seed_and_pixel() {
+        let sampling = pass(3, SamplePattern::Jittered);
+        assert_eq!(offsets(&sampling, 5.0, 6.0), offsets(&sampling, 5.0, 6.0));
+        assert_ne!(offsets(&sampling, 5.0, 6.0), offsets(&sampling, 6.0, 5.0));
+        let reseeded = SamplingPass {
+            seed: 8,
+            ..sampling
+        };
+        assert_ne!(offsets(&sampling, 5.0, 6.0), offsets(&reseeded, 5.0, 6.0));
+    }
+}
@@ -0,0 +1,276 @@
+//! series approximation (SA) による最初の iteration のスキップ。
+//!
+//! Δn を Δc の冪級数 Δn ≈ Σ_{k=1..K} A_{k,n} Δc^k で近似し、係数を reference orbit から
+//! job ごとに 1 回だけ漸化式で進める。
+//! - A_{1,n+1} = 2 X_n A_{1,n} + 1
+//! - A_{k,n+1} = 2 X_n A_{k,n} + Σ_{i+j=k} A_{i,n} A_{j,n}
+//!
+//! 近似がどこまで使えるかは、area の端に置いた probe 点を f64 の perturbation で実際に反復し、
+//! 級数の値との相対誤差が許容値を超える直前までとする。全ピクセルはその iteration から
+//! 級数で求めた Δ を初期値として計算を始め、そこから先は通常どおり BLA を使う。
+//!
+//! A_{k,n} は k が大きいほど桁が大きくなり f64 から溢れやすいので、probe 点の |Δc| の最大値
+//! s で割った u = Δc / s についての係数 B_{k,n} = A_{k,n} s^k で持つ。
+
+use crate::{BAILOUT_RADIUS, mul_im, mul_re, n_norm};
+
+/// 級数の項数の上限。係数の更新が項数の 2 乗で重くなるので適当なところで抑える
+pub(crate) const MAX_SERIES_TERMS: u32 = 64;
+
+/// JS から設定する SA のパラメータ
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct SeriesConfig {
+    terms: usize,
+    /// probe 点で許容する |近似値 - 実際の Δ| / |Δ|
+    tolerance: f64,
+}
+
+impl SeriesConfig {
+    /// `terms` が 0 なら SA を使わないので None
+    pub(crate) fn new(terms: u32, tolerance: f64) -> Option<Self> {
+        if terms == 0 {
+            return None;
+        }
+        Some(Self {
+            terms: terms.min(MAX_SERIES_TERMS) as usize,
+            tolerance: tolerance.max(0.0),
+        })
+    }
+}
+
+/// job ごとに求めたスキップ先と、その iteration での係数
+pub(crate) struct SeriesApproximation {
+    skip: u32,
+    /// B_{k,skip} (k = 1..=K) を [re, im] で持つ
+    coefficients: Vec<[f64; 2]>,
+    inv_scale: f64,
+}
+
+impl SeriesApproximation {
+    /// 係数を進めながら probe 点で検証し、1 iteration もスキップできなければ None。
+    ///
+    /// `probes` は probe 点の Δc。スキップ先は `max_ref_iteration` と `max_iteration` より手前に抑える
+    pub(crate) fn build(
+        config: SeriesConfig,
+        xn: &[f64],
+        max_ref_iteration: u32,
+        max_iteration: u32,
+        probes: &[(f64, f64)],
+    ) -> Option<Self> {
+        let scale = probes
+            .iter()
+            .map(|&(re, im)| n_norm(re, im))
+            .fold(0.0, f64::max)
+            .sqrt();
+        if !(scale > 0.0 && scale.is_finite()) {
+            return None;
+        }
+        let inv_scale = 1.0 / scale;
+
+        let xn_len = (xn.len() / 2) as u32;
+        let limit = max_ref_iteration
+            .min(max_iteration)
+            .min(xn_len)
+            .saturating_sub(1);
+
+        // Δ_0 = 0 なので係数はすべて 0 から始まる
+        let mut current = vec![[0.0; 2]; config.terms];
+        let mut next = current.clone();
+        let probe_u: Vec<(f64, f64)> = probes
+            .iter()
+            .map(|&(re, im)| (re * inv_scale, im * inv_scale))
+            .collect();
+        let mut probe_deltas = vec![(0.0, 0.0); probes.len()];
+        let tolerance_sq = config.tolerance * config.tolerance;
+
+        let mut skip = 0;
+        while skip < limit {
+            let n = skip as usize;
+            let x_re = xn[n * 2];
+            let x_im = xn[n * 2 + 1];
+
+            if !advance(&current, &mut next, x_re, x_im, scale) {
+                break;
+            }
+
+            let next_x_re = xn[n * 2 + 2];
+            let next_x_im = xn[n * 2 + 3];
+            let mut is_valid = true;
+            for ((delta, &(dc_re, dc_im)), &(u_re, u_im)) in
+                probe_deltas.iter_mut().zip(probes).zip(&probe_u)
+            {
+                // Δ_{n+1} = (2 X_n + Δ_n) Δ_n + Δc
+                let (d_re, d_im) = *delta;
+                let t_re = x_re * 2.0 + d_re;
+                let t_im = x_im * 2.0 + d_im;
+                let new_re = mul_re(t_re, t_im, d_re, d_im) + dc_re;
+                let new_im = mul_im(t_re, t_im, d_re, d_im) + dc_im;
+                *delta = (new_re, new_im);
+
+                // 途中で発散したり rebase が必要になる点があるなら、そこより先はスキップしない
+                let z_norm = n_norm(next_x_re + new_re, next_x_im + new_im);
+                let delta_norm = n_norm(new_re, new_im);
+                if z_norm > BAILOUT_RADIUS || z_norm < delta_norm {
+                    is_valid = false;
+                    break;
+                }
+
+                let (approx_re, approx_im) = evaluate(&next, u_re, u_im);
+                let error_norm = n_norm(approx_re - new_re, approx_im - new_im);
+                // NaN も弾きたいので <= の否定で判定する
+                let is_close = error_norm <= tolerance_sq * delta_norm;
+                if !is_close {
+                    is_valid = false;
+                    break;
+                }
+            }
+            if !is_valid {
+                break;
+            }
+
+            std::mem::swap(&mut current, &mut next);
+            skip += 1;
+        }
+
+        if skip == 0 {
+            return None;
+        }
+        Some(Self {
+            skip,
+            coefficients: current,
+            inv_scale,
+        })
+    }
+
+    /// 全ピクセルが計算を始める iteration
+    #[inline(always)]
+    pub(crate) fn skip(&self) -> u32 {
+        self.skip
+    }
+
+    /// Δc から Δ_skip を求める
+    #[inline(always)]
+    pub(crate) fn evaluate(&self, delta_c_re: f64, delta_c_im: f64) -> (f64, f64) {
+        evaluate(
+            &self.coefficients,
+            delta_c_re * self.inv_scale,
+            delta_c_im * self.inv_scale,
+        )
+    }
+}
+
+/// 係数を 1 iteration 進める。非有限値が出たら false
+fn advance(current: &[[f64; 2]], next: &mut [[f64; 2]], x_re: f64, x_im: f64, scale: f64) -> bool {
+    let two_x_re = x_re * 2.0;
+    let two_x_im = x_im * 2.0;
+    let mut is_finite = true;
+    for k in 0..current.len() {
+        // next[k] は u^(k+1) の係数。(i+1) + (j+1) = k+1 となる組を足す
+        let [c_re, c_im] = current[k];
+        let mut re = mul_re(two_x_re, two_x_im, c_re, c_im);
+        let mut im = mul_im(two_x_re, two_x_im, c_re, c_im);
+        for i in 0..k {
+            let [a_re, a_im] = current[i];
+            let [b_re, b_im] = current[k - 1 - i];
+            re += mul_re(a_re, a_im, b_re, b_im);
+            im += mul_im(a_re, a_im, b_re, b_im);
+        }
+        if k == 0 {
+            re += scale;
+        }
+        is_finite &= re.is_finite() && im.is_finite();
+        next[k] = [re, im];
+    }
+    is_finite
+}
+
+/// Σ B_k u^k を Horner 法で求める
+#[inline(always)]
+fn evaluate(coefficients: &[[f64; 2]], u_re: f64, u_im: f64) -> (f64, f64) {
+    let mut re = 0.0;
+    let mut im = 0.0;
+    for &[b_re, b_im] in coefficients.iter().rev() {
+        let sum_re = re + b_re;
+        let sum_im = im + b_im;
+        re = mul_re(sum_re, sum_im, u_re, u_im);
+        im = mul_im(sum_re, sum_im, u_re, u_im);
+    }
+    (re, im)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn create_xn(c_re: f64, c_im: f64, length: usize) -> Vec<f64> {
+        let mut xn = Vec::with_capacity(length * 2);
+        let (mut z_re, mut z_im) = (0.0f64, 0.0f64);
+        for _ in 0..length {
+            xn.push(z_re);
+            xn.push(z_im);
+            let next_re = z_re * z_re - z_im * z_im + c_re;
+            z_im = 2.0 * z_re * z_im + c_im;
+            z_re = next_re;
+        }
+        xn
+    }
+
+    /// Δ_n を perturbation でそのまま反復する
+    fn perturb(xn: &[f64], dc: (f64, f64), n: u32) -> (f64, f64) {
+        let (mut d_re, mut d_im) = (0.0, 0.0);
+        for i in 0..n as usize {
+            let t_re = xn[i * 2] * 2.0 + d_re;
+            let t_im = xn[i * 2 + 1] * 2.0 + d_im;
+            let re = mul_re(t_re, t_im, d_re, d_im) + dc.0;
+            d_im = mul_im(t_re, t_im, d_re, d_im) + dc.1;
+            d_re = re;
+        }
+        (d_re, d_im)
+    }
+
+    fn corners(radius: f64) -> Vec<(f64, f64)> {
+        vec![
+            (-radius, -radius),
+            (radius, -radius),
+            (-radius, radius),
+            (radius, radius),
+        ]
+    }
+
+    #[test]
+    fn approximates_points_inside_probes() {
+        let xn = create_xn(-0.7451, 0.11302, 2048);
+        let config = SeriesConfig::new(16, 1e-6).unwrap();
+        let sa = SeriesApproximation::build(config, &xn, 2047, 10000, &corners(1e-8)).unwrap();
+        assert!(sa.skip() > 10);
+
+        let dc = (3e-9, -7e-9);
+        let (re, im) = sa.evaluate(dc.0, dc.1);
+        let (ex_re, ex_im) = perturb(&xn, dc, sa.skip());
+        let error = n_norm(re - ex_re, im - ex_im).sqrt();
+        assert!(error <= 1e-5 * n_norm(ex_re, ex_im).sqrt());
+    }
+
+    #[test]
+    fn more_terms_skip_further() {
+        let xn = create_xn(-0.7451, 0.11302, 2048);
+        let probes = corners(1e-6);
+        let skip = |terms| {
+            let config = SeriesConfig::new(terms, 1e-6).unwrap();
+            SeriesApproximation::build(config, &xn, 2047, 10000, &probes).map_or(0, |sa| sa.skip())
+        };
+        assert!(skip(1) <= skip(4));
+        assert!(skip(4) <= skip(16));
+        assert!(skip(1) < skip(16));
+    }
+
+    #[test]
+    fn skip_stays_below_limits() {
+        // 主カーディオイド内の c は発散しないので、級数は上限まで持つ
+        let xn = create_xn(-0.1, 0.1, 256);
+        let config = SeriesConfig::new(8, 1e-3).unwrap();
+        let sa = SeriesApproximation::build(config, &xn, 255, 100, &corners(1e-10)).unwrap();
+        assert_eq!(sa.skip(), 99);
+        assert!(SeriesConfig::new(0, 1e-3).is_none());
+    }
+}
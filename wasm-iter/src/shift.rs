@@ -0,0 +1,365 @@
+//! 整数ピクセルの平行移動で、前の job の iterations キャッシュを引き継ぐ。
+//!
+//! reference と 1 ピクセルあたりの幅が同じなら、平行移動した area の iteration 数は
+//! 前の area の値をずらしたものになる。`IterationJob::set_iteration_shift` で移動量を渡すと、
+//! 次の `begin` / `begin_direct` で前の job と同じ格子をずらしたものか確かめ、
+//! キャッシュをずらして新しく見えた行と列だけを未計算 (0) にする。
+//!
+//! ちょうど整数倍の拡大では、前の画像のピクセルが新しい格子の一部のピクセルに重なる。
+//! `IterationJob::import_iterations` で前の iteration 数を渡すと、重なるピクセルだけを
+//! キャッシュに書き、pass ではそこを計算済みとして飛ばす。
+//! maxIteration だけを上げた job かどうかの判定 (`continuation.rs` で使う) もここに置く。
+
+/// 移動量が整数ピクセルとみなせる誤差
+const SHIFT_EPSILON: f64 = 1e-6;
+
+/// ピクセル座標と c の対応を決めるもの
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum GridAnchor {
+    /// perturbation の job。reference の c と、それがどのピクセル座標にあるか
+    Reference {
+        c_re: f64,
+        c_im: f64,
+        max_ref_iteration: u32,
+        pixel_x: f64,
+        pixel_y: f64,
+    },
+    /// direct mode の job。`DirectView` と同じ
+    Direct {
+        center_re: f64,
+        center_im: f64,
+        center_pixel_x: f64,
+        center_pixel_y: f64,
+    },
+}
+
+/// iterations キャッシュを書いた job の格子。同じ値なら同じピクセルに同じ iteration 数が入る
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct CachedGrid {
+    pub(crate) max_iteration: u32,
+    pub(crate) delta_c_scale: f64,
+    pub(crate) area_width: u32,
+    pub(crate) area_height: u32,
+    pub(crate) area_start_x: i32,
+    pub(crate) area_start_y: i32,
+    /// f32 の kernel と f64 の kernel の結果を混ぜないように区別する
+    pub(crate) uses_f32: bool,
+    pub(crate) anchor: GridAnchor,
+}
+
+impl CachedGrid {
+    /// `prev` の格子を何ピクセル動かしたものか。reference やスケールが違えば None
+    fn offset_from(&self, prev: &CachedGrid) -> Option<(f64, f64)> {
+        let is_same_area = self.max_iteration == prev.max_iteration
+            && self.delta_c_scale == prev.delta_c_scale
+            && self.area_width == prev.area_width
+            && self.area_height == prev.area_height
+            && self.area_start_x == prev.area_start_x
+            && self.area_start_y == prev.area_start_y
+            && self.uses_f32 == prev.uses_f32;
+        if !is_same_area || self.delta_c_scale == 0.0 {
+            return None;
+        }
+        match (self.anchor, prev.anchor) {
+            (
+                GridAnchor::Reference {
+                    c_re,
+                    c_im,
+                    max_ref_iteration,
+                    pixel_x,
+                    pixel_y,
+                },
+                GridAnchor::Reference {
+                    c_re: prev_c_re,
+                    c_im: prev_c_im,
+                    max_ref_iteration: prev_max_ref_iteration,
+                    pixel_x: prev_pixel_x,
+                    pixel_y: prev_pixel_y,
+                },
+            ) => {
+                let is_same_reference = c_re == prev_c_re
+                    && c_im == prev_c_im
+                    && max_ref_iteration == prev_max_ref_iteration;
+                is_same_reference.then_some((pixel_x - prev_pixel_x, pixel_y - prev_pixel_y))
+            }
+            (
+                GridAnchor::Direct {
+                    center_re,
+                    center_im,
+                    center_pixel_x,
+                    center_pixel_y,
+                },
+                GridAnchor::Direct {
+                    center_re: prev_center_re,
+                    center_im: prev_center_im,
+                    center_pixel_x: prev_center_pixel_x,
+                    center_pixel_y: prev_center_pixel_y,
+                },
+            ) => {
+                // c = center + (x - center_pixel_x, -(y - center_pixel_y)) * scale なので、
+                // 同じ c のピクセル座標の差を中心のずれから求める
+                let scale = self.delta_c_scale;
+                Some((
+                    center_pixel_x - prev_center_pixel_x - (center_re - prev_center_re) / scale,
+                    center_pixel_y - prev_center_pixel_y + (center_im - prev_center_im) / scale,
+                ))
+            }
+            _ => None,
+        }
+    }
+
+    /// `prev` と同じ格子で maxIteration だけを上げた perturbation の job か。
+    /// reference は同じ c で同じ位置にあればよく、orbit を伸ばしたものでもよい
+    pub(crate) fn is_continued_from(&self, prev: &CachedGrid) -> bool {
+        let (
+            GridAnchor::Reference {
+                max_ref_iteration, ..
+            },
+            GridAnchor::Reference {
+                max_ref_iteration: prev_max_ref_iteration,
+                ..
+            },
+        ) = (self.anchor, prev.anchor)
+        else {
+            return false;
+        };
+        let mut same = *self;
+        same.max_iteration = prev.max_iteration;
+        if let GridAnchor::Reference {
+            max_ref_iteration, ..
+        } = &mut same.anchor
+        {
+            *max_ref_iteration = prev_max_ref_iteration;
+        }
+        self.max_iteration > prev.max_iteration
+            && max_ref_iteration >= prev_max_ref_iteration
+            && same == *prev
+    }
+
+    /// `prev` の格子をちょうど (dx, dy) ピクセル動かしたものか
+    pub(crate) fn is_shifted_from(&self, prev: &CachedGrid, dx: i32, dy: i32) -> bool {
+        self.offset_from(prev).is_some_and(|(x, y)| {
+            (x - dx as f64).abs() < SHIFT_EPSILON && (y - dy as f64).abs() < SHIFT_EPSILON
+        })
+    }
+}
+
+/// 幅 `width` 高さ `height` の格子の値を (dx, dy) ずらす。元が範囲外になる位置は default にする
+pub(crate) fn shift_grid<T: Copy + Default>(
+    values: &mut [T],
+    width: u32,
+    height: u32,
+    dx: i32,
+    dy: i32,
+) {
+    let (w, h) = (width as usize, height as usize);
+    let values = &mut values[..w * h];
+    if dx.unsigned_abs() as usize >= w || dy.unsigned_abs() as usize >= h {
+        values.fill(T::default());
+        return;
+    }
+    let kept_w = w - dx.unsigned_abs() as usize;
+    let src_x = (-dx).max(0) as usize;
+    let dst_x = dx.max(0) as usize;
+
+    // 下にずらすときは下の行から書き、まだ読んでいない行を潰さないようにする
+    for i in 0..h {
+        let y = if dy > 0 { h - 1 - i } else { i };
+        let row = y * w;
+        let Some(src_y) = y
+            .checked_add_signed(-dy as isize)
+            .filter(|&src_y| src_y < h)
+        else {
+            values[row..row + w].fill(T::default());
+            continue;
+        };
+        let src = src_y * w + src_x;
+        values.copy_within(src..src + kept_w, row + dst_x);
+        values[row..row + dst_x].fill(T::default());
+        values[row + dst_x + kept_w..row + w].fill(T::default());
+    }
+}
+
+/// `import_grid` に渡す元の格子と、それを area にどう重ねるか
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct GridImport {
+    pub(crate) src_width: u32,
+    pub(crate) src_height: u32,
+    /// 元の 1 ピクセルが新しい格子の何ピクセル分か。2 なら 2 倍の拡大
+    pub(crate) scale: u32,
+    /// 元のピクセル (0, 0) が来る、新しい格子の pixel 座標 (area_start を含む)
+    pub(crate) offset_x: i32,
+    pub(crate) offset_y: i32,
+}
+
+impl GridImport {
+    /// 新しい格子の pixel 座標 1 軸分に重なる元の座標
+    fn src_coord(&self, pixel: i64, offset: i32, src_len: u32) -> Option<usize> {
+        let scale = self.scale as i64;
+        let relative = pixel - offset as i64;
+        if relative < 0 || relative % scale != 0 || relative / scale >= src_len as i64 {
+            return None;
+        }
+        Some((relative / scale) as usize)
+    }
+}
+
+/// 元の格子の iteration 数を、重なる area のピクセルのうちまだ 0 のものに書く。
+/// 元が 0 (未計算) のピクセルは書かない。書いたピクセル数を返す
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn import_grid(
+    iterations: &mut [u32],
+    area_width: u32,
+    area_height: u32,
+    area_start_x: i32,
+    area_start_y: i32,
+    src: &[u32],
+    import: &GridImport,
+) -> u32 {
+    let mut imported = 0;
+    for y in 0..area_height {
+        let pixel_y = area_start_y as i64 + y as i64;
+        let Some(src_y) = import.src_coord(pixel_y, import.offset_y, import.src_height) else {
+            continue;
+        };
+        for x in 0..area_width {
+            let pixel_x = area_start_x as i64 + x as i64;
+            let Some(src_x) = import.src_coord(pixel_x, import.offset_x, import.src_width) else {
+                continue;
+            };
+            let n = src[src_x + src_y * import.src_width as usize];
+            let index = (x + y * area_width) as usize;
+            if n != 0 && iterations[index] == 0 {
+                iterations[index] = n;
+                imported += 1;
+            }
+        }
+    }
+    imported
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn direct_grid(center_re: f64, center_pixel_x: f64, center_pixel_y: f64) -> CachedGrid {
+        CachedGrid {
+            max_iteration: 100,
+            delta_c_scale: 0.25,
+            area_width: 8,
+            area_height: 6,
+            area_start_x: 0,
+            area_start_y: 0,
+            uses_f32: false,
+            anchor: GridAnchor::Direct {
+                center_re,
+                center_im: 0.0,
+                center_pixel_x,
+                center_pixel_y,
+            },
+        }
+    }
+
+    #[test]
+    fn shift_grid_moves_values_and_clears_exposed_pixels() {
+        let (w, h) = (5u32, 4u32);
+        let original: Vec<u32> = (1..=w * h).collect();
+        for (dx, dy) in [(0, 0), (2, 1), (-1, 2), (3, -3), (-4, -1), (5, 0), (0, -4)] {
+            let mut values = original.clone();
+            shift_grid(&mut values, w, h, dx, dy);
+            for y in 0..h as i32 {
+                for x in 0..w as i32 {
+                    let (src_x, src_y) = (x - dx, y - dy);
+                    let is_inside =
+                        (0..w as i32).contains(&src_x) && (0..h as i32).contains(&src_y);
+                    let expected = if is_inside {
+                        original[(src_x + src_y * w as i32) as usize]
+                    } else {
+                        0
+                    };
+                    assert_eq!(values[(x + y * w as i32) as usize], expected, "{dx}, {dy}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn shifted_grid_needs_the_same_mapping() {
+        let prev = direct_grid(-0.5, 4.0, 3.0);
+        // 中心の c を 1 ピクセル分右に動かすと、同じ c は 1 ピクセル左に来る
+        assert!(direct_grid(-0.25, 4.0, 3.0).is_shifted_from(&prev, -1, 0));
+        assert!(direct_grid(-0.5, 6.0, 1.0).is_shifted_from(&prev, 2, -2));
+        assert!(!direct_grid(-0.5, 6.0, 1.0).is_shifted_from(&prev, 2, 2));
+        assert!(!direct_grid(-0.4, 4.0, 3.0).is_shifted_from(&prev, 0, 0));
+
+        let zoomed = CachedGrid {
+            delta_c_scale: 0.125,
+            ..prev
+        };
+        assert!(!zoomed.is_shifted_from(&prev, 0, 0));
+
+        let reference = |c_re: f64, pixel_x: f64| CachedGrid {
+            anchor: GridAnchor::Reference {
+                c_re,
+                c_im: 0.0,
+                max_ref_iteration: 100,
+                pixel_x,
+                pixel_y: 3.0,
+            },
+            ..prev
+        };
+        assert!(reference(-0.5, 7.0).is_shifted_from(&reference(-0.5, 4.0), 3, 0));
+        assert!(!reference(-0.6, 7.0).is_shifted_from(&reference(-0.5, 4.0), 3, 0));
+        assert!(!reference(-0.5, 4.0).is_shifted_from(&prev, 0, 0));
+    }
+
+    #[test]
+    fn continued_grid_only_raises_max_iteration() {
+        let grid = |max_iteration: u32, max_ref_iteration: u32, pixel_x: f64| CachedGrid {
+            max_iteration,
+            anchor: GridAnchor::Reference {
+                c_re: -0.5,
+                c_im: 0.0,
+                max_ref_iteration,
+                pixel_x,
+                pixel_y: 3.0,
+            },
+            ..direct_grid(-0.5, 4.0, 3.0)
+        };
+        let prev = grid(100, 100, 4.0);
+        assert!(grid(200, 100, 4.0).is_continued_from(&prev));
+        // orbit を伸ばした reference でもよい
+        assert!(grid(200, 200, 4.0).is_continued_from(&prev));
+        assert!(!grid(100, 100, 4.0).is_continued_from(&prev));
+        assert!(!grid(200, 50, 4.0).is_continued_from(&prev));
+        assert!(!grid(200, 100, 5.0).is_continued_from(&prev));
+        assert!(!direct_grid(-0.5, 4.0, 3.0).is_continued_from(&direct_grid(-0.5, 4.0, 3.0)));
+    }
+
+    #[test]
+    fn import_grid_writes_only_overlapping_pixels() {
+        // 3x2 の元の格子を 2 倍にして、pixel (1, 0) から始まる 4x4 の area に重ねる。
+        // 計算済みのピクセルと、元が未計算のピクセルは書かない
+        let src = [1, 2, 0, 4, 5, 6];
+        let import = GridImport {
+            src_width: 3,
+            src_height: 2,
+            scale: 2,
+            offset_x: -1,
+            offset_y: 0,
+        };
+        let mut iterations = vec![0; 16];
+        iterations[0] = 9;
+        let imported = import_grid(&mut iterations, 4, 4, 1, 0, &src, &import);
+
+        #[rustfmt::skip]
+        let expected = [
+            9, 0, 0, 0,
+            0, 0, 0, 0,
+            5, 0, 6, 0,
+            0, 0, 0, 0,
+        ];
+        assert_eq!(iterations, expected);
+        assert_eq!(imported, 2);
+    }
+}
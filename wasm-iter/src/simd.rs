@@ -0,0 +1,413 @@
+//! 2 ピクセルを f64x2 の lane に載せて同時に進める iteration ループ。
+//!
+//! wasm の simd128 を有効にしてビルドした場合だけ `calc_band` から使い、それ以外では
+//! 今までどおり 1 ピクセルずつの scalar ループを使う (`IS_ENABLED`)。
+//!
+//! lane ごとに ref_iteration が違うので、xn の読み出しと BLA の探索は lane ごとに scalar で行い、
+//! Δ の更新だけをまとめて計算する。BLA を使う lane と使わない lane が混ざっても同じ式
+//! Δ' = A Δ + U で書けるように、BLA なら A = A_bla, U = B Δc、使わなければ A = 2X + Δ, U = Δc を
+//! lane ごとに選ぶ。演算の順序は scalar の `calc_iteration_at` と揃えてあるので、
+//! iteration 数は scalar ループと完全に一致する。
+//!
+//! bailout などで終わった lane にはすぐ次のピクセルを詰めて、片方の lane だけが空回りする時間を減らす。
+
+use crate::reference::Reference;
+use crate::{
+    BAILOUT_RADIUS, IterationJob, NoObserver, PixelState, continue_iteration, start_pixel,
+};
+use lanes::F64x2;
+
+/// simd128 の kernel を使うビルドか
+pub(crate) const IS_ENABLED: bool = cfg!(all(target_arch = "wasm32", target_feature = "simd128"));
+
+#[cfg(all(target_arch = "wasm32", target_feature = "simd128"))]
+mod lanes {
+    use core::arch::wasm32::*;
+    use std::ops::{Add, Mul, Sub};
+
+    #[derive(Clone, Copy)]
+    pub(crate) struct F64x2(v128);
+
+    impl F64x2 {
+        #[inline(always)]
+        pub(crate) fn new(lane0: f64, lane1: f64) -> Self {
+            Self(f64x2(lane0, lane1))
+        }
+
+        #[inline(always)]
+        pub(crate) fn splat(value: f64) -> Self {
+            Self(f64x2_splat(value))
+        }
+
+        #[inline(always)]
+        pub(crate) fn lane(self, lane: usize) -> f64 {
+            if lane == 0 {
+                f64x2_extract_lane::<0>(self.0)
+            } else {
+                f64x2_extract_lane::<1>(self.0)
+            }
+        }
+
+        #[inline(always)]
+        pub(crate) fn replace(self, lane: usize, value: f64) -> Self {
+            if lane == 0 {
+                Self(f64x2_replace_lane::<0>(self.0, value))
+            } else {
+                Self(f64x2_replace_lane::<1>(self.0, value))
+            }
+        }
+
+        /// `mask` の bit が立っている lane は `if_true`、それ以外は `if_false` を取る
+        #[inline(always)]
+        pub(crate) fn select(mask: u8, if_true: Self, if_false: Self) -> Self {
+            let mask = i64x2(-((mask & 1) as i64), -((mask >> 1) as i64));
+            Self(v128_bitselect(if_true.0, if_false.0, mask))
+        }
+
+        /// self > other となる lane の bit を立てる
+        #[inline(always)]
+        pub(crate) fn gt_bits(self, other: Self) -> u8 {
+            i64x2_bitmask(f64x2_gt(self.0, other.0))
+        }
+
+        /// self < other となる lane の bit を立てる
+        #[inline(always)]
+        pub(crate) fn lt_bits(self, other: Self) -> u8 {
+            i64x2_bitmask(f64x2_lt(self.0, other.0))
+        }
+    }
+
+    impl Add for F64x2 {
+        type Output = Self;
+        #[inline(always)]
+        fn add(self, rhs: Self) -> Self {
+            Self(f64x2_add(self.0, rhs.0))
+        }
+    }
+
+    impl Sub for F64x2 {
+        type Output = Self;
+        #[inline(always)]
+        fn sub(self, rhs: Self) -> Self {
+            Self(f64x2_sub(self.0, rhs.0))
+        }
+    }
+
+    impl Mul for F64x2 {
+        type Output = Self;
+        #[inline(always)]
+        fn mul(self, rhs: Self) -> Self {
+            Self(f64x2_mul(self.0, rhs.0))
+        }
+    }
+}
+
+/// simd128 がないビルド用。テストでも lane の扱いを確かめられるよう、同じ API を配列で実装する
+#[cfg(not(all(target_arch = "wasm32", target_feature = "simd128")))]
+mod lanes {
+    use std::ops::{Add, Mul, Sub};
+
+    #[derive(Clone, Copy)]
+    pub(crate) struct F64x2([f64; 2]);
+
+    impl F64x2 {
+        #[inline(always)]
+        pub(crate) fn new(lane0: f64, lane1: f64) -> Self {
+            Self([lane0, lane1])
+        }
+
+        #[inline(always)]
+        pub(crate) fn splat(value: f64) -> Self {
+            Self([value; 2])
+        }
+
+        #[inline(always)]
+        pub(crate) fn lane(self, lane: usize) -> f64 {
+            self.0[lane]
+        }
+
+        #[inline(always)]
+        pub(crate) fn replace(mut self, lane: usize, value: f64) -> Self {
+            self.0[lane] = value;
+            self
+        }
+
+        /// `mask` の bit が立っている lane は `if_true`、それ以外は `if_false` を取る
+        #[inline(always)]
+        pub(crate) fn select(mask: u8, if_true: Self, if_false: Self) -> Self {
+            Self([0, 1].map(|lane| {
+                if mask & (1 << lane) != 0 {
+                    if_true.0[lane]
+                } else {
+                    if_false.0[lane]
+                }
+            }))
+        }
+
+        /// self > other となる lane の bit を立てる
+        #[inline(always)]
+        pub(crate) fn gt_bits(self, other: Self) -> u8 {
+            (self.0[0] > other.0[0]) as u8 | ((self.0[1] > other.0[1]) as u8) << 1
+        }
+
+        /// self < other となる lane の bit を立てる
+        #[inline(always)]
+        pub(crate) fn lt_bits(self, other: Self) -> u8 {
+            (self.0[0] < other.0[0]) as u8 | ((self.0[1] < other.0[1]) as u8) << 1
+        }
+    }
+
+    impl Add for F64x2 {
+        type Output = Self;
+        #[inline(always)]
+        fn add(self, rhs: Self) -> Self {
+            Self([self.0[0] + rhs.0[0], self.0[1] + rhs.0[1]])
+        }
+    }
+
+    impl Sub for F64x2 {
+        type Output = Self;
+        #[inline(always)]
+        fn sub(self, rhs: Self) -> Self {
+            Self([self.0[0] - rhs.0[0], self.0[1] - rhs.0[1]])
+        }
+    }
+
+    impl Mul for F64x2 {
+        type Output = Self;
+        #[inline(always)]
+        fn mul(self, rhs: Self) -> Self {
+            Self([self.0[0] * rhs.0[0], self.0[1] * rhs.0[1]])
+        }
+    }
+}
+
+/// 2 lane 分の計算途中の状態
+struct Lanes {
+    /// lane が計算中のピクセルの、`points` 内の index
+    pixels: [usize; 2],
+    iteration: [u32; 2],
+    ref_iteration: [u32; 2],
+    delta_re: F64x2,
+    delta_im: F64x2,
+    delta_c_re: F64x2,
+    delta_c_im: F64x2,
+}
+
+impl Lanes {
+    /// lane に新しいピクセルを載せる。初期値は scalar と同じ `start_pixel` で求める
+    #[inline(always)]
+    fn start(&mut self, lane: usize, pixel: usize, state: PixelState) {
+        self.pixels[lane] = pixel;
+        self.iteration[lane] = state.iteration;
+        self.ref_iteration[lane] = state.ref_iteration;
+        self.delta_re = self.delta_re.replace(lane, state.delta_n_re);
+        self.delta_im = self.delta_im.replace(lane, state.delta_n_im);
+        self.delta_c_re = self.delta_c_re.replace(lane, state.delta_c_re);
+        self.delta_c_im = self.delta_c_im.replace(lane, state.delta_c_im);
+    }
+
+    /// lane の状態を scalar の `continue_iteration` に渡せる形で取り出す
+    #[inline(always)]
+    fn state(&self, lane: usize) -> PixelState {
+        PixelState {
+            delta_n_re: self.delta_re.lane(lane),
+            delta_n_im: self.delta_im.lane(lane),
+            delta_c_re: self.delta_c_re.lane(lane),
+            delta_c_im: self.delta_c_im.lane(lane),
+            iteration: self.iteration[lane],
+            ref_iteration: self.ref_iteration[lane],
+        }
+    }
+}
+
+/// `points` の各ピクセル座標の iteration 数を `results` に書く。
+/// 追加出力を持たない (`NoObserver` の) `calc_iteration_at` と同じ結果になる
+pub(crate) fn calc_iterations_x2(
+    job: &IterationJob,
+    reference: &Reference,
+    points: &[(f64, f64)],
+    results: &mut [u32],
+) {
+    let start = |pixel: usize| {
+        let (x, y) = points[pixel];
+        start_pixel(job, reference, x, y, &mut NoObserver)
+    };
+
+    // 2 lane に載せるピクセルがなければ scalar で計算する
+    if points.len() < 2 {
+        for (pixel, result) in results.iter_mut().enumerate() {
+            *result = continue_iteration(job, reference, start(pixel), &mut NoObserver);
+        }
+        return;
+    }
+
+    let zero = F64x2::splat(0.0);
+    let mut lanes = Lanes {
+        pixels: [0, 1],
+        iteration: [0; 2],
+        ref_iteration: [0; 2],
+        delta_re: zero,
+        delta_im: zero,
+        delta_c_re: zero,
+        delta_c_im: zero,
+    };
+    lanes.start(0, 0, start(0));
+    lanes.start(1, 1, start(1));
+    let mut next_pixel = 2;
+
+    loop {
+        let finished = run_pair(job, reference, &mut lanes);
+
+        // 終わった lane にはすぐ次のピクセルを載せる
+        let mut is_running = [true; 2];
+        for (lane, n) in finished.into_iter().enumerate() {
+            let Some(n) = n else {
+                continue;
+            };
+            results[lanes.pixels[lane]] = n;
+            if next_pixel < points.len() {
+                lanes.start(lane, next_pixel, start(next_pixel));
+                next_pixel += 1;
+            } else {
+                is_running[lane] = false;
+            }
+        }
+        if is_running == [true, true] {
+            continue;
+        }
+
+        // 載せるピクセルがなくなったら、計算中の lane は scalar で最後まで進める
+        for lane in 0..2 {
+            if is_running[lane] {
+                let n = continue_iteration(job, reference, lanes.state(lane), &mut NoObserver);
+                results[lanes.pixels[lane]] = n;
+            }
+        }
+        break;
+    }
+}
+
+/// 両方の lane が計算中のあいだ進めて、どちらかが終わったらその lane の iteration 数を返す
+#[inline(always)]
+fn run_pair(job: &IterationJob, reference: &Reference, lanes: &mut Lanes) -> [Option<u32>; 2] {
+    let max_iteration = job.max_iteration;
+    let max_ref_iteration = reference.max_ref_iteration;
+    let bla_rows = reference.bla_rows;
+    let start_bla_index = job.start_bla_index;
+    let xn = &reference.xn;
+    let bla = &reference.bla;
+    let bailout = F64x2::splat(BAILOUT_RADIUS);
+    let two = F64x2::splat(2.0);
+
+    let delta_c_re = lanes.delta_c_re;
+    let delta_c_im = lanes.delta_c_im;
+    let mut delta_re = lanes.delta_re;
+    let mut delta_im = lanes.delta_im;
+    let mut iteration = lanes.iteration;
+    let mut ref_iteration = lanes.ref_iteration;
+
+    let finished = loop {
+        if iteration[0] >= max_iteration || iteration[1] >= max_iteration {
+            break iteration.map(|n| (n >= max_iteration).then_some(n.min(max_iteration)));
+        }
+
+        let i0 = ref_iteration[0] as usize * 2;
+        let i1 = ref_iteration[1] as usize * 2;
+        let x_re = F64x2::new(xn[i0], xn[i1]);
+        let x_im = F64x2::new(xn[i0 + 1], xn[i1 + 1]);
+        let z_re = x_re + delta_re;
+        let z_im = x_im + delta_im;
+        let z_norm = z_re * z_re + z_im * z_im;
+
+        let escaped = z_norm.gt_bits(bailout);
+        if escaped != 0 {
+            break [0, 1].map(|lane| (escaped & (1 << lane) != 0).then_some(iteration[lane]));
+        }
+
+        // rebase
+        let dz_norm = delta_re * delta_re + delta_im * delta_im;
+        let mut rebase = z_norm.lt_bits(dz_norm);
+        for (lane, &ref_iteration) in ref_iteration.iter().enumerate() {
+            if ref_iteration == max_ref_iteration {
+                rebase |= 1 << lane;
+            }
+        }
+        let mut cur_x_re = x_re;
+        let mut cur_x_im = x_im;
+        if rebase != 0 {
+            delta_re = F64x2::select(rebase, z_re, delta_re);
+            delta_im = F64x2::select(rebase, z_im, delta_im);
+            cur_x_re = F64x2::select(rebase, F64x2::splat(xn[0]), x_re);
+            cur_x_im = F64x2::select(rebase, F64x2::splat(xn[1]), x_im);
+            for (lane, ref_iteration) in ref_iteration.iter_mut().enumerate() {
+                if rebase & (1 << lane) != 0 {
+                    *ref_iteration = 0;
+                }
+            }
+        }
+
+        // BLA は lane ごとに探す
+        let steps = [0, 1].map(|lane| {
+            let found = bla.find(
+                bla_rows,
+                start_bla_index,
+                ref_iteration[lane],
+                dz_norm.lane(lane),
+            );
+            found
+                .map(|index| bla.step(index))
+                .filter(|step| ref_iteration[lane].wrapping_add(step.skip) < max_ref_iteration)
+        });
+
+        // perturbation: Δ' = (2X + Δ) Δ + Δc
+        let t_re = cur_x_re * two + delta_re;
+        let t_im = cur_x_im * two + delta_im;
+        if let [None, None] = steps {
+            let new_re = (t_re * delta_re - t_im * delta_im) + delta_c_re;
+            let new_im = (t_re * delta_im + t_im * delta_re) + delta_c_im;
+            delta_re = new_re;
+            delta_im = new_im;
+            ref_iteration = ref_iteration.map(|n| n + 1);
+            iteration = iteration.map(|n| n + 1);
+            continue;
+        }
+
+        // BLA を使う lane が混ざるときは Δ' = A Δ + U として、BLA の lane は A = A_bla, U = B Δc、
+        // 使わない lane は A = 2X + Δ, U = Δc を選ぶ。使わない lane の B は 1 にしておく
+        let mut use_bla = 0;
+        let mut a = [[0.0; 2]; 2];
+        let mut b = [[1.0, 0.0]; 2];
+        for (lane, step) in steps.iter().enumerate() {
+            if let Some(step) = step {
+                use_bla |= 1 << lane;
+                a[lane] = [step.a_re, step.a_im];
+                b[lane] = [step.b_re, step.b_im];
+                ref_iteration[lane] = ref_iteration[lane].wrapping_add(step.skip);
+                iteration[lane] = iteration[lane].wrapping_add(step.skip);
+            } else {
+                ref_iteration[lane] += 1;
+                iteration[lane] += 1;
+            }
+        }
+        let a_re = F64x2::select(use_bla, F64x2::new(a[0][0], a[1][0]), t_re);
+        let a_im = F64x2::select(use_bla, F64x2::new(a[0][1], a[1][1]), t_im);
+        let b_re = F64x2::new(b[0][0], b[1][0]);
+        let b_im = F64x2::new(b[0][1], b[1][1]);
+        let bdc_re = b_re * delta_c_re - b_im * delta_c_im;
+        let bdc_im = b_re * delta_c_im + b_im * delta_c_re;
+        let u_re = F64x2::select(use_bla, bdc_re, delta_c_re);
+        let u_im = F64x2::select(use_bla, bdc_im, delta_c_im);
+
+        let new_re = (a_re * delta_re - a_im * delta_im) + u_re;
+        let new_im = (a_re * delta_im + a_im * delta_re) + u_im;
+        delta_re = new_re;
+        delta_im = new_im;
+    };
+
+    lanes.delta_re = delta_re;
+    lanes.delta_im = delta_im;
+    lanes.iteration = iteration;
+    lanes.ref_iteration = ref_iteration;
+    finished
+}
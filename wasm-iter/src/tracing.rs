@@ -0,0 +1,151 @@
+//! iteration 数の境界をたどって、囲まれた内側を埋める boundary tracing。
+//!
+//! band の縁のピクセルから始め、4 近傍のどれかと iteration 数が違う (境界にある) ピクセルの
+//! 近傍だけを計算していく。境界をたどり終えたら、各行を左から見て未計算のピクセルを
+//! 左隣の値で埋める。境界が閉じていれば埋めた値は 1 ピクセルずつ計算した場合と一致し、
+//! maxIteration が大きく集合の内部が広い area ほど計算するピクセルが減る。
+//! 1 ピクセル幅より細い構造は境界として見つからずに埋まることがあるので、
+//! `IterationJob::set_guess_check` を有効にすると埋めたピクセルも計算して間違いを数える。
+
+use crate::guessing::{load_band, point, store_band};
+use crate::{IterationJob, PointsKernel};
+
+/// band の計算中の状態。`values` は band 内の scaled 座標ごとの iteration 数で、0 なら未計算
+struct TraceBand {
+    band_y_from: u32,
+    scaled_w: u32,
+    band_h: u32,
+    values: Vec<u32>,
+    is_queued: Vec<bool>,
+    queue: Vec<usize>,
+}
+
+impl TraceBand {
+    /// band 内の index のピクセルの iteration 数。未計算なら計算する
+    fn load(&mut self, job: &mut IterationJob, index: usize, calc_points: PointsKernel) -> u32 {
+        if self.values[index] != 0 {
+            return self.values[index];
+        }
+        let x = index as u32 % self.scaled_w;
+        let y = self.band_y_from + index as u32 / self.scaled_w;
+        let mut result = [0];
+        calc_points(job, &job.primary, &[point(job, x, y)], &mut result);
+        job.calculated_count += 1;
+        self.values[index] = result[0];
+        result[0]
+    }
+
+    fn enqueue(&mut self, index: usize) {
+        if !self.is_queued[index] {
+            self.is_queued[index] = true;
+            self.queue.push(index);
+        }
+    }
+
+    /// ピクセルと 4 近傍を比べ、違う値の近傍があればその方向の近傍と斜めの近傍を queue に入れる
+    fn scan(&mut self, job: &mut IterationJob, index: usize, calc_points: PointsKernel) {
+        let w = self.scaled_w as usize;
+        let x = index % w;
+        let y = index / w;
+        let has_left = x >= 1;
+        let has_right = x + 1 < w;
+        let has_up = y >= 1;
+        let has_down = y + 1 < self.band_h as usize;
+
+        let center = self.load(job, index, calc_points);
+        let mut differs = |this: &mut Self, is_inside: bool, neighbor: usize| {
+            is_inside && this.load(job, neighbor, calc_points) != center
+        };
+        let left = differs(self, has_left, index.wrapping_sub(1));
+        let right = differs(self, has_right, index + 1);
+        let up = differs(self, has_up, index.wrapping_sub(w));
+        let down = differs(self, has_down, index + w);
+
+        let neighbors = [
+            (left, index.wrapping_sub(1)),
+            (right, index + 1),
+            (up, index.wrapping_sub(w)),
+            (down, index + w),
+            (
+                has_up && has_left && (up || left),
+                index.wrapping_sub(w + 1),
+            ),
+            (
+                has_up && has_right && (up || right),
+                index.wrapping_sub(w - 1),
+            ),
+            (has_down && has_left && (down || left), index + w - 1),
+            (has_down && has_right && (down || right), index + w + 1),
+        ];
+        for (is_boundary, neighbor) in neighbors {
+            if is_boundary {
+                self.enqueue(neighbor);
+            }
+        }
+    }
+}
+
+/// pass 内の scaled_y が [from, to) の band を boundary tracing で計算する。
+///
+/// 前の pass で計算済みのピクセルはキャッシュの値を使い、埋めたピクセルもキャッシュに書く。
+/// 埋めたピクセルは guessed count に数える
+pub(crate) fn calc_band_traced(
+    job: &mut IterationJob,
+    band_scaled_y_from: u32,
+    band_scaled_y_to: u32,
+    calc_points: PointsKernel,
+) {
+    if band_scaled_y_from >= band_scaled_y_to || job.scaled_width == 0 {
+        return;
+    }
+    let scaled_w = job.scaled_width;
+    let band_h = band_scaled_y_to - band_scaled_y_from;
+    let pixels = (scaled_w * band_h) as usize;
+    let mut band = TraceBand {
+        band_y_from: band_scaled_y_from,
+        scaled_w,
+        band_h,
+        values: load_band(job, band_scaled_y_from, band_scaled_y_to),
+        is_queued: vec![false; pixels],
+        queue: Vec::new(),
+    };
+
+    // band の縁から境界をたどり始める
+    for x in 0..scaled_w {
+        band.enqueue(x as usize);
+        band.enqueue((x + (band_h - 1) * scaled_w) as usize);
+    }
+    for y in 0..band_h {
+        band.enqueue((y * scaled_w) as usize);
+        band.enqueue((scaled_w - 1 + y * scaled_w) as usize);
+    }
+    while let Some(index) = band.queue.pop() {
+        band.scan(job, index, calc_points);
+    }
+
+    // 境界に囲まれた未計算のピクセルを、左隣に入るはずの値で埋める。
+    // 左端の列は縁として計算済み
+    let is_check_enabled = job.is_guess_check_enabled;
+    for y in 0..band_h {
+        let mut fill_value = 0;
+        for x in 0..scaled_w {
+            let index = (x + y * scaled_w) as usize;
+            if band.values[index] != 0 {
+                fill_value = band.values[index];
+                continue;
+            }
+            job.guessed_count += 1;
+            if is_check_enabled {
+                if band.load(job, index, calc_points) != fill_value {
+                    job.mistaken_guess_count += 1;
+                }
+                continue;
+            }
+            band.values[index] = fill_value;
+            // 埋めたピクセルも progress の分母に入っているので計算済みとして数える
+            job.calculated_count += 1;
+        }
+    }
+
+    store_band(job, band_scaled_y_from, &band.values);
+}